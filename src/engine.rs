@@ -0,0 +1,132 @@
+use std::{fmt, time::{Duration, Instant}};
+
+use log::{error,info,debug};
+use winit::{dpi::PhysicalSize, error::OsError, event::{Event, WindowEvent}, event_loop::{ControlFlow, EventLoop}, platform::run_return::EventLoopExtRunReturn, window::WindowBuilder};
+
+use crate::renderer::{DeviceReport, Renderer, RendererBuilder, RendererError};
+
+///The hooks the engine calls while running an application, every hook has an empty default implementation.
+pub trait Application{
+    ///The title of the window.
+    fn title(&self)->String{
+        return String::from("GPEngine");
+    }
+    ///The builder used to create the renderer, override it to change the renderer configuration.
+    fn renderer_builder(&self)->RendererBuilder{
+        return RendererBuilder::new();
+    }
+    ///Called once after the renderer has been created.
+    fn init(&mut self , _renderer : &mut Renderer){}
+    ///Called once per iteration of the event loop, with the time since the previous update.
+    fn update(&mut self , _dt : Duration){}
+    ///Called when the window should be redrawn, rendering is paused while the window is minimized.
+    fn render(&mut self , _renderer : &mut Renderer){}
+    ///Called when the window has been resized and the swapchain has been recreated, the size is in physical pixels.
+    ///Not called while the window is minimized.
+    fn on_resize(&mut self , _renderer : &mut Renderer , _width : u32 , _height : u32){}
+    ///Called for every window event, before the engine handles it.
+    fn on_event(&mut self , _event : &WindowEvent){}
+    ///Called when the window is closed, before the renderer is destroyed.
+    fn on_close(&mut self , _renderer : &mut Renderer){}
+}
+///Describes why the engine failed to start.
+#[derive(Debug)]
+pub enum EngineError{
+    ///The window could not be created.
+    CreateWindow(OsError),
+    ///The renderer could not be created.
+    CreateRenderer(RendererError),
+}
+impl fmt::Display for EngineError{
+    fn fmt(&self , f : &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            EngineError::CreateWindow(e)=>write!(f,"failed to create the window: {}",e),
+            EngineError::CreateRenderer(e)=>write!(f,"failed to create the renderer: {}",e),
+        }
+    }
+}
+impl std::error::Error for EngineError{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)>{
+        match self{
+            EngineError::CreateWindow(e)=>Some(e),
+            EngineError::CreateRenderer(e)=>Some(e),
+        }
+    }
+}
+///Creates a window and a renderer and runs the event loop until the window is closed.
+///The renderer is destroyed before this function returns.
+pub fn run<A : Application>(mut app : A)->Result<(),EngineError>{
+    let mut event_loop = EventLoop::new();
+    let window = WindowBuilder::new().with_title(app.title()).build(&event_loop).map_err(|e|{
+        error!("Failed to create window {}.",e);
+        EngineError::CreateWindow(e)
+    })?;
+    let mut renderer = app.renderer_builder().build(&window).map_err(EngineError::CreateRenderer)?;
+    app.init(&mut renderer);
+    info!("Starting event loop.");
+    let mut last_update = Instant::now();
+    event_loop.run_return(|event , _ , control_flow|{
+        //Events can still arrive after exiting, the application has been closed by then.
+        if *control_flow == ControlFlow::Exit{return}
+        *control_flow = ControlFlow::Poll;
+        match event{
+            Event::WindowEvent{event , window_id} if window_id == window.id()=>{
+                app.on_event(&event);
+                match event{
+                    WindowEvent::CloseRequested=>{
+                        debug!("Window close requested.");
+                        app.on_close(&mut renderer);
+                        *control_flow = ControlFlow::Exit;
+                    }
+                    WindowEvent::Resized(PhysicalSize{width , height})=>{
+                        if let Err(e) = renderer.recreate_swapchain(width, height){
+                            error!("Failed to recreate the swapchain, {}.",e);
+                            app.on_close(&mut renderer);
+                            *control_flow = ControlFlow::Exit;
+                            return;
+                        }
+                        if !renderer.is_paused(){app.on_resize(&mut renderer, width, height)}
+                    }
+                    _=>{}
+                }
+            }
+            Event::MainEventsCleared=>{
+                let now = Instant::now();
+                app.update(now - last_update);
+                last_update = now;
+                window.request_redraw();
+            }
+            Event::RedrawRequested(window_id) if window_id == window.id() && !renderer.is_paused()=>{
+                app.render(&mut renderer);
+            }
+            _=>{}
+        }
+    });
+    info!("Event loop stopped, shutting down.");
+    return Ok(());
+}
+///Describes every GPU, see `DeviceReport`.
+///Presentation support is queried from the display server without a window, with `test_surface` it is tested against a surface for a window that is never shown instead.
+///It is reported as unknown when no display is available.
+pub fn gather_device_reports(test_surface : bool)->Result<Vec<DeviceReport>,RendererError>{
+    if !test_surface{return DeviceReport::gather(None)}
+    if !display_available(){
+        debug!("No display available, presentation support is not tested.");
+        return DeviceReport::gather(None);
+    }
+    let event_loop = EventLoop::new();
+    match WindowBuilder::new().with_visible(false).build(&event_loop){
+        Ok(window)=>return DeviceReport::gather(Some(&window)),
+        Err(e)=>{
+            debug!("Failed to create a hidden window, presentation support is not tested, {}.",e);
+            return DeviceReport::gather(None);
+        }
+    }
+}
+///Whether a window can be created, creating the event loop panics on Unix when there is no X11 or Wayland display.
+fn display_available()->bool{
+    if cfg!(all(unix, not(any(target_os = "macos", target_os = "ios", target_os = "android")))){
+        return std::env::var_os("DISPLAY").is_some() || std::env::var_os("WAYLAND_DISPLAY").is_some();
+    }
+    return true;
+}
@@ -0,0 +1,12 @@
+//!GPEngine, a general purpose Vulkan engine.
+//!
+//!The `Renderer` owns every Vulkan object it creates, the underlying handles are only exposed through accessor methods.
+//!Applications implement the `Application` trait and are started with `engine::run`.
+#![allow(clippy::needless_return)]
+
+pub mod engine;
+pub mod logging;
+pub mod renderer;
+
+pub use engine::{Application, EngineError};
+pub use renderer::{DeviceReport, Frame, Renderer, RendererBuilder, RendererConfig, RendererError};
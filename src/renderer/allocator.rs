@@ -0,0 +1,567 @@
+use ash::{Device, Instance, version::{DeviceV1_0, InstanceV1_0}, vk::{self, Buffer, DeviceMemory, DeviceSize, Image, ImageTiling, MappedMemoryRange, MemoryAllocateInfo, MemoryMapFlags, MemoryPropertyFlags, MemoryRequirements, PhysicalDevice, PhysicalDeviceMemoryProperties, StructureType}};
+use log::{error,debug,trace};
+
+use super::{RendererError, get_memorytype_index};
+
+///Images of at least this size get their own device memory allocation.
+const DEDICATED_IMAGE_SIZE : DeviceSize = 32 << 20;
+///The block size used for heaps larger than `SMALL_HEAP_SIZE`, smaller heaps use an eighth of their size.
+const LARGE_HEAP_BLOCK_SIZE : DeviceSize = 256 << 20;
+const SMALL_HEAP_SIZE : DeviceSize = 1 << 30;
+
+///Where a resource lives, each location maps to a list of memory property flags that are tried in order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryLocation{
+    ///Only accessed by the GPU, device local memory is used when available.
+    GpuOnly,
+    ///Written by the CPU and read by the GPU, mapped and host coherent, preferably also device local.
+    CpuToGpu,
+    ///Written by the GPU and read back by the CPU, mapped and host coherent, preferably also host cached.
+    GpuToCpu,
+    ///Mapped but not necessarily host coherent, writes must be flushed and reads invalidated with the allocator.
+    HostVisible,
+}
+impl MemoryLocation{
+    fn candidate_flags(&self)->[MemoryPropertyFlags;2]{
+        let host = MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT;
+        match self{
+            MemoryLocation::GpuOnly=>return [MemoryPropertyFlags::DEVICE_LOCAL,MemoryPropertyFlags::empty()],
+            MemoryLocation::CpuToGpu=>return [host | MemoryPropertyFlags::DEVICE_LOCAL,host],
+            MemoryLocation::GpuToCpu=>return [host | MemoryPropertyFlags::HOST_CACHED,host],
+            MemoryLocation::HostVisible=>return [MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_CACHED,MemoryPropertyFlags::HOST_VISIBLE],
+        }
+    }
+}
+///How a resource lays out its memory, linear and optimal resources must be `bufferImageGranularity` apart when they share a block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResourceTiling{
+    ///Buffers and linearly tiled images, placed from the start of a block.
+    Linear,
+    ///Optimally tiled images, placed from the end of a block so they rarely border linear resources.
+    Optimal,
+}
+impl From<ImageTiling> for ResourceTiling{
+    fn from(tiling : ImageTiling)->Self{
+        if tiling == ImageTiling::LINEAR{return ResourceTiling::Linear}
+        return ResourceTiling::Optimal;
+    }
+}
+///A range of device memory handed out by the `MemoryAllocator`, it must be returned with `MemoryAllocator::free`.
+#[derive(Debug)]
+pub struct Allocation{
+    memory : DeviceMemory,
+    offset : DeviceSize,
+    size : DeviceSize,
+    memory_type_index : u32,
+    property_flags : MemoryPropertyFlags,
+    ///The size of `memory`, flushed ranges are clamped to it.
+    memory_size : DeviceSize,
+    mapped : *mut u8,
+    ///The block the allocation was taken from, `None` for dedicated allocations.
+    block_id : Option<u64>,
+}
+impl Allocation{
+    pub fn memory(&self)->DeviceMemory{
+        return self.memory;
+    }
+    ///The offset into `memory`, already aligned for the resource.
+    pub fn offset(&self)->DeviceSize{
+        return self.offset;
+    }
+    pub fn size(&self)->DeviceSize{
+        return self.size;
+    }
+    pub fn memory_type_index(&self)->u32{
+        return self.memory_type_index;
+    }
+    ///The CPU address of the allocation, `None` unless the memory is host visible.
+    pub fn mapped_ptr(&self)->Option<*mut u8>{
+        if self.mapped.is_null(){return None}
+        return Some(self.mapped);
+    }
+    pub fn is_dedicated(&self)->bool{
+        return self.block_id.is_none();
+    }
+    ///The property flags of the memory type, mapped writes to memory without `HOST_COHERENT` must be flushed.
+    pub fn property_flags(&self)->MemoryPropertyFlags{
+        return self.property_flags;
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum RangeKind{
+    Free,
+    Used(ResourceTiling),
+}
+#[derive(Clone, Copy, Debug)]
+struct Range{
+    offset : DeviceSize,
+    size : DeviceSize,
+    kind : RangeKind,
+}
+impl Range{
+    fn end(&self)->DeviceSize{
+        return self.offset + self.size;
+    }
+}
+///A large device memory allocation that is divided into ranges, the ranges always cover the whole block and free ranges are never adjacent.
+struct MemoryBlock{
+    id : u64,
+    memory : DeviceMemory,
+    size : DeviceSize,
+    mapped : *mut u8,
+    ranges : Vec<Range>,
+}
+impl MemoryBlock{
+    fn is_empty(&self)->bool{
+        return self.ranges.len() == 1 && self.ranges[0].kind == RangeKind::Free;
+    }
+    ///Finds an offset for the resource, linear resources take the lowest offset and optimal resources the highest.
+    fn find(&self , size : DeviceSize , alignment : DeviceSize , tiling : ResourceTiling , granularity : DeviceSize)->Option<(usize,DeviceSize)>{
+        let indices : Box<dyn Iterator<Item=usize>> = match tiling{
+            ResourceTiling::Linear=>Box::new(0..self.ranges.len()),
+            ResourceTiling::Optimal=>Box::new((0..self.ranges.len()).rev()),
+        };
+        for i in indices{
+            let range = self.ranges[i];
+            if range.kind != RangeKind::Free || range.size < size{continue}
+            let offset = match tiling{
+                ResourceTiling::Linear=>{
+                    let offset = align_up(range.offset, alignment);
+                    match self.page_conflicts(i, offset, offset + size, tiling, granularity){
+                        (Some(_),_)=>align_up(offset, granularity.max(alignment)),
+                        _=>offset,
+                    }
+                }
+                ResourceTiling::Optimal=>{
+                    let end = match self.page_conflicts(i, range.end() - size, range.end(), tiling, granularity){
+                        (_,Some(next_offset))=>range.end().min(align_down(next_offset, granularity)),
+                        _=>range.end(),
+                    };
+                    if end < range.offset + size{continue}
+                    align_down(end - size, alignment)
+                }
+            };
+            if offset < range.offset || offset + size > range.end(){continue}
+            if self.page_conflicts(i, offset, offset + size, tiling, granularity) != (None,None){continue}
+            return Some((i,offset));
+        }
+        return None;
+    }
+    ///The resources of the other tiling that share the first or last `granularity` page of `offset..end` in the free range at `index`.
+    ///Returns the end of the closest one before the free range and the offset of the closest one after it, they need not be adjacent to it.
+    fn page_conflicts(&self , index : usize , offset : DeviceSize , end : DeviceSize , tiling : ResourceTiling , granularity : DeviceSize)->(Option<DeviceSize>,Option<DeviceSize>){
+        let first_page = align_down(offset, granularity);
+        let last_page_end = align_down(end - 1, granularity) + granularity;
+        let conflicts = |range : &&Range|matches!(range.kind,RangeKind::Used(other) if other != tiling);
+        let before = self.ranges[..index].iter().rev().take_while(|r|r.end() > first_page).find(conflicts).map(|r|r.end());
+        let after = self.ranges[index + 1..].iter().take_while(|r|r.offset < last_page_end).find(conflicts).map(|r|r.offset);
+        return (before,after);
+    }
+    ///Splits the free range at `index` around the new allocation.
+    fn insert(&mut self , index : usize , offset : DeviceSize , size : DeviceSize , tiling : ResourceTiling){
+        let range = self.ranges[index];
+        let mut replacement = vec!();
+        if offset > range.offset{replacement.push(Range{offset : range.offset , size : offset - range.offset , kind : RangeKind::Free})}
+        replacement.push(Range{offset , size , kind : RangeKind::Used(tiling)});
+        if offset + size < range.end(){replacement.push(Range{offset : offset + size , size : range.end() - offset - size , kind : RangeKind::Free})}
+        self.ranges.splice(index..=index, replacement);
+    }
+    ///Marks the range at `offset` free and merges it with free neighbours, returns false when there is no allocation at `offset`.
+    fn remove(&mut self , offset : DeviceSize)->bool{
+        let mut index = match self.ranges.iter().position(|r|r.offset == offset && r.kind != RangeKind::Free){
+            Some(index)=>index,
+            None=>return false,
+        };
+        self.ranges[index].kind = RangeKind::Free;
+        if index + 1 < self.ranges.len() && self.ranges[index + 1].kind == RangeKind::Free{
+            self.ranges[index].size += self.ranges[index + 1].size;
+            self.ranges.remove(index + 1);
+        }
+        if index > 0 && self.ranges[index - 1].kind == RangeKind::Free{
+            self.ranges[index - 1].size += self.ranges[index].size;
+            self.ranges.remove(index);
+            index -= 1;
+        }
+        trace!("Block {} has {} ranges after freeing range {}.",self.id,self.ranges.len(),index);
+        return true;
+    }
+}
+///Usage of one memory type.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MemoryTypeStatistics{
+    pub memory_type_index : u32,
+    pub block_count : u32,
+    ///The bytes reserved by blocks, used or not.
+    pub block_bytes : DeviceSize,
+    ///The bytes of blocks handed out to resources, including alignment padding only where it is part of a range.
+    pub used_bytes : DeviceSize,
+    pub allocation_count : u32,
+    pub free_range_count : u32,
+    pub largest_free_range : DeviceSize,
+    pub dedicated_count : u32,
+    pub dedicated_bytes : DeviceSize,
+}
+impl MemoryTypeStatistics{
+    ///0 when all free memory of the blocks is a single range, approaching 1 as it is split into smaller ranges.
+    pub fn fragmentation(&self)->f32{
+        let free_bytes = self.block_bytes - self.used_bytes;
+        if free_bytes == 0{return 0.0}
+        return 1.0 - self.largest_free_range as f32 / free_bytes as f32;
+    }
+}
+///Usage of every memory type that has allocations.
+#[derive(Clone, Debug, Default)]
+pub struct AllocatorStatistics{
+    pub memory_types : Vec<MemoryTypeStatistics>,
+    ///The number of device memory allocations, limited by `maxMemoryAllocationCount`.
+    pub device_allocation_count : u32,
+}
+impl AllocatorStatistics{
+    pub fn reserved_bytes(&self)->DeviceSize{
+        return self.memory_types.iter().map(|t|t.block_bytes + t.dedicated_bytes).sum();
+    }
+    pub fn used_bytes(&self)->DeviceSize{
+        return self.memory_types.iter().map(|t|t.used_bytes + t.dedicated_bytes).sum();
+    }
+}
+///Sub-allocates resources from large blocks of device memory, one list of blocks per memory type.
+///Large images and resources that don't fit in a block get a dedicated allocation.
+pub struct MemoryAllocator{
+    device : Device,
+    memory_properties : PhysicalDeviceMemoryProperties,
+    buffer_image_granularity : DeviceSize,
+    non_coherent_atom_size : DeviceSize,
+    max_allocation_count : u32,
+    blocks : Vec<Vec<MemoryBlock>>,
+    ///The memory of every dedicated allocation, with its memory type and size.
+    dedicated : Vec<(DeviceMemory,u32,DeviceSize)>,
+    device_allocation_count : u32,
+    next_block_id : u64,
+}
+impl MemoryAllocator{
+    pub(crate) unsafe fn new(instance : &Instance , physical_device : PhysicalDevice , device : &Device)->Self{
+        let memory_properties = instance.get_physical_device_memory_properties(physical_device);
+        let limits = instance.get_physical_device_properties(physical_device).limits;
+        let memory_type_count = memory_properties.memory_type_count as usize;
+        debug!("Creating memory allocator for {} memory types, buffer image granularity {}.",memory_type_count,limits.buffer_image_granularity);
+        return Self{
+            device : device.clone(),
+            memory_properties,
+            buffer_image_granularity : limits.buffer_image_granularity.max(1),
+            non_coherent_atom_size : limits.non_coherent_atom_size.max(1),
+            max_allocation_count : limits.max_memory_allocation_count,
+            blocks : (0..memory_type_count).map(|_|vec!()).collect(),
+            dedicated : vec!(),
+            device_allocation_count : 0,
+            next_block_id : 0,
+        };
+    }
+    ///Allocates memory for `requirements` from the first memory type that matches the location.
+    pub fn allocate(&mut self , requirements : &MemoryRequirements , location : MemoryLocation , tiling : ResourceTiling)->Result<Allocation,RendererError>{
+        return self.allocate_with(requirements, location, tiling, false);
+    }
+    ///Allocates and binds memory for a buffer.
+    pub fn allocate_for_buffer(&mut self , buffer : Buffer , location : MemoryLocation)->Result<Allocation,RendererError>{
+        let requirements = unsafe{self.device.get_buffer_memory_requirements(buffer)};
+        let allocation = self.allocate_with(&requirements, location, ResourceTiling::Linear, false)?;
+        if let Err(e) = unsafe{self.device.bind_buffer_memory(buffer, allocation.memory, allocation.offset)}{
+            error!("Failed to bind buffer memory, {}.",e);
+            self.free(allocation);
+            return Err(RendererError::AllocateMemory(e));
+        }
+        return Ok(allocation);
+    }
+    ///Allocates and binds memory for an image, large images get a dedicated allocation.
+    pub fn allocate_for_image(&mut self , image : Image , tiling : ImageTiling , location : MemoryLocation)->Result<Allocation,RendererError>{
+        let requirements = unsafe{self.device.get_image_memory_requirements(image)};
+        let allocation = self.allocate_with(&requirements, location, tiling.into(), requirements.size >= DEDICATED_IMAGE_SIZE)?;
+        if let Err(e) = unsafe{self.device.bind_image_memory(image, allocation.memory, allocation.offset)}{
+            error!("Failed to bind image memory, {}.",e);
+            self.free(allocation);
+            return Err(RendererError::AllocateMemory(e));
+        }
+        return Ok(allocation);
+    }
+    fn allocate_with(&mut self , requirements : &MemoryRequirements , location : MemoryLocation , tiling : ResourceTiling , dedicated : bool)->Result<Allocation,RendererError>{
+        let memory_type_index = location.candidate_flags().iter().find_map(|&flags|get_memorytype_index(requirements, &self.memory_properties, flags)).ok_or_else(||{
+            error!("No memory type for {:?} supports the memory type bits {:#b}.",location,requirements.memory_type_bits);
+            RendererError::NoSupportedMemoryType
+        })?;
+        let property_flags = self.memory_properties.memory_types[memory_type_index as usize].property_flags;
+        let block_size = self.block_size(memory_type_index);
+        if dedicated || requirements.size > block_size / 2{
+            return self.allocate_dedicated(requirements.size, memory_type_index);
+        }
+        let granularity = self.buffer_image_granularity;
+        let blocks = &mut self.blocks[memory_type_index as usize];
+        for block in blocks.iter_mut(){
+            if let Some((index,offset)) = block.find(requirements.size, requirements.alignment.max(1), tiling, granularity){
+                block.insert(index, offset, requirements.size, tiling);
+                return Ok(Allocation{
+                    memory : block.memory,
+                    offset,
+                    size : requirements.size,
+                    memory_type_index,
+                    property_flags,
+                    memory_size : block.size,
+                    mapped : if block.mapped.is_null(){std::ptr::null_mut()}else{unsafe{block.mapped.add(offset as usize)}},
+                    block_id : Some(block.id),
+                });
+            }
+        }
+        let (memory,mapped) = self.allocate_device_memory(block_size, memory_type_index)?;
+        let mut block = MemoryBlock{
+            id : self.next_block_id,
+            memory,
+            size : block_size,
+            mapped,
+            ranges : vec!(Range{offset : 0 , size : block_size , kind : RangeKind::Free}),
+        };
+        self.next_block_id += 1;
+        debug!("Allocated memory block {} of {} MiB for memory type {}.",block.id,block_size >> 20,memory_type_index);
+        //A new block always fits a resource of at most half its size.
+        let (index,offset) = block.find(requirements.size, requirements.alignment.max(1), tiling, granularity).expect("a resource fits in an empty block");
+        block.insert(index, offset, requirements.size, tiling);
+        let allocation = Allocation{
+            memory,
+            offset,
+            size : requirements.size,
+            memory_type_index,
+            property_flags,
+            memory_size : block_size,
+            mapped : if mapped.is_null(){std::ptr::null_mut()}else{unsafe{mapped.add(offset as usize)}},
+            block_id : Some(block.id),
+        };
+        self.blocks[memory_type_index as usize].push(block);
+        return Ok(allocation);
+    }
+    fn allocate_dedicated(&mut self , size : DeviceSize , memory_type_index : u32)->Result<Allocation,RendererError>{
+        let (memory,mapped) = self.allocate_device_memory(size, memory_type_index)?;
+        self.dedicated.push((memory,memory_type_index,size));
+        trace!("Created a dedicated allocation of {} bytes for memory type {}.",size,memory_type_index);
+        let property_flags = self.memory_properties.memory_types[memory_type_index as usize].property_flags;
+        return Ok(Allocation{memory , offset : 0 , size , memory_type_index , property_flags , memory_size : size , mapped , block_id : None});
+    }
+    ///Allocates device memory and maps it when it is host visible.
+    fn allocate_device_memory(&mut self , size : DeviceSize , memory_type_index : u32)->Result<(DeviceMemory,*mut u8),RendererError>{
+        if self.device_allocation_count >= self.max_allocation_count{
+            error!("The maximum of {} device memory allocations has been reached.",self.max_allocation_count);
+            return Err(RendererError::AllocateMemory(vk::Result::ERROR_TOO_MANY_OBJECTS));
+        }
+        let memory_allocate_info = MemoryAllocateInfo{
+            s_type : StructureType::MEMORY_ALLOCATE_INFO,
+            p_next : std::ptr::null(),
+            allocation_size : size,
+            memory_type_index,
+        };
+        let memory = unsafe{self.device.allocate_memory(&memory_allocate_info, None)}.map_err(|e|{
+            error!("Failed to allocate {} bytes of memory type {}, {}.",size,memory_type_index,e);
+            RendererError::AllocateMemory(e)
+        })?;
+        let host_visible = self.memory_properties.memory_types[memory_type_index as usize].property_flags.contains(MemoryPropertyFlags::HOST_VISIBLE);
+        let mapped = if host_visible{
+            match unsafe{self.device.map_memory(memory, 0, vk::WHOLE_SIZE, MemoryMapFlags::empty())}{
+                Ok(mapped)=>mapped as *mut u8,
+                Err(e)=>{
+                    error!("Failed to map memory of memory type {}, {}.",memory_type_index,e);
+                    unsafe{self.device.free_memory(memory, None)};
+                    return Err(RendererError::AllocateMemory(e));
+                }
+            }
+        }else{std::ptr::null_mut()};
+        self.device_allocation_count += 1;
+        return Ok((memory,mapped));
+    }
+    ///Returns an allocation, the resource using it must already be destroyed or no longer used.
+    ///Empty blocks are released, except for the last one of each memory type.
+    pub fn free(&mut self , allocation : Allocation){
+        let memory_type_index = allocation.memory_type_index as usize;
+        let block_id = match allocation.block_id{
+            Some(block_id)=>block_id,
+            None=>{
+                match self.dedicated.iter().position(|d|d.0 == allocation.memory){
+                    Some(index)=>{self.dedicated.swap_remove(index);}
+                    None=>{error!("Freed an unknown dedicated allocation.");return}
+                }
+                unsafe{self.device.free_memory(allocation.memory, None)};
+                self.device_allocation_count -= 1;
+                return;
+            }
+        };
+        let blocks = &mut self.blocks[memory_type_index];
+        let index = match blocks.iter().position(|b|b.id == block_id){
+            Some(index)=>index,
+            None=>{error!("Freed an allocation of unknown memory block {}.",block_id);return}
+        };
+        if !blocks[index].remove(allocation.offset){
+            error!("Freed an unknown allocation at offset {} of memory block {}.",allocation.offset,block_id);
+            return;
+        }
+        //Keeping one empty block avoids reallocating it when resources are recreated, like the depth buffer on resize.
+        if blocks[index].is_empty() && blocks.iter().filter(|b|b.is_empty()).count() > 1{
+            let block = blocks.remove(index);
+            debug!("Releasing empty memory block {}.",block.id);
+            unsafe{self.device.free_memory(block.memory, None)};
+            self.device_allocation_count -= 1;
+        }
+    }
+    ///Makes CPU writes to a range of a mapped allocation visible to the GPU, does nothing for host coherent memory.
+    ///The range is relative to the allocation.
+    pub fn flush(&self , allocation : &Allocation , offset : DeviceSize , size : DeviceSize)->Result<(),RendererError>{
+        if allocation.property_flags.contains(MemoryPropertyFlags::HOST_COHERENT){return Ok(())}
+        let range = self.mapped_range(allocation, offset, size);
+        return unsafe{self.device.flush_mapped_memory_ranges(&[range])}.map_err(|e|{
+            error!("Failed to flush mapped memory, {}.",e);
+            RendererError::AllocateMemory(e)
+        });
+    }
+    ///Makes GPU writes to a range of a mapped allocation visible to the CPU, does nothing for host coherent memory.
+    ///The range is relative to the allocation.
+    pub fn invalidate(&self , allocation : &Allocation , offset : DeviceSize , size : DeviceSize)->Result<(),RendererError>{
+        if allocation.property_flags.contains(MemoryPropertyFlags::HOST_COHERENT){return Ok(())}
+        let range = self.mapped_range(allocation, offset, size);
+        return unsafe{self.device.invalidate_mapped_memory_ranges(&[range])}.map_err(|e|{
+            error!("Failed to invalidate mapped memory, {}.",e);
+            RendererError::AllocateMemory(e)
+        });
+    }
+    ///Widens a range to `nonCoherentAtomSize`, the neighbouring bytes that get included belong to the same memory and are harmless to flush.
+    fn mapped_range(&self , allocation : &Allocation , offset : DeviceSize , size : DeviceSize)->MappedMemoryRange{
+        let start = align_down(allocation.offset + offset, self.non_coherent_atom_size);
+        let end = align_up(allocation.offset + offset + size, self.non_coherent_atom_size).min(allocation.memory_size);
+        return MappedMemoryRange{
+            s_type : StructureType::MAPPED_MEMORY_RANGE,
+            p_next : std::ptr::null(),
+            memory : allocation.memory,
+            offset : start,
+            size : end - start,
+        };
+    }
+    pub fn statistics(&self)->AllocatorStatistics{
+        let mut statistics = AllocatorStatistics{memory_types : vec!() , device_allocation_count : self.device_allocation_count};
+        for (i,blocks) in self.blocks.iter().enumerate(){
+            let mut memory_type = MemoryTypeStatistics{memory_type_index : i as u32 , ..Default::default()};
+            for &(_,_,size) in self.dedicated.iter().filter(|d|d.1 == i as u32){
+                memory_type.dedicated_count += 1;
+                memory_type.dedicated_bytes += size;
+            }
+            if blocks.is_empty() && memory_type.dedicated_count == 0{continue}
+            for block in blocks.iter(){
+                memory_type.block_count += 1;
+                memory_type.block_bytes += block.size;
+                for range in block.ranges.iter(){
+                    match range.kind{
+                        RangeKind::Free=>{
+                            memory_type.free_range_count += 1;
+                            memory_type.largest_free_range = memory_type.largest_free_range.max(range.size);
+                        }
+                        RangeKind::Used(_)=>{
+                            memory_type.allocation_count += 1;
+                            memory_type.used_bytes += range.size;
+                        }
+                    }
+                }
+            }
+            statistics.memory_types.push(memory_type);
+        }
+        return statistics;
+    }
+    fn block_size(&self , memory_type_index : u32)->DeviceSize{
+        let heap_index = self.memory_properties.memory_types[memory_type_index as usize].heap_index;
+        let heap_size = self.memory_properties.memory_heaps[heap_index as usize].size;
+        if heap_size <= SMALL_HEAP_SIZE{return heap_size / 8}
+        return LARGE_HEAP_BLOCK_SIZE;
+    }
+    ///Frees every block and dedicated allocation, called by the renderer before the device is destroyed.
+    pub(crate) unsafe fn destroy(&mut self){
+        for blocks in self.blocks.iter_mut(){
+            for block in blocks.drain(..){self.device.free_memory(block.memory, None)}
+        }
+        for (memory,_,_) in self.dedicated.drain(..){self.device.free_memory(memory, None)}
+        self.device_allocation_count = 0;
+    }
+}
+impl Drop for MemoryAllocator{
+    //Only does something when renderer creation failed, the renderer destroys the allocator itself.
+    fn drop(&mut self){
+        unsafe{self.destroy()};
+    }
+}
+fn align_up(value : DeviceSize , alignment : DeviceSize)->DeviceSize{
+    return value.div_ceil(alignment) * alignment;
+}
+fn align_down(value : DeviceSize , alignment : DeviceSize)->DeviceSize{
+    return value / alignment * alignment;
+}
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn empty_block(size : DeviceSize)->MemoryBlock{
+        return MemoryBlock{id : 0 , memory : DeviceMemory::null() , size , mapped : std::ptr::null_mut() , ranges : vec!(Range{offset : 0 , size , kind : RangeKind::Free})};
+    }
+    fn allocate(block : &mut MemoryBlock , size : DeviceSize , alignment : DeviceSize , tiling : ResourceTiling , granularity : DeviceSize)->Option<DeviceSize>{
+        let (index,offset) = block.find(size, alignment, tiling, granularity)?;
+        block.insert(index, offset, size, tiling);
+        return Some(offset);
+    }
+    ///Places a resource without any checks, to set up layouts `find` wouldn't produce on its own.
+    fn place(block : &mut MemoryBlock , offset : DeviceSize , size : DeviceSize , tiling : ResourceTiling){
+        let index = block.ranges.iter().position(|r|r.kind == RangeKind::Free && r.offset <= offset && offset + size <= r.end()).unwrap();
+        block.insert(index, offset, size, tiling);
+    }
+
+    #[test]
+    fn offsets_are_aligned(){
+        let mut block = empty_block(1024);
+        assert_eq!(allocate(&mut block, 10, 1, ResourceTiling::Linear, 1), Some(0));
+        assert_eq!(allocate(&mut block, 16, 64, ResourceTiling::Linear, 1), Some(64));
+        assert_eq!(allocate(&mut block, 100, 256, ResourceTiling::Optimal, 1), Some(768));
+        assert_eq!(allocate(&mut block, 8, 8, ResourceTiling::Optimal, 1), Some(1016));
+    }
+    #[test]
+    fn linear_and_optimal_resources_keep_granularity_apart(){
+        let mut block = empty_block(1024);
+        assert_eq!(allocate(&mut block, 100, 1, ResourceTiling::Linear, 256), Some(0));
+        assert_eq!(allocate(&mut block, 600, 1, ResourceTiling::Optimal, 256), Some(424));
+        //Ending at 300 would share the page of the optimal image at 424.
+        assert_eq!(allocate(&mut block, 200, 1, ResourceTiling::Linear, 256), None);
+        assert_eq!(allocate(&mut block, 150, 1, ResourceTiling::Linear, 256), Some(100));
+        assert_eq!(allocate(&mut block, 20, 1, ResourceTiling::Optimal, 256), Some(404));
+    }
+    #[test]
+    fn granularity_checks_ranges_that_are_not_adjacent(){
+        let mut block = empty_block(8192);
+        place(&mut block, 0, 100, ResourceTiling::Optimal);
+        place(&mut block, 100, 20, ResourceTiling::Linear);
+        //The optimal image at 0 shares the first page even though a linear buffer lies in between.
+        assert_eq!(allocate(&mut block, 50, 1, ResourceTiling::Linear, 1024), Some(1024));
+        let mut block = empty_block(8192);
+        place(&mut block, 7000, 50, ResourceTiling::Optimal);
+        place(&mut block, 7050, 50, ResourceTiling::Linear);
+        place(&mut block, 7100, 1092, ResourceTiling::Optimal);
+        //The linear buffer at 7050 shares the last page even though an optimal image lies in between.
+        assert_eq!(allocate(&mut block, 100, 1, ResourceTiling::Optimal, 1024), Some(6044));
+    }
+    #[test]
+    fn freed_ranges_merge_with_free_neighbours(){
+        let mut block = empty_block(1024);
+        for offset in [0,100,200]{assert_eq!(allocate(&mut block, 100, 1, ResourceTiling::Linear, 1), Some(offset))}
+        assert!(block.remove(100));
+        assert_eq!(block.ranges.len(), 4);
+        assert!(block.remove(0));
+        assert_eq!(block.ranges.len(), 3);
+        assert!(!block.remove(0));
+        assert!(block.remove(200));
+        assert!(block.is_empty());
+    }
+    #[test]
+    fn full_blocks_find_no_offset(){
+        let mut block = empty_block(256);
+        for offset in [0,64,128,192]{assert_eq!(allocate(&mut block, 64, 64, ResourceTiling::Linear, 1), Some(offset))}
+        assert_eq!(allocate(&mut block, 1, 1, ResourceTiling::Linear, 1), None);
+        assert_eq!(allocate(&mut block, 1, 1, ResourceTiling::Optimal, 1), None);
+        assert!(block.remove(128));
+        assert_eq!(allocate(&mut block, 64, 64, ResourceTiling::Optimal, 1), Some(128));
+    }
+}
@@ -0,0 +1,240 @@
+//!CPU decompression of the BC1 to BC5 block compressed formats into RGBA8, for GPUs that can't sample them.
+
+use ash::vk::Format;
+
+///The RGBA8 format a block compressed format is decompressed to, `None` when there is no CPU decoder for it.
+pub(crate) fn decompressed_format(format : Format)->Option<Format>{
+    match format{
+        Format::BC1_RGB_UNORM_BLOCK | Format::BC1_RGBA_UNORM_BLOCK | Format::BC2_UNORM_BLOCK | Format::BC3_UNORM_BLOCK | Format::BC4_UNORM_BLOCK | Format::BC5_UNORM_BLOCK=>return Some(Format::R8G8B8A8_UNORM),
+        Format::BC1_RGB_SRGB_BLOCK | Format::BC1_RGBA_SRGB_BLOCK | Format::BC2_SRGB_BLOCK | Format::BC3_SRGB_BLOCK=>return Some(Format::R8G8B8A8_SRGB),
+        Format::BC4_SNORM_BLOCK | Format::BC5_SNORM_BLOCK=>return Some(Format::R8G8B8A8_SNORM),
+        _=>return None,
+    }
+}
+///Decompresses one image of `width` by `height` pixels, `data` holds the blocks row by row.
+///Returns `None` when there is no CPU decoder for the format or `data` is too short.
+pub(crate) fn decompress(format : Format , width : u32 , height : u32 , data : &[u8])->Option<Vec<u8>>{
+    decompressed_format(format)?;
+    let block_size = if matches!(format,Format::BC1_RGB_UNORM_BLOCK | Format::BC1_RGBA_UNORM_BLOCK | Format::BC1_RGB_SRGB_BLOCK | Format::BC1_RGBA_SRGB_BLOCK | Format::BC4_UNORM_BLOCK | Format::BC4_SNORM_BLOCK){8}else{16};
+    let (blocks_x,blocks_y) = (width.div_ceil(4) as usize,height.div_ceil(4) as usize);
+    if data.len() < blocks_x * blocks_y * block_size{return None}
+    let mut pixels = vec!(0u8;width as usize * height as usize * 4);
+    for block_y in 0..blocks_y{
+        for block_x in 0..blocks_x{
+            let offset = (block_y * blocks_x + block_x) * block_size;
+            let block = &data[offset..offset + block_size];
+            let texels = decompress_block(format, block);
+            //Blocks at the right and bottom edges may extend past the image.
+            for y in 0..4{
+                for x in 0..4{
+                    let (pixel_x,pixel_y) = (block_x * 4 + x,block_y * 4 + y);
+                    if pixel_x >= width as usize || pixel_y >= height as usize{continue}
+                    let pixel = (pixel_y * width as usize + pixel_x) * 4;
+                    pixels[pixel..pixel + 4].copy_from_slice(&texels[y * 4 + x]);
+                }
+            }
+        }
+    }
+    return Some(pixels);
+}
+fn decompress_block(format : Format , block : &[u8])->[[u8;4];16]{
+    let mut texels = [[0u8,0,0,255];16];
+    match format{
+        Format::BC1_RGB_UNORM_BLOCK | Format::BC1_RGB_SRGB_BLOCK=>{
+            decode_color(&block[0..8], false, &mut texels);
+            //Without alpha the transparent black of the three color mode is opaque black.
+            for texel in texels.iter_mut(){texel[3] = 255}
+        }
+        Format::BC1_RGBA_UNORM_BLOCK | Format::BC1_RGBA_SRGB_BLOCK=>decode_color(&block[0..8], false, &mut texels),
+        Format::BC2_UNORM_BLOCK | Format::BC2_SRGB_BLOCK=>{
+            decode_color(&block[8..16], true, &mut texels);
+            for (i,texel) in texels.iter_mut().enumerate(){
+                let alpha = (block[i / 2] >> ((i % 2) * 4)) & 0xF;
+                texel[3] = alpha * 17;
+            }
+        }
+        Format::BC3_UNORM_BLOCK | Format::BC3_SRGB_BLOCK=>{
+            decode_color(&block[8..16], true, &mut texels);
+            let alpha = decode_unorm_channel(&block[0..8]);
+            for (texel,alpha) in texels.iter_mut().zip(alpha.iter()){texel[3] = *alpha}
+        }
+        Format::BC4_UNORM_BLOCK=>{
+            let red = decode_unorm_channel(&block[0..8]);
+            for (texel,red) in texels.iter_mut().zip(red.iter()){texel[0] = *red}
+        }
+        Format::BC5_UNORM_BLOCK=>{
+            let red = decode_unorm_channel(&block[0..8]);
+            let green = decode_unorm_channel(&block[8..16]);
+            for (i,texel) in texels.iter_mut().enumerate(){
+                texel[0] = red[i];
+                texel[1] = green[i];
+            }
+        }
+        Format::BC4_SNORM_BLOCK=>{
+            let red = decode_snorm_channel(&block[0..8]);
+            for (texel,red) in texels.iter_mut().zip(red.iter()){*texel = [*red as u8,0,0,127]}
+        }
+        Format::BC5_SNORM_BLOCK=>{
+            let red = decode_snorm_channel(&block[0..8]);
+            let green = decode_snorm_channel(&block[8..16]);
+            for (i,texel) in texels.iter_mut().enumerate(){*texel = [red[i] as u8,green[i] as u8,0,127]}
+        }
+        _=>{},
+    }
+    return texels;
+}
+fn expand_565(color : u16)->[u32;3]{
+    let (r,g,b) = ((color >> 11) as u32 & 0x1F,(color >> 5) as u32 & 0x3F,color as u32 & 0x1F);
+    return [(r << 3) | (r >> 2),(g << 2) | (g >> 4),(b << 3) | (b >> 2)];
+}
+///Decodes the color half of a BC1, BC2 or BC3 block, BC2 and BC3 always use the four color mode.
+fn decode_color(block : &[u8] , four_colors : bool , texels : &mut [[u8;4];16]){
+    let color0 = u16::from_le_bytes([block[0],block[1]]);
+    let color1 = u16::from_le_bytes([block[2],block[3]]);
+    let (c0,c1) = (expand_565(color0),expand_565(color1));
+    let mut palette = [[0u8;4];4];
+    for channel in 0..3{
+        palette[0][channel] = c0[channel] as u8;
+        palette[1][channel] = c1[channel] as u8;
+        if four_colors || color0 > color1{
+            palette[2][channel] = ((2 * c0[channel] + c1[channel]) / 3) as u8;
+            palette[3][channel] = ((c0[channel] + 2 * c1[channel]) / 3) as u8;
+        }else{
+            palette[2][channel] = ((c0[channel] + c1[channel]) / 2) as u8;
+        }
+    }
+    palette[0][3] = 255;
+    palette[1][3] = 255;
+    palette[2][3] = 255;
+    //The fourth color of the three color mode is transparent black.
+    palette[3][3] = if four_colors || color0 > color1{255}else{0};
+    let indices = u32::from_le_bytes([block[4],block[5],block[6],block[7]]);
+    for (i,texel) in texels.iter_mut().enumerate(){
+        *texel = palette[((indices >> (i * 2)) & 0x3) as usize];
+    }
+}
+///The 3 bit indices of a BC4 channel, packed into 48 bits after the two endpoints.
+fn channel_indices(block : &[u8])->u64{
+    let mut indices = 0u64;
+    for (i,byte) in block[2..8].iter().enumerate(){indices |= (*byte as u64) << (i * 8)}
+    return indices;
+}
+fn decode_unorm_channel(block : &[u8])->[u8;16]{
+    let (a0,a1) = (block[0] as u32,block[1] as u32);
+    let mut palette = [a0,a1,0,0,0,0,0,255];
+    if a0 > a1{
+        for i in 1..7{palette[i + 1] = ((7 - i as u32) * a0 + i as u32 * a1) / 7}
+    }else{
+        for i in 1..5{palette[i + 1] = ((5 - i as u32) * a0 + i as u32 * a1) / 5}
+    }
+    let indices = channel_indices(block);
+    let mut values = [0u8;16];
+    for (i,value) in values.iter_mut().enumerate(){*value = palette[((indices >> (i * 3)) & 0x7) as usize] as u8}
+    return values;
+}
+fn decode_snorm_channel(block : &[u8])->[i8;16]{
+    //-128 and -127 both decode to -1.
+    let (a0,a1) = ((block[0] as i8).max(-127) as i32,(block[1] as i8).max(-127) as i32);
+    let mut palette = [a0,a1,0,0,0,0,-127,127];
+    if a0 > a1{
+        for i in 1..7{palette[i + 1] = ((7 - i as i32) * a0 + i as i32 * a1) / 7}
+    }else{
+        for i in 1..5{palette[i + 1] = ((5 - i as i32) * a0 + i as i32 * a1) / 5}
+    }
+    let indices = channel_indices(block);
+    let mut values = [0i8;16];
+    for (i,value) in values.iter_mut().enumerate(){*value = palette[((indices >> (i * 3)) & 0x7) as usize] as i8}
+    return values;
+}
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    ///A BC4 channel block with every texel using the same index.
+    fn channel(a0 : u8 , a1 : u8 , index : u64)->[u8;8]{
+        let mut indices = 0u64;
+        for i in 0..16{indices |= index << (i * 3)}
+        let bytes = indices.to_le_bytes();
+        return [a0,a1,bytes[0],bytes[1],bytes[2],bytes[3],bytes[4],bytes[5]];
+    }
+
+    #[test]
+    fn bc1_solid_color(){
+        //Red in 565 with every index selecting the first endpoint.
+        let block = [0x00,0xF8,0x00,0x00,0x00,0x00,0x00,0x00];
+        let pixels = decompress(Format::BC1_RGBA_UNORM_BLOCK, 4, 4, &block).unwrap();
+        assert!(pixels.chunks_exact(4).all(|p|p == [255,0,0,255]));
+    }
+    #[test]
+    fn bc1_three_color_mode(){
+        //color0 <= color1 selects the three color mode, index 3 is transparent black and index 2 the midpoint.
+        let block = [0x00,0x00,0x1F,0x00,0xFF,0xAA,0x00,0x00];
+        let pixels = decompress(Format::BC1_RGBA_UNORM_BLOCK, 4, 4, &block).unwrap();
+        assert_eq!(&pixels[0..4], &[0,0,0,0]);
+        assert_eq!(&pixels[16..20], &[0,0,127,255]);
+        assert_eq!(&pixels[32..36], &[0,0,0,255]);
+        let opaque = decompress(Format::BC1_RGB_UNORM_BLOCK, 4, 4, &block).unwrap();
+        assert_eq!(&opaque[0..4], &[0,0,0,255]);
+    }
+    #[test]
+    fn bc2_explicit_alpha(){
+        let mut block = [0u8;16];
+        block[0] = 0xF0;
+        block[8..10].copy_from_slice(&0xFFFFu16.to_le_bytes());
+        let pixels = decompress(Format::BC2_UNORM_BLOCK, 4, 4, &block).unwrap();
+        assert_eq!(&pixels[0..4], &[255,255,255,0]);
+        assert_eq!(&pixels[4..8], &[255,255,255,255]);
+        assert_eq!(&pixels[8..12], &[255,255,255,0]);
+    }
+    #[test]
+    fn bc3_and_bc4_interpolate_channels(){
+        //With a0 > a1 index 2 is (6 * a0 + a1) / 7.
+        let alpha = channel(140, 0, 2);
+        let mut block = [0u8;16];
+        block[0..8].copy_from_slice(&alpha);
+        let pixels = decompress(Format::BC3_UNORM_BLOCK, 4, 4, &block).unwrap();
+        assert!(pixels.chunks_exact(4).all(|p|p == [0,0,0,120]));
+        //With a0 <= a1 indices 6 and 7 are the extremes.
+        let pixels = decompress(Format::BC4_UNORM_BLOCK, 4, 4, &channel(10, 20, 7)).unwrap();
+        assert!(pixels.chunks_exact(4).all(|p|p == [255,0,0,255]));
+        let pixels = decompress(Format::BC4_UNORM_BLOCK, 4, 4, &channel(10, 20, 1)).unwrap();
+        assert!(pixels.chunks_exact(4).all(|p|p == [20,0,0,255]));
+    }
+    #[test]
+    fn bc4_and_bc5_snorm(){
+        //-128 clamps to -127, index 1 selects it.
+        let pixels = decompress(Format::BC4_SNORM_BLOCK, 4, 4, &channel(100, 0x80, 1)).unwrap();
+        assert!(pixels.chunks_exact(4).all(|p|p == [0x81,0,0,127]));
+        let mut block = [0u8;16];
+        block[0..8].copy_from_slice(&channel(100, 0, 0));
+        block[8..16].copy_from_slice(&channel(50, 0, 0));
+        let pixels = decompress(Format::BC5_SNORM_BLOCK, 4, 4, &block).unwrap();
+        assert!(pixels.chunks_exact(4).all(|p|p == [100,50,0,127]));
+    }
+    #[test]
+    fn bc5_two_channels(){
+        let mut block = [0u8;16];
+        block[0..8].copy_from_slice(&channel(200, 0, 0));
+        block[8..16].copy_from_slice(&channel(0, 90, 1));
+        let pixels = decompress(Format::BC5_UNORM_BLOCK, 4, 4, &block).unwrap();
+        assert!(pixels.chunks_exact(4).all(|p|p == [200,90,0,255]));
+    }
+    #[test]
+    fn edge_blocks_are_cropped(){
+        //A 5x2 image needs two blocks in a row, the second one only fills the first column.
+        let mut data = [0u8;16];
+        data[0..2].copy_from_slice(&0xF800u16.to_le_bytes());
+        data[8..10].copy_from_slice(&0x001Fu16.to_le_bytes());
+        let pixels = decompress(Format::BC1_RGBA_UNORM_BLOCK, 5, 2, &data).unwrap();
+        assert_eq!(pixels.len(), 5 * 2 * 4);
+        assert_eq!(&pixels[12..16], &[255,0,0,255]);
+        assert_eq!(&pixels[16..20], &[0,0,255,255]);
+        assert_eq!(&pixels[36..40], &[0,0,255,255]);
+    }
+    #[test]
+    fn short_data_and_unsupported_formats(){
+        assert!(decompress(Format::BC1_RGBA_UNORM_BLOCK, 8, 4, &[0u8;8]).is_none());
+        assert!(decompress(Format::BC7_UNORM_BLOCK, 4, 4, &[0u8;16]).is_none());
+        assert!(decompressed_format(Format::BC6H_UFLOAT_BLOCK).is_none());
+    }
+}
@@ -0,0 +1,340 @@
+use std::ffi::CStr;
+
+use ash::{Device, Instance, version::{DeviceV1_0, InstanceV1_0, InstanceV1_1}, vk::{self, CommandBuffer, DescriptorBindingFlags, DescriptorBufferInfo, DescriptorImageInfo, DescriptorPool, DescriptorPoolCreateFlags, DescriptorPoolCreateInfo, DescriptorPoolSize, DescriptorSet, DescriptorSetAllocateInfo, DescriptorSetLayout, DescriptorSetLayoutBinding, DescriptorSetLayoutBindingFlagsCreateInfo, DescriptorSetLayoutCreateFlags, DescriptorSetLayoutCreateInfo, DescriptorType, ExtDescriptorIndexingFn, ImageLayout, ImageView, PhysicalDevice, PhysicalDeviceDescriptorIndexingFeatures, PhysicalDeviceDescriptorIndexingProperties, PhysicalDeviceFeatures2, PhysicalDeviceProperties2, PipelineBindPoint, Sampler, ShaderStageFlags, StructureType, WriteDescriptorSet}};
+use log::{error,info,warn,debug};
+
+use super::{Buffer, PipelineLayout, Renderer, RendererError, deletion::Garbage};
+
+///The kind of resource a bindless handle refers to, each kind has its own binding and index space in the bindless set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BindlessKind{
+    ///Binding 0, `texture2D textures[]` in GLSL.
+    SampledImage,
+    ///Binding 1, `image2D images[]` in GLSL.
+    StorageImage,
+    ///Binding 2, `sampler samplers[]` in GLSL.
+    Sampler,
+    ///Binding 3, `buffer Data{...} buffers[]` in GLSL.
+    StorageBuffer,
+}
+const KINDS : [BindlessKind;4] = [BindlessKind::SampledImage,BindlessKind::StorageImage,BindlessKind::Sampler,BindlessKind::StorageBuffer];
+impl BindlessKind{
+    pub fn binding(&self)->u32{
+        match self{
+            BindlessKind::SampledImage=>return 0,
+            BindlessKind::StorageImage=>return 1,
+            BindlessKind::Sampler=>return 2,
+            BindlessKind::StorageBuffer=>return 3,
+        }
+    }
+    pub fn descriptor_type(&self)->DescriptorType{
+        match self{
+            BindlessKind::SampledImage=>return DescriptorType::SAMPLED_IMAGE,
+            BindlessKind::StorageImage=>return DescriptorType::STORAGE_IMAGE,
+            BindlessKind::Sampler=>return DescriptorType::SAMPLER,
+            BindlessKind::StorageBuffer=>return DescriptorType::STORAGE_BUFFER,
+        }
+    }
+    ///The kind that uses a binding of the bindless set.
+    pub fn from_binding(binding : u32)->Option<Self>{
+        return KINDS.get(binding as usize).copied();
+    }
+}
+///The index of a resource in the bindless set, stable until the handle is released.
+///Handles can't be copied, releasing one gives up the index for good.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct BindlessHandle{
+    kind : BindlessKind,
+    index : u32,
+    ///The generation of the index when it was handed out, so handles from before it was recycled are rejected.
+    generation : u32,
+}
+impl BindlessHandle{
+    pub fn kind(&self)->BindlessKind{
+        return self.kind;
+    }
+    ///The index into the array of the handle's kind, usually passed to shaders in push constants.
+    pub fn index(&self)->u32{
+        return self.index;
+    }
+}
+///The descriptor indexing features and limits of a GPU that supports bindless resources.
+pub(crate) struct BindlessSupport{
+    ///Set when the device is older than Vulkan 1.2 and `VK_EXT_descriptor_indexing` has to be enabled.
+    pub(crate) needs_extension : bool,
+    ///The features to enable, chained into the device create info.
+    pub(crate) features : PhysicalDeviceDescriptorIndexingFeatures,
+    capacities : [u32;4],
+}
+///The number of descriptors of each kind the bindless set holds, lowered to the limits of the GPU.
+const CAPACITIES : [u32;4] = [16384,4096,1024,16384];
+///Checks whether a GPU supports bindless resources, Vulkan 1.1 is required to query descriptor indexing support.
+pub(crate) unsafe fn query_support(instance : &Instance , instance_version : u32 , physical_device : PhysicalDevice)->Option<BindlessSupport>{
+    let properties = instance.get_physical_device_properties(physical_device);
+    let version_1_1 = vk::make_version(1, 1, 0);
+    if instance_version < version_1_1 || properties.api_version < version_1_1{
+        debug!("Bindless resources need Vulkan 1.1 to query descriptor indexing support.");
+        return None;
+    }
+    let needs_extension = instance_version.min(properties.api_version) < vk::make_version(1, 2, 0);
+    if needs_extension{
+        let extensions = instance.enumerate_device_extension_properties(physical_device).unwrap_or_default();
+        if !extensions.iter().any(|e|CStr::from_ptr(e.extension_name.as_ptr()) == ExtDescriptorIndexingFn::name()){
+            debug!("The GPU supports neither Vulkan 1.2 nor {}.",ExtDescriptorIndexingFn::name().to_string_lossy());
+            return None;
+        }
+    }
+    let mut supported = PhysicalDeviceDescriptorIndexingFeatures::default();
+    let mut features2 = PhysicalDeviceFeatures2{
+        s_type : StructureType::PHYSICAL_DEVICE_FEATURES_2,
+        p_next : &mut supported as *mut PhysicalDeviceDescriptorIndexingFeatures as *mut std::ffi::c_void,
+        features : Default::default(),
+    };
+    instance.get_physical_device_features2(physical_device, &mut features2);
+    let required = [
+        ("runtimeDescriptorArray",supported.runtime_descriptor_array),
+        ("descriptorBindingPartiallyBound",supported.descriptor_binding_partially_bound),
+        ("descriptorBindingUpdateUnusedWhilePending",supported.descriptor_binding_update_unused_while_pending),
+        ("descriptorBindingSampledImageUpdateAfterBind",supported.descriptor_binding_sampled_image_update_after_bind),
+        ("descriptorBindingStorageImageUpdateAfterBind",supported.descriptor_binding_storage_image_update_after_bind),
+        ("descriptorBindingStorageBufferUpdateAfterBind",supported.descriptor_binding_storage_buffer_update_after_bind),
+    ];
+    if let Some((name,_)) = required.iter().find(|(_,supported)|*supported == vk::FALSE){
+        debug!("The GPU doesn't support the descriptor indexing feature {}.",name);
+        return None;
+    }
+    //Non uniform indexing is enabled when available, shaders that need it won't run without it either way.
+    let features = PhysicalDeviceDescriptorIndexingFeatures{
+        s_type : StructureType::PHYSICAL_DEVICE_DESCRIPTOR_INDEXING_FEATURES,
+        p_next : std::ptr::null_mut(),
+        runtime_descriptor_array : vk::TRUE,
+        descriptor_binding_partially_bound : vk::TRUE,
+        descriptor_binding_update_unused_while_pending : vk::TRUE,
+        descriptor_binding_sampled_image_update_after_bind : vk::TRUE,
+        descriptor_binding_storage_image_update_after_bind : vk::TRUE,
+        descriptor_binding_storage_buffer_update_after_bind : vk::TRUE,
+        shader_sampled_image_array_non_uniform_indexing : supported.shader_sampled_image_array_non_uniform_indexing,
+        shader_storage_image_array_non_uniform_indexing : supported.shader_storage_image_array_non_uniform_indexing,
+        shader_storage_buffer_array_non_uniform_indexing : supported.shader_storage_buffer_array_non_uniform_indexing,
+        ..Default::default()
+    };
+    let mut limits = PhysicalDeviceDescriptorIndexingProperties::default();
+    let mut properties2 = PhysicalDeviceProperties2{
+        s_type : StructureType::PHYSICAL_DEVICE_PROPERTIES_2,
+        p_next : &mut limits as *mut PhysicalDeviceDescriptorIndexingProperties as *mut std::ffi::c_void,
+        properties : Default::default(),
+    };
+    instance.get_physical_device_properties2(physical_device, &mut properties2);
+    //The set is visible to every stage, so every kind also counts against the per stage limit of all resources.
+    let per_kind = limits.max_per_stage_update_after_bind_resources / KINDS.len() as u32;
+    let capacities = [
+        CAPACITIES[0].min(limits.max_descriptor_set_update_after_bind_sampled_images).min(limits.max_per_stage_descriptor_update_after_bind_sampled_images).min(per_kind),
+        CAPACITIES[1].min(limits.max_descriptor_set_update_after_bind_storage_images).min(limits.max_per_stage_descriptor_update_after_bind_storage_images).min(per_kind),
+        CAPACITIES[2].min(limits.max_descriptor_set_update_after_bind_samplers).min(limits.max_per_stage_descriptor_update_after_bind_samplers).min(per_kind),
+        CAPACITIES[3].min(limits.max_descriptor_set_update_after_bind_storage_buffers).min(limits.max_per_stage_descriptor_update_after_bind_storage_buffers).min(per_kind),
+    ];
+    return Some(BindlessSupport{needs_extension , features , capacities});
+}
+///Hands out the indices of one binding, released indices are reused once the GPU is done with them.
+struct IndexTable{
+    capacity : u32,
+    ///Indices below this have been handed out at least once.
+    next : u32,
+    free : Vec<u32>,
+    ///The generation of each handed out index, bumped when its handle is released.
+    generations : Vec<u32>,
+}
+impl IndexTable{
+    ///Hands out an index and its current generation.
+    fn acquire(&mut self)->Option<(u32,u32)>{
+        let index = match self.free.pop(){
+            Some(index)=>index,
+            None if self.next < self.capacity=>{self.next += 1;self.generations.push(0);self.next - 1}
+            None=>return None,
+        };
+        return Some((index,self.generations[index as usize]));
+    }
+    ///Invalidates the handle of an index, false if the handle was already released or is stale.
+    fn release(&mut self , index : u32 , generation : u32)->bool{
+        match self.generations.get_mut(index as usize){
+            Some(current) if *current == generation=>{
+                *current = current.wrapping_add(1);
+                return true;
+            }
+            _=>return false,
+        }
+    }
+}
+///The global update after bind descriptor set of bindless mode.
+pub(crate) struct BindlessSet{
+    layout : DescriptorSetLayout,
+    pool : DescriptorPool,
+    set : DescriptorSet,
+    tables : [IndexTable;4],
+}
+impl BindlessSet{
+    pub(crate) unsafe fn new(device : &Device , support : &BindlessSupport)->Result<Self,RendererError>{
+        let bindings = KINDS.iter().map(|kind|DescriptorSetLayoutBinding{
+            binding : kind.binding(),
+            descriptor_type : kind.descriptor_type(),
+            descriptor_count : support.capacities[kind.binding() as usize],
+            stage_flags : ShaderStageFlags::ALL,
+            p_immutable_samplers : std::ptr::null(),
+        }).collect::<Vec<_>>();
+        let binding_flags = [DescriptorBindingFlags::UPDATE_AFTER_BIND | DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING | DescriptorBindingFlags::PARTIALLY_BOUND;4];
+        let binding_flags_create_info = DescriptorSetLayoutBindingFlagsCreateInfo{
+            s_type : StructureType::DESCRIPTOR_SET_LAYOUT_BINDING_FLAGS_CREATE_INFO,
+            p_next : std::ptr::null(),
+            binding_count : binding_flags.len() as u32,
+            p_binding_flags : binding_flags.as_ptr(),
+        };
+        let layout_create_info = DescriptorSetLayoutCreateInfo{
+            s_type : StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
+            p_next : &binding_flags_create_info as *const DescriptorSetLayoutBindingFlagsCreateInfo as *const std::ffi::c_void,
+            flags : DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL,
+            binding_count : bindings.len() as u32,
+            p_bindings : bindings.as_ptr(),
+        };
+        let layout = device.create_descriptor_set_layout(&layout_create_info, None).map_err(|e|{
+            error!("Failed to create the bindless descriptor set layout, {}.",e);
+            RendererError::CreateDescriptorSetLayout(e)
+        })?;
+        let pool_sizes = bindings.iter().map(|b|DescriptorPoolSize{ty : b.descriptor_type , descriptor_count : b.descriptor_count}).collect::<Vec<_>>();
+        let pool_create_info = DescriptorPoolCreateInfo{
+            s_type : StructureType::DESCRIPTOR_POOL_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : DescriptorPoolCreateFlags::UPDATE_AFTER_BIND,
+            max_sets : 1,
+            pool_size_count : pool_sizes.len() as u32,
+            p_pool_sizes : pool_sizes.as_ptr(),
+        };
+        let mut bindless = Self{
+            layout , pool : DescriptorPool::null() , set : DescriptorSet::null(),
+            tables : support.capacities.map(|capacity|IndexTable{capacity , next : 0 , free : vec!() , generations : vec!()}),
+        };
+        let result = (||->Result<(),vk::Result>{
+            bindless.pool = device.create_descriptor_pool(&pool_create_info, None)?;
+            let allocate_info = DescriptorSetAllocateInfo{
+                s_type : StructureType::DESCRIPTOR_SET_ALLOCATE_INFO,
+                p_next : std::ptr::null(),
+                descriptor_pool : bindless.pool,
+                descriptor_set_count : 1,
+                p_set_layouts : &bindless.layout,
+            };
+            bindless.set = device.allocate_descriptor_sets(&allocate_info)?[0];
+            return Ok(());
+        })();
+        if let Err(e) = result{
+            error!("Failed to allocate the bindless descriptor set, {}.",e);
+            bindless.destroy(device);
+            return Err(RendererError::AllocateDescriptorSet(e));
+        }
+        info!("Created the bindless descriptor set with room for {:?} sampled images, storage images, samplers and storage buffers.",support.capacities);
+        return Ok(bindless);
+    }
+    pub(crate) fn pool(&self)->DescriptorPool{
+        return self.pool;
+    }
+    pub(crate) fn layout(&self)->DescriptorSetLayout{
+        return self.layout;
+    }
+    ///Returns an index to its table, called once the GPU can no longer be using it.
+    pub(crate) fn recycle(&mut self , handle : BindlessHandle){
+        self.tables[handle.kind.binding() as usize].free.push(handle.index);
+    }
+    ///Checks that the bindings a shader declares in the bindless set match it.
+    pub(crate) fn validate_bindings<I : Iterator<Item = (u32,DescriptorType,u32)>>(&self , mut bindings : I)->Result<(),String>{
+        return bindings.try_for_each(|(binding,descriptor_type,count)|{
+            let kind = BindlessKind::from_binding(binding).ok_or_else(||format!("binding {} is not part of the bindless set",binding))?;
+            if kind.descriptor_type() != descriptor_type{return Err(format!("binding {} of the bindless set is a {:?} instead of a {:?}",binding,descriptor_type,kind.descriptor_type()))}
+            let capacity = self.tables[binding as usize].capacity;
+            if count > capacity{return Err(format!("binding {} of the bindless set holds {} descriptors, not {}",binding,capacity,count))}
+            return Ok(());
+        });
+    }
+    pub(crate) unsafe fn destroy(&self , device : &Device){
+        device.destroy_descriptor_pool(self.pool, None);
+        device.destroy_descriptor_set_layout(self.layout, None);
+    }
+}
+impl Renderer{
+    fn bindless_mut(&mut self)->Result<&mut BindlessSet,RendererError>{
+        return self.bindless.as_mut().ok_or_else(||{
+            error!("Bindless mode is not enabled.");
+            RendererError::BindlessUnavailable
+        });
+    }
+    ///Whether bindless mode was requested with `RendererBuilder::bindless` and is supported by the GPU.
+    pub fn bindless_enabled(&self)->bool{
+        return self.bindless.is_some();
+    }
+    ///The global bindless descriptor set, see `Renderer::bind_bindless_set`.
+    pub fn bindless_set(&self)->Option<DescriptorSet>{
+        return self.bindless.as_ref().map(|bindless|bindless.set);
+    }
+    pub fn bindless_set_layout(&self)->Option<DescriptorSetLayout>{
+        return self.bindless.as_ref().map(|bindless|bindless.layout);
+    }
+    ///The number of descriptors of a kind the bindless set holds, 0 when bindless mode is not enabled.
+    pub fn bindless_capacity(&self , kind : BindlessKind)->u32{
+        return self.bindless.as_ref().map_or(0,|bindless|bindless.tables[kind.binding() as usize].capacity);
+    }
+    ///Binds the bindless set to the set number the pipeline layout uses it at, does nothing if the layout doesn't use it.
+    ///# Safety
+    ///The command buffer must be recording.
+    pub unsafe fn bind_bindless_set(&self , command_buffer : CommandBuffer , bind_point : PipelineBindPoint , layout : &PipelineLayout){
+        if let (Some(bindless),Some(set)) = (&self.bindless,layout.bindless_set()){
+            self.device.cmd_bind_descriptor_sets(command_buffer, bind_point, layout.handle(), set, &[bindless.set], &[]);
+        }
+    }
+    fn register(&mut self , kind : BindlessKind , image_info : Option<DescriptorImageInfo> , buffer_info : Option<DescriptorBufferInfo>)->Result<BindlessHandle,RendererError>{
+        let bindless = self.bindless_mut()?;
+        let (index,generation) = bindless.tables[kind.binding() as usize].acquire().ok_or_else(||{
+            error!("The bindless set has no room for another {:?}.",kind);
+            RendererError::BindlessSetFull(kind)
+        })?;
+        let write = WriteDescriptorSet{
+            s_type : StructureType::WRITE_DESCRIPTOR_SET,
+            p_next : std::ptr::null(),
+            dst_set : bindless.set,
+            dst_binding : kind.binding(),
+            dst_array_element : index,
+            descriptor_count : 1,
+            descriptor_type : kind.descriptor_type(),
+            p_image_info : image_info.as_ref().map_or(std::ptr::null(),|info|info as *const DescriptorImageInfo),
+            p_buffer_info : buffer_info.as_ref().map_or(std::ptr::null(),|info|info as *const DescriptorBufferInfo),
+            p_texel_buffer_view : std::ptr::null(),
+        };
+        //The index isn't used by any pending command buffer, so it can be written while the set is bound.
+        unsafe{self.device.update_descriptor_sets(&[write], &[])};
+        return Ok(BindlessHandle{kind , index , generation});
+    }
+    ///Adds an image view that is sampled in `layout` to the bindless set.
+    pub fn register_sampled_image(&mut self , view : ImageView , layout : ImageLayout)->Result<BindlessHandle,RendererError>{
+        return self.register(BindlessKind::SampledImage, Some(DescriptorImageInfo{sampler : Sampler::null() , image_view : view , image_layout : layout}), None);
+    }
+    ///Adds an image view that is accessed as a storage image in the general layout to the bindless set.
+    pub fn register_storage_image(&mut self , view : ImageView)->Result<BindlessHandle,RendererError>{
+        return self.register(BindlessKind::StorageImage, Some(DescriptorImageInfo{sampler : Sampler::null() , image_view : view , image_layout : ImageLayout::GENERAL}), None);
+    }
+    pub fn register_sampler(&mut self , sampler : Sampler)->Result<BindlessHandle,RendererError>{
+        return self.register(BindlessKind::Sampler, Some(DescriptorImageInfo{sampler , image_view : ImageView::null() , image_layout : ImageLayout::UNDEFINED}), None);
+    }
+    ///Adds a whole storage buffer to the bindless set.
+    pub fn register_storage_buffer<T : Copy>(&mut self , buffer : &Buffer<T>)->Result<BindlessHandle,RendererError>{
+        return self.register(BindlessKind::StorageBuffer, None, Some(DescriptorBufferInfo{buffer : buffer.handle() , offset : 0 , range : buffer.size_bytes()}));
+    }
+    ///Releases a handle, its index is reused once every frame and dispatch submitted before the release has completed.
+    ///The resource itself isn't destroyed and should be released before it is.
+    pub fn release_bindless(&mut self , handle : BindlessHandle){
+        let released = match &mut self.bindless{
+            Some(bindless)=>bindless.tables[handle.kind.binding() as usize].release(handle.index, handle.generation),
+            None=>false,
+        };
+        if !released{
+            warn!("Ignoring the release of stale or unknown bindless handle {:?}.",handle);
+            return;
+        }
+        self.destroy_deferred(Garbage::BindlessIndex(handle));
+    }
+}
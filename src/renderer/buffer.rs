@@ -0,0 +1,137 @@
+use std::marker::PhantomData;
+
+use ash::{version::DeviceV1_0, vk::{self, BufferCreateFlags, BufferCreateInfo, BufferUsageFlags, DeviceSize, SharingMode, StructureType}};
+use bytemuck::Pod;
+use log::{error,warn};
+
+use super::{Allocation, MemoryLocation, Renderer, RendererError, UploadDestination, UploadHandle, deletion::Garbage};
+
+///A buffer holding `len` elements of `T`, created with `Renderer::create_buffer` and destroyed with `Renderer::destroy_buffer`.
+///Reading, mapping and writing the contents needs `T : Pod`, plain data without padding like vertices, indices and `#[repr(C)]` uniform structs.
+pub struct Buffer<T : Copy>{
+    handle : vk::Buffer,
+    allocation : Option<Allocation>,
+    len : usize,
+    usage : BufferUsageFlags,
+    location : MemoryLocation,
+    _marker : PhantomData<T>,
+}
+impl<T : Copy> Buffer<T>{
+    pub fn handle(&self)->vk::Buffer{
+        return self.handle;
+    }
+    ///The number of elements.
+    pub fn len(&self)->usize{
+        return self.len;
+    }
+    pub fn is_empty(&self)->bool{
+        return self.len == 0;
+    }
+    pub fn size_bytes(&self)->DeviceSize{
+        return (self.len * std::mem::size_of::<T>()) as DeviceSize;
+    }
+    pub fn usage(&self)->BufferUsageFlags{
+        return self.usage;
+    }
+    pub fn location(&self)->MemoryLocation{
+        return self.location;
+    }
+}
+impl<T : Pod> Buffer<T>{
+    ///The mapped contents, `None` for device local buffers.
+    ///Memory that isn't host coherent must be invalidated with `Renderer::invalidate_buffer` before reading GPU writes.
+    pub fn mapped_slice(&self)->Option<&[T]>{
+        let mapped = self.allocation.as_ref()?.mapped_ptr()?;
+        return Some(unsafe{std::slice::from_raw_parts(mapped as *const T, self.len)});
+    }
+    ///The mapped contents, `None` for device local buffers.
+    ///Memory that isn't host coherent must be flushed with `Renderer::flush_buffer` after writing.
+    pub fn mapped_slice_mut(&mut self)->Option<&mut [T]>{
+        let mapped = self.allocation.as_ref()?.mapped_ptr()?;
+        return Some(unsafe{std::slice::from_raw_parts_mut(mapped as *mut T, self.len)});
+    }
+}
+impl<T : Copy> Drop for Buffer<T>{
+    fn drop(&mut self){
+        if self.handle != vk::Buffer::null(){warn!("A buffer of {} bytes was dropped without Renderer::destroy_buffer, its memory is leaked.",self.size_bytes())}
+    }
+}
+impl Renderer{
+    ///Creates a buffer for `len` elements.
+    ///Device local buffers get `TRANSFER_DST` usage so they can be written through the transfer queue.
+    pub fn create_buffer<T : Copy>(&mut self , len : usize , usage : BufferUsageFlags , location : MemoryLocation)->Result<Buffer<T>,RendererError>{
+        let usage = if location == MemoryLocation::GpuOnly{usage | BufferUsageFlags::TRANSFER_DST}else{usage};
+        let size = ((len * std::mem::size_of::<T>()) as DeviceSize).max(1);
+        let buffer_create_info = BufferCreateInfo{
+            s_type : StructureType::BUFFER_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : BufferCreateFlags::empty(),
+            size,
+            usage,
+            sharing_mode : SharingMode::EXCLUSIVE,
+            queue_family_index_count : 0,
+            p_queue_family_indices : std::ptr::null(),
+        };
+        let handle = unsafe{self.device.create_buffer(&buffer_create_info, None)}.map_err(|e|{
+            error!("Failed to create a buffer of {} bytes, {}.",size,e);
+            RendererError::CreateBuffer(e)
+        })?;
+        let allocation = match self.allocator.allocate_for_buffer(handle, location){
+            Ok(allocation)=>allocation,
+            Err(e)=>{
+                unsafe{self.device.destroy_buffer(handle, None)};
+                return Err(e);
+            }
+        };
+        return Ok(Buffer{handle , allocation : Some(allocation) , len , usage , location , _marker : PhantomData});
+    }
+    ///Creates a buffer holding `data`, device local buffers are filled through a staging upload that the destination queue waits for.
+    pub fn create_buffer_with_data<T : Pod>(&mut self , data : &[T] , usage : BufferUsageFlags , location : MemoryLocation , destination : UploadDestination)->Result<Buffer<T>,RendererError>{
+        let buffer = self.create_buffer(data.len(), usage, location)?;
+        if let Err(e) = self.write_buffer(&buffer, 0, data, destination){
+            self.destroy_buffer(buffer);
+            return Err(e);
+        }
+        return Ok(buffer);
+    }
+    ///Writes `data` starting at element `offset`.
+    ///Mapped buffers are written and flushed directly and `None` is returned.
+    ///Device local buffers are written through a staging upload, the returned handle completes once the destination queue can read the data.
+    pub fn write_buffer<T : Pod>(&mut self , buffer : &Buffer<T> , offset : usize , data : &[T] , destination : UploadDestination)->Result<Option<UploadHandle>,RendererError>{
+        if offset + data.len() > buffer.len{
+            error!("Writing {} elements at offset {} overflows a buffer of {} elements.",data.len(),offset,buffer.len);
+            return Err(RendererError::BufferOverflow{offset , len : data.len() , capacity : buffer.len});
+        }
+        let element_size = std::mem::size_of::<T>();
+        let bytes : &[u8] = bytemuck::cast_slice(data);
+        let byte_offset = (offset * element_size) as DeviceSize;
+        let allocation = buffer.allocation.as_ref().expect("live buffers have an allocation");
+        match allocation.mapped_ptr(){
+            Some(mapped)=>{
+                unsafe{std::ptr::copy_nonoverlapping(bytes.as_ptr(), mapped.add(byte_offset as usize), bytes.len())};
+                self.allocator.flush(allocation, byte_offset, bytes.len() as DeviceSize)?;
+                return Ok(None);
+            }
+            None=>return self.upload_to_buffer(bytes, buffer.handle, byte_offset, destination).map(Some),
+        }
+    }
+    ///Makes CPU writes through `Buffer::mapped_slice_mut` visible to the GPU, only needed for memory that isn't host coherent.
+    pub fn flush_buffer<T : Copy>(&self , buffer : &Buffer<T>)->Result<(),RendererError>{
+        match &buffer.allocation{
+            Some(allocation)=>return self.allocator.flush(allocation, 0, buffer.size_bytes()),
+            None=>return Ok(()),
+        }
+    }
+    ///Makes GPU writes visible to `Buffer::mapped_slice`, only needed for memory that isn't host coherent.
+    pub fn invalidate_buffer<T : Copy>(&self , buffer : &Buffer<T>)->Result<(),RendererError>{
+        match &buffer.allocation{
+            Some(allocation)=>return self.allocator.invalidate(allocation, 0, buffer.size_bytes()),
+            None=>return Ok(()),
+        }
+    }
+    ///Destroys the buffer once the frames and uploads submitted so far have completed, so it may still be in use by the GPU.
+    pub fn destroy_buffer<T : Copy>(&mut self , mut buffer : Buffer<T>){
+        let handle = std::mem::replace(&mut buffer.handle, vk::Buffer::null());
+        self.destroy_deferred(Garbage::Buffer{buffer : handle , allocation : buffer.allocation.take()});
+    }
+}
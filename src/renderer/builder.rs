@@ -0,0 +1,198 @@
+use std::ffi::{CStr, CString};
+
+use ash::vk::{ColorSpaceKHR, Extent2D, Format, PhysicalDeviceFeatures, PhysicalDeviceType, PresentModeKHR, SampleCountFlags, SurfaceFormatKHR};
+use winit::window::Window;
+
+use super::{DeviceSelector, Presentation, Renderer, RendererError};
+
+///A choice the renderer makes from the values the device supports.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Preference<T>{
+    ///Use the first supported value of the list, the renderer picks a supported fallback when none of them are supported.
+    Prefer(Vec<T>),
+    ///Use exactly this value, renderer creation fails when it is not supported.
+    Require(T),
+}
+impl<T : Copy + PartialEq> Preference<T>{
+    ///Selects a value using `is_supported`.
+    ///Returns `Ok(None)` when none of the preferred values are supported and `Err` with the required value when it is not supported.
+    pub(crate) fn select<F : Fn(&T)->bool>(&self , is_supported : F)->Result<Option<T>,T>{
+        match self{
+            Preference::Prefer(values)=>return Ok(values.iter().copied().find(|v|is_supported(v))),
+            Preference::Require(value)=>if is_supported(value){return Ok(Some(*value))}else{return Err(*value)},
+        }
+    }
+}
+///The number of images requested for the swapchain.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SwapchainImageCount{
+    ///The minimum image count of the surface plus the given amount, clamped to the maximum image count.
+    MinimumPlus(u32),
+    ///The given amount, clamped to the image count range of the surface.
+    Prefer(u32),
+    ///Exactly the given amount, renderer creation fails when the surface doesn't support it.
+    Require(u32),
+}
+///How the passes of the frame graph begin and end rendering.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderingBackend{
+    ///Render pass and framebuffer objects, supported by every driver.
+    RenderPass,
+    ///`vkCmdBeginRendering` with the attachments given while recording, on Vulkan 1.3 or `VK_KHR_dynamic_rendering`.
+    ///Render passes are used when the GPU supports neither.
+    DynamicRendering,
+}
+///Every choice the renderer makes during creation.
+///The defaults match the renderer's behaviour before any configuration was possible.
+#[derive(Clone, Debug)]
+pub struct RendererConfig{
+    ///The present mode, ignored by headless renderers. FIFO is used when none of the preferred modes are available.
+    pub present_mode : Preference<PresentModeKHR>,
+    ///The swapchain format, the first format reported by the surface is used when none of the preferred formats are available.
+    ///Headless renderers only use the format of each entry for their offscreen color image.
+    pub surface_format : Preference<SurfaceFormatKHR>,
+    ///The depth buffer format, optimal tiling is tried for every format before linear tiling.
+    pub depth_format : Preference<Format>,
+    ///The sample count of the depth buffer and the default frame graph's color attachment, which is resolved into the color image.
+    ///A single sample is used when none of the preferred counts are available, it can be changed later with `Renderer::set_sample_count`.
+    pub sample_count : Preference<SampleCountFlags>,
+    ///The number of swapchain images, ignored by headless renderers.
+    pub swapchain_image_count : SwapchainImageCount,
+    ///The preferred GPU types, earlier types score higher during GPU selection.
+    ///Any supported GPU can be selected when none of the preferred types are available.
+    pub device_type : Preference<PhysicalDeviceType>,
+    ///Selects a GPU directly instead of scoring them, `--gpu` and `GPENGINE_GPU` take precedence over it.
+    pub device_override : Option<DeviceSelector>,
+    ///The device extensions the GPU must support, they are enabled on the device.
+    pub required_extensions : Vec<CString>,
+    ///The features the GPU must support, they are enabled on the device.
+    pub required_features : PhysicalDeviceFeatures,
+    ///The number of frames that can be recorded while the GPU is still working on earlier frames, at least 1.
+    pub frames_in_flight : u32,
+    ///Loads the pipeline cache from the project data directory and saves it when the renderer is dropped.
+    pub persistent_pipeline_cache : bool,
+    ///Enables the global bindless descriptor set when the GPU supports descriptor indexing, the renderer works without it otherwise.
+    pub bindless : bool,
+    ///The preferred rendering backend, both draw exactly the same frames.
+    pub rendering_backend : RenderingBackend,
+}
+impl Default for RendererConfig{
+    fn default()->Self{
+        return Self{
+            present_mode : Preference::Prefer(vec!(PresentModeKHR::MAILBOX,PresentModeKHR::FIFO)),
+            surface_format : Preference::Prefer(vec!(
+                SurfaceFormatKHR{format : Format::R8G8B8A8_UNORM, color_space : ColorSpaceKHR::SRGB_NONLINEAR},
+                SurfaceFormatKHR{format : Format::B8G8R8A8_UNORM, color_space : ColorSpaceKHR::SRGB_NONLINEAR},
+            )),
+            depth_format : Preference::Prefer(vec!(
+                Format::D24_UNORM_S8_UINT,
+                Format::D16_UNORM,
+                Format::D16_UNORM_S8_UINT,
+                Format::D32_SFLOAT,
+                Format::D32_SFLOAT_S8_UINT,
+                Format::X8_D24_UNORM_PACK32,
+            )),
+            sample_count : Preference::Prefer(vec!(SampleCountFlags::TYPE_1)),
+            swapchain_image_count : SwapchainImageCount::MinimumPlus(1),
+            device_type : Preference::Prefer(vec!(PhysicalDeviceType::DISCRETE_GPU)),
+            device_override : None,
+            required_extensions : vec!(),
+            required_features : PhysicalDeviceFeatures::default(),
+            frames_in_flight : 2,
+            persistent_pipeline_cache : true,
+            bindless : false,
+            rendering_backend : RenderingBackend::RenderPass,
+        }
+    }
+}
+///Creates a `Renderer` with a custom `RendererConfig`.
+///```no_run
+///# use gpengine::renderer::{RendererBuilder, Preference};
+///# use ash::vk::PresentModeKHR;
+///# let event_loop = winit::event_loop::EventLoop::new();
+///# let window = winit::window::Window::new(&event_loop).unwrap();
+///let renderer = RendererBuilder::new()
+///    .present_mode(Preference::Require(PresentModeKHR::FIFO))
+///    .build(&window)?;
+///# Ok::<(),gpengine::RendererError>(())
+///```
+#[derive(Clone, Debug, Default)]
+pub struct RendererBuilder{
+    config : RendererConfig,
+}
+impl RendererBuilder{
+    pub fn new()->Self{
+        return Self::default();
+    }
+    ///Starts from an existing configuration instead of the defaults.
+    pub fn from_config(config : RendererConfig)->Self{
+        return Self{config};
+    }
+    pub fn present_mode(mut self , present_mode : Preference<PresentModeKHR>)->Self{
+        self.config.present_mode = present_mode;
+        return self;
+    }
+    pub fn surface_format(mut self , surface_format : Preference<SurfaceFormatKHR>)->Self{
+        self.config.surface_format = surface_format;
+        return self;
+    }
+    pub fn depth_format(mut self , depth_format : Preference<Format>)->Self{
+        self.config.depth_format = depth_format;
+        return self;
+    }
+    pub fn sample_count(mut self , sample_count : Preference<SampleCountFlags>)->Self{
+        self.config.sample_count = sample_count;
+        return self;
+    }
+    pub fn swapchain_image_count(mut self , swapchain_image_count : SwapchainImageCount)->Self{
+        self.config.swapchain_image_count = swapchain_image_count;
+        return self;
+    }
+    pub fn device_type(mut self , device_type : Preference<PhysicalDeviceType>)->Self{
+        self.config.device_type = device_type;
+        return self;
+    }
+    pub fn device_override(mut self , device_override : DeviceSelector)->Self{
+        self.config.device_override = Some(device_override);
+        return self;
+    }
+    ///Adds a device extension the GPU must support.
+    pub fn required_extension(mut self , extension : &CStr)->Self{
+        if !self.config.required_extensions.iter().any(|e|e.as_c_str() == extension){self.config.required_extensions.push(extension.to_owned())}
+        return self;
+    }
+    pub fn required_features(mut self , required_features : PhysicalDeviceFeatures)->Self{
+        self.config.required_features = required_features;
+        return self;
+    }
+    pub fn frames_in_flight(mut self , frames_in_flight : u32)->Self{
+        self.config.frames_in_flight = frames_in_flight;
+        return self;
+    }
+    pub fn persistent_pipeline_cache(mut self , persistent_pipeline_cache : bool)->Self{
+        self.config.persistent_pipeline_cache = persistent_pipeline_cache;
+        return self;
+    }
+    pub fn bindless(mut self , bindless : bool)->Self{
+        self.config.bindless = bindless;
+        return self;
+    }
+    pub fn rendering_backend(mut self , rendering_backend : RenderingBackend)->Self{
+        self.config.rendering_backend = rendering_backend;
+        return self;
+    }
+    ///The configuration the renderer will be created with.
+    pub fn config(&self)->&RendererConfig{
+        return &self.config;
+    }
+    ///Creates a renderer that draws to the given window.
+    pub fn build(self , window : &Window)->Result<Renderer,RendererError>{
+        return Renderer::create(Presentation::Window(window), self.config);
+    }
+    ///Creates a renderer without a window that draws to offscreen color and depth images of the given size.
+    pub fn build_headless(self , width : u32 , height : u32)->Result<Renderer,RendererError>{
+        let extent = Extent2D{width , height};
+        if width == 0 || height == 0{return Err(RendererError::InvalidExtent(extent))}
+        return Renderer::create(Presentation::Headless(extent), self.config);
+    }
+}
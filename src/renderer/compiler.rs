@@ -0,0 +1,226 @@
+//!Compiles GLSL and WGSL to SPIR-V with naga, enabled by the `shader-compiler` feature.
+
+use std::path::{Path, PathBuf};
+
+use ash::{version::InstanceV1_1, vk::{self, PhysicalDeviceDescriptorIndexingFeatures, PhysicalDeviceFeatures, PhysicalDeviceFeatures2, ShaderStageFlags, StructureType}};
+use log::{error,info};
+use naga::{back::spv, front::{glsl, wgsl}, valid::{Capabilities, ValidationFlags, Validator}};
+
+use super::{Renderer, RendererError, ShaderModule};
+
+///The language of a shader source file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShaderLanguage{
+    ///GLSL written for Vulkan, the stage isn't part of the language so it has to be given.
+    Glsl(ShaderStageFlags),
+    Wgsl,
+}
+impl ShaderLanguage{
+    ///Detects the language from the file extension, `.vert`, `.frag` and `.comp` are GLSL and `.wgsl` is WGSL.
+    ///A trailing `.glsl` is ignored, so `shader.vert.glsl` is a GLSL vertex shader.
+    pub fn from_path(path : &Path)->Option<Self>{
+        let path = if path.extension().is_some_and(|e|e == "glsl"){path.file_stem().map(Path::new)?}else{path};
+        match path.extension()?.to_str()?{
+            "vert"=>return Some(ShaderLanguage::Glsl(ShaderStageFlags::VERTEX)),
+            "frag"=>return Some(ShaderLanguage::Glsl(ShaderStageFlags::FRAGMENT)),
+            "comp"=>return Some(ShaderLanguage::Glsl(ShaderStageFlags::COMPUTE)),
+            "wgsl"=>return Some(ShaderLanguage::Wgsl),
+            _=>return None,
+        }
+    }
+}
+///What every Vulkan device supports, used by `compile_for_build` as the device isn't known yet.
+pub const BASELINE_CAPABILITIES : Capabilities = Capabilities::IMMEDIATES.union(Capabilities::EARLY_DEPTH_TEST);
+///Maps enabled device features to what naga allows shaders to use.
+fn device_capabilities(features : &PhysicalDeviceFeatures , indexing : Option<&PhysicalDeviceDescriptorIndexingFeatures>)->Capabilities{
+    let mut capabilities = BASELINE_CAPABILITIES;
+    let mut enable = |enabled : vk::Bool32 , capability : Capabilities|capabilities.set(capability, enabled == vk::TRUE);
+    enable(features.shader_float64, Capabilities::FLOAT64);
+    enable(features.shader_int64, Capabilities::SHADER_INT64);
+    enable(features.shader_clip_distance, Capabilities::CLIP_DISTANCES);
+    enable(features.shader_cull_distance, Capabilities::CULL_DISTANCE);
+    enable(features.geometry_shader, Capabilities::PRIMITIVE_INDEX);
+    enable(features.sample_rate_shading, Capabilities::MULTISAMPLED_SHADING);
+    enable(features.image_cube_array, Capabilities::CUBE_ARRAY_TEXTURES);
+    enable(features.dual_src_blend, Capabilities::DUAL_SOURCE_BLENDING);
+    enable(features.shader_storage_image_extended_formats, Capabilities::STORAGE_TEXTURE_16BIT_NORM_FORMATS);
+    enable(features.shader_sampled_image_array_dynamic_indexing, Capabilities::TEXTURE_AND_SAMPLER_BINDING_ARRAY);
+    enable(features.shader_uniform_buffer_array_dynamic_indexing, Capabilities::BUFFER_BINDING_ARRAY);
+    enable(features.shader_storage_image_array_dynamic_indexing, Capabilities::STORAGE_TEXTURE_BINDING_ARRAY);
+    enable(features.shader_storage_buffer_array_dynamic_indexing, Capabilities::STORAGE_BUFFER_BINDING_ARRAY);
+    if let Some(indexing) = indexing{
+        enable(indexing.shader_sampled_image_array_non_uniform_indexing, Capabilities::TEXTURE_AND_SAMPLER_BINDING_ARRAY_NON_UNIFORM_INDEXING);
+        enable(indexing.shader_storage_image_array_non_uniform_indexing, Capabilities::STORAGE_TEXTURE_BINDING_ARRAY_NON_UNIFORM_INDEXING);
+        enable(indexing.shader_storage_buffer_array_non_uniform_indexing, Capabilities::STORAGE_BUFFER_BINDING_ARRAY_NON_UNIFORM_INDEXING);
+    }
+    return capabilities;
+}
+///Formats a compile error as `name:line:column: message` when its location is known.
+fn compile_error(name : &str , location : Option<naga::SourceLocation> , message : &str)->RendererError{
+    let message = match location{
+        Some(location)=>format!("{}:{}:{}: {}",name,location.line_number,location.line_position,message),
+        None=>format!("{}: {}",name,message),
+    };
+    error!("Failed to compile shader, {}.",message);
+    return RendererError::CompileShader(message);
+}
+///Compiles one entry point of a shader to SPIR-V, `name` is used in error messages and is usually the file path.
+///GLSL shaders have a single entry point called `main`. Errors are logged with their file and line.
+pub fn compile(source : &str , language : ShaderLanguage , name : &str , entry_point : &str , capabilities : Capabilities)->Result<Vec<u32>,RendererError>{
+    let mut options = spv::Options::default();
+    let module = match language{
+        ShaderLanguage::Glsl(stage)=>{
+            let stage = match stage{
+                ShaderStageFlags::VERTEX=>naga::ShaderStage::Vertex,
+                ShaderStageFlags::FRAGMENT=>naga::ShaderStage::Fragment,
+                ShaderStageFlags::COMPUTE=>naga::ShaderStage::Compute,
+                _=>return Err(compile_error(name, None, &format!("the GLSL stage {:?} is not supported",stage))),
+            };
+            //Vulkan GLSL already uses the Vulkan coordinate space.
+            options.flags.remove(spv::WriterFlags::ADJUST_COORDINATE_SPACE);
+            glsl::Frontend::default().parse(&glsl::Options::from(stage), source).map_err(|e|{
+                let mut errors = e.errors.iter();
+                let first = errors.next().map(|e|(e.location(source),e.kind.to_string()));
+                for e in errors{compile_error(name, e.location(source), &e.kind.to_string());}
+                match first{
+                    Some((location,message))=>compile_error(name, location, &message),
+                    None=>compile_error(name, None, "unknown GLSL error"),
+                }
+            })?
+        }
+        ShaderLanguage::Wgsl=>wgsl::parse_str(source).map_err(|e|compile_error(name, e.location(source), e.message()))?,
+    };
+    let info = Validator::new(ValidationFlags::all(), capabilities).validate(&module).map_err(|e|{
+        //Validation errors wrap the reason in their sources.
+        let mut message = e.as_inner().to_string();
+        let mut cause = std::error::Error::source(e.as_inner());
+        while let Some(e) = cause{
+            message = format!("{}: {}",message,e);
+            cause = e.source();
+        }
+        compile_error(name, e.location(source), &message)
+    })?;
+    let shader_stage = match module.entry_points.iter().find(|e|e.name == entry_point){
+        Some(e)=>e.stage,
+        None=>{
+            let names = module.entry_points.iter().map(|e|e.name.as_str()).collect::<Vec<_>>();
+            return Err(compile_error(name, None, &format!("there is no entry point called {}, the entry points are {:?}",entry_point,names)));
+        }
+    };
+    let pipeline_options = spv::PipelineOptions{shader_stage , entry_point : entry_point.to_string()};
+    return spv::write_vec(&module, &info, &options, Some(&pipeline_options)).map_err(|e|compile_error(name, None, &e.to_string()));
+}
+///Reads and compiles one entry point of a shader file, the language is detected from its extension.
+pub fn compile_file<P : AsRef<Path>>(path : P , entry_point : &str , capabilities : Capabilities)->Result<Vec<u32>,RendererError>{
+    let path = path.as_ref();
+    let name = path.display().to_string();
+    let language = ShaderLanguage::from_path(path).ok_or_else(||compile_error(&name, None, "the extension isn't .vert, .frag, .comp or .wgsl"))?;
+    let source = std::fs::read_to_string(path).map_err(|e|compile_error(&name, None, &e.to_string()))?;
+    return compile(&source, language, &name, entry_point, capabilities);
+}
+///Compiles one entry point of a shader from a build script into `out_dir`, limited to `BASELINE_CAPABILITIES`.
+///The output is named after the file with `.spv` appended, entry points other than `main` are added before the extension, as in `shader.wgsl.vs_main.spv`.
+///Cargo is told to rerun the build script when the shader changes, and errors are also printed as cargo warnings.
+pub fn compile_for_build<P : AsRef<Path> , Q : AsRef<Path>>(path : P , entry_point : &str , out_dir : Q)->Result<PathBuf,RendererError>{
+    let path = path.as_ref();
+    println!("cargo:rerun-if-changed={}",path.display());
+    let words = compile_file(path, entry_point, BASELINE_CAPABILITIES).inspect_err(|e|println!("cargo:warning={}",e))?;
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    if entry_point != "main"{file_name.push(format!(".{}",entry_point))}
+    file_name.push(".spv");
+    let output = out_dir.as_ref().join(file_name);
+    let bytes = words.iter().flat_map(|word|word.to_le_bytes()).collect::<Vec<_>>();
+    std::fs::write(&output, bytes).map_err(|e|{
+        println!("cargo:warning=failed to write {}: {}",output.display(),e);
+        compile_error(&output.display().to_string(), None, &e.to_string())
+    })?;
+    return Ok(output);
+}
+impl Renderer{
+    ///Compiles one entry point of a GLSL or WGSL file and creates a shader module for it, see `compile_file`.
+    ///The shader may only use what the enabled device features allow, see `Renderer::shader_capabilities`.
+    pub fn compile_shader<P : AsRef<Path>>(&self , path : P , entry_point : &str)->Result<ShaderModule,RendererError>{
+        let words = compile_file(path.as_ref(), entry_point, self.shader_capabilities())?;
+        let bytes = words.iter().flat_map(|word|word.to_le_bytes()).collect::<Vec<_>>();
+        let module = self.create_shader_module(&bytes)?;
+        info!("Compiled entry point {} of shader {}.",entry_point,path.as_ref().display());
+        return Ok(module);
+    }
+    ///What compiled shaders may use, derived from the required features and the descriptor indexing features of bindless mode.
+    pub fn shader_capabilities(&self)->Capabilities{
+        let indexing = self.bindless.as_ref().map(|_|unsafe{
+            //Bindless mode enables every non uniform indexing feature the GPU supports.
+            let mut indexing = PhysicalDeviceDescriptorIndexingFeatures::default();
+            let mut features2 = PhysicalDeviceFeatures2{
+                s_type : StructureType::PHYSICAL_DEVICE_FEATURES_2,
+                p_next : &mut indexing as *mut PhysicalDeviceDescriptorIndexingFeatures as *mut std::ffi::c_void,
+                features : Default::default(),
+            };
+            self.instance.get_physical_device_features2(self.physical_device, &mut features2);
+            indexing
+        });
+        return device_capabilities(&self.config.required_features, indexing.as_ref());
+    }
+}
+#[cfg(all(test, feature = "shader-compiler"))]
+mod tests{
+    use super::*;
+    use crate::renderer::ShaderReflection;
+
+    const WGSL : &str = "struct Camera{
+    view_projection : mat4x4<f32>,
+}
+@group(0) @binding(0) var<uniform> camera : Camera;
+@vertex
+fn vs_main(@location(0) position : vec3<f32>)->@builtin(position) vec4<f32>{
+    return camera.view_projection * vec4<f32>(position, 1.0);
+}
+@compute @workgroup_size(8, 4, 1)
+fn cs_main(){
+}
+";
+    const GLSL : &str = "#version 450
+layout(location = 0) out vec4 color;
+void main(){
+    color = vec4(1.0, 0.5, 0.25, 1.0);
+}
+";
+
+    fn reflect(words : &[u32])->ShaderReflection{
+        return ShaderReflection::new(words).unwrap();
+    }
+    #[test]
+    fn compiles_wgsl_entry_points(){
+        let reflection = reflect(&compile(WGSL, ShaderLanguage::Wgsl, "shader.wgsl", "vs_main", BASELINE_CAPABILITIES).unwrap());
+        assert_eq!(reflection.entry_points.len(), 1);
+        assert_eq!(reflection.entry_points[0].name, "vs_main");
+        assert_eq!(reflection.entry_points[0].stage, ShaderStageFlags::VERTEX);
+        assert_eq!(reflection.descriptor_bindings.len(), 1);
+        assert_eq!((reflection.descriptor_bindings[0].set,reflection.descriptor_bindings[0].binding), (0,0));
+        assert_eq!(reflection.descriptor_bindings[0].descriptor_type, vk::DescriptorType::UNIFORM_BUFFER);
+        assert_eq!(reflection.vertex_inputs.len(), 1);
+        let reflection = reflect(&compile(WGSL, ShaderLanguage::Wgsl, "shader.wgsl", "cs_main", BASELINE_CAPABILITIES).unwrap());
+        assert_eq!(reflection.entry_points[0].stage, ShaderStageFlags::COMPUTE);
+        assert_eq!(reflection.entry_points[0].local_size, Some([8,4,1]));
+    }
+    #[test]
+    fn compiles_glsl_fragment_shaders(){
+        let reflection = reflect(&compile(GLSL, ShaderLanguage::Glsl(ShaderStageFlags::FRAGMENT), "shader.frag", "main", BASELINE_CAPABILITIES).unwrap());
+        assert_eq!(reflection.entry_points.len(), 1);
+        assert_eq!(reflection.entry_points[0].name, "main");
+        assert_eq!(reflection.entry_points[0].stage, ShaderStageFlags::FRAGMENT);
+    }
+    #[test]
+    fn syntax_errors_give_their_location(){
+        let source = WGSL.replace("fn cs_main(){", "fn cs_main({");
+        match compile(&source, ShaderLanguage::Wgsl, "broken.wgsl", "cs_main", BASELINE_CAPABILITIES){
+            Err(RendererError::CompileShader(message))=>assert!(message.starts_with("broken.wgsl:10:"), "{}",message),
+            other=>panic!("expected a compile error, got {:?}",other.map(|words|words.len())),
+        }
+        let source = GLSL.replace("color = vec4", "color = = vec4");
+        match compile(&source, ShaderLanguage::Glsl(ShaderStageFlags::FRAGMENT), "broken.frag", "main", BASELINE_CAPABILITIES){
+            Err(RendererError::CompileShader(message))=>assert!(message.starts_with("broken.frag:4:"), "{}",message),
+            other=>panic!("expected a compile error, got {:?}",other.map(|words|words.len())),
+        }
+    }
+}
@@ -0,0 +1,375 @@
+use std::ffi::CString;
+
+use ash::{Device, version::{DeviceV1_0, InstanceV1_0}, vk::{self, AccessFlags, CommandBuffer, CommandBufferAllocateInfo, CommandBufferBeginInfo, CommandBufferLevel, CommandBufferUsageFlags, CommandPool, CommandPoolCreateFlags, CommandPoolCreateInfo, ComputePipelineCreateInfo, DependencyFlags, DescriptorBufferInfo, DescriptorPool, DescriptorPoolCreateFlags, DescriptorPoolCreateInfo, DescriptorPoolSize, DescriptorSetAllocateInfo, DescriptorType, DeviceSize, Fence, FenceCreateFlags, FenceCreateInfo, MemoryBarrier, PipelineBindPoint, PipelineCreateFlags, PipelineStageFlags, StructureType, SubmitInfo, WriteDescriptorSet}};
+use bytemuck::Pod;
+use log::{error,info,trace};
+
+use super::{Buffer, PipelineLayout, Renderer, RendererError, ShaderModule, deletion::Garbage};
+
+///A compute pipeline and the layout generated from its shader.
+pub struct ComputePipeline{
+    handle : vk::Pipeline,
+    layout : PipelineLayout,
+    local_size : [u32;3],
+}
+impl ComputePipeline{
+    pub fn handle(&self)->vk::Pipeline{
+        return self.handle;
+    }
+    pub fn layout(&self)->&PipelineLayout{
+        return &self.layout;
+    }
+    ///The workgroup size of the shader.
+    pub fn local_size(&self)->[u32;3]{
+        return self.local_size;
+    }
+}
+///Identifies a dispatch, returned by `Renderer::dispatch_compute`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ComputeHandle(u64);
+
+struct BufferBinding{
+    set : u32,
+    binding : u32,
+    buffer : vk::Buffer,
+    offset : DeviceSize,
+    range : DeviceSize,
+}
+///The buffers, push constants and workgroup counts of a submission to the compute queue.
+///```no_run
+///# use gpengine::renderer::{ComputeContext, MemoryLocation, Renderer};
+///# use ash::vk::BufferUsageFlags;
+///# fn example(renderer : &mut Renderer)->Result<(),gpengine::RendererError>{
+///let shader = renderer.load_shader("shaders/double.comp.spv")?;
+///let pipeline = renderer.create_compute_pipeline(&shader)?;
+///let data = renderer.create_buffer::<f32>(1024, BufferUsageFlags::STORAGE_BUFFER, MemoryLocation::HostVisible)?;
+///let handle = renderer.dispatch_compute(ComputeContext::new(&pipeline).bind_buffer(0, 0, &data).dispatch_invocations(1024, 1, 1))?;
+///renderer.wait_for_compute(handle)?;
+///renderer.invalidate_buffer(&data)?;
+///# Ok(())}
+///```
+pub struct ComputeContext<'a>{
+    pipeline : &'a ComputePipeline,
+    buffers : Vec<BufferBinding>,
+    push_constants : Vec<u8>,
+    dispatches : Vec<[u32;3]>,
+}
+impl<'a> ComputeContext<'a>{
+    pub fn new(pipeline : &'a ComputePipeline)->Self{
+        return Self{pipeline , buffers : vec!() , push_constants : vec!() , dispatches : vec!()};
+    }
+    ///Binds a whole buffer to a uniform or storage buffer binding.
+    ///Buffers in device local memory must have been uploaded with `UploadDestination::Compute`.
+    pub fn bind_buffer<T : Copy>(self , set : u32 , binding : u32 , buffer : &Buffer<T>)->Self{
+        return self.bind_buffer_range(set, binding, buffer, 0, buffer.len());
+    }
+    ///Binds `len` elements of a buffer starting at element `offset`.
+    pub fn bind_buffer_range<T : Copy>(mut self , set : u32 , binding : u32 , buffer : &Buffer<T> , offset : usize , len : usize)->Self{
+        let size = std::mem::size_of::<T>() as DeviceSize;
+        self.buffers.retain(|b|(b.set,b.binding) != (set,binding));
+        self.buffers.push(BufferBinding{set , binding , buffer : buffer.handle() , offset : offset as DeviceSize * size , range : len as DeviceSize * size});
+        return self;
+    }
+    ///Sets the push constants, `value` is the whole push constant block starting at offset 0.
+    pub fn push_constants<T : Pod>(mut self , value : &T)->Self{
+        self.push_constants = bytemuck::bytes_of(value).to_vec();
+        return self;
+    }
+    ///Dispatches workgroups, every dispatch sees the writes of the dispatches before it.
+    pub fn dispatch(mut self , x : u32 , y : u32 , z : u32)->Self{
+        self.dispatches.push([x,y,z]);
+        return self;
+    }
+    ///Dispatches enough workgroups to run the shader at least once for every invocation.
+    pub fn dispatch_invocations(self , x : u32 , y : u32 , z : u32)->Self{
+        let local_size = self.pipeline.local_size;
+        return self.dispatch(x.div_ceil(local_size[0].max(1)), y.div_ceil(local_size[1].max(1)), z.div_ceil(local_size[2].max(1)));
+    }
+}
+///A dispatch that was submitted but may not have finished yet.
+struct PendingDispatch{
+    id : u64,
+    command_buffer : CommandBuffer,
+    descriptor_pool : DescriptorPool,
+    complete : Fence,
+}
+///Records and tracks submissions to the compute queue.
+pub(crate) struct ComputeDispatcher{
+    ///Created when the first dispatch is recorded.
+    command_pool : CommandPool,
+    pending : Vec<PendingDispatch>,
+    next_id : u64,
+}
+impl ComputeDispatcher{
+    pub(crate) fn new()->Self{
+        return Self{command_pool : CommandPool::null() , pending : vec!() , next_id : 0};
+    }
+    ///Destroys every dispatch and the command pool, the device must be idle.
+    pub(crate) unsafe fn destroy(&mut self , device : &Device){
+        for dispatch in self.pending.drain(..){
+            device.destroy_fence(dispatch.complete, None);
+            device.destroy_descriptor_pool(dispatch.descriptor_pool, None);
+        }
+        if self.command_pool != CommandPool::null(){device.destroy_command_pool(self.command_pool, None)}
+    }
+    ///The id the next dispatch will get.
+    pub(crate) fn next_dispatch_id(&self)->u64{
+        return self.next_id;
+    }
+    ///The id of the oldest dispatch that hasn't been collected yet.
+    pub(crate) fn oldest_pending_dispatch_id(&self)->Option<u64>{
+        return self.pending.iter().map(|dispatch|dispatch.id).min();
+    }
+}
+impl Renderer{
+    ///Creates a compute pipeline, its layout is generated from the shader.
+    pub fn create_compute_pipeline(&mut self , shader : &ShaderModule)->Result<ComputePipeline,RendererError>{
+        let entry_point = match shader.entry_point(){
+            Some(entry_point) if entry_point.stage == vk::ShaderStageFlags::COMPUTE=>entry_point,
+            _=>{
+                error!("A compute pipeline needs a shader with exactly one compute entry point.");
+                return Err(RendererError::InvalidPipeline("the shader doesn't have exactly one compute entry point".to_string()));
+            }
+        };
+        let layout = self.create_pipeline_layout(&[shader])?;
+        if let Some(set) = layout.bindless_set(){
+            error!("Compute pipelines can't use the bindless set, the shader uses it at set {}.",set);
+            self.destroy_pipeline_layout(layout);
+            return Err(RendererError::InvalidPipeline("compute pipelines can't use the bindless set".to_string()));
+        }
+        let name = CString::new(entry_point.name.as_str()).unwrap_or_default();
+        let create_info = ComputePipelineCreateInfo{
+            s_type : StructureType::COMPUTE_PIPELINE_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : PipelineCreateFlags::empty(),
+            stage : shader.stage_create_info(entry_point, &name),
+            layout : layout.handle(),
+            base_pipeline_handle : vk::Pipeline::null(),
+            base_pipeline_index : -1,
+        };
+        let handle = match unsafe{self.device.create_compute_pipelines(self.pipeline_cache(), &[create_info], None)}{
+            Ok(pipelines)=>pipelines[0],
+            Err((_,e))=>{
+                error!("Failed to create compute pipeline, {}.",e);
+                self.destroy_pipeline_layout(layout);
+                return Err(RendererError::CreatePipeline(e));
+            }
+        };
+        let local_size = entry_point.local_size.unwrap_or([1,1,1]);
+        info!("Created compute pipeline {} with workgroup size {:?}.",entry_point.name,local_size);
+        return Ok(ComputePipeline{handle , layout , local_size});
+    }
+    ///Destroys a compute pipeline and its layout once the GPU is no longer using them.
+    ///Renderers that never draw frames reclaim them as soon as the dispatches submitted before are complete.
+    ///```no_run
+    ///# use gpengine::renderer::{ComputeContext, MemoryLocation, Renderer};
+    ///# use ash::vk::BufferUsageFlags;
+    ///# fn main()->Result<(),gpengine::RendererError>{
+    ///let mut renderer = Renderer::new_headless(1, 1)?;
+    ///let shader = renderer.load_shader("shaders/double.comp.spv")?;
+    ///let pipeline = renderer.create_compute_pipeline(&shader)?;
+    ///let data = renderer.create_buffer::<f32>(1024, BufferUsageFlags::STORAGE_BUFFER, MemoryLocation::HostVisible)?;
+    ///let handle = renderer.dispatch_compute(ComputeContext::new(&pipeline).bind_buffer(0, 0, &data).dispatch_invocations(1024, 1, 1))?;
+    ///renderer.wait_for_compute(handle)?;
+    ///renderer.destroy_compute_pipeline(pipeline);
+    ///renderer.destroy_buffer(data);
+    ///renderer.destroy_shader_module(shader);
+    ///assert_eq!(renderer.pending_destructions(), 0);
+    ///# Ok(())}
+    ///```
+    pub fn destroy_compute_pipeline(&mut self , pipeline : ComputePipeline){
+        self.destroy_deferred(Garbage::Pipeline{pipeline : pipeline.handle , layout : Some(pipeline.layout)});
+    }
+    ///Records the dispatches of the context and submits them to the compute queue.
+    ///The writes of the shader are visible to the host and to later submissions once the returned dispatch has completed.
+    pub fn dispatch_compute(&mut self , context : ComputeContext)->Result<ComputeHandle,RendererError>{
+        let invalid = |reason : String|{error!("Invalid compute dispatch, {}.",reason);RendererError::InvalidDispatch(reason)};
+        let layout = &context.pipeline.layout;
+        for binding in layout.bindings(){
+            if binding.descriptor_type != DescriptorType::UNIFORM_BUFFER && binding.descriptor_type != DescriptorType::STORAGE_BUFFER{
+                return Err(invalid(format!("binding {} in set {} is a {:?}, only uniform and storage buffers can be bound",binding.binding,binding.set,binding.descriptor_type)));
+            }
+            if !context.buffers.iter().any(|b|(b.set,b.binding) == (binding.set,binding.binding)){
+                return Err(invalid(format!("nothing is bound to binding {} in set {}",binding.binding,binding.set)));
+            }
+        }
+        if let Some(b) = context.buffers.iter().find(|b|!layout.bindings().iter().any(|l|(l.set,l.binding) == (b.set,b.binding))){
+            return Err(invalid(format!("the shader doesn't use binding {} in set {}",b.binding,b.set)));
+        }
+        if let Some(range) = layout.push_constant_ranges().first(){
+            if context.push_constants.len() < (range.offset + range.size) as usize{
+                return Err(invalid(format!("{} bytes of push constants were given for a block of {} bytes",context.push_constants.len(),range.offset + range.size)));
+            }
+        }
+        let limits = unsafe{self.instance.get_physical_device_properties(self.physical_device)}.limits;
+        if let Some(dispatch) = context.dispatches.iter().find(|d|d.iter().zip(limits.max_compute_work_group_count.iter()).any(|(count,max)|count > max)){
+            return Err(invalid(format!("{:?} workgroups exceed the limit of {:?}",dispatch,limits.max_compute_work_group_count)));
+        }
+        let id = self.compute.next_id;
+        let dispatch = unsafe{self.record_dispatch(&context, id)}?;
+        let submit_info = SubmitInfo{
+            s_type : StructureType::SUBMIT_INFO,
+            p_next : std::ptr::null(),
+            wait_semaphore_count : 0,
+            p_wait_semaphores : std::ptr::null(),
+            p_wait_dst_stage_mask : std::ptr::null(),
+            command_buffer_count : 1,
+            p_command_buffers : &dispatch.command_buffer,
+            signal_semaphore_count : 0,
+            p_signal_semaphores : std::ptr::null(),
+        };
+        if let Err(e) = self.submit_compute(&[submit_info], dispatch.complete){
+            unsafe{self.destroy_dispatch(dispatch)};
+            return Err(e);
+        }
+        self.compute.pending.push(dispatch);
+        self.compute.next_id += 1;
+        trace!("Submitted compute dispatch {} with {} dispatches.",id,context.dispatches.len());
+        return Ok(ComputeHandle(id));
+    }
+    unsafe fn record_dispatch(&mut self , context : &ComputeContext , id : u64)->Result<PendingDispatch,RendererError>{
+        let to_error = |e : vk::Result|{error!("Failed to create the resources of a compute dispatch, {}.",e);RendererError::CreateComputeResources(e)};
+        if self.compute.command_pool == CommandPool::null(){
+            let command_pool_create_info = CommandPoolCreateInfo{
+                s_type : StructureType::COMMAND_POOL_CREATE_INFO,
+                p_next : std::ptr::null(),
+                flags : CommandPoolCreateFlags::TRANSIENT,
+                queue_family_index : self.queues.compute().family_index(),
+            };
+            self.compute.command_pool = self.device.create_command_pool(&command_pool_create_info, None).map_err(to_error)?;
+        }
+        let layout = &context.pipeline.layout;
+        let mut pool_sizes : Vec<DescriptorPoolSize> = vec!();
+        for binding in layout.bindings(){
+            match pool_sizes.iter_mut().find(|size|size.ty == binding.descriptor_type){
+                Some(size)=>size.descriptor_count += binding.count,
+                None=>pool_sizes.push(DescriptorPoolSize{ty : binding.descriptor_type , descriptor_count : binding.count}),
+            }
+        }
+        let fence_create_info = FenceCreateInfo{s_type : StructureType::FENCE_CREATE_INFO , p_next : std::ptr::null() , flags : FenceCreateFlags::empty()};
+        let mut dispatch = PendingDispatch{id , command_buffer : CommandBuffer::null() , descriptor_pool : DescriptorPool::null() , complete : self.device.create_fence(&fence_create_info, None).map_err(to_error)?};
+        if let Err(e) = self.record_dispatch_commands(context, &pool_sizes, &mut dispatch){
+            self.destroy_dispatch(dispatch);
+            return Err(e);
+        }
+        return Ok(dispatch);
+    }
+    unsafe fn destroy_dispatch(&self , dispatch : PendingDispatch){
+        self.device.destroy_fence(dispatch.complete, None);
+        if dispatch.descriptor_pool != DescriptorPool::null(){self.device.destroy_descriptor_pool(dispatch.descriptor_pool, None)}
+        if dispatch.command_buffer != CommandBuffer::null(){self.device.free_command_buffers(self.compute.command_pool, &[dispatch.command_buffer])}
+    }
+    unsafe fn record_dispatch_commands(&self , context : &ComputeContext , pool_sizes : &[DescriptorPoolSize] , dispatch : &mut PendingDispatch)->Result<(),RendererError>{
+        let to_error = |e : vk::Result|{error!("Failed to create the resources of a compute dispatch, {}.",e);RendererError::CreateComputeResources(e)};
+        let layout = &context.pipeline.layout;
+        let mut descriptor_sets = vec!();
+        if !layout.set_layouts().is_empty(){
+            let descriptor_pool_create_info = DescriptorPoolCreateInfo{
+                s_type : StructureType::DESCRIPTOR_POOL_CREATE_INFO,
+                p_next : std::ptr::null(),
+                flags : DescriptorPoolCreateFlags::empty(),
+                max_sets : layout.set_layouts().len() as u32,
+                pool_size_count : pool_sizes.len() as u32,
+                p_pool_sizes : pool_sizes.as_ptr(),
+            };
+            dispatch.descriptor_pool = self.device.create_descriptor_pool(&descriptor_pool_create_info, None).map_err(to_error)?;
+            let descriptor_set_allocate_info = DescriptorSetAllocateInfo{
+                s_type : StructureType::DESCRIPTOR_SET_ALLOCATE_INFO,
+                p_next : std::ptr::null(),
+                descriptor_pool : dispatch.descriptor_pool,
+                descriptor_set_count : layout.set_layouts().len() as u32,
+                p_set_layouts : layout.set_layouts().as_ptr(),
+            };
+            descriptor_sets = self.device.allocate_descriptor_sets(&descriptor_set_allocate_info).map_err(to_error)?;
+            let buffer_infos = context.buffers.iter().map(|b|DescriptorBufferInfo{buffer : b.buffer , offset : b.offset , range : b.range}).collect::<Vec<_>>();
+            let writes = context.buffers.iter().zip(buffer_infos.iter()).map(|(b,info)|WriteDescriptorSet{
+                s_type : StructureType::WRITE_DESCRIPTOR_SET,
+                p_next : std::ptr::null(),
+                dst_set : descriptor_sets[b.set as usize],
+                dst_binding : b.binding,
+                dst_array_element : 0,
+                descriptor_count : 1,
+                descriptor_type : layout.bindings().iter().find(|l|(l.set,l.binding) == (b.set,b.binding)).map(|l|l.descriptor_type).unwrap_or(DescriptorType::STORAGE_BUFFER),
+                p_image_info : std::ptr::null(),
+                p_buffer_info : info,
+                p_texel_buffer_view : std::ptr::null(),
+            }).collect::<Vec<_>>();
+            self.device.update_descriptor_sets(&writes, &[]);
+        }
+        let command_buffer_allocate_info = CommandBufferAllocateInfo{
+            s_type : StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
+            p_next : std::ptr::null(),
+            command_pool : self.compute.command_pool,
+            level : CommandBufferLevel::PRIMARY,
+            command_buffer_count : 1,
+        };
+        dispatch.command_buffer = self.device.allocate_command_buffers(&command_buffer_allocate_info).map_err(to_error)?[0];
+        let command_buffer = dispatch.command_buffer;
+        let command_buffer_begin_info = CommandBufferBeginInfo{
+            s_type : StructureType::COMMAND_BUFFER_BEGIN_INFO,
+            p_next : std::ptr::null(),
+            flags : CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+            p_inheritance_info : std::ptr::null(),
+        };
+        let to_record_error = |e : vk::Result|{error!("Failed to record a compute command buffer, {}.",e);RendererError::RecordCommands(e)};
+        self.device.begin_command_buffer(command_buffer, &command_buffer_begin_info).map_err(to_record_error)?;
+        self.device.cmd_bind_pipeline(command_buffer, PipelineBindPoint::COMPUTE, context.pipeline.handle);
+        if !descriptor_sets.is_empty(){self.device.cmd_bind_descriptor_sets(command_buffer, PipelineBindPoint::COMPUTE, layout.handle(), 0, &descriptor_sets, &[])}
+        if let Some(range) = layout.push_constant_ranges().first(){
+            let bytes = &context.push_constants[range.offset as usize..(range.offset + range.size) as usize];
+            self.device.cmd_push_constants(command_buffer, layout.handle(), range.stage_flags, range.offset, bytes);
+        }
+        for (i,&[x,y,z]) in context.dispatches.iter().enumerate(){
+            if i > 0{
+                let barrier = memory_barrier(AccessFlags::SHADER_WRITE, AccessFlags::SHADER_READ | AccessFlags::SHADER_WRITE);
+                self.device.cmd_pipeline_barrier(command_buffer, PipelineStageFlags::COMPUTE_SHADER, PipelineStageFlags::COMPUTE_SHADER, DependencyFlags::empty(), &[barrier], &[], &[]);
+            }
+            self.device.cmd_dispatch(command_buffer, x, y, z);
+        }
+        //Makes the results visible to the host and to whatever runs after the dispatch.
+        let barrier = memory_barrier(AccessFlags::SHADER_WRITE, AccessFlags::HOST_READ | AccessFlags::MEMORY_READ);
+        self.device.cmd_pipeline_barrier(command_buffer, PipelineStageFlags::COMPUTE_SHADER, PipelineStageFlags::HOST | PipelineStageFlags::ALL_COMMANDS, DependencyFlags::empty(), &[barrier], &[], &[]);
+        self.device.end_command_buffer(command_buffer).map_err(to_record_error)?;
+        return Ok(());
+    }
+    ///Whether the dispatch has completed, the resources of completed dispatches are freed.
+    pub fn is_compute_complete(&mut self , handle : ComputeHandle)->Result<bool,RendererError>{
+        self.collect_dispatches()?;
+        return Ok(!self.compute.pending.iter().any(|dispatch|dispatch.id == handle.0));
+    }
+    ///Blocks until the dispatch has completed.
+    pub fn wait_for_compute(&mut self , handle : ComputeHandle)->Result<(),RendererError>{
+        if let Some(dispatch) = self.compute.pending.iter().find(|dispatch|dispatch.id == handle.0){
+            unsafe{self.device.wait_for_fences(&[dispatch.complete], true, u64::MAX)}.map_err(|e|{
+                error!("Failed to wait for compute dispatch {}, {}.",handle.0,e);
+                RendererError::WaitFence(e)
+            })?;
+        }
+        return self.collect_dispatches();
+    }
+    ///Frees the command buffers and descriptor pools of every completed dispatch and the deferred resources that are no longer in use.
+    pub(crate) fn collect_dispatches(&mut self)->Result<(),RendererError>{
+        let mut i = 0;
+        while i < self.compute.pending.len(){
+            let complete = unsafe{self.device.get_fence_status(self.compute.pending[i].complete)}.map_err(|e|{
+                error!("Failed to get the status of a compute dispatch, {}.",e);
+                RendererError::WaitFence(e)
+            })?;
+            if complete{
+                let dispatch = self.compute.pending.swap_remove(i);
+                trace!("Compute dispatch {} completed.",dispatch.id);
+                unsafe{self.destroy_dispatch(dispatch)};
+            }else{i += 1}
+        }
+        self.collect_garbage();
+        return Ok(());
+    }
+}
+fn memory_barrier(src_access_mask : AccessFlags , dst_access_mask : AccessFlags)->MemoryBarrier{
+    return MemoryBarrier{
+        s_type : StructureType::MEMORY_BARRIER,
+        p_next : std::ptr::null(),
+        src_access_mask,
+        dst_access_mask,
+    };
+}
@@ -0,0 +1,172 @@
+use ash::{Device, version::DeviceV1_0, vk};
+use log::trace;
+
+use super::{Allocation, BindlessHandle, Image, MemoryAllocator, PipelineLayout, Renderer, pipeline};
+
+///A resource that was destroyed by the application but may still be used by the GPU.
+pub(crate) enum Garbage{
+    Buffer{buffer : vk::Buffer , allocation : Option<Allocation>},
+    Image(Image),
+    Pipeline{pipeline : vk::Pipeline , layout : Option<PipelineLayout>},
+    ///An index of the bindless set, it is recycled instead of destroyed.
+    BindlessIndex(BindlessHandle),
+}
+impl Garbage{
+    unsafe fn destroy(self , device : &Device , allocator : &mut MemoryAllocator){
+        match self{
+            Garbage::Buffer{buffer , allocation}=>{
+                device.destroy_buffer(buffer, None);
+                if let Some(allocation) = allocation{allocator.free(allocation)}
+            }
+            Garbage::Image(mut image)=>image.destroy(device, allocator),
+            Garbage::Pipeline{pipeline , layout}=>pipeline::destroy_pipeline(device, pipeline, layout),
+            Garbage::BindlessIndex(_)=>{}
+        }
+    }
+}
+struct Entry{
+    ///The serial of the frame that has to complete before the resource is destroyed.
+    frame : u64,
+    ///Every upload with a lower id has to complete before the resource is destroyed.
+    upload : u64,
+    ///Every compute dispatch with a lower id has to complete before the resource is destroyed.
+    dispatch : u64,
+    garbage : Garbage,
+}
+///Holds destroyed resources until the frames and uploads that were submitted before their destruction have completed.
+pub(crate) struct DeletionQueue{
+    entries : Vec<Entry>,
+    ///The serial of the last submitted frame, frames are numbered from 1.
+    submitted_frames : u64,
+    ///Every frame up to this serial has completed.
+    completed_frames : u64,
+    ///Set between `begin_frame` and the submission of the frame, resources destroyed then may be used by the frame being recorded.
+    recording : bool,
+}
+impl DeletionQueue{
+    pub(crate) fn new()->Self{
+        return Self{entries : vec!() , submitted_frames : 0 , completed_frames : 0 , recording : false};
+    }
+    ///Called once a frame is being recorded.
+    pub(crate) fn frame_begun(&mut self){
+        self.recording = true;
+    }
+    ///Called when a frame is abandoned instead of submitted, resources destroyed while recording it wait for the next frame.
+    pub(crate) fn frame_abandoned(&mut self){
+        self.recording = false;
+    }
+    ///Called after a frame is submitted, returns its serial.
+    pub(crate) fn frame_submitted(&mut self)->u64{
+        self.recording = false;
+        self.submitted_frames += 1;
+        return self.submitted_frames;
+    }
+    ///Called after waiting for the fence of a frame.
+    ///Frame fences are waited on in submission order, so every earlier frame has completed as well.
+    pub(crate) fn frame_completed(&mut self , serial : u64){
+        self.completed_frames = self.completed_frames.max(serial);
+    }
+    ///Queues a resource until the frames, uploads below `upload` and dispatches below `dispatch` that may use it have completed.
+    fn push(&mut self , upload : u64 , dispatch : u64 , garbage : Garbage){
+        //While a frame is recorded the resource may be used by it, and that frame gets the next serial.
+        //Outside of frames only the frames submitted so far can use it, so renderers that never draw don't wait for a frame.
+        let frame = self.submitted_frames + self.recording as u64;
+        self.entries.push(Entry{frame , upload , dispatch , garbage});
+    }
+    ///Removes the resources whose frames have completed and that no pending upload or dispatch can use, given the oldest pending ids.
+    fn take_completed(&mut self , oldest_upload : Option<u64> , oldest_dispatch : Option<u64>)->Vec<Garbage>{
+        let mut completed = vec!();
+        let mut i = 0;
+        while i < self.entries.len(){
+            let entry = &self.entries[i];
+            let uploads_complete = oldest_upload.is_none_or(|oldest|oldest >= entry.upload);
+            let dispatches_complete = oldest_dispatch.is_none_or(|oldest|oldest >= entry.dispatch);
+            if entry.frame <= self.completed_frames && uploads_complete && dispatches_complete{
+                let entry = self.entries.swap_remove(i);
+                trace!("Destroying a deferred resource of frame {}.",entry.frame);
+                completed.push(entry.garbage);
+            }else{i += 1}
+        }
+        return completed;
+    }
+    pub(crate) fn len(&self)->usize{
+        return self.entries.len();
+    }
+    ///Destroys everything regardless of the GPU, the device must be idle.
+    pub(crate) unsafe fn destroy(&mut self , device : &Device , allocator : &mut MemoryAllocator){
+        for entry in self.entries.drain(..){entry.garbage.destroy(device, allocator)}
+    }
+}
+impl Renderer{
+    ///Destroys a resource once the GPU can no longer be using it, right away when nothing that may use it is still pending.
+    pub(crate) fn destroy_deferred(&mut self , garbage : Garbage){
+        let upload = self.uploader.next_upload_id();
+        let dispatch = self.compute.next_dispatch_id();
+        self.deletion_queue.push(upload, dispatch, garbage);
+        self.collect_garbage();
+    }
+    ///The number of destroyed resources still waiting for the GPU to finish using them.
+    pub fn pending_destructions(&self)->usize{
+        return self.deletion_queue.len();
+    }
+    ///Destroys the deferred resources that are no longer in use.
+    ///Called when frames begin and when uploads and dispatches are collected, so renderers that only compute reclaim resources too.
+    pub(crate) fn collect_garbage(&mut self){
+        //A signaled fence means the last frame submitted with it has completed, fences are only reset right before submitting.
+        for frame in self.frames.iter(){
+            if unsafe{self.device.get_fence_status(frame.in_flight())}.unwrap_or(false){self.deletion_queue.frame_completed(frame.serial)}
+        }
+        let completed = self.deletion_queue.take_completed(self.uploader.oldest_pending_upload_id(), self.compute.oldest_pending_dispatch_id());
+        for garbage in completed{
+            match garbage{
+                Garbage::BindlessIndex(handle)=>if let Some(bindless) = &mut self.bindless{bindless.recycle(handle)},
+                garbage=>unsafe{garbage.destroy(&self.device, &mut self.allocator)},
+            }
+        }
+    }
+}
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn garbage()->Garbage{
+        return Garbage::Pipeline{pipeline : vk::Pipeline::null() , layout : None};
+    }
+    #[test]
+    fn outside_of_frames_only_pending_dispatches_delay_destruction(){
+        let mut queue = DeletionQueue::new();
+        queue.push(0, 3, garbage());
+        assert_eq!(queue.take_completed(None, Some(2)).len(), 0);
+        assert_eq!(queue.take_completed(None, Some(3)).len(), 1);
+        queue.push(0, 3, garbage());
+        assert_eq!(queue.take_completed(None, None).len(), 1);
+        assert_eq!(queue.len(), 0);
+    }
+    #[test]
+    fn resources_destroyed_while_recording_wait_for_the_frame(){
+        let mut queue = DeletionQueue::new();
+        queue.frame_begun();
+        queue.push(0, 0, garbage());
+        let serial = queue.frame_submitted();
+        assert_eq!(queue.take_completed(None, None).len(), 0);
+        queue.frame_completed(serial);
+        assert_eq!(queue.take_completed(None, None).len(), 1);
+    }
+    #[test]
+    fn resources_destroyed_between_frames_wait_for_submitted_frames(){
+        let mut queue = DeletionQueue::new();
+        queue.frame_begun();
+        let serial = queue.frame_submitted();
+        queue.push(0, 0, garbage());
+        assert_eq!(queue.take_completed(None, None).len(), 0);
+        queue.frame_completed(serial);
+        assert_eq!(queue.take_completed(None, None).len(), 1);
+    }
+    #[test]
+    fn pending_uploads_delay_destruction(){
+        let mut queue = DeletionQueue::new();
+        queue.push(5, 0, garbage());
+        assert_eq!(queue.take_completed(Some(4), None).len(), 0);
+        assert_eq!(queue.take_completed(Some(5), None).len(), 1);
+    }
+}
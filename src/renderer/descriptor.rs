@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+
+use ash::{Device, version::DeviceV1_0, vk::{self, DescriptorBufferInfo, DescriptorImageInfo, DescriptorPool, DescriptorPoolCreateFlags, DescriptorPoolCreateInfo, DescriptorPoolResetFlags, DescriptorPoolSize, DescriptorSet, DescriptorSetAllocateInfo, DescriptorSetLayout, DescriptorSetLayoutBinding, DescriptorSetLayoutCreateFlags, DescriptorSetLayoutCreateInfo, DescriptorType, DeviceSize, ImageLayout, ImageView, Sampler, StructureType, WriteDescriptorSet}};
+use log::{error,debug};
+
+use super::{Buffer, Frame, Renderer, RendererError};
+
+///The number of descriptors of each type a pool holds per set.
+const POOL_RATIOS : [(DescriptorType,f32);11] = [
+    (DescriptorType::UNIFORM_BUFFER,2.0),
+    (DescriptorType::STORAGE_BUFFER,2.0),
+    (DescriptorType::UNIFORM_BUFFER_DYNAMIC,1.0),
+    (DescriptorType::STORAGE_BUFFER_DYNAMIC,1.0),
+    (DescriptorType::COMBINED_IMAGE_SAMPLER,4.0),
+    (DescriptorType::SAMPLED_IMAGE,2.0),
+    (DescriptorType::STORAGE_IMAGE,1.0),
+    (DescriptorType::SAMPLER,1.0),
+    (DescriptorType::UNIFORM_TEXEL_BUFFER,1.0),
+    (DescriptorType::STORAGE_TEXEL_BUFFER,1.0),
+    (DescriptorType::INPUT_ATTACHMENT,0.5),
+];
+const INITIAL_SETS_PER_POOL : u32 = 64;
+const MAX_SETS_PER_POOL : u32 = 4096;
+
+///Allocates descriptor sets from a growing list of pools, a new and larger pool is created whenever the current one runs out.
+pub(crate) struct DescriptorAllocator{
+    ///Pools that may have room left, sets are allocated from the last one.
+    ready : Vec<DescriptorPool>,
+    ///Pools that ran out, they are reused after a reset.
+    full : Vec<DescriptorPool>,
+    ///The number of descriptors of each type every pool was created with.
+    pool_sizes : HashMap<DescriptorPool,Vec<DescriptorPoolSize>>,
+    sets_per_pool : u32,
+}
+impl DescriptorAllocator{
+    pub(crate) fn new()->Self{
+        return Self{ready : vec!() , full : vec!() , pool_sizes : HashMap::new() , sets_per_pool : INITIAL_SETS_PER_POOL};
+    }
+    ///Creates a pool whose sizes are raised where a set with the given needs wouldn't fit otherwise.
+    unsafe fn create_pool(&mut self , device : &Device , needs : &[DescriptorPoolSize])->Result<DescriptorPool,vk::Result>{
+        let pool_sizes = POOL_RATIOS.iter().map(|&(ty,ratio)|{
+            let need = needs.iter().filter(|n|n.ty == ty).map(|n|n.descriptor_count).sum::<u32>();
+            return DescriptorPoolSize{ty , descriptor_count : ((ratio * self.sets_per_pool as f32) as u32).max(need).max(1)};
+        }).collect::<Vec<_>>();
+        let create_info = DescriptorPoolCreateInfo{
+            s_type : StructureType::DESCRIPTOR_POOL_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : DescriptorPoolCreateFlags::empty(),
+            max_sets : self.sets_per_pool,
+            pool_size_count : pool_sizes.len() as u32,
+            p_pool_sizes : pool_sizes.as_ptr(),
+        };
+        let pool = device.create_descriptor_pool(&create_info, None)?;
+        self.pool_sizes.insert(pool, pool_sizes);
+        debug!("Created a descriptor pool for {} sets.",self.sets_per_pool);
+        self.sets_per_pool = (self.sets_per_pool * 2).min(MAX_SETS_PER_POOL);
+        return Ok(pool);
+    }
+    ///Allocates a set from the current pool, moving on to another pool when it is out of memory, fragmented or too small for the set.
+    ///`needs` are the descriptors a set of the layout takes by type, see `DescriptorLayoutCache::needs`.
+    pub(crate) unsafe fn allocate(&mut self , device : &Device , layout : DescriptorSetLayout , needs : &[DescriptorPoolSize])->Result<DescriptorSet,RendererError>{
+        let to_error = |e : vk::Result|{error!("Failed to allocate a descriptor set, {}.",e);RendererError::AllocateDescriptorSet(e)};
+        if let Some(need) = needs.iter().find(|n|!POOL_RATIOS.iter().any(|&(ty,_)|ty == n.ty)){
+            error!("Failed to allocate a descriptor set, the pools don't hold {:?} descriptors.",need.ty);
+            return Err(RendererError::AllocateDescriptorSet(vk::Result::ERROR_OUT_OF_POOL_MEMORY));
+        }
+        //Pools kept from before a reset may be too small for the set, they are skipped and a fresh pool sized for the set is created once the others are used up.
+        let mut fresh = false;
+        loop{
+            let pool = match self.ready.last(){
+                Some(&pool) if !self.fits(pool, needs)=>{
+                    self.full.push(pool);
+                    self.ready.pop();
+                    continue;
+                }
+                Some(&pool)=>pool,
+                //A set that doesn't fit a fresh pool sized for it never will.
+                None if fresh=>return Err(to_error(vk::Result::ERROR_OUT_OF_POOL_MEMORY)),
+                None=>{
+                    let pool = self.create_pool(device, needs).map_err(to_error)?;
+                    self.ready.push(pool);
+                    fresh = true;
+                    pool
+                }
+            };
+            let allocate_info = DescriptorSetAllocateInfo{
+                s_type : StructureType::DESCRIPTOR_SET_ALLOCATE_INFO,
+                p_next : std::ptr::null(),
+                descriptor_pool : pool,
+                descriptor_set_count : 1,
+                p_set_layouts : &layout,
+            };
+            match device.allocate_descriptor_sets(&allocate_info){
+                Ok(sets)=>return Ok(sets[0]),
+                Err(vk::Result::ERROR_OUT_OF_POOL_MEMORY) | Err(vk::Result::ERROR_FRAGMENTED_POOL)=>{
+                    if let Some(pool) = self.ready.pop(){self.full.push(pool)}
+                }
+                Err(e)=>return Err(to_error(e)),
+            }
+        }
+    }
+    ///Whether an empty pool holds enough descriptors of each type for a set with the given needs.
+    fn fits(&self , pool : DescriptorPool , needs : &[DescriptorPoolSize])->bool{
+        let sizes = match self.pool_sizes.get(&pool){
+            Some(sizes)=>sizes,
+            None=>return false,
+        };
+        let count = |sizes : &[DescriptorPoolSize] , ty : DescriptorType|sizes.iter().filter(|size|size.ty == ty).map(|size|size.descriptor_count).sum::<u32>();
+        return needs.iter().all(|need|count(sizes, need.ty) >= count(needs, need.ty));
+    }
+    ///Frees every set allocated so far, the pools are kept for the next allocations.
+    pub(crate) unsafe fn reset(&mut self , device : &Device)->Result<(),RendererError>{
+        self.ready.append(&mut self.full);
+        for &pool in self.ready.iter(){
+            device.reset_descriptor_pool(pool, DescriptorPoolResetFlags::empty()).map_err(|e|{
+                error!("Failed to reset a descriptor pool, {}.",e);
+                RendererError::AllocateDescriptorSet(e)
+            })?;
+        }
+        return Ok(());
+    }
+    pub(crate) unsafe fn destroy(&self , device : &Device){
+        for &pool in self.ready.iter().chain(self.full.iter()){device.destroy_descriptor_pool(pool, None)}
+    }
+}
+///A descriptor set layout binding without the immutable sampler pointer, so it can be compared and hashed.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct BindingKey{
+    binding : u32,
+    descriptor_type : i32,
+    count : u32,
+    stages : u32,
+    immutable_samplers : Vec<Sampler>,
+}
+///Creates each distinct descriptor set layout once and hands out the same layout for identical bindings.
+pub(crate) struct DescriptorLayoutCache{
+    layouts : HashMap<Vec<BindingKey>,DescriptorSetLayout>,
+    ///The descriptors a set of each layout takes by type.
+    needs : HashMap<DescriptorSetLayout,Vec<DescriptorPoolSize>>,
+}
+impl DescriptorLayoutCache{
+    pub(crate) fn new()->Self{
+        return Self{layouts : HashMap::new() , needs : HashMap::new()};
+    }
+    ///The descriptors a set of the layout takes by type, empty for layouts the cache didn't create.
+    pub(crate) fn needs(&self , layout : DescriptorSetLayout)->&[DescriptorPoolSize]{
+        return self.needs.get(&layout).map_or(&[],|needs|needs.as_slice());
+    }
+    ///The layout of the given bindings, the order of the bindings doesn't matter.
+    pub(crate) unsafe fn get(&mut self , device : &Device , bindings : &[DescriptorSetLayoutBinding])->Result<DescriptorSetLayout,RendererError>{
+        let mut key = bindings.iter().map(|b|BindingKey{
+            binding : b.binding,
+            descriptor_type : b.descriptor_type.as_raw(),
+            count : b.descriptor_count,
+            stages : b.stage_flags.as_raw(),
+            immutable_samplers : if b.p_immutable_samplers.is_null(){vec!()}else{std::slice::from_raw_parts(b.p_immutable_samplers, b.descriptor_count as usize).to_vec()},
+        }).collect::<Vec<_>>();
+        key.sort_by_key(|b|b.binding);
+        if let Some(&layout) = self.layouts.get(&key){return Ok(layout)}
+        let create_info = DescriptorSetLayoutCreateInfo{
+            s_type : StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : DescriptorSetLayoutCreateFlags::empty(),
+            binding_count : bindings.len() as u32,
+            p_bindings : bindings.as_ptr(),
+        };
+        let layout = device.create_descriptor_set_layout(&create_info, None).map_err(|e|{
+            error!("Failed to create a descriptor set layout, {}.",e);
+            RendererError::CreateDescriptorSetLayout(e)
+        })?;
+        debug!("Created a descriptor set layout with {} bindings.",bindings.len());
+        let mut needs : Vec<DescriptorPoolSize> = vec!();
+        for binding in bindings.iter(){
+            match needs.iter_mut().find(|n|n.ty == binding.descriptor_type){
+                Some(need)=>need.descriptor_count += binding.descriptor_count,
+                None=>needs.push(DescriptorPoolSize{ty : binding.descriptor_type , descriptor_count : binding.descriptor_count}),
+            }
+        }
+        self.needs.insert(layout, needs);
+        self.layouts.insert(key, layout);
+        return Ok(layout);
+    }
+    pub(crate) unsafe fn destroy(&mut self , device : &Device){
+        for (_,layout) in self.layouts.drain(){device.destroy_descriptor_set_layout(layout, None)}
+        self.needs.clear();
+    }
+}
+enum DescriptorInfo{
+    Buffer(DescriptorBufferInfo),
+    Image(DescriptorImageInfo),
+}
+struct DescriptorWrite{
+    binding : u32,
+    array_element : u32,
+    descriptor_type : DescriptorType,
+    info : DescriptorInfo,
+}
+///Collects the buffers, images and samplers of a descriptor set, applied with `Renderer::write_descriptors`.
+///Writing a binding twice keeps the last write.
+///```no_run
+///# use gpengine::renderer::{DescriptorWriter, Renderer};
+///# use ash::vk::{DescriptorSetLayout, DescriptorType, ImageLayout, ImageView, Sampler};
+///# fn example(renderer : &mut Renderer , layout : DescriptorSetLayout , view : ImageView , sampler : Sampler)->Result<(),gpengine::RendererError>{
+///let uniforms = renderer.create_buffer::<[f32;16]>(1, ash::vk::BufferUsageFlags::UNIFORM_BUFFER, gpengine::renderer::MemoryLocation::HostVisible)?;
+///let set = renderer.allocate_descriptor_set(layout)?;
+///renderer.write_descriptors(&DescriptorWriter::new(set)
+///    .buffer(0, &uniforms, DescriptorType::UNIFORM_BUFFER)
+///    .combined_image_sampler(1, view, ImageLayout::SHADER_READ_ONLY_OPTIMAL, sampler));
+///# Ok(())}
+///```
+pub struct DescriptorWriter{
+    set : DescriptorSet,
+    writes : Vec<DescriptorWrite>,
+}
+impl DescriptorWriter{
+    pub fn new(set : DescriptorSet)->Self{
+        return Self{set , writes : vec!()};
+    }
+    fn push(mut self , binding : u32 , array_element : u32 , descriptor_type : DescriptorType , info : DescriptorInfo)->Self{
+        self.writes.retain(|w|(w.binding,w.array_element) != (binding,array_element));
+        self.writes.push(DescriptorWrite{binding , array_element , descriptor_type , info});
+        return self;
+    }
+    ///Writes a whole buffer to a uniform or storage buffer binding.
+    pub fn buffer<T : Copy>(self , binding : u32 , buffer : &Buffer<T> , descriptor_type : DescriptorType)->Self{
+        return self.buffer_range(binding, buffer, 0, buffer.len(), descriptor_type);
+    }
+    ///Writes `len` elements of a buffer starting at element `offset`.
+    pub fn buffer_range<T : Copy>(self , binding : u32 , buffer : &Buffer<T> , offset : usize , len : usize , descriptor_type : DescriptorType)->Self{
+        let size = std::mem::size_of::<T>() as DeviceSize;
+        let info = DescriptorBufferInfo{buffer : buffer.handle() , offset : offset as DeviceSize * size , range : len as DeviceSize * size};
+        return self.push(binding, 0, descriptor_type, DescriptorInfo::Buffer(info));
+    }
+    ///Writes an image view to a sampled image, storage image or input attachment binding, `layout` is the layout the image is in when it is accessed.
+    pub fn image(self , binding : u32 , view : ImageView , layout : ImageLayout , descriptor_type : DescriptorType)->Self{
+        let info = DescriptorImageInfo{sampler : Sampler::null() , image_view : view , image_layout : layout};
+        return self.push(binding, 0, descriptor_type, DescriptorInfo::Image(info));
+    }
+    ///Writes an image view and a sampler to a combined image sampler binding.
+    pub fn combined_image_sampler(self , binding : u32 , view : ImageView , layout : ImageLayout , sampler : Sampler)->Self{
+        let info = DescriptorImageInfo{sampler , image_view : view , image_layout : layout};
+        return self.push(binding, 0, DescriptorType::COMBINED_IMAGE_SAMPLER, DescriptorInfo::Image(info));
+    }
+    ///Writes a sampler to a sampler binding.
+    pub fn sampler(self , binding : u32 , sampler : Sampler)->Self{
+        let info = DescriptorImageInfo{sampler , image_view : ImageView::null() , image_layout : ImageLayout::UNDEFINED};
+        return self.push(binding, 0, DescriptorType::SAMPLER, DescriptorInfo::Image(info));
+    }
+    ///Writes an image view and sampler to one element of an arrayed binding, the sampler is ignored by bindings without one.
+    pub fn image_element(self , binding : u32 , array_element : u32 , view : ImageView , layout : ImageLayout , sampler : Sampler , descriptor_type : DescriptorType)->Self{
+        let info = DescriptorImageInfo{sampler , image_view : view , image_layout : layout};
+        return self.push(binding, array_element, descriptor_type, DescriptorInfo::Image(info));
+    }
+    ///The set the descriptors are written to.
+    pub fn set(&self)->DescriptorSet{
+        return self.set;
+    }
+}
+impl Renderer{
+    ///The descriptor set layout of the given bindings, identical bindings always return the same layout.
+    ///Layouts are owned by the renderer and destroyed with it.
+    pub fn descriptor_set_layout(&mut self , bindings : &[DescriptorSetLayoutBinding])->Result<DescriptorSetLayout,RendererError>{
+        return unsafe{self.descriptor_layouts.get(&self.device, bindings)};
+    }
+    ///Allocates a descriptor set that lives as long as the renderer.
+    pub fn allocate_descriptor_set(&mut self , layout : DescriptorSetLayout)->Result<DescriptorSet,RendererError>{
+        return unsafe{self.descriptor_allocator.allocate(&self.device, layout, self.descriptor_layouts.needs(layout))};
+    }
+    ///Allocates a descriptor set for a single frame, it is freed when the frame's resources are reused `frames_in_flight` frames later.
+    pub fn allocate_frame_descriptor_set(&mut self , frame : &Frame , layout : DescriptorSetLayout)->Result<DescriptorSet,RendererError>{
+        return unsafe{self.frames[frame.frame_index()].descriptor_allocator.allocate(&self.device, layout, self.descriptor_layouts.needs(layout))};
+    }
+    ///Updates a descriptor set, the set must not be in use by a command buffer that is pending execution.
+    pub fn write_descriptors(&self , writer : &DescriptorWriter){
+        let writes = writer.writes.iter().map(|w|{
+            let (p_buffer_info,p_image_info) = match &w.info{
+                DescriptorInfo::Buffer(info)=>(info as *const DescriptorBufferInfo,std::ptr::null()),
+                DescriptorInfo::Image(info)=>(std::ptr::null(),info as *const DescriptorImageInfo),
+            };
+            WriteDescriptorSet{
+                s_type : StructureType::WRITE_DESCRIPTOR_SET,
+                p_next : std::ptr::null(),
+                dst_set : writer.set,
+                dst_binding : w.binding,
+                dst_array_element : w.array_element,
+                descriptor_count : 1,
+                descriptor_type : w.descriptor_type,
+                p_image_info,
+                p_buffer_info,
+                p_texel_buffer_view : std::ptr::null(),
+            }
+        }).collect::<Vec<_>>();
+        unsafe{self.device.update_descriptor_sets(&writes, &[])};
+    }
+}
@@ -0,0 +1,331 @@
+use std::{ffi::{CStr, CString}, fmt, str::FromStr};
+
+use ash::{Instance, extensions::khr::Surface, version::{InstanceV1_0, InstanceV1_1}, vk::{self, Bool32, MemoryHeapFlags, PhysicalDevice, PhysicalDeviceFeatures, PhysicalDeviceIDProperties, PhysicalDeviceProperties, PhysicalDeviceProperties2, PhysicalDeviceType, QueueFlags, StructureType, SurfaceKHR}};
+use log::{error,info,debug,trace};
+
+use super::{Preference, RendererConfig, RendererError};
+
+///The environment variable that overrides the GPU selection, it takes the same values as `--gpu`.
+pub const DEVICE_OVERRIDE_ENV : &str = "GPENGINE_GPU";
+
+///Picks one GPU by name, enumeration index or UUID, bypassing the automatic selection.
+///Parsed from `--gpu <selector>`, `--gpu=<selector>` or the `GPENGINE_GPU` environment variable.
+///A number is an index, 32 hexadecimal digits (dashes allowed) are a UUID and anything else is a name.
+///The prefixes `index:`, `uuid:` and `name:` force one interpretation.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DeviceSelector{
+    ///The first supported GPU whose name contains this text, ignoring case.
+    Name(String),
+    ///The GPU at this position in the order the Vulkan driver enumerates them.
+    Index(usize),
+    ///The GPU with this device UUID, only known for GPU's that support Vulkan 1.1.
+    Uuid([u8;16]),
+}
+impl FromStr for DeviceSelector{
+    type Err = RendererError;
+    fn from_str(s : &str)->Result<Self,Self::Err>{
+        let s = s.trim();
+        let invalid = ||RendererError::InvalidDeviceSelector(s.to_string());
+        if let Some(index) = s.strip_prefix("index:"){
+            return index.trim().parse().map(DeviceSelector::Index).map_err(|_|invalid());
+        }
+        if let Some(uuid) = s.strip_prefix("uuid:"){
+            return parse_uuid(uuid.trim()).map(DeviceSelector::Uuid).ok_or_else(invalid);
+        }
+        if let Some(name) = s.strip_prefix("name:"){
+            if name.trim().is_empty(){return Err(invalid())}
+            return Ok(DeviceSelector::Name(name.trim().to_string()));
+        }
+        if s.is_empty(){return Err(invalid())}
+        if let Ok(index) = s.parse(){return Ok(DeviceSelector::Index(index))}
+        if let Some(uuid) = parse_uuid(s){return Ok(DeviceSelector::Uuid(uuid))}
+        return Ok(DeviceSelector::Name(s.to_string()));
+    }
+}
+impl fmt::Display for DeviceSelector{
+    fn fmt(&self , f : &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            DeviceSelector::Name(name)=>write!(f,"name:{}",name),
+            DeviceSelector::Index(index)=>write!(f,"index:{}",index),
+            DeviceSelector::Uuid(uuid)=>write!(f,"uuid:{}",format_uuid(uuid)),
+        }
+    }
+}
+///Formats a UUID as 32 lowercase hexadecimal digits in the usual 8-4-4-4-12 groups.
+pub fn format_uuid(uuid : &[u8;16])->String{
+    let mut text = String::with_capacity(36);
+    for (i,byte) in uuid.iter().enumerate(){
+        if i == 4 || i == 6 || i == 8 || i == 10{text.push('-')}
+        text.push_str(&format!("{:02x}",byte));
+    }
+    return text;
+}
+fn parse_uuid(text : &str)->Option<[u8;16]>{
+    let digits = text.chars().filter(|&c|c != '-').collect::<Vec<_>>();
+    if digits.len() != 32 || !digits.iter().all(|c|c.is_ascii_hexdigit()){return None}
+    let mut uuid = [0;16];
+    for (i,pair) in digits.chunks(2).enumerate(){
+        uuid[i] = u8::from_str_radix(&pair.iter().collect::<String>(), 16).ok()?;
+    }
+    return Some(uuid);
+}
+///Gets the GPU override from the command line or the environment, the command line takes precedence.
+///Returns `Ok(None)` when neither is set.
+pub(crate) fn get_device_override_from_env()->Result<Option<DeviceSelector>,RendererError>{
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next(){
+        if arg == "--gpu"{
+            let value = args.next().unwrap_or_default();
+            return value.parse().map(Some);
+        }
+        if let Some(value) = arg.strip_prefix("--gpu="){
+            return value.parse().map(Some);
+        }
+    }
+    match std::env::var(DEVICE_OVERRIDE_ENV){
+        Ok(value) if !value.trim().is_empty()=>return value.parse().map(Some),
+        _=>return Ok(None),
+    }
+}
+///A GPU that supports graphics and compute operations, and presentation when the renderer has a surface.
+pub(crate) struct DeviceCandidate{
+    pub(crate) physical_device : PhysicalDevice,
+    ///The position in the order the driver enumerates the GPU's.
+    pub(crate) index : usize,
+    pub(crate) name : String,
+    pub(crate) properties : PhysicalDeviceProperties,
+    pub(crate) uuid : Option<[u8;16]>,
+}
+impl DeviceCandidate{
+    fn matches(&self , selector : &DeviceSelector)->bool{
+        match selector{
+            DeviceSelector::Name(name)=>return self.name.to_lowercase().contains(&name.to_lowercase()),
+            DeviceSelector::Index(index)=>return self.index == *index,
+            DeviceSelector::Uuid(uuid)=>return self.uuid.as_ref() == Some(uuid),
+        }
+    }
+}
+///How a GPU scored, every part is logged so the choice can be understood from the debug log.
+struct DeviceScore{
+    device_type : u64,
+    preference : u64,
+    memory : u64,
+    api_version : u64,
+}
+impl DeviceScore{
+    fn total(&self)->u64{
+        return self.device_type + self.preference + self.memory + self.api_version;
+    }
+}
+///Gets the device UUID, Vulkan 1.1 is required of both the instance and the GPU to query it.
+pub(crate) unsafe fn get_device_uuid(instance : &Instance , instance_version : u32 , physical_device : PhysicalDevice , properties : &PhysicalDeviceProperties)->Option<[u8;16]>{
+    let version_1_1 = vk::make_version(1, 1, 0);
+    if instance_version < version_1_1 || properties.api_version < version_1_1{return None}
+    let mut id_properties = PhysicalDeviceIDProperties::default();
+    let mut properties2 = PhysicalDeviceProperties2{
+        s_type : StructureType::PHYSICAL_DEVICE_PROPERTIES_2,
+        p_next : &mut id_properties as *mut PhysicalDeviceIDProperties as *mut std::ffi::c_void,
+        properties : PhysicalDeviceProperties::default(),
+    };
+    instance.get_physical_device_properties2(physical_device, &mut properties2);
+    return Some(id_properties.device_uuid);
+}
+///Gets the GPU's that support graphics and compute operations, and presentation to the surface when there is one.
+pub(crate) unsafe fn get_supported_physical_devices(instance : &Instance , instance_version : u32 , surface : Option<(&Surface,&SurfaceKHR)>)->Result<Vec<DeviceCandidate>,RendererError>{
+    let physical_devices = instance.enumerate_physical_devices().map_err(|e|{error!("Failed to get supported devices, {}.",e);RendererError::PhysicalDevice(e)})?;
+    let mut supported_devices = vec!();
+    for (index,&physical_device) in physical_devices.iter().enumerate(){
+        let device_properties = instance.get_physical_device_properties(physical_device);
+        let gpu_name = CStr::from_ptr(device_properties.device_name.as_ptr()).to_string_lossy();
+        trace!("Found GPU :{} of type:{:?}.",gpu_name,device_properties.device_type);
+        let device_queue_family_properties = instance.get_physical_device_queue_family_properties(physical_device);
+        let mut supports_graphics = false;
+        let mut supports_compute = false;
+        let mut supports_presentation = surface.is_none();
+        for (i,&queue_family) in device_queue_family_properties.iter().enumerate(){
+            supports_graphics = supports_graphics || queue_family.queue_flags.contains(QueueFlags::GRAPHICS);
+            supports_compute = supports_compute || queue_family.queue_flags.contains(QueueFlags::COMPUTE);
+            if let Some((surface_loader,surface)) = surface{
+                supports_presentation = supports_presentation || surface_loader.get_physical_device_surface_support(physical_device, i as u32, *surface).map_err(|e|{error!("Failed to check GPU surface support, {}.",e);RendererError::PhysicalDevice(e)})?;
+            }
+        }
+        if supports_graphics && supports_compute && supports_presentation {
+            trace!("GPU is compatible :{}.",gpu_name);
+            supported_devices.push(DeviceCandidate{
+                physical_device,
+                index,
+                name : gpu_name.to_string(),
+                uuid : get_device_uuid(instance, instance_version, physical_device, &device_properties),
+                properties : device_properties,
+            });
+        }
+    }
+    return Ok(supported_devices);
+}
+///Selects the GPU to render with.
+///An override from the command line, the environment or the config is used as is, as long as it supports the required features and extensions.
+///Otherwise every supported GPU is scored on its type, the preferred device types, its device local memory and its Vulkan version, and the highest score wins.
+pub(crate) unsafe fn select_physical_device(instance : &Instance , instance_version : u32 , surface : Option<(&Surface,&SurfaceKHR)> , config : &RendererConfig)->Result<(PhysicalDevice,String),RendererError>{
+    let supported_gpus = get_supported_physical_devices(instance, instance_version, surface)?;
+    if supported_gpus.is_empty(){
+        error!("No supported GPU's found.");
+        return Err(RendererError::NoSupportedPhysicalDevice);
+    }
+    let device_override = match get_device_override_from_env()?{
+        Some(selector)=>Some(selector),
+        None=>config.device_override.clone(),
+    };
+    if let Some(selector) = device_override{
+        let candidate = supported_gpus.iter().find(|c|c.matches(&selector)).ok_or_else(||{
+            error!("No supported GPU matches the override {}.",selector);
+            RendererError::DeviceOverrideNotFound(selector.clone())
+        })?;
+        let missing = get_missing_requirements(instance, candidate.physical_device, config)?;
+        if !missing.is_empty(){
+            error!("The GPU {} selected by the override {} is missing {}.",candidate.name,selector,missing.join(", "));
+            return Err(RendererError::DeviceOverrideUnsupported(selector));
+        }
+        info!("Using GPU {} selected by the override {}.",candidate.name,selector);
+        return Ok((candidate.physical_device,candidate.name.clone()));
+    }
+    if let Preference::Require(device_type) = config.device_type{
+        if !supported_gpus.iter().any(|c|c.properties.device_type == device_type){
+            error!("No supported GPU of type {:?} found.",device_type);
+            return Err(RendererError::RequiredDeviceTypeUnavailable(device_type));
+        }
+    }
+    let mut best : Option<(u64,&DeviceCandidate)> = None;
+    for candidate in supported_gpus.iter(){
+        let score = match score_physical_device(instance, candidate, config)?{
+            Some(score)=>score,
+            None=>continue,
+        };
+        //Ties go to the GPU enumerated first, which is usually the one the driver considers primary.
+        if best.is_none_or(|(best_score,_)|score > best_score){best = Some((score,candidate))}
+    }
+    match best{
+        Some((score,candidate))=>{
+            debug!("Selected GPU {} with a score of {}.",candidate.name,score);
+            return Ok((candidate.physical_device,candidate.name.clone()));
+        }
+        None=>{
+            error!("No supported GPU supports the required features and extensions.");
+            return Err(RendererError::NoSupportedPhysicalDevice);
+        }
+    }
+}
+///Scores a GPU, returns `None` when it doesn't meet the requirements of the config.
+unsafe fn score_physical_device(instance : &Instance , candidate : &DeviceCandidate , config : &RendererConfig)->Result<Option<u64>,RendererError>{
+    let properties = &candidate.properties;
+    if let Preference::Require(device_type) = config.device_type{
+        if properties.device_type != device_type{
+            debug!("GPU {} [{}] rejected: it is of type {:?} instead of the required {:?}.",candidate.index,candidate.name,properties.device_type,device_type);
+            return Ok(None);
+        }
+    }
+    let missing = get_missing_requirements(instance, candidate.physical_device, config)?;
+    if !missing.is_empty(){
+        debug!("GPU {} [{}] rejected: missing {}.",candidate.index,candidate.name,missing.join(", "));
+        return Ok(None);
+    }
+    let device_type = match properties.device_type{
+        PhysicalDeviceType::DISCRETE_GPU=>400,
+        PhysicalDeviceType::INTEGRATED_GPU=>300,
+        PhysicalDeviceType::VIRTUAL_GPU=>200,
+        PhysicalDeviceType::CPU=>100,
+        _=>0,
+    };
+    //Earlier entries of the preferred types are worth more, and every preferred type outweighs the type and memory scores.
+    let preference = match &config.device_type{
+        Preference::Prefer(types)=>match types.iter().position(|&t|t == properties.device_type){
+            Some(position)=>2000u64.saturating_sub(position as u64 * 100).max(1100),
+            None=>0,
+        },
+        Preference::Require(_)=>0,
+    };
+    let memory_properties = instance.get_physical_device_memory_properties(candidate.physical_device);
+    let device_local_bytes = memory_properties.memory_heaps[..memory_properties.memory_heap_count as usize].iter()
+    .filter(|heap|heap.flags.contains(MemoryHeapFlags::DEVICE_LOCAL)).map(|heap|heap.size).sum::<u64>();
+    let device_local_mib = device_local_bytes >> 20;
+    //One point per 256 MiB, capped at 64 GiB so memory can't outweigh the device type.
+    let memory = (device_local_mib / 256).min(256);
+    let api_version = vk::version_minor(properties.api_version) as u64 * 10;
+    let score = DeviceScore{device_type , preference , memory , api_version};
+    debug!("GPU {} [{}] scored {}: type {:?} {} + preference {} + memory {} ({} MiB device local) + Vulkan {}.{} {}.",
+        candidate.index,candidate.name,score.total(),properties.device_type,score.device_type,score.preference,score.memory,device_local_mib,
+        vk::version_major(properties.api_version),vk::version_minor(properties.api_version),score.api_version
+    );
+    return Ok(Some(score.total()));
+}
+///Lists the required features and device extensions of the config that the GPU doesn't support.
+pub(crate) unsafe fn get_missing_requirements(instance : &Instance , physical_device : PhysicalDevice , config : &RendererConfig)->Result<Vec<String>,RendererError>{
+    let mut missing = vec!();
+    let available_extensions = instance.enumerate_device_extension_properties(physical_device).map_err(|e|{
+        error!("Failed to get the device extensions, {}.",e);
+        RendererError::PhysicalDevice(e)
+    })?;
+    let available_extensions = available_extensions.iter().map(|e|CStr::from_ptr(e.extension_name.as_ptr()).to_owned()).collect::<Vec<CString>>();
+    for extension in config.required_extensions.iter(){
+        if !available_extensions.contains(extension){missing.push(format!("extension {}",extension.to_string_lossy()))}
+    }
+    let supported_features = instance.get_physical_device_features(physical_device);
+    let missing_features = count_missing_features(&config.required_features, &supported_features);
+    if missing_features > 0{missing.push(format!("{} required features",missing_features))}
+    return Ok(missing);
+}
+fn count_missing_features(required : &PhysicalDeviceFeatures , supported : &PhysicalDeviceFeatures)->usize{
+    //PhysicalDeviceFeatures is a C struct made up of nothing but Bool32 fields.
+    const FEATURE_COUNT : usize = std::mem::size_of::<PhysicalDeviceFeatures>() / std::mem::size_of::<Bool32>();
+    let required = unsafe{std::slice::from_raw_parts(required as *const PhysicalDeviceFeatures as *const Bool32, FEATURE_COUNT)};
+    let supported = unsafe{std::slice::from_raw_parts(supported as *const PhysicalDeviceFeatures as *const Bool32, FEATURE_COUNT)};
+    return required.iter().zip(supported.iter()).filter(|(&required,&supported)|required != vk::FALSE && supported == vk::FALSE).count();
+}
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    const UUID : [u8;16] = [0x12,0x34,0x56,0x78,0x9a,0xbc,0xde,0xf0,0x01,0x23,0x45,0x67,0x89,0xab,0xcd,0xef];
+
+    fn parse(s : &str)->Option<DeviceSelector>{
+        return s.parse().ok();
+    }
+    #[test]
+    fn parses_uuids_with_and_without_dashes(){
+        assert_eq!(parse_uuid("123456789abcdef0123456789abcdef"), None);
+        assert_eq!(parse_uuid("123456789abcdef00123456789abcdef"), Some(UUID));
+        assert_eq!(parse_uuid("12345678-9ABC-DEF0-0123-456789ABCDEF"), Some(UUID));
+        assert_eq!(parse_uuid(&format_uuid(&UUID)), Some(UUID));
+        assert_eq!(parse_uuid("123456789abcdef00123456789abcdeg"), None);
+        assert_eq!(parse_uuid("+23456789abcdef00123456789abcdef"), None);
+        assert_eq!(parse_uuid(""), None);
+    }
+    #[test]
+    fn guesses_the_kind_of_unprefixed_selectors(){
+        assert_eq!(parse("1"), Some(DeviceSelector::Index(1)));
+        assert_eq!(parse(" 12345678-9abc-def0-0123-456789abcdef "), Some(DeviceSelector::Uuid(UUID)));
+        assert_eq!(parse("RTX 3070"), Some(DeviceSelector::Name("RTX 3070".to_string())));
+        assert_eq!(parse("123456789abcdef00123456789abcdeg"), Some(DeviceSelector::Name("123456789abcdef00123456789abcdeg".to_string())));
+    }
+    #[test]
+    fn prefixes_force_the_kind(){
+        assert_eq!(parse("index:2"), Some(DeviceSelector::Index(2)));
+        assert_eq!(parse("uuid:123456789abcdef00123456789abcdef"), Some(DeviceSelector::Uuid(UUID)));
+        assert_eq!(parse("name:0"), Some(DeviceSelector::Name("0".to_string())));
+        assert_eq!(parse("index:gpu"), None);
+        assert_eq!(parse("uuid:123456789abcdef00123456789abcdeg"), None);
+        assert_eq!(parse("name: "), None);
+    }
+    #[test]
+    fn empty_selectors_are_invalid(){
+        assert!(matches!("".parse::<DeviceSelector>(), Err(RendererError::InvalidDeviceSelector(_))));
+        assert!(matches!("  ".parse::<DeviceSelector>(), Err(RendererError::InvalidDeviceSelector(_))));
+        assert_eq!(parse("index:"), None);
+        assert_eq!(parse("uuid:"), None);
+    }
+    #[test]
+    fn selectors_display_as_they_parse(){
+        for selector in [DeviceSelector::Index(3),DeviceSelector::Uuid(UUID),DeviceSelector::Name("Intel".to_string())].iter(){
+            assert_eq!(parse(&selector.to_string()).as_ref(), Some(selector));
+        }
+    }
+}
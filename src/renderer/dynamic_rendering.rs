@@ -0,0 +1,186 @@
+use std::ffi::{CStr, c_void};
+
+use ash::{Device, Instance, version::{InstanceV1_0, InstanceV1_1}, vk::{self, AttachmentLoadOp, AttachmentStoreOp, Bool32, ClearValue, CommandBuffer, Format, ImageLayout, ImageView, PhysicalDevice, PhysicalDeviceFeatures2, Rect2D, ResolveModeFlags, StructureType}};
+use log::debug;
+
+//ash 0.32 predates dynamic rendering, the structures and commands below mirror the Vulkan 1.3 headers.
+const STRUCTURE_TYPE_RENDERING_INFO : StructureType = StructureType::from_raw(1000044000);
+const STRUCTURE_TYPE_RENDERING_ATTACHMENT_INFO : StructureType = StructureType::from_raw(1000044001);
+const STRUCTURE_TYPE_PIPELINE_RENDERING_CREATE_INFO : StructureType = StructureType::from_raw(1000044002);
+const STRUCTURE_TYPE_PHYSICAL_DEVICE_DYNAMIC_RENDERING_FEATURES : StructureType = StructureType::from_raw(1000044003);
+const EXTENSION_NAME : &[u8] = b"VK_KHR_dynamic_rendering\0";
+
+///`VkRenderingInfo`, the area and attachments rendered to between `vkCmdBeginRendering` and `vkCmdEndRendering`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct RenderingInfo{
+    pub(crate) s_type : StructureType,
+    pub(crate) p_next : *const c_void,
+    pub(crate) flags : u32,
+    pub(crate) render_area : Rect2D,
+    pub(crate) layer_count : u32,
+    pub(crate) view_mask : u32,
+    pub(crate) color_attachment_count : u32,
+    pub(crate) p_color_attachments : *const RenderingAttachmentInfo,
+    pub(crate) p_depth_attachment : *const RenderingAttachmentInfo,
+    pub(crate) p_stencil_attachment : *const RenderingAttachmentInfo,
+}
+///`VkRenderingAttachmentInfo`, an image view used as an attachment and what happens to it at the start and end of rendering.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct RenderingAttachmentInfo{
+    pub(crate) s_type : StructureType,
+    pub(crate) p_next : *const c_void,
+    pub(crate) image_view : ImageView,
+    pub(crate) image_layout : ImageLayout,
+    pub(crate) resolve_mode : ResolveModeFlags,
+    pub(crate) resolve_image_view : ImageView,
+    pub(crate) resolve_image_layout : ImageLayout,
+    pub(crate) load_op : AttachmentLoadOp,
+    pub(crate) store_op : AttachmentStoreOp,
+    pub(crate) clear_value : ClearValue,
+}
+impl RenderingAttachmentInfo{
+    ///An attachment without a resolve target.
+    pub(crate) fn new(image_view : ImageView , image_layout : ImageLayout , load_op : AttachmentLoadOp , store_op : AttachmentStoreOp , clear_value : ClearValue)->Self{
+        return Self{
+            s_type : STRUCTURE_TYPE_RENDERING_ATTACHMENT_INFO,
+            p_next : std::ptr::null(),
+            image_view,
+            image_layout,
+            resolve_mode : ResolveModeFlags::NONE,
+            resolve_image_view : ImageView::null(),
+            resolve_image_layout : ImageLayout::UNDEFINED,
+            load_op,
+            store_op,
+            clear_value,
+        };
+    }
+}
+impl RenderingInfo{
+    pub(crate) fn new(render_area : Rect2D , color_attachments : &[RenderingAttachmentInfo] , depth_attachment : Option<&RenderingAttachmentInfo> , stencil_attachment : Option<&RenderingAttachmentInfo>)->Self{
+        return Self{
+            s_type : STRUCTURE_TYPE_RENDERING_INFO,
+            p_next : std::ptr::null(),
+            flags : 0,
+            render_area,
+            layer_count : 1,
+            view_mask : 0,
+            color_attachment_count : color_attachments.len() as u32,
+            p_color_attachments : color_attachments.as_ptr(),
+            p_depth_attachment : depth_attachment.map_or(std::ptr::null(),|a|a as *const RenderingAttachmentInfo),
+            p_stencil_attachment : stencil_attachment.map_or(std::ptr::null(),|a|a as *const RenderingAttachmentInfo),
+        };
+    }
+}
+///`VkPipelineRenderingCreateInfo`, the attachment formats a pipeline renders to when it is built without a render pass.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct PipelineRenderingCreateInfo{
+    pub(crate) s_type : StructureType,
+    pub(crate) p_next : *const c_void,
+    pub(crate) view_mask : u32,
+    pub(crate) color_attachment_count : u32,
+    pub(crate) p_color_attachment_formats : *const Format,
+    pub(crate) depth_attachment_format : Format,
+    pub(crate) stencil_attachment_format : Format,
+}
+impl PipelineRenderingCreateInfo{
+    pub(crate) fn new(color_formats : &[Format] , depth_format : Format , stencil_format : Format)->Self{
+        return Self{
+            s_type : STRUCTURE_TYPE_PIPELINE_RENDERING_CREATE_INFO,
+            p_next : std::ptr::null(),
+            view_mask : 0,
+            color_attachment_count : color_formats.len() as u32,
+            p_color_attachment_formats : color_formats.as_ptr(),
+            depth_attachment_format : depth_format,
+            stencil_attachment_format : stencil_format,
+        };
+    }
+}
+///`VkPhysicalDeviceDynamicRenderingFeatures`, chained into the device create info to enable dynamic rendering.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct PhysicalDeviceDynamicRenderingFeatures{
+    pub(crate) s_type : StructureType,
+    pub(crate) p_next : *mut c_void,
+    pub(crate) dynamic_rendering : Bool32,
+}
+type CmdBeginRendering = unsafe extern "system" fn(CommandBuffer , *const RenderingInfo);
+type CmdEndRendering = unsafe extern "system" fn(CommandBuffer);
+///How a GPU supports dynamic rendering.
+pub(crate) struct DynamicRenderingSupport{
+    ///Set when the device is older than Vulkan 1.3 and `VK_KHR_dynamic_rendering` has to be enabled.
+    pub(crate) needs_extension : bool,
+}
+impl DynamicRenderingSupport{
+    ///The features to enable, chained into the device create info.
+    pub(crate) fn features(&self)->PhysicalDeviceDynamicRenderingFeatures{
+        return PhysicalDeviceDynamicRenderingFeatures{
+            s_type : STRUCTURE_TYPE_PHYSICAL_DEVICE_DYNAMIC_RENDERING_FEATURES,
+            p_next : std::ptr::null_mut(),
+            dynamic_rendering : vk::TRUE,
+        };
+    }
+}
+///The name of `VK_KHR_dynamic_rendering`.
+pub(crate) fn extension_name()->&'static CStr{
+    return unsafe{CStr::from_bytes_with_nul_unchecked(EXTENSION_NAME)};
+}
+///Checks whether a GPU supports dynamic rendering, either in core Vulkan 1.3 or through `VK_KHR_dynamic_rendering` on Vulkan 1.2.
+///The extension depends on depth stencil resolve, which is only core since Vulkan 1.2.
+pub(crate) unsafe fn query_support(instance : &Instance , instance_version : u32 , physical_device : PhysicalDevice)->Option<DynamicRenderingSupport>{
+    let properties = instance.get_physical_device_properties(physical_device);
+    let version = instance_version.min(properties.api_version);
+    if version < vk::make_version(1, 2, 0){
+        debug!("Dynamic rendering needs Vulkan 1.2 or later.");
+        return None;
+    }
+    let needs_extension = version < vk::make_version(1, 3, 0);
+    if needs_extension{
+        let extensions = instance.enumerate_device_extension_properties(physical_device).unwrap_or_default();
+        if !extensions.iter().any(|e|CStr::from_ptr(e.extension_name.as_ptr()) == extension_name()){
+            debug!("The GPU supports neither Vulkan 1.3 nor {}.",extension_name().to_string_lossy());
+            return None;
+        }
+    }
+    let mut supported = PhysicalDeviceDynamicRenderingFeatures{
+        s_type : STRUCTURE_TYPE_PHYSICAL_DEVICE_DYNAMIC_RENDERING_FEATURES,
+        p_next : std::ptr::null_mut(),
+        dynamic_rendering : vk::FALSE,
+    };
+    let mut features2 = PhysicalDeviceFeatures2{
+        s_type : StructureType::PHYSICAL_DEVICE_FEATURES_2,
+        p_next : &mut supported as *mut PhysicalDeviceDynamicRenderingFeatures as *mut c_void,
+        features : Default::default(),
+    };
+    instance.get_physical_device_features2(physical_device, &mut features2);
+    if supported.dynamic_rendering == vk::FALSE{
+        debug!("The GPU doesn't support the dynamicRendering feature.");
+        return None;
+    }
+    return Some(DynamicRenderingSupport{needs_extension});
+}
+///The dynamic rendering commands of a device, loaded by hand since ash doesn't know them.
+pub(crate) struct DynamicRendering{
+    begin_rendering : CmdBeginRendering,
+    end_rendering : CmdEndRendering,
+}
+impl DynamicRendering{
+    ///Loads the core commands, or their KHR aliases when the extension is used, `None` if the driver doesn't return them.
+    pub(crate) unsafe fn load(instance : &Instance , device : &Device , support : &DynamicRenderingSupport)->Option<Self>{
+        let (begin_name,end_name) : (&[u8],&[u8]) = if support.needs_extension{(b"vkCmdBeginRenderingKHR\0",b"vkCmdEndRenderingKHR\0")}else{(b"vkCmdBeginRendering\0",b"vkCmdEndRendering\0")};
+        let begin_rendering = instance.get_device_proc_addr(device.handle(), begin_name.as_ptr() as *const std::os::raw::c_char)?;
+        let end_rendering = instance.get_device_proc_addr(device.handle(), end_name.as_ptr() as *const std::os::raw::c_char)?;
+        return Some(Self{
+            begin_rendering : std::mem::transmute::<unsafe extern "system" fn(),CmdBeginRendering>(begin_rendering),
+            end_rendering : std::mem::transmute::<unsafe extern "system" fn(),CmdEndRendering>(end_rendering),
+        });
+    }
+    pub(crate) unsafe fn cmd_begin_rendering(&self , command_buffer : CommandBuffer , rendering_info : &RenderingInfo){
+        (self.begin_rendering)(command_buffer, rendering_info);
+    }
+    pub(crate) unsafe fn cmd_end_rendering(&self , command_buffer : CommandBuffer){
+        (self.end_rendering)(command_buffer);
+    }
+}
@@ -0,0 +1,214 @@
+use std::fmt;
+
+use ash::{InstanceError, LoadingError, vk};
+
+use super::{BindlessKind, DeviceSelector};
+
+///Describes the stage at which the renderer failed to initialize.
+///Every variant that originates from a Vulkan call carries the underlying error as its source.
+#[derive(Debug)]
+pub enum RendererError{
+    ///The Vulkan library could not be loaded.
+    LoadLibrary(LoadingError),
+    ///The Vulkan instance could not be created.
+    CreateInstance(InstanceError),
+    ///The window surface could not be created.
+    CreateSurface(vk::Result),
+    ///Querying the physical devices or their surface support failed.
+    PhysicalDevice(vk::Result),
+    ///None of the physical devices meet the renderer's requirements.
+    NoSupportedPhysicalDevice,
+    ///No supported GPU is of the required device type.
+    RequiredDeviceTypeUnavailable(vk::PhysicalDeviceType),
+    ///A GPU override from `--gpu` or `GPENGINE_GPU` could not be parsed.
+    InvalidDeviceSelector(String),
+    ///No supported GPU matches the GPU override.
+    DeviceOverrideNotFound(DeviceSelector),
+    ///The GPU selected by the override doesn't support the required features or extensions.
+    DeviceOverrideUnsupported(DeviceSelector),
+    ///The selected physical device has no queue family that supports graphics operations.
+    NoGraphicsQueueFamily,
+    ///The selected physical device has no queue family that supports compute operations.
+    NoComputeQueueFamily,
+    ///Neither the graphics nor the compute queue family can present to the surface.
+    NoPresentationQueueFamily,
+    ///The logical device could not be created.
+    CreateDevice(vk::Result),
+    ///The swapchain, or one of its image views, could not be created.
+    CreateSwapchain(vk::Result),
+    ///The surface doesn't support the required present mode.
+    RequiredPresentModeUnsupported(vk::PresentModeKHR),
+    ///The surface doesn't support the required surface format.
+    RequiredSurfaceFormatUnsupported(vk::SurfaceFormatKHR),
+    ///The surface doesn't support the required number of swapchain images.
+    RequiredImageCountUnsupported{requested : u32 , min : u32 , max : u32},
+    ///The required depth format can't be used as a depth attachment.
+    RequiredDepthFormatUnsupported(vk::Format),
+    ///The required sample count isn't supported for both color and depth attachments.
+    RequiredSampleCountUnsupported(vk::SampleCountFlags),
+    ///None of the color formats are supported as an offscreen color attachment.
+    NoSupportedColorFormat,
+    ///The offscreen color image, its memory or its view could not be created.
+    CreateOffscreenImage(vk::Result),
+    ///A headless renderer was requested with a width or height of zero.
+    InvalidExtent(vk::Extent2D),
+    ///None of the depth formats are supported as a depth attachment.
+    NoSupportedDepthFormat,
+    ///No memory type satisfies the requirements of a resource.
+    NoSupportedMemoryType,
+    ///Allocating, mapping or binding device memory failed, or the maximum number of allocations was reached.
+    AllocateMemory(vk::Result),
+    ///The depth image, its memory or its view could not be created.
+    CreateDepthImage(vk::Result),
+    ///Waiting for the device to become idle failed.
+    WaitIdle(vk::Result),
+    ///Acquiring the next swapchain image failed.
+    AcquireImage(vk::Result),
+    ///Presenting a swapchain image failed.
+    Present(vk::Result),
+    ///The command pools, command buffers or synchronization objects of the frames in flight could not be created.
+    CreateFrameResources(vk::Result),
+    ///A staging buffer, or the command buffers and synchronization objects of an upload, could not be created.
+    CreateUploadResources(vk::Result),
+    ///An image, its memory or one of its views could not be created.
+    CreateImage(vk::Result),
+    ///An image description is invalid, the reason is included.
+    InvalidImageDescription(String),
+    ///A texture file could not be read or decoded, the reason is included.
+    LoadTexture(String),
+    ///The GPU can't sample or upload to the texture format, and it can't be decompressed on the CPU, which only decodes BC1 to BC5 so BC6H and BC7 need GPU support.
+    UnsupportedTextureFormat(vk::Format),
+    ///A buffer could not be created.
+    CreateBuffer(vk::Result),
+    ///A write doesn't fit in the buffer, `offset`, `len` and `capacity` are in elements.
+    BufferOverflow{offset : usize , len : usize , capacity : usize},
+    ///A shader file could not be read or isn't valid SPIR-V, the reason is included.
+    InvalidShader(String),
+    ///A GLSL or WGSL shader could not be compiled, the message includes the file and line.
+    CompileShader(String),
+    ///A shader module could not be created.
+    CreateShaderModule(vk::Result),
+    ///The shaders of a pipeline declare the same binding or push constants differently, the reason is included.
+    IncompatibleShaders(String),
+    ///A descriptor set layout or pipeline layout could not be created.
+    CreatePipelineLayout(vk::Result),
+    ///A pipeline description is invalid, the reason is included.
+    InvalidPipeline(String),
+    ///A pipeline could not be created.
+    CreatePipeline(vk::Result),
+    ///The pipeline cache could not be created.
+    CreatePipelineCache(vk::Result),
+    ///A compute dispatch doesn't match its pipeline, the reason is included.
+    InvalidDispatch(String),
+    ///The command pool, descriptor pool or fence of a compute dispatch could not be created.
+    CreateComputeResources(vk::Result),
+    ///A descriptor set layout could not be created.
+    CreateDescriptorSetLayout(vk::Result),
+    ///A descriptor pool could not be created or reset, or a descriptor set could not be allocated from it.
+    AllocateDescriptorSet(vk::Result),
+    ///Bindless mode was not requested or the GPU doesn't support descriptor indexing.
+    BindlessUnavailable,
+    ///Every index of a kind in the bindless set is in use.
+    BindlessSetFull(BindlessKind),
+    ///Waiting for or resetting a fence failed.
+    WaitFence(vk::Result),
+    ///Recording a command buffer failed.
+    RecordCommands(vk::Result),
+    ///Submitting command buffers to a queue failed.
+    Submit(vk::Result),
+    ///The render pass could not be created.
+    CreateRenderPass(vk::Result),
+    ///One of the framebuffers could not be created.
+    CreateFramebuffer(vk::Result),
+    ///A render graph can't be compiled, the reason is included.
+    InvalidGraph(String),
+    ///The image format properties of the color or depth format could not be queried.
+    ImageFormatProperties(vk::Result),
+    ///The sample count can't change while a custom frame graph is set, its attachments wouldn't match the new depth buffer.
+    CustomFrameGraph,
+}
+impl fmt::Display for RendererError{
+    fn fmt(&self , f : &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            RendererError::LoadLibrary(e)=>write!(f,"failed to load the Vulkan library: {}",e),
+            RendererError::CreateInstance(e)=>write!(f,"failed to create the Vulkan instance: {}",e),
+            RendererError::CreateSurface(e)=>write!(f,"failed to create the Vulkan surface: {}",e),
+            RendererError::PhysicalDevice(e)=>write!(f,"failed to query the physical devices: {}",e),
+            RendererError::NoSupportedPhysicalDevice=>write!(f,"no supported GPU found"),
+            RendererError::RequiredDeviceTypeUnavailable(t)=>write!(f,"no supported GPU of the required type {:?} found",t),
+            RendererError::InvalidDeviceSelector(s)=>write!(f,"invalid GPU selector \"{}\"",s),
+            RendererError::DeviceOverrideNotFound(s)=>write!(f,"no supported GPU matches {}",s),
+            RendererError::DeviceOverrideUnsupported(s)=>write!(f,"the GPU selected by {} doesn't support the required features and extensions",s),
+            RendererError::NoGraphicsQueueFamily=>write!(f,"no queue family supports graphics operations"),
+            RendererError::NoComputeQueueFamily=>write!(f,"no queue family supports compute operations"),
+            RendererError::NoPresentationQueueFamily=>write!(f,"neither the graphics nor the compute queue family supports presentation"),
+            RendererError::CreateDevice(e)=>write!(f,"failed to create the Vulkan device: {}",e),
+            RendererError::CreateSwapchain(e)=>write!(f,"failed to create the swapchain: {}",e),
+            RendererError::RequiredPresentModeUnsupported(m)=>write!(f,"the required present mode {:?} is not supported by the surface",m),
+            RendererError::RequiredSurfaceFormatUnsupported(s)=>write!(f,"the required surface format {:?} with color space {:?} is not supported by the surface",s.format,s.color_space),
+            RendererError::RequiredImageCountUnsupported{requested,min,max}=>if *max == 0{
+                write!(f,"the required swapchain image count {} is less than the minimum of {}",requested,min)
+            }else{
+                write!(f,"the required swapchain image count {} is outside the supported range {}..={}",requested,min,max)
+            },
+            RendererError::RequiredDepthFormatUnsupported(d)=>write!(f,"the required depth format {:?} is not supported as a depth attachment",d),
+            RendererError::RequiredSampleCountUnsupported(c)=>write!(f,"the required sample count {:?} is not supported",c),
+            RendererError::NoSupportedColorFormat=>write!(f,"no supported offscreen color format found"),
+            RendererError::CreateOffscreenImage(e)=>write!(f,"failed to create the offscreen color image: {}",e),
+            RendererError::InvalidExtent(e)=>write!(f,"invalid render target size {}x{}",e.width,e.height),
+            RendererError::NoSupportedDepthFormat=>write!(f,"no supported depth format found"),
+            RendererError::NoSupportedMemoryType=>write!(f,"no supported memory type found"),
+            RendererError::AllocateMemory(e)=>write!(f,"failed to allocate device memory: {}",e),
+            RendererError::CreateDepthImage(e)=>write!(f,"failed to create the depth image: {}",e),
+            RendererError::WaitIdle(e)=>write!(f,"failed to wait for the device to become idle: {}",e),
+            RendererError::AcquireImage(e)=>write!(f,"failed to acquire a swapchain image: {}",e),
+            RendererError::Present(e)=>write!(f,"failed to present a swapchain image: {}",e),
+            RendererError::CreateFrameResources(e)=>write!(f,"failed to create the frame resources: {}",e),
+            RendererError::CreateUploadResources(e)=>write!(f,"failed to create the upload resources: {}",e),
+            RendererError::CreateImage(e)=>write!(f,"failed to create an image: {}",e),
+            RendererError::InvalidImageDescription(r)=>write!(f,"invalid image description: {}",r),
+            RendererError::LoadTexture(r)=>write!(f,"failed to load a texture: {}",r),
+            RendererError::UnsupportedTextureFormat(format)=>write!(f,"the texture format {:?} is not supported",format),
+            RendererError::CreateBuffer(e)=>write!(f,"failed to create a buffer: {}",e),
+            RendererError::BufferOverflow{offset,len,capacity}=>write!(f,"writing {} elements at offset {} overflows a buffer of {} elements",len,offset,capacity),
+            RendererError::InvalidShader(r)=>write!(f,"invalid shader: {}",r),
+            RendererError::CompileShader(r)=>write!(f,"failed to compile a shader: {}",r),
+            RendererError::CreateShaderModule(e)=>write!(f,"failed to create a shader module: {}",e),
+            RendererError::IncompatibleShaders(r)=>write!(f,"incompatible shaders: {}",r),
+            RendererError::CreatePipelineLayout(e)=>write!(f,"failed to create a pipeline layout: {}",e),
+            RendererError::InvalidPipeline(r)=>write!(f,"invalid pipeline: {}",r),
+            RendererError::CreatePipeline(e)=>write!(f,"failed to create a pipeline: {}",e),
+            RendererError::CreatePipelineCache(e)=>write!(f,"failed to create the pipeline cache: {}",e),
+            RendererError::InvalidDispatch(r)=>write!(f,"invalid compute dispatch: {}",r),
+            RendererError::CreateComputeResources(e)=>write!(f,"failed to create the compute dispatch resources: {}",e),
+            RendererError::CreateDescriptorSetLayout(e)=>write!(f,"failed to create a descriptor set layout: {}",e),
+            RendererError::AllocateDescriptorSet(e)=>write!(f,"failed to allocate a descriptor set: {}",e),
+            RendererError::BindlessUnavailable=>write!(f,"bindless mode is not enabled"),
+            RendererError::BindlessSetFull(k)=>write!(f,"the bindless set has no room for another {:?}",k),
+            RendererError::WaitFence(e)=>write!(f,"failed to wait for a fence: {}",e),
+            RendererError::RecordCommands(e)=>write!(f,"failed to record a command buffer: {}",e),
+            RendererError::Submit(e)=>write!(f,"failed to submit to a queue: {}",e),
+            RendererError::CreateRenderPass(e)=>write!(f,"failed to create the render pass: {}",e),
+            RendererError::CreateFramebuffer(e)=>write!(f,"failed to create a framebuffer: {}",e),
+            RendererError::InvalidGraph(r)=>write!(f,"invalid render graph: {}",r),
+            RendererError::ImageFormatProperties(e)=>write!(f,"failed to get image format properties: {}",e),
+            RendererError::CustomFrameGraph=>write!(f,"the sample count can't change while a custom frame graph is set"),
+        }
+    }
+}
+impl std::error::Error for RendererError{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)>{
+        match self{
+            RendererError::LoadLibrary(e)=>Some(e),
+            RendererError::CreateInstance(e)=>Some(e),
+            RendererError::CreateSurface(e) | RendererError::PhysicalDevice(e) | RendererError::CreateDevice(e) |
+            RendererError::CreateSwapchain(e) | RendererError::CreateOffscreenImage(e) | RendererError::AllocateMemory(e) | RendererError::CreateDepthImage(e) | RendererError::WaitIdle(e) |
+            RendererError::AcquireImage(e) | RendererError::Present(e) | RendererError::CreateFrameResources(e) | RendererError::CreateUploadResources(e) | RendererError::CreateImage(e) | RendererError::CreateBuffer(e) | RendererError::WaitFence(e) | RendererError::ImageFormatProperties(e) |
+            RendererError::CreateShaderModule(e) | RendererError::CreatePipelineLayout(e) | RendererError::CreatePipeline(e) | RendererError::CreatePipelineCache(e) | RendererError::CreateComputeResources(e) |
+            RendererError::CreateDescriptorSetLayout(e) | RendererError::AllocateDescriptorSet(e) |
+            RendererError::RecordCommands(e) | RendererError::Submit(e) | RendererError::CreateRenderPass(e) |
+            RendererError::CreateFramebuffer(e)=>Some(e),
+            _=>None,
+        }
+    }
+}
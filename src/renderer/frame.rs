@@ -0,0 +1,341 @@
+use ash::{Device, version::DeviceV1_0, vk::{ClearColorValue, CommandBuffer, CommandBufferAllocateInfo, CommandBufferBeginInfo, CommandBufferLevel, CommandBufferUsageFlags, CommandPool, CommandPoolCreateFlags, CommandPoolCreateInfo, CommandPoolResetFlags, Extent2D, Fence, ImageAspectFlags, ImageLayout, ImageUsageFlags, FenceCreateFlags, FenceCreateInfo, PipelineStageFlags, Semaphore, SemaphoreCreateFlags, SemaphoreCreateInfo, StructureType, SubmitInfo, Viewport}};
+use log::{error,debug,trace};
+
+use super::{Renderer, RendererError, descriptor::DescriptorAllocator};
+
+///The resources used to record and submit a single frame, the renderer keeps one set per frame in flight.
+pub(crate) struct FrameResources{
+    command_pool : CommandPool,
+    command_buffer : CommandBuffer,
+    ///Signaled when the color image has been acquired.
+    image_available : Semaphore,
+    ///Signaled when rendering has finished, presentation waits on it.
+    render_finished : Semaphore,
+    ///Signaled when the frame's submission has completed on the GPU.
+    in_flight : Fence,
+    ///The serial of the last frame submitted with these resources, 0 before the first submission.
+    pub(crate) serial : u64,
+    ///Descriptor sets allocated for the frame, reset when the frame's resources are reused.
+    pub(crate) descriptor_allocator : DescriptorAllocator,
+}
+impl FrameResources{
+    pub(crate) fn in_flight(&self)->Fence{
+        return self.in_flight;
+    }
+    pub(crate) unsafe fn destroy(&self , device : &Device){
+        device.destroy_fence(self.in_flight, None);
+        device.destroy_semaphore(self.render_finished, None);
+        device.destroy_semaphore(self.image_available, None);
+        device.destroy_command_pool(self.command_pool, None);
+        self.descriptor_allocator.destroy(device);
+    }
+}
+///A frame being recorded, returned by `Renderer::begin_frame` and handed back to `Renderer::end_frame`.
+///The command buffer is already rendering to the attachments of the frame graph's external pass, with the viewport and scissor set to them.
+#[must_use = "a frame must be handed back to Renderer::end_frame, or its slot's fence stays unsignaled"]
+pub struct Frame{
+    command_buffer : CommandBuffer,
+    image_index : u32,
+    frame_index : usize,
+}
+impl Frame{
+    ///The command buffer to record draw commands into.
+    pub fn command_buffer(&self)->CommandBuffer{
+        return self.command_buffer;
+    }
+    ///The index of the color image and framebuffer drawn to.
+    pub fn image_index(&self)->u32{
+        return self.image_index;
+    }
+    ///The index of the frame in flight, between 0 and the number of frames in flight.
+    pub fn frame_index(&self)->usize{
+        return self.frame_index;
+    }
+}
+///Creates the resources for each frame in flight, everything created is destroyed again when a later step fails.
+pub(crate) unsafe fn create_frame_resources(device : &Device , queue_family_index : u32 , frames_in_flight : u32)->Result<Vec<FrameResources>,RendererError>{
+    let mut frames : Vec<FrameResources> = vec!();
+    for i in 0..frames_in_flight{
+        trace!("Creating resources for frame {}.",i);
+        match create_single_frame_resources(device, queue_family_index){
+            Ok(frame)=>frames.push(frame),
+            Err(e)=>{
+                for frame in frames.iter(){frame.destroy(device)}
+                return Err(e);
+            }
+        }
+    }
+    return Ok(frames);
+}
+unsafe fn create_single_frame_resources(device : &Device , queue_family_index : u32)->Result<FrameResources,RendererError>{
+    let command_pool_create_info = CommandPoolCreateInfo{
+        s_type : StructureType::COMMAND_POOL_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : CommandPoolCreateFlags::TRANSIENT,
+        queue_family_index,
+    };
+    let command_pool = device.create_command_pool(&command_pool_create_info, None).map_err(|e|{
+        error!("Failed to create frame command pool, {}.",e);
+        RendererError::CreateFrameResources(e)
+    })?;
+    let command_buffer_allocate_info = CommandBufferAllocateInfo{
+        s_type : StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
+        p_next : std::ptr::null(),
+        command_pool,
+        level : CommandBufferLevel::PRIMARY,
+        command_buffer_count : 1,
+    };
+    let semaphore_create_info = SemaphoreCreateInfo{
+        s_type : StructureType::SEMAPHORE_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : SemaphoreCreateFlags::empty(),
+    };
+    //The fence starts signaled, so waiting for the first use of a frame returns immediately.
+    let fence_create_info = FenceCreateInfo{
+        s_type : StructureType::FENCE_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : FenceCreateFlags::SIGNALED,
+    };
+    let mut frame = FrameResources{
+        command_pool,
+        command_buffer : CommandBuffer::null(),
+        image_available : Semaphore::null(),
+        render_finished : Semaphore::null(),
+        in_flight : Fence::null(),
+        serial : 0,
+        descriptor_allocator : DescriptorAllocator::new(),
+    };
+    //Destroying null handles is a no-op, so a partially created frame can be destroyed as a whole.
+    let result = (||->Result<(),ash::vk::Result>{
+        frame.command_buffer = device.allocate_command_buffers(&command_buffer_allocate_info)?[0];
+        frame.image_available = device.create_semaphore(&semaphore_create_info, None)?;
+        frame.render_finished = device.create_semaphore(&semaphore_create_info, None)?;
+        frame.in_flight = device.create_fence(&fence_create_info, None)?;
+        return Ok(());
+    })();
+    if let Err(e) = result{
+        error!("Failed to create frame resources, {}.",e);
+        frame.destroy(device);
+        return Err(RendererError::CreateFrameResources(e));
+    }
+    return Ok(frame);
+}
+impl Renderer{
+    ///The number of frames that can be recorded before waiting for the GPU to finish the oldest one.
+    pub fn frames_in_flight(&self)->usize{
+        return self.frames.len();
+    }
+    ///Sets the color the external pass of the frame graph clears the backbuffer to, when it clears it, and the clear color of `default_frame_graph`.
+    pub fn set_clear_color(&mut self , clear_color : [f32;4]){
+        self.clear_color = clear_color;
+        self.frame_graph.set_backbuffer_clear_color(clear_color);
+    }
+    ///Waits for the oldest frame in flight, acquires the next color image, records the frame graph's passes before the external pass and begins the external pass.
+    ///Returns `None` when no frame can be drawn right now, because rendering is paused or the swapchain was recreated.
+    ///When recording the frame graph fails the frame is abandoned and the error returned, the next frame can be begun as usual.
+    pub fn begin_frame(&mut self)->Result<Option<Frame>,RendererError>{
+        let frame_index = self.current_frame;
+        let (in_flight,image_available,command_pool,command_buffer,serial) = {
+            let frame = &self.frames[frame_index];
+            (frame.in_flight,frame.image_available,frame.command_pool,frame.command_buffer,frame.serial)
+        };
+        unsafe{self.device.wait_for_fences(&[in_flight], true, u64::MAX)}.map_err(|e|{
+            error!("Failed to wait for frame {}, {}.",frame_index,e);
+            RendererError::WaitFence(e)
+        })?;
+        //Uploads are usually finished by the time a frame slot is reused, free their staging buffers.
+        self.collect_uploads()?;
+        self.collect_dispatches()?;
+        self.deletion_queue.frame_completed(serial);
+        unsafe{self.frames[frame_index].descriptor_allocator.reset(&self.device)}?;
+        self.collect_garbage();
+        let image_index = match self.acquire_next_image(image_available, Fence::null())?{
+            Some(image_index)=>image_index,
+            None=>return Ok(None),
+        };
+        //Swapchain images can be acquired out of order, so the image may still be used by another frame in flight.
+        let image_fence = self.images_in_flight[image_index as usize];
+        if image_fence != Fence::null() && image_fence != in_flight{
+            unsafe{self.device.wait_for_fences(&[image_fence], true, u64::MAX)}.map_err(|e|{
+                error!("Failed to wait for color image {}, {}.",image_index,e);
+                RendererError::WaitFence(e)
+            })?;
+        }
+        self.images_in_flight[image_index as usize] = in_flight;
+        self.deletion_queue.frame_begun();
+        if let Err(e) = unsafe{self.begin_recording(command_pool, command_buffer, image_index, frame_index)}{
+            self.abandon_frame(frame_index, image_index);
+            return Err(e);
+        }
+        return Ok(Some(Frame{command_buffer , image_index , frame_index}));
+    }
+    ///Begins the frame's command buffer and records the frame graph up to and including the start of the external pass.
+    unsafe fn begin_recording(&mut self , command_pool : CommandPool , command_buffer : CommandBuffer , image_index : u32 , frame_index : usize)->Result<(),RendererError>{
+        self.device.reset_command_pool(command_pool, CommandPoolResetFlags::empty()).map_err(|e|{
+            error!("Failed to reset the command pool of frame {}, {}.",frame_index,e);
+            RendererError::RecordCommands(e)
+        })?;
+        let command_buffer_begin_info = CommandBufferBeginInfo{
+            s_type : StructureType::COMMAND_BUFFER_BEGIN_INFO,
+            p_next : std::ptr::null(),
+            flags : CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+            p_inheritance_info : std::ptr::null(),
+        };
+        self.device.begin_command_buffer(command_buffer, &command_buffer_begin_info).map_err(|e|{
+            error!("Failed to begin the command buffer of frame {}, {}.",frame_index,e);
+            RendererError::RecordCommands(e)
+        })?;
+        let external = self.frame_graph.external_position();
+        self.record_graph_passes(command_buffer, image_index, frame_index, 0..external)?;
+        self.begin_graph_pass(command_buffer, image_index, external);
+        return Ok(());
+    }
+    ///Ends the external pass, records the rest of the frame graph and ends the frame's command buffer.
+    unsafe fn end_recording(&mut self , frame : &Frame)->Result<(),RendererError>{
+        self.end_graph_pass(frame.command_buffer);
+        let remaining = self.frame_graph.external_position() + 1..self.frame_graph.pass_count();
+        self.record_graph_passes(frame.command_buffer, frame.image_index, frame.frame_index, remaining)?;
+        self.record_graph_final_barriers(frame.command_buffer, frame.image_index);
+        self.device.end_command_buffer(frame.command_buffer).map_err(|e|{
+            error!("Failed to end the command buffer of frame {}, {}.",frame.frame_index,e);
+            RendererError::RecordCommands(e)
+        })?;
+        return Ok(());
+    }
+    ///Gives up on a frame whose recording failed after its color image was acquired.
+    ///The color image is cleared and presented so the swapchain gets it back, the submission waits on the acquire semaphore and signals the frame's fence so the frame's resources can be reused.
+    fn abandon_frame(&mut self , frame_index : usize , image_index : u32){
+        self.deletion_queue.frame_abandoned();
+        let (in_flight,image_available,render_finished,command_pool,command_buffer) = {
+            let frame = &self.frames[frame_index];
+            (frame.in_flight,frame.image_available,frame.render_finished,frame.command_pool,frame.command_buffer)
+        };
+        //When even the clear can't be recorded the image can't be presented, the submission then only waits on the acquire semaphore.
+        let recorded = match unsafe{self.record_cleared_image(command_pool, command_buffer, image_index)}{
+            Ok(())=>true,
+            Err(e)=>{
+                error!("Failed to record the release of color image {}, it stays acquired, {}.",image_index,e);
+                false
+            }
+        };
+        let wait_semaphores = [image_available];
+        let wait_stages = [PipelineStageFlags::ALL_COMMANDS];
+        let command_buffers = [command_buffer];
+        let signal_semaphores = [render_finished];
+        let submit_info = SubmitInfo{
+            s_type : StructureType::SUBMIT_INFO,
+            p_next : std::ptr::null(),
+            wait_semaphore_count : wait_semaphores.len() as u32,
+            p_wait_semaphores : wait_semaphores.as_ptr(),
+            p_wait_dst_stage_mask : wait_stages.as_ptr(),
+            command_buffer_count : if recorded{1}else{0},
+            p_command_buffers : command_buffers.as_ptr(),
+            signal_semaphore_count : if recorded{1}else{0},
+            p_signal_semaphores : signal_semaphores.as_ptr(),
+        };
+        let result = unsafe{self.device.reset_fences(&[in_flight])}.map_err(|e|{
+            error!("Failed to reset the fence of frame {}, {}.",frame_index,e);
+            RendererError::WaitFence(e)
+        }).and_then(|_|self.submit_graphics(&[submit_info], in_flight));
+        if result.is_err(){
+            if let Err(e) = unsafe{self.recreate_frame_fence(frame_index)}{error!("Failed to abandon frame {}, {}.",frame_index,e)}
+            return;
+        }
+        if recorded{
+            if let Err(e) = self.present(image_index, &signal_semaphores){error!("Failed to present abandoned frame {}, {}.",frame_index,e)}
+        }
+    }
+    ///Records a command buffer that clears the color image to the clear color, when its usage allows it, and leaves it in the layout frames end in.
+    ///Nothing of the abandoned recording was submitted, so the image is still in the layout it was acquired in and its contents can be discarded.
+    unsafe fn record_cleared_image(&self , command_pool : CommandPool , command_buffer : CommandBuffer , image_index : u32)->Result<(),ash::vk::Result>{
+        self.device.reset_command_pool(command_pool, CommandPoolResetFlags::empty())?;
+        let command_buffer_begin_info = CommandBufferBeginInfo{
+            s_type : StructureType::COMMAND_BUFFER_BEGIN_INFO,
+            p_next : std::ptr::null(),
+            flags : CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+            p_inheritance_info : std::ptr::null(),
+        };
+        self.device.begin_command_buffer(command_buffer, &command_buffer_begin_info)?;
+        let image = &self.color_images[image_index as usize];
+        let final_layout = self.target.color_final_layout();
+        if image.description().usage.contains(ImageUsageFlags::TRANSFER_DST){
+            image.transition_layout(&self.device, command_buffer, ImageLayout::UNDEFINED, ImageLayout::TRANSFER_DST_OPTIMAL);
+            let clear_color = ClearColorValue{float32 : self.clear_color};
+            self.device.cmd_clear_color_image(command_buffer, image.handle(), ImageLayout::TRANSFER_DST_OPTIMAL, &clear_color, &[image.subresource_range(ImageAspectFlags::COLOR)]);
+            image.transition_layout(&self.device, command_buffer, ImageLayout::TRANSFER_DST_OPTIMAL, final_layout);
+        }else{
+            image.transition_layout(&self.device, command_buffer, ImageLayout::UNDEFINED, final_layout);
+        }
+        return self.device.end_command_buffer(command_buffer);
+    }
+    ///Ends the external pass, records the rest of the frame graph, submits the frame on the graphics queue and presents it.
+    pub fn end_frame(&mut self , frame : Frame)->Result<(),RendererError>{
+        let (in_flight,image_available,render_finished) = {
+            let resources = &self.frames[frame.frame_index];
+            (resources.in_flight,resources.image_available,resources.render_finished)
+        };
+        if let Err(e) = unsafe{self.end_recording(&frame)}{
+            self.abandon_frame(frame.frame_index, frame.image_index);
+            return Err(e);
+        }
+        let wait_semaphores = [image_available];
+        let wait_stages = [PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
+        let command_buffers = [frame.command_buffer];
+        let signal_semaphores = [render_finished];
+        let submit_info = SubmitInfo{
+            s_type : StructureType::SUBMIT_INFO,
+            p_next : std::ptr::null(),
+            wait_semaphore_count : wait_semaphores.len() as u32,
+            p_wait_semaphores : wait_semaphores.as_ptr(),
+            p_wait_dst_stage_mask : wait_stages.as_ptr(),
+            command_buffer_count : command_buffers.len() as u32,
+            p_command_buffers : command_buffers.as_ptr(),
+            signal_semaphore_count : signal_semaphores.len() as u32,
+            p_signal_semaphores : signal_semaphores.as_ptr(),
+        };
+        //The fence is reset right before the submission, so it stays signaled when recording the frame fails.
+        unsafe{self.device.reset_fences(&[in_flight])}.map_err(|e|{
+            error!("Failed to reset the fence of frame {}, {}.",frame.frame_index,e);
+            RendererError::WaitFence(e)
+        })?;
+        if let Err(e) = self.submit_graphics(&[submit_info], in_flight){
+            self.deletion_queue.frame_abandoned();
+            unsafe{self.recreate_frame_fence(frame.frame_index)}?;
+            return Err(e);
+        }
+        self.frames[frame.frame_index].serial = self.deletion_queue.frame_submitted();
+        self.current_frame = (self.current_frame + 1) % self.frames.len();
+        return self.present(frame.image_index, &signal_semaphores);
+    }
+    ///Replaces the fence of a frame with a signaled one after the submission that should have signaled it failed, so waiting for the frame doesn't block forever.
+    unsafe fn recreate_frame_fence(&mut self , frame_index : usize)->Result<(),RendererError>{
+        let fence_create_info = FenceCreateInfo{
+            s_type : StructureType::FENCE_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : FenceCreateFlags::SIGNALED,
+        };
+        let fence = self.device.create_fence(&fence_create_info, None).map_err(|e|{
+            error!("Failed to recreate the fence of frame {}, {}.",frame_index,e);
+            RendererError::CreateFrameResources(e)
+        })?;
+        let old_fence = std::mem::replace(&mut self.frames[frame_index].in_flight, fence);
+        for image_fence in self.images_in_flight.iter_mut().filter(|image_fence|**image_fence == old_fence){*image_fence = Fence::null()}
+        self.device.destroy_fence(old_fence, None);
+        return Ok(());
+    }
+    ///Forgets which frames use which color images, called after the color images have been recreated.
+    pub(crate) fn reset_images_in_flight(&mut self){
+        debug!("Resetting color image fences for {} images.",self.color_images.len());
+        self.images_in_flight = vec!(Fence::null();self.color_images.len());
+    }
+}
+pub(crate) fn viewport(extent : Extent2D)->Viewport{
+    return Viewport{
+        x : 0.0,
+        y : 0.0,
+        width : extent.width as f32,
+        height : extent.height as f32,
+        min_depth : 0.0,
+        max_depth : 1.0,
+    };
+}
\ No newline at end of file
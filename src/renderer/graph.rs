@@ -0,0 +1,1271 @@
+use std::{collections::HashSet, ops::Range};
+
+use ash::{Device, version::DeviceV1_0, vk::{self, AccessFlags, AttachmentDescription, AttachmentDescriptionFlags, AttachmentLoadOp, AttachmentReference, AttachmentStoreOp, ClearColorValue, ClearDepthStencilValue, ClearValue, CommandBuffer, DependencyFlags, DescriptorSet, DescriptorSetLayout, Extent2D, Format, Framebuffer, FramebufferCreateFlags, FramebufferCreateInfo, ImageAspectFlags, ImageLayout, ImageMemoryBarrier, ImageSubresourceRange, ImageUsageFlags, ImageView, MemoryBarrier, MemoryRequirements, Offset2D, PipelineBindPoint, PipelineStageFlags, QUEUE_FAMILY_IGNORED, Rect2D, RenderPass, RenderPassBeginInfo, RenderPassCreateFlags, RenderPassCreateInfo, ResolveModeFlags, SampleCountFlags, StructureType, SubpassContents, SubpassDescription, SubpassDescriptionFlags}};
+use log::{error,debug,trace};
+
+use super::{Allocation, Buffer, DescriptorWriter, dynamic_rendering::{RenderingAttachmentInfo, RenderingInfo}, Image, ImageDescription, ImageKind, MemoryAllocator, MemoryLocation, Renderer, RendererError, ResourceTiling, format_aspects, frame::viewport, layout_access_and_stages};
+
+///An image used by the passes of a `RenderGraph`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GraphImage(usize);
+///A buffer used by the passes of a `RenderGraph`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GraphBuffer(usize);
+///A pass of a `RenderGraph`, used to look up its render pass once the graph is compiled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GraphPass(usize);
+///The size of a transient image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GraphImageSize{
+    ///The size of the render target, transient images of this size are recreated with the swapchain.
+    RenderTarget,
+    ///The size of the render target multiplied by a factor, at least 1x1.
+    Scaled(f32),
+    Absolute{width : u32 , height : u32},
+}
+impl GraphImageSize{
+    fn extent(&self , render_extent : Extent2D)->Extent2D{
+        match *self{
+            GraphImageSize::RenderTarget=>return render_extent,
+            GraphImageSize::Scaled(factor)=>{
+                let scale = |size : u32|((size as f32 * factor).round() as u32).max(1);
+                return Extent2D{width : scale(render_extent.width) , height : scale(render_extent.height)};
+            }
+            GraphImageSize::Absolute{width , height}=>return Extent2D{width , height},
+        }
+    }
+}
+///An image owned by the graph, its contents only live during a frame and its memory is shared with transient images used by other passes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TransientImage{
+    pub format : Format,
+    pub size : GraphImageSize,
+    pub samples : SampleCountFlags,
+}
+impl TransientImage{
+    ///A single sample image the size of the render target.
+    pub fn new(format : Format)->Self{
+        return Self{format , size : GraphImageSize::RenderTarget , samples : SampleCountFlags::TYPE_1};
+    }
+    pub fn size(mut self , size : GraphImageSize)->Self{
+        self.size = size;
+        return self;
+    }
+    pub fn samples(mut self , samples : SampleCountFlags)->Self{
+        self.samples = samples;
+        return self;
+    }
+}
+///What an attachment holds when its pass begins.
+#[derive(Clone, Copy)]
+pub enum AttachmentLoad{
+    ///Cleared to the value.
+    Clear(ClearValue),
+    ///The contents written by earlier passes.
+    Load,
+    ///Undefined, for passes that overwrite every pixel.
+    DontCare,
+}
+impl AttachmentLoad{
+    fn load_op(&self)->AttachmentLoadOp{
+        match self{
+            AttachmentLoad::Clear(_)=>return AttachmentLoadOp::CLEAR,
+            AttachmentLoad::Load=>return AttachmentLoadOp::LOAD,
+            AttachmentLoad::DontCare=>return AttachmentLoadOp::DONT_CARE,
+        }
+    }
+}
+///How a pass uses an image, each use has a fixed layout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ImageUse{
+    Color,
+    ///Resolves the color attachment of the given graph image into the image at the end of the pass.
+    Resolve(usize),
+    Depth,
+    DepthRead,
+    Sampled,
+    StorageRead,
+    StorageWrite,
+    TransferSrc,
+    TransferDst,
+}
+impl ImageUse{
+    fn layout(&self)->ImageLayout{
+        match self{
+            ImageUse::Color | ImageUse::Resolve(_)=>return ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            ImageUse::Depth=>return ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            ImageUse::DepthRead=>return ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+            ImageUse::Sampled=>return ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ImageUse::StorageRead | ImageUse::StorageWrite=>return ImageLayout::GENERAL,
+            ImageUse::TransferSrc=>return ImageLayout::TRANSFER_SRC_OPTIMAL,
+            ImageUse::TransferDst=>return ImageLayout::TRANSFER_DST_OPTIMAL,
+        }
+    }
+    fn access(&self)->AccessFlags{
+        match self{
+            ImageUse::Color=>return AccessFlags::COLOR_ATTACHMENT_READ | AccessFlags::COLOR_ATTACHMENT_WRITE,
+            ImageUse::Resolve(_)=>return AccessFlags::COLOR_ATTACHMENT_WRITE,
+            ImageUse::Depth=>return AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ | AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            ImageUse::DepthRead=>return AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ,
+            ImageUse::Sampled | ImageUse::StorageRead=>return AccessFlags::SHADER_READ,
+            ImageUse::StorageWrite=>return AccessFlags::SHADER_READ | AccessFlags::SHADER_WRITE,
+            ImageUse::TransferSrc=>return AccessFlags::TRANSFER_READ,
+            ImageUse::TransferDst=>return AccessFlags::TRANSFER_WRITE,
+        }
+    }
+    ///The stages of attachment and transfer uses, shader uses take their stages from the pass.
+    fn fixed_stages(&self)->Option<PipelineStageFlags>{
+        match self{
+            ImageUse::Color | ImageUse::Resolve(_)=>return Some(PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT),
+            ImageUse::Depth | ImageUse::DepthRead=>return Some(PipelineStageFlags::EARLY_FRAGMENT_TESTS | PipelineStageFlags::LATE_FRAGMENT_TESTS),
+            ImageUse::TransferSrc | ImageUse::TransferDst=>return Some(PipelineStageFlags::TRANSFER),
+            _=>return None,
+        }
+    }
+    fn image_usage(&self)->ImageUsageFlags{
+        match self{
+            ImageUse::Color | ImageUse::Resolve(_)=>return ImageUsageFlags::COLOR_ATTACHMENT,
+            ImageUse::Depth | ImageUse::DepthRead=>return ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            ImageUse::Sampled=>return ImageUsageFlags::SAMPLED,
+            ImageUse::StorageRead | ImageUse::StorageWrite=>return ImageUsageFlags::STORAGE,
+            ImageUse::TransferSrc=>return ImageUsageFlags::TRANSFER_SRC,
+            ImageUse::TransferDst=>return ImageUsageFlags::TRANSFER_DST,
+        }
+    }
+    fn is_attachment(&self)->bool{
+        return matches!(self,ImageUse::Color | ImageUse::Resolve(_) | ImageUse::Depth | ImageUse::DepthRead);
+    }
+}
+#[derive(Clone, Copy)]
+struct ImageAccess{
+    image : usize,
+    usage : ImageUse,
+    stages : PipelineStageFlags,
+    ///`Load` for everything but color and depth attachments.
+    load : AttachmentLoad,
+}
+impl ImageAccess{
+    fn write_access(&self)->AccessFlags{
+        return write_access(self.usage.access());
+    }
+    ///Whether the access needs the contents written by earlier passes.
+    fn reads(&self)->bool{
+        match self.usage{
+            ImageUse::Color | ImageUse::Depth=>return matches!(self.load,AttachmentLoad::Load),
+            ImageUse::Resolve(_) | ImageUse::StorageWrite | ImageUse::TransferDst=>return false,
+            _=>return true,
+        }
+    }
+    ///Whether the access replaces the whole image, so earlier contents are never needed.
+    fn overwrites(&self)->bool{
+        return matches!(self.usage,ImageUse::Color | ImageUse::Resolve(_) | ImageUse::Depth) && !matches!(self.load,AttachmentLoad::Load);
+    }
+}
+#[derive(Clone, Copy)]
+struct BufferAccess{
+    buffer : usize,
+    stages : PipelineStageFlags,
+    access : AccessFlags,
+}
+///The write accesses of `access`.
+fn write_access(access : AccessFlags)->AccessFlags{
+    let writes = AccessFlags::SHADER_WRITE | AccessFlags::COLOR_ATTACHMENT_WRITE | AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE | AccessFlags::TRANSFER_WRITE | AccessFlags::HOST_WRITE | AccessFlags::MEMORY_WRITE;
+    return access & writes;
+}
+type PassCallback = Box<dyn FnMut(&mut PassContext)->Result<(),RendererError>>;
+///Declares the images and buffers a pass reads and writes, added to a graph with `RenderGraph::add_pass`.
+///Color attachments are bound in the order they are declared.
+pub struct GraphPassBuilder{
+    name : String,
+    images : Vec<ImageAccess>,
+    buffers : Vec<BufferAccess>,
+    side_effect : bool,
+    external : bool,
+    execute : Option<PassCallback>,
+}
+impl GraphPassBuilder{
+    pub fn new(name : &str)->Self{
+        return Self{name : name.to_string() , images : vec!() , buffers : vec!() , side_effect : false , external : false , execute : None};
+    }
+    fn image(mut self , image : GraphImage , usage : ImageUse , stages : PipelineStageFlags , load : AttachmentLoad)->Self{
+        self.images.push(ImageAccess{image : image.0 , usage , stages : usage.fixed_stages().unwrap_or(stages) , load});
+        return self;
+    }
+    ///Renders to the image as the next color attachment.
+    pub fn color_attachment(self , image : GraphImage , load : AttachmentLoad)->Self{
+        return self.image(image, ImageUse::Color, PipelineStageFlags::empty(), load);
+    }
+    ///Resolves the multisampled color attachment `color` into the single sample image at the end of the pass.
+    pub fn resolve_attachment(self , color : GraphImage , image : GraphImage)->Self{
+        return self.image(image, ImageUse::Resolve(color.0), PipelineStageFlags::empty(), AttachmentLoad::DontCare);
+    }
+    ///Renders to the image as the depth attachment.
+    pub fn depth_attachment(self , image : GraphImage , load : AttachmentLoad)->Self{
+        return self.image(image, ImageUse::Depth, PipelineStageFlags::empty(), load);
+    }
+    ///Depth tests against the image without writing it.
+    pub fn depth_read_only(self , image : GraphImage)->Self{
+        return self.image(image, ImageUse::DepthRead, PipelineStageFlags::empty(), AttachmentLoad::Load);
+    }
+    ///Samples the image in the given shader stages, it is in `SHADER_READ_ONLY_OPTIMAL` layout during the pass.
+    pub fn sampled(self , image : GraphImage , stages : PipelineStageFlags)->Self{
+        return self.image(image, ImageUse::Sampled, stages, AttachmentLoad::Load);
+    }
+    ///Reads the image as a storage image in the given shader stages, it is in `GENERAL` layout during the pass.
+    pub fn storage_read(self , image : GraphImage , stages : PipelineStageFlags)->Self{
+        return self.image(image, ImageUse::StorageRead, stages, AttachmentLoad::Load);
+    }
+    ///Reads and writes the image as a storage image in the given shader stages, it is in `GENERAL` layout during the pass.
+    pub fn storage_write(self , image : GraphImage , stages : PipelineStageFlags)->Self{
+        return self.image(image, ImageUse::StorageWrite, stages, AttachmentLoad::Load);
+    }
+    ///Copies or blits from the image.
+    pub fn transfer_src(self , image : GraphImage)->Self{
+        return self.image(image, ImageUse::TransferSrc, PipelineStageFlags::empty(), AttachmentLoad::Load);
+    }
+    ///Copies, blits or clears into the image.
+    pub fn transfer_dst(self , image : GraphImage)->Self{
+        return self.image(image, ImageUse::TransferDst, PipelineStageFlags::empty(), AttachmentLoad::Load);
+    }
+    ///Accesses the buffer in the given stages, the pass writes the buffer when `access` contains a write access.
+    pub fn buffer(mut self , buffer : GraphBuffer , stages : PipelineStageFlags , access : AccessFlags)->Self{
+        self.buffers.push(BufferAccess{buffer : buffer.0 , stages , access});
+        return self;
+    }
+    ///Keeps the pass even when nothing uses what it writes, for passes with effects the graph can't see.
+    pub fn side_effect(mut self)->Self{
+        self.side_effect = true;
+        return self;
+    }
+    ///Makes this the pass recorded by the application between `Renderer::begin_frame` and `Renderer::end_frame`, a graph has exactly one.
+    ///The pass needs attachments and its `execute` callback is never called.
+    pub fn external(mut self)->Self{
+        self.external = true;
+        return self;
+    }
+    ///Sets the callback that records the pass every frame, inside its render pass when it has attachments.
+    pub fn execute<F : FnMut(&mut PassContext)->Result<(),RendererError> + 'static>(mut self , execute : F)->Self{
+        self.execute = Some(Box::new(execute));
+        return self;
+    }
+    fn is_graphics(&self)->bool{
+        return self.images.iter().any(|access|access.usage.is_attachment());
+    }
+}
+///An image imported into a graph, the graph doesn't own it.
+#[derive(Clone, Copy)]
+struct ImportedImage{
+    handle : vk::Image,
+    view : ImageView,
+    range : ImageSubresourceRange,
+    format : Format,
+    extent : Extent2D,
+    samples : SampleCountFlags,
+    usage : ImageUsageFlags,
+    initial_layout : ImageLayout,
+    final_layout : ImageLayout,
+}
+#[derive(Clone, Copy)]
+enum ImageSource{
+    ///The color image of the frame.
+    Backbuffer,
+    ///The renderer's depth buffer.
+    DepthBuffer,
+    Imported(ImportedImage),
+    Transient(TransientImage),
+}
+impl ImageSource{
+    ///Whether the contents are undefined at the start of each frame and not needed after it.
+    fn is_frame_local(&self)->bool{
+        return matches!(self,ImageSource::DepthBuffer | ImageSource::Transient(_));
+    }
+}
+struct GraphImageResource{
+    name : String,
+    source : ImageSource,
+}
+struct GraphBufferResource{
+    name : String,
+}
+///The passes of a frame and the resources they use, compiled with `Renderer::set_frame_graph`.
+///Passes are recorded in the order they were added, passes whose results are never used are culled.
+pub struct RenderGraph{
+    images : Vec<GraphImageResource>,
+    buffers : Vec<GraphBufferResource>,
+    passes : Vec<GraphPassBuilder>,
+    ///Set on an unchanged graph from `Renderer::default_frame_graph`, which `Renderer::set_sample_count` can rebuild.
+    default : bool,
+}
+impl RenderGraph{
+    pub fn new()->Self{
+        let images = vec!(
+            GraphImageResource{name : "backbuffer".to_string() , source : ImageSource::Backbuffer},
+            GraphImageResource{name : "depth buffer".to_string() , source : ImageSource::DepthBuffer},
+        );
+        return Self{images , buffers : vec!() , passes : vec!() , default : false};
+    }
+    ///The color image of the frame, a swapchain image or the offscreen image, it is left in the layout it is presented or sampled in.
+    pub fn backbuffer(&self)->GraphImage{
+        return GraphImage(0);
+    }
+    ///The renderer's depth buffer, its contents are undefined at the start of each frame.
+    pub fn depth_buffer(&self)->GraphImage{
+        return GraphImage(1);
+    }
+    ///Uses an image created outside the graph, it must outlive the compiled graph.
+    ///The image is in `initial_layout` when a frame begins and is left in `final_layout` when it ends.
+    pub fn import_image(&mut self , name : &str , image : &Image , initial_layout : ImageLayout , final_layout : ImageLayout)->GraphImage{
+        let extent = image.extent();
+        let imported = ImportedImage{
+            handle : image.handle(),
+            view : image.view(),
+            range : image.subresource_range(image.aspects()),
+            format : image.format(),
+            extent : Extent2D{width : extent.width , height : extent.height},
+            samples : image.description().samples,
+            usage : image.description().usage,
+            initial_layout,
+            final_layout,
+        };
+        return self.add_image(name, ImageSource::Imported(imported));
+    }
+    ///Adds an image owned by the graph, it is created when the graph is compiled.
+    pub fn create_image(&mut self , name : &str , image : TransientImage)->GraphImage{
+        return self.add_image(name, ImageSource::Transient(image));
+    }
+    fn add_image(&mut self , name : &str , source : ImageSource)->GraphImage{
+        self.default = false;
+        self.images.push(GraphImageResource{name : name.to_string() , source});
+        return GraphImage(self.images.len() - 1);
+    }
+    ///Uses a buffer created outside the graph, the graph only tracks its accesses to place barriers.
+    pub fn import_buffer<T : Copy>(&mut self , name : &str , _buffer : &Buffer<T>)->GraphBuffer{
+        self.default = false;
+        self.buffers.push(GraphBufferResource{name : name.to_string()});
+        return GraphBuffer(self.buffers.len() - 1);
+    }
+    pub fn add_pass(&mut self , pass : GraphPassBuilder)->GraphPass{
+        self.default = false;
+        self.passes.push(pass);
+        return GraphPass(self.passes.len() - 1);
+    }
+}
+impl Default for RenderGraph{
+    fn default()->Self{
+        return Self::new();
+    }
+}
+///What the compiled graph needs to know about the renderer's own images.
+struct RendererTargets{
+    backbuffer_usage : ImageUsageFlags,
+    backbuffer_final_layout : ImageLayout,
+    depth_usage : ImageUsageFlags,
+}
+///The accesses of an image or buffer since its last write, used to decide which later accesses need a barrier.
+#[derive(Clone, Copy)]
+struct AccessState{
+    ///Every stage that accessed the resource since its last write, including the write, a later write waits for all of them.
+    stages : PipelineStageFlags,
+    ///The stages of the last write or layout transition.
+    write_stages : PipelineStageFlags,
+    ///The accesses of the last write, empty after a layout transition since the barrier made it available already.
+    write_access : AccessFlags,
+    ///The stages and accesses the last write has been made visible to, reads outside of them need another barrier.
+    visible_stages : PipelineStageFlags,
+    visible_access : AccessFlags,
+}
+impl AccessState{
+    ///Written in `stages` and not yet visible to anything.
+    fn written(stages : PipelineStageFlags , access : AccessFlags)->Self{
+        return Self{stages , write_stages : stages , write_access : access , visible_stages : PipelineStageFlags::empty() , visible_access : AccessFlags::empty()};
+    }
+    ///Contents that every later read can see without a barrier.
+    fn visible(stages : PipelineStageFlags)->Self{
+        return Self{stages , write_stages : stages , write_access : AccessFlags::empty() , visible_stages : PipelineStageFlags::all() , visible_access : AccessFlags::all()};
+    }
+    fn is_visible(&self , stages : PipelineStageFlags , access : AccessFlags)->bool{
+        return self.visible_stages.contains(stages) && self.visible_access.contains(access);
+    }
+}
+#[derive(Clone, Copy)]
+struct ImageState{
+    layout : ImageLayout,
+    access : AccessState,
+}
+impl ImageState{
+    fn initial(source : &ImageSource)->Self{
+        match source{
+            //The acquire semaphore is waited on in the color attachment output stage.
+            ImageSource::Backbuffer=>return Self{layout : ImageLayout::UNDEFINED , access : AccessState::visible(PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)},
+            ImageSource::DepthBuffer=>return Self{
+                layout : ImageLayout::UNDEFINED,
+                access : AccessState::written(PipelineStageFlags::EARLY_FRAGMENT_TESTS | PipelineStageFlags::LATE_FRAGMENT_TESTS, AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE),
+            },
+            ImageSource::Imported(imported)=>{
+                let (access,stages) = layout_access_and_stages(imported.initial_layout);
+                let writes = write_access(access);
+                let access = if writes.is_empty(){AccessState::visible(stages)}else{AccessState::written(stages, writes)};
+                return Self{layout : imported.initial_layout , access};
+            }
+            //The memory may have been written through another transient image.
+            ImageSource::Transient(_)=>return Self{layout : ImageLayout::UNDEFINED , access : AccessState::written(PipelineStageFlags::ALL_COMMANDS, AccessFlags::MEMORY_WRITE)},
+        }
+    }
+}
+struct ImageBarrier{
+    image : usize,
+    old_layout : ImageLayout,
+    new_layout : ImageLayout,
+    src_access : AccessFlags,
+    dst_access : AccessFlags,
+}
+///The barriers recorded before a pass, merged into a single pipeline barrier.
+#[derive(Default)]
+struct Barriers{
+    src_stages : PipelineStageFlags,
+    dst_stages : PipelineStageFlags,
+    images : Vec<ImageBarrier>,
+    ///The source and destination access of a global memory barrier, used for buffers.
+    memory : Option<(AccessFlags,AccessFlags)>,
+}
+impl Barriers{
+    ///Adds a barrier when the layout changes, the access writes or it reads in stages the last write isn't visible to yet, otherwise the access is merged into the state.
+    fn image(&mut self , image : usize , state : &mut ImageState , layout : ImageLayout , stages : PipelineStageFlags , access : AccessFlags , discard : bool){
+        let writes = write_access(access);
+        if state.layout == layout && writes.is_empty(){
+            if !state.access.is_visible(stages, access){
+                self.src_stages |= state.access.write_stages;
+                self.dst_stages |= stages;
+                self.images.push(ImageBarrier{image , old_layout : layout , new_layout : layout , src_access : state.access.write_access , dst_access : access});
+                state.access.visible_stages |= stages;
+                state.access.visible_access |= access;
+            }
+            state.access.stages |= stages;
+            return;
+        }
+        self.src_stages |= state.access.stages;
+        self.dst_stages |= stages;
+        self.images.push(ImageBarrier{
+            image,
+            old_layout : if discard{ImageLayout::UNDEFINED}else{state.layout},
+            new_layout : layout,
+            src_access : state.access.write_access,
+            dst_access : access,
+        });
+        //A layout transition is a write too, reads in other stages have to wait for it.
+        let access = if writes.is_empty(){
+            AccessState{stages , write_stages : stages , write_access : AccessFlags::empty() , visible_stages : stages , visible_access : access}
+        }else{AccessState::written(stages, writes)};
+        *state = ImageState{layout , access};
+    }
+    fn buffer(&mut self , state : &mut AccessState , stages : PipelineStageFlags , access : AccessFlags){
+        let writes = write_access(access);
+        if writes.is_empty(){
+            if !state.is_visible(stages, access){
+                self.memory_barrier(state.write_stages, state.write_access, stages, access);
+                state.visible_stages |= stages;
+                state.visible_access |= access;
+            }
+            state.stages |= stages;
+            return;
+        }
+        self.memory_barrier(state.stages, state.write_access, stages, access);
+        *state = AccessState::written(stages, writes);
+    }
+    fn memory_barrier(&mut self , src_stages : PipelineStageFlags , src_access : AccessFlags , dst_stages : PipelineStageFlags , dst_access : AccessFlags){
+        self.src_stages |= src_stages;
+        self.dst_stages |= dst_stages;
+        let (merged_src,merged_dst) = self.memory.unwrap_or_default();
+        self.memory = Some((merged_src | src_access,merged_dst | dst_access));
+    }
+    fn is_empty(&self)->bool{
+        return self.images.is_empty() && self.memory.is_none();
+    }
+}
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum AttachmentKind{
+    Color,
+    Depth,
+    ///Resolves the color attachment at the given index of the pass's attachments.
+    Resolve(usize),
+}
+#[derive(Clone, Copy)]
+struct AttachmentPlan{
+    ///The index of the access in the pass.
+    access : usize,
+    image : usize,
+    layout : ImageLayout,
+    load_op : AttachmentLoadOp,
+    store_op : AttachmentStoreOp,
+    kind : AttachmentKind,
+}
+struct PassPlan{
+    ///The index of the pass in the graph.
+    pass : usize,
+    barriers : Barriers,
+    ///Color attachments in declaration order followed by the depth attachment and the resolve attachments, empty for passes without attachments.
+    attachments : Vec<AttachmentPlan>,
+}
+///The render pass a graph pass draws in, pipelines drawing in the pass are built against it.
+///The render pass is null with dynamic rendering, pipelines are built against the formats then.
+#[derive(Clone, Debug)]
+pub(crate) struct PassTarget{
+    pub(crate) render_pass : RenderPass,
+    pub(crate) color_formats : Vec<Format>,
+    pub(crate) depth_format : Option<Format>,
+    pub(crate) samples : SampleCountFlags,
+    pub(crate) extent : Extent2D,
+}
+///The Vulkan objects of a compiled graph, sized after the render target and recreated with it.
+#[derive(Default)]
+struct GraphTargets{
+    ///The transient images by graph image, `None` for every other image and for transient images no pass uses.
+    transients : Vec<Option<Image>>,
+    ///The memory shared by the transient images.
+    memory : Vec<Allocation>,
+    ///The target of each planned pass, `None` for passes without attachments.
+    pass_targets : Vec<Option<PassTarget>>,
+    ///The framebuffers of each planned pass, one per color image when the pass draws to the backbuffer.
+    framebuffers : Vec<Vec<Framebuffer>>,
+}
+impl GraphTargets{
+    unsafe fn destroy(&mut self , device : &Device , allocator : &mut MemoryAllocator){
+        for framebuffer in self.framebuffers.drain(..).flatten(){device.destroy_framebuffer(framebuffer, None)}
+        for target in self.pass_targets.drain(..).flatten().filter(|target|target.render_pass != RenderPass::null()){device.destroy_render_pass(target.render_pass, None)}
+        for mut image in self.transients.drain(..).flatten(){image.destroy(device, allocator)}
+        for allocation in self.memory.drain(..){allocator.free(allocation)}
+    }
+}
+///A render graph compiled into render passes and barriers, the renderer records it every frame.
+pub struct CompiledGraph{
+    graph : RenderGraph,
+    ///The passes that survived culling, in recording order.
+    plans : Vec<PassPlan>,
+    ///The position of the external pass in `plans`.
+    external : usize,
+    ///The position of each pass of the graph in `plans`, `None` for culled passes.
+    positions : Vec<Option<usize>>,
+    ///Transitions the backbuffer and imported images to their final layouts at the end of the frame.
+    final_barriers : Barriers,
+    ///The usage of each image over every pass.
+    image_usage : Vec<ImageUsageFlags>,
+    ///The first and last position in `plans` that uses each image, `None` for unused images.
+    lifetimes : Vec<Option<(usize,usize)>>,
+    targets : GraphTargets,
+}
+impl CompiledGraph{
+    ///Whether the graph is the unchanged default frame graph.
+    pub(crate) fn is_default(&self)->bool{
+        return self.graph.default;
+    }
+    ///A graph without passes, only used while the renderer is created.
+    pub(crate) fn empty()->Self{
+        return Self{
+            graph : RenderGraph::new() , plans : vec!() , external : 0 , positions : vec!() , final_barriers : Barriers::default(),
+            image_usage : vec!() , lifetimes : vec!() , targets : GraphTargets::default(),
+        };
+    }
+    ///Culls the graph and computes its barriers, returns why the graph is invalid on failure.
+    fn plan(graph : RenderGraph , renderer : &RendererTargets)->Result<Self,String>{
+        let externals = graph.passes.iter().filter(|pass|pass.external).count();
+        if externals != 1{return Err(format!("the graph has {} external passes instead of 1",externals))}
+        for pass in graph.passes.iter(){
+            let mut used = HashSet::new();
+            for access in pass.images.iter(){
+                let image = graph.images.get(access.image).ok_or_else(||format!("pass \"{}\" uses an image of another graph",pass.name))?;
+                if !used.insert(access.image){return Err(format!("pass \"{}\" uses \"{}\" more than once",pass.name,image.name))}
+                if access.stages.is_empty(){return Err(format!("pass \"{}\" uses \"{}\" in no pipeline stage",pass.name,image.name))}
+                let available = match &image.source{
+                    ImageSource::Backbuffer=>renderer.backbuffer_usage,
+                    ImageSource::DepthBuffer=>renderer.depth_usage,
+                    ImageSource::Imported(imported)=>imported.usage,
+                    ImageSource::Transient(_)=>ImageUsageFlags::all(),
+                };
+                if !available.contains(access.usage.image_usage()){return Err(format!("pass \"{}\" uses \"{}\" as {:?} but the image lacks {:?} usage",pass.name,image.name,access.usage,access.usage.image_usage()))}
+            }
+            for access in pass.buffers.iter(){
+                if access.buffer >= graph.buffers.len(){return Err(format!("pass \"{}\" uses a buffer of another graph",pass.name))}
+                if access.stages.is_empty(){return Err(format!("pass \"{}\" uses \"{}\" in no pipeline stage",pass.name,graph.buffers[access.buffer].name))}
+            }
+            if pass.images.iter().filter(|access|matches!(access.usage,ImageUse::Depth | ImageUse::DepthRead)).count() > 1{return Err(format!("pass \"{}\" has more than one depth attachment",pass.name))}
+            let mut resolved = HashSet::new();
+            for access in pass.images.iter(){
+                if let ImageUse::Resolve(color) = access.usage{
+                    if !pass.images.iter().any(|other|other.image == color && other.usage == ImageUse::Color){return Err(format!("pass \"{}\" resolves into \"{}\" from an image that isn't one of its color attachments",pass.name,graph.images[access.image].name))}
+                    if !resolved.insert(color){return Err(format!("pass \"{}\" resolves \"{}\" more than once",pass.name,graph.images[color].name))}
+                }
+            }
+            if pass.external && !pass.is_graphics(){return Err(format!("the external pass \"{}\" has no attachments",pass.name))}
+        }
+        //Walks the passes backwards, keeping the ones that write something a later kept pass reads or that outlives the frame.
+        let mut needed = HashSet::new();
+        let mut needed_after = vec!(HashSet::new();graph.passes.len());
+        let mut kept = vec!(false;graph.passes.len());
+        for (i,pass) in graph.passes.iter().enumerate().rev(){
+            let writes_needed = pass.images.iter().any(|access|!access.write_access().is_empty() && (!graph.images[access.image].source.is_frame_local() || needed.contains(&access.image)));
+            let writes_buffer = pass.buffers.iter().any(|access|!write_access(access.access).is_empty());
+            if !(pass.external || pass.side_effect || writes_needed || writes_buffer){
+                debug!("Culling render graph pass \"{}\".",pass.name);
+                continue;
+            }
+            kept[i] = true;
+            needed_after[i] = needed.clone();
+            for access in pass.images.iter(){
+                if access.reads(){needed.insert(access.image);}
+                else if access.overwrites(){needed.remove(&access.image);}
+            }
+        }
+        let mut states = graph.images.iter().map(|image|ImageState::initial(&image.source)).collect::<Vec<_>>();
+        let mut written = graph.images.iter().map(|image|matches!(image.source,ImageSource::Imported(_))).collect::<Vec<_>>();
+        let mut buffer_states = vec!(AccessState::written(PipelineStageFlags::ALL_COMMANDS, AccessFlags::MEMORY_WRITE);graph.buffers.len());
+        let mut image_usage = vec!(ImageUsageFlags::empty();graph.images.len());
+        let mut lifetimes : Vec<Option<(usize,usize)>> = vec!(None;graph.images.len());
+        let mut positions = vec!(None;graph.passes.len());
+        let mut plans = vec!();
+        let mut external = 0;
+        for (i,pass) in graph.passes.iter().enumerate().filter(|(i,_)|kept[*i]){
+            let position = plans.len();
+            positions[i] = Some(position);
+            if pass.external{external = position}
+            let mut barriers = Barriers::default();
+            for access in pass.images.iter(){
+                let image = &graph.images[access.image];
+                if access.reads() && !written[access.image]{return Err(format!("pass \"{}\" reads \"{}\" before any pass writes it",pass.name,image.name))}
+                if !access.write_access().is_empty(){written[access.image] = true}
+                image_usage[access.image] |= access.usage.image_usage();
+                lifetimes[access.image] = Some((lifetimes[access.image].map_or(position,|(first,_)|first),position));
+                barriers.image(access.image, &mut states[access.image], access.usage.layout(), access.stages, access.usage.access(), access.overwrites());
+            }
+            for access in pass.buffers.iter(){
+                barriers.buffer(&mut buffer_states[access.buffer], access.stages, access.access);
+            }
+            let mut attachments : Vec<AttachmentPlan> = vec!();
+            let colors = pass.images.iter().enumerate().filter(|(_,access)|access.usage == ImageUse::Color);
+            let depth = pass.images.iter().enumerate().filter(|(_,access)|matches!(access.usage,ImageUse::Depth | ImageUse::DepthRead));
+            let resolves = pass.images.iter().enumerate().filter(|(_,access)|matches!(access.usage,ImageUse::Resolve(_)));
+            for (index,access) in colors.chain(depth).chain(resolves){
+                let store = !graph.images[access.image].source.is_frame_local() || needed_after[i].contains(&access.image);
+                let kind = match access.usage{
+                    ImageUse::Color=>AttachmentKind::Color,
+                    ImageUse::Resolve(color)=>AttachmentKind::Resolve(attachments.iter().position(|attachment|attachment.image == color).unwrap_or_default()),
+                    _=>AttachmentKind::Depth,
+                };
+                attachments.push(AttachmentPlan{
+                    access : index,
+                    image : access.image,
+                    layout : access.usage.layout(),
+                    load_op : access.load.load_op(),
+                    store_op : if store{AttachmentStoreOp::STORE}else{AttachmentStoreOp::DONT_CARE},
+                    kind,
+                });
+            }
+            plans.push(PassPlan{pass : i , barriers , attachments});
+        }
+        let mut final_barriers = Barriers::default();
+        for (i,image) in graph.images.iter().enumerate(){
+            let final_layout = match &image.source{
+                ImageSource::Backbuffer=>renderer.backbuffer_final_layout,
+                ImageSource::Imported(imported)=>imported.final_layout,
+                _=>continue,
+            };
+            let (access,stages) = layout_access_and_stages(final_layout);
+            final_barriers.image(i, &mut states[i], final_layout, stages, access, false);
+        }
+        return Ok(Self{graph , plans , external , positions , final_barriers , image_usage , lifetimes , targets : GraphTargets::default()});
+    }
+    ///Creates the transient images, render passes and framebuffers, everything created is destroyed again when a step fails.
+    ///With dynamic rendering only the transient images are created, the attachments are given when the passes are recorded.
+    pub(crate) unsafe fn create_targets(&mut self , device : &Device , allocator : &mut MemoryAllocator , color_images : &[Image] , depth_image : &Image , extent : Extent2D , dynamic_rendering : bool)->Result<(),RendererError>{
+        let mut targets = GraphTargets::default();
+        if let Err(e) = self.fill_targets(&mut targets, device, allocator, color_images, depth_image, extent, dynamic_rendering){
+            targets.destroy(device, allocator);
+            return Err(e);
+        }
+        self.targets = targets;
+        return Ok(());
+    }
+    #[allow(clippy::too_many_arguments)]
+    unsafe fn fill_targets(&self , targets : &mut GraphTargets , device : &Device , allocator : &mut MemoryAllocator , color_images : &[Image] , depth_image : &Image , extent : Extent2D , dynamic_rendering : bool)->Result<(),RendererError>{
+        let mut transients = vec!();
+        for (i,image) in self.graph.images.iter().enumerate(){
+            targets.transients.push(None);
+            let (transient,lifetime) = match (&image.source,self.lifetimes[i]){
+                (ImageSource::Transient(transient),Some(lifetime))=>(transient,lifetime),
+                _=>continue,
+            };
+            let size = transient.size.extent(extent);
+            let description = ImageDescription::new(ImageKind::Image2D{width : size.width , height : size.height}, transient.format, self.image_usage[i]).samples(transient.samples);
+            let image = Image::new_unbound(device, &description, RendererError::CreateImage)?;
+            transients.push((i,lifetime,device.get_image_memory_requirements(image.handle())));
+            targets.transients[i] = Some(image);
+        }
+        //Transient images whose passes don't overlap share memory, they are grouped greedily in order of their first use.
+        transients.sort_by_key(|(_,(first,_),_)|*first);
+        let mut groups : Vec<(MemoryRequirements,Vec<usize>)> = vec!();
+        for (i,lifetime,requirements) in transients{
+            let overlaps = |members : &Vec<usize>|members.iter().filter_map(|&member|self.lifetimes[member]).any(|(first,last)|first <= lifetime.1 && lifetime.0 <= last);
+            match groups.iter_mut().find(|(group,members)|group.memory_type_bits & requirements.memory_type_bits != 0 && !overlaps(members)){
+                Some((group,members))=>{
+                    group.size = group.size.max(requirements.size);
+                    group.alignment = group.alignment.max(requirements.alignment);
+                    group.memory_type_bits &= requirements.memory_type_bits;
+                    members.push(i);
+                }
+                None=>groups.push((requirements,vec!(i))),
+            }
+        }
+        for (requirements,members) in groups.iter(){
+            let allocation = allocator.allocate(requirements, MemoryLocation::GpuOnly, ResourceTiling::Optimal)?;
+            let (memory,offset) = (allocation.memory(),allocation.offset());
+            targets.memory.push(allocation);
+            for &i in members.iter(){
+                if let Some(image) = &mut targets.transients[i]{image.bind_memory(device, memory, offset, RendererError::CreateImage)?}
+            }
+            trace!("Aliased {} transient images in {} bytes.",members.len(),requirements.size);
+        }
+        for plan in self.plans.iter(){
+            if plan.attachments.is_empty(){
+                targets.pass_targets.push(None);
+                targets.framebuffers.push(vec!());
+                continue;
+            }
+            let pass = &self.graph.passes[plan.pass];
+            let transients = &targets.transients;
+            let resolve = |attachment : &AttachmentPlan , image_index : usize|resolve_image(&self.graph.images[attachment.image].source, transients[attachment.image].as_ref(), color_images, depth_image, image_index);
+            let first = resolve(&plan.attachments[0], 0);
+            for attachment in plan.attachments.iter(){
+                let image = resolve(attachment, 0);
+                let reason = match attachment.kind{
+                    _ if image.extent != first.extent=>Some(format!("the attachments of pass \"{}\" differ in size",pass.name)),
+                    AttachmentKind::Resolve(color)=>{
+                        let color = resolve(&plan.attachments[color], 0);
+                        if image.samples != SampleCountFlags::TYPE_1 || color.samples == SampleCountFlags::TYPE_1{Some(format!("pass \"{}\" resolves a single sample image or into a multisampled image",pass.name))}
+                        else if image.format != color.format{Some(format!("pass \"{}\" resolves into an image of another format",pass.name))}
+                        else{None}
+                    }
+                    _ if image.samples != first.samples=>Some(format!("the attachments of pass \"{}\" differ in sample count",pass.name)),
+                    _=>None,
+                };
+                if let Some(reason) = reason{
+                    error!("Invalid render graph, {}.",reason);
+                    return Err(RendererError::InvalidGraph(reason));
+                }
+            }
+            let color_formats = plan.attachments.iter().filter(|attachment|attachment.kind == AttachmentKind::Color).map(|attachment|resolve(attachment, 0).format).collect();
+            let depth_format = plan.attachments.iter().find(|attachment|attachment.kind == AttachmentKind::Depth).map(|attachment|resolve(attachment, 0).format);
+            if dynamic_rendering{
+                targets.pass_targets.push(Some(PassTarget{render_pass : RenderPass::null() , color_formats , depth_format , samples : first.samples , extent : first.extent}));
+                targets.framebuffers.push(vec!());
+                continue;
+            }
+            let render_pass = create_render_pass(device, &plan.attachments, &|attachment|resolve(attachment, 0))?;
+            targets.pass_targets.push(Some(PassTarget{render_pass , color_formats , depth_format , samples : first.samples , extent : first.extent}));
+            let mut framebuffers = vec!();
+            let uses_backbuffer = plan.attachments.iter().any(|attachment|matches!(self.graph.images[attachment.image].source,ImageSource::Backbuffer));
+            for image_index in 0..if uses_backbuffer{color_images.len()}else{1}{
+                let views = plan.attachments.iter().map(|attachment|resolve(attachment, image_index).view).collect::<Vec<_>>();
+                let framebuffer_create_info = FramebufferCreateInfo{
+                    s_type : StructureType::FRAMEBUFFER_CREATE_INFO,
+                    p_next : std::ptr::null(),
+                    flags : FramebufferCreateFlags::empty(),
+                    render_pass,
+                    width : first.extent.width,
+                    height : first.extent.height,
+                    attachment_count : views.len() as u32,
+                    p_attachments : views.as_ptr(),
+                    layers : 1,
+                };
+                match device.create_framebuffer(&framebuffer_create_info, None){
+                    Ok(framebuffer)=>framebuffers.push(framebuffer),
+                    Err(e)=>{
+                        error!("Failed to create the framebuffer of pass \"{}\", {}.",pass.name,e);
+                        for framebuffer in framebuffers{device.destroy_framebuffer(framebuffer, None)}
+                        return Err(RendererError::CreateFramebuffer(e));
+                    }
+                }
+            }
+            targets.framebuffers.push(framebuffers);
+        }
+        debug!("Created render graph targets, {} transient images share {} allocations.",targets.transients.iter().flatten().count(),targets.memory.len());
+        return Ok(());
+    }
+    ///Destroys the transient images, render passes and framebuffers, they must no longer be in use.
+    pub(crate) unsafe fn destroy_targets(&mut self , device : &Device , allocator : &mut MemoryAllocator){
+        self.targets.destroy(device, allocator);
+    }
+    ///The render pass of a pass with attachments, `None` for culled passes and passes without attachments and null with dynamic rendering.
+    pub fn render_pass(&self , pass : GraphPass)->Option<RenderPass>{
+        return self.pass_target(pass).map(|target|target.render_pass);
+    }
+    ///The sample count of a pass's attachments, `None` for culled passes and passes without attachments.
+    pub fn sample_count(&self , pass : GraphPass)->Option<SampleCountFlags>{
+        return self.pass_target(pass).map(|target|target.samples);
+    }
+    ///Whether the pass was removed because nothing uses what it writes.
+    pub fn is_culled(&self , pass : GraphPass)->bool{
+        return self.positions.get(pass.0).is_none_or(|position|position.is_none());
+    }
+    pub(crate) fn pass_target(&self , pass : GraphPass)->Option<&PassTarget>{
+        let position = (*self.positions.get(pass.0)?)?;
+        return self.targets.pass_targets.get(position)?.as_ref();
+    }
+    ///The target of the pass recorded between `begin_frame` and `end_frame`.
+    pub(crate) fn external_target(&self)->Option<&PassTarget>{
+        return self.targets.pass_targets.get(self.external)?.as_ref();
+    }
+    ///The framebuffers of the external pass.
+    pub(crate) fn external_framebuffers(&self)->&[Framebuffer]{
+        return self.targets.framebuffers.get(self.external).map_or(&[],|framebuffers|framebuffers.as_slice());
+    }
+    pub(crate) fn external_position(&self)->usize{
+        return self.external;
+    }
+    pub(crate) fn pass_count(&self)->usize{
+        return self.plans.len();
+    }
+    ///Sets the clear value of the backbuffer in the external pass, when it clears the backbuffer or the multisampled image resolved into it.
+    pub(crate) fn set_backbuffer_clear_color(&mut self , clear_color : [f32;4]){
+        let pass = match self.plans.get(self.external){
+            Some(plan)=>&mut self.graph.passes[plan.pass],
+            None=>return,
+        };
+        let resolved = pass.images.iter().find_map(|access|match access.usage{
+            ImageUse::Resolve(color) if access.image == 0=>Some(color),
+            _=>None,
+        });
+        for access in pass.images.iter_mut().filter(|access|access.image == 0 || Some(access.image) == resolved){
+            if let AttachmentLoad::Clear(_) = access.load{access.load = AttachmentLoad::Clear(ClearValue{color : ClearColorValue{float32 : clear_color}})}
+        }
+    }
+}
+///The handles of a graph image, for the swapchain image or transient image currently in use.
+#[derive(Clone, Copy)]
+struct ResolvedImage{
+    handle : vk::Image,
+    view : ImageView,
+    range : ImageSubresourceRange,
+    format : Format,
+    extent : Extent2D,
+    samples : SampleCountFlags,
+}
+impl ResolvedImage{
+    fn from_image(image : &Image)->Self{
+        let extent = image.extent();
+        return Self{
+            handle : image.handle(),
+            view : image.view(),
+            range : image.subresource_range(image.aspects()),
+            format : image.format(),
+            extent : Extent2D{width : extent.width , height : extent.height},
+            samples : image.description().samples,
+        };
+    }
+    fn null()->Self{
+        let range = ImageSubresourceRange{aspect_mask : ImageAspectFlags::empty() , base_mip_level : 0 , level_count : 0 , base_array_layer : 0 , layer_count : 0};
+        return Self{handle : vk::Image::null() , view : ImageView::null() , range , format : Format::UNDEFINED , extent : Extent2D{width : 0 , height : 0} , samples : SampleCountFlags::TYPE_1};
+    }
+}
+fn resolve_image(source : &ImageSource , transient : Option<&Image> , color_images : &[Image] , depth_image : &Image , image_index : usize)->ResolvedImage{
+    match source{
+        ImageSource::Backbuffer=>return color_images.get(image_index).map_or(ResolvedImage::null(),ResolvedImage::from_image),
+        ImageSource::DepthBuffer=>return ResolvedImage::from_image(depth_image),
+        ImageSource::Imported(imported)=>return ResolvedImage{
+            handle : imported.handle , view : imported.view , range : imported.range , format : imported.format , extent : imported.extent , samples : imported.samples,
+        },
+        ImageSource::Transient(_)=>return transient.map_or(ResolvedImage::null(),ResolvedImage::from_image),
+    }
+}
+///Creates a single subpass render pass, the attachments stay in the layout of their use because the graph's barriers transition them.
+unsafe fn create_render_pass(device : &Device , attachments : &[AttachmentPlan] , resolve : &dyn Fn(&AttachmentPlan)->ResolvedImage)->Result<RenderPass,RendererError>{
+    let descriptions = attachments.iter().map(|attachment|{
+        let image = resolve(attachment);
+        let has_stencil = format_aspects(image.format).contains(ImageAspectFlags::STENCIL);
+        AttachmentDescription{
+            flags : AttachmentDescriptionFlags::empty(),
+            format : image.format,
+            samples : image.samples,
+            load_op : attachment.load_op,
+            store_op : attachment.store_op,
+            stencil_load_op : if has_stencil{attachment.load_op}else{AttachmentLoadOp::DONT_CARE},
+            stencil_store_op : if has_stencil{attachment.store_op}else{AttachmentStoreOp::DONT_CARE},
+            initial_layout : attachment.layout,
+            final_layout : attachment.layout,
+        }
+    }).collect::<Vec<_>>();
+    let color_references = attachments.iter().enumerate().filter(|(_,attachment)|attachment.kind == AttachmentKind::Color).map(|(i,attachment)|AttachmentReference{attachment : i as u32 , layout : attachment.layout}).collect::<Vec<_>>();
+    let depth_reference = attachments.iter().enumerate().find(|(_,attachment)|attachment.kind == AttachmentKind::Depth).map(|(i,attachment)|AttachmentReference{attachment : i as u32 , layout : attachment.layout});
+    //Color attachments come first, so the index of a color attachment is also its index in the resolve references.
+    let mut resolve_references = vec!(AttachmentReference{attachment : vk::ATTACHMENT_UNUSED , layout : ImageLayout::UNDEFINED};color_references.len());
+    for (i,attachment) in attachments.iter().enumerate(){
+        if let AttachmentKind::Resolve(color) = attachment.kind{resolve_references[color] = AttachmentReference{attachment : i as u32 , layout : attachment.layout}}
+    }
+    let has_resolves = resolve_references.iter().any(|reference|reference.attachment != vk::ATTACHMENT_UNUSED);
+    let subpasses = [SubpassDescription{
+        flags : SubpassDescriptionFlags::empty(),
+        pipeline_bind_point : PipelineBindPoint::GRAPHICS,
+        input_attachment_count : 0,
+        p_input_attachments : std::ptr::null(),
+        color_attachment_count : color_references.len() as u32,
+        p_color_attachments : color_references.as_ptr(),
+        p_resolve_attachments : if has_resolves{resolve_references.as_ptr()}else{std::ptr::null()},
+        p_depth_stencil_attachment : depth_reference.as_ref().map_or(std::ptr::null(),|reference|reference as *const AttachmentReference),
+        preserve_attachment_count : 0,
+        p_preserve_attachments : std::ptr::null(),
+    }];
+    let render_pass_create_info = RenderPassCreateInfo{
+        s_type : StructureType::RENDER_PASS_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : RenderPassCreateFlags::empty(),
+        attachment_count : descriptions.len() as u32,
+        p_attachments : descriptions.as_ptr(),
+        subpass_count : subpasses.len() as u32,
+        p_subpasses : subpasses.as_ptr(),
+        dependency_count : 0,
+        p_dependencies : std::ptr::null(),
+    };
+    return device.create_render_pass(&render_pass_create_info, None).map_err(|e|{
+        error!("Failed to create a render graph render pass, {}.",e);
+        RendererError::CreateRenderPass(e)
+    });
+}
+///Records a pass of the frame graph, handed to the callback given to `GraphPassBuilder::execute`.
+pub struct PassContext<'a>{
+    renderer : &'a mut Renderer,
+    command_buffer : CommandBuffer,
+    image_index : u32,
+    frame_index : usize,
+    render_pass : RenderPass,
+    extent : Extent2D,
+}
+impl PassContext<'_>{
+    pub fn renderer(&self)->&Renderer{
+        return self.renderer;
+    }
+    pub fn device(&self)->&Device{
+        return &self.renderer.device;
+    }
+    ///The command buffer of the frame, rendering to the pass's attachments with the viewport and scissor set when the pass has attachments.
+    pub fn command_buffer(&self)->CommandBuffer{
+        return self.command_buffer;
+    }
+    ///The pass's render pass, null for passes without attachments and with dynamic rendering.
+    pub fn render_pass(&self)->RenderPass{
+        return self.render_pass;
+    }
+    ///The size of the pass's attachments, or of the render target for passes without attachments.
+    pub fn extent(&self)->Extent2D{
+        return self.extent;
+    }
+    ///The index of the color image drawn to this frame.
+    pub fn image_index(&self)->u32{
+        return self.image_index;
+    }
+    ///The index of the frame in flight.
+    pub fn frame_index(&self)->usize{
+        return self.frame_index;
+    }
+    ///The image behind a graph image this frame, null for transient images no kept pass uses.
+    pub fn image(&self , image : GraphImage)->vk::Image{
+        return self.renderer.resolve_graph_image(image.0, self.image_index).handle;
+    }
+    ///The view of every aspect of a graph image this frame.
+    pub fn image_view(&self , image : GraphImage)->ImageView{
+        return self.renderer.resolve_graph_image(image.0, self.image_index).view;
+    }
+    ///Allocates a descriptor set that is freed when the frame's resources are reused.
+    pub fn allocate_descriptor_set(&mut self , layout : DescriptorSetLayout)->Result<DescriptorSet,RendererError>{
+        return unsafe{self.renderer.frames[self.frame_index].descriptor_allocator.allocate(&self.renderer.device, layout, self.renderer.descriptor_layouts.needs(layout))};
+    }
+    pub fn write_descriptors(&self , writer : &DescriptorWriter){
+        self.renderer.write_descriptors(writer);
+    }
+}
+impl Renderer{
+    ///The graph the renderer starts with, a single external pass that clears the backbuffer to the clear color and the depth buffer to 1.
+    ///With multisampling the pass draws to a transient multisampled color image instead, which is resolved into the backbuffer.
+    pub fn default_frame_graph(&self)->RenderGraph{
+        let mut graph = RenderGraph::new();
+        let clear_color = AttachmentLoad::Clear(ClearValue{color : ClearColorValue{float32 : self.clear_color}});
+        let pass = GraphPassBuilder::new("forward");
+        let pass = if self.sample_count == SampleCountFlags::TYPE_1{pass.color_attachment(graph.backbuffer(), clear_color)}else{
+            let color = graph.create_image("multisampled color", TransientImage::new(self.color_format).samples(self.sample_count));
+            pass.color_attachment(color, clear_color).resolve_attachment(color, graph.backbuffer())
+        };
+        let pass = pass
+            .depth_attachment(graph.depth_buffer(), AttachmentLoad::Clear(ClearValue{depth_stencil : ClearDepthStencilValue{depth : 1.0 , stencil : 0}}))
+            .external();
+        graph.add_pass(pass);
+        graph.default = true;
+        return graph;
+    }
+    ///Compiles the graph and records it from the next frame on, waiting for the device to become idle first.
+    ///The previous graph is kept when the new one is invalid.
+    pub fn set_frame_graph(&mut self , graph : RenderGraph)->Result<(),RendererError>{
+        unsafe{
+            let compiled = self.compile_frame_graph(graph, None)?;
+            let mut previous = std::mem::replace(&mut self.frame_graph, compiled);
+            previous.destroy_targets(&self.device, &mut self.allocator);
+        }
+        debug!("Compiled a frame graph with {} of {} passes.",self.frame_graph.plans.len(),self.frame_graph.graph.passes.len());
+        return Ok(());
+    }
+    ///Compiles a graph and creates its targets once the device is idle, against `depth_image` instead of the depth buffer when given.
+    pub(crate) unsafe fn compile_frame_graph(&mut self , graph : RenderGraph , depth_image : Option<&Image>)->Result<CompiledGraph,RendererError>{
+        let depth_image = depth_image.unwrap_or(&self.depth_image);
+        let renderer_targets = RendererTargets{
+            backbuffer_usage : self.color_images.first().map_or(ImageUsageFlags::empty(),|image|image.description().usage),
+            backbuffer_final_layout : self.target.color_final_layout(),
+            depth_usage : depth_image.description().usage,
+        };
+        let mut compiled = CompiledGraph::plan(graph, &renderer_targets).map_err(|reason|{
+            error!("Invalid render graph, {}.",reason);
+            RendererError::InvalidGraph(reason)
+        })?;
+        self.device.device_wait_idle().map_err(|e|{error!("Failed to wait for the device to become idle, {}.",e);RendererError::WaitIdle(e)})?;
+        compiled.create_targets(&self.device, &mut self.allocator, &self.color_images, depth_image, self.extent, self.dynamic_rendering.is_some())?;
+        return Ok(compiled);
+    }
+    ///The compiled frame graph, used to look up the render passes of its passes.
+    pub fn frame_graph(&self)->&CompiledGraph{
+        return &self.frame_graph;
+    }
+    fn resolve_graph_image(&self , image : usize , image_index : u32)->ResolvedImage{
+        let source = match self.frame_graph.graph.images.get(image){
+            Some(resource)=>&resource.source,
+            None=>return ResolvedImage::null(),
+        };
+        let transient = self.frame_graph.targets.transients.get(image).and_then(|transient|transient.as_ref());
+        return resolve_image(source, transient, &self.color_images, &self.depth_image, image_index as usize);
+    }
+    unsafe fn record_graph_barriers(&self , command_buffer : CommandBuffer , barriers : &Barriers , image_index : u32){
+        if barriers.is_empty(){return}
+        let image_barriers = barriers.images.iter().map(|barrier|{
+            let image = self.resolve_graph_image(barrier.image, image_index);
+            ImageMemoryBarrier{
+                s_type : StructureType::IMAGE_MEMORY_BARRIER,
+                p_next : std::ptr::null(),
+                src_access_mask : barrier.src_access,
+                dst_access_mask : barrier.dst_access,
+                old_layout : barrier.old_layout,
+                new_layout : barrier.new_layout,
+                src_queue_family_index : QUEUE_FAMILY_IGNORED,
+                dst_queue_family_index : QUEUE_FAMILY_IGNORED,
+                image : image.handle,
+                subresource_range : image.range,
+            }
+        }).filter(|barrier|barrier.image != vk::Image::null()).collect::<Vec<_>>();
+        let memory_barriers = barriers.memory.iter().map(|&(src_access_mask,dst_access_mask)|MemoryBarrier{
+            s_type : StructureType::MEMORY_BARRIER,
+            p_next : std::ptr::null(),
+            src_access_mask,
+            dst_access_mask,
+        }).collect::<Vec<_>>();
+        self.device.cmd_pipeline_barrier(command_buffer, barriers.src_stages, barriers.dst_stages, DependencyFlags::empty(), &memory_barriers, &[], &image_barriers);
+    }
+    ///Records the barriers of a planned pass and begins rendering to its attachments, returns whether rendering was begun.
+    ///Dynamic rendering is given the same views, layouts and load and store operations as the render pass, so both draw the same.
+    pub(crate) unsafe fn begin_graph_pass(&self , command_buffer : CommandBuffer , image_index : u32 , position : usize)->bool{
+        let plan = &self.frame_graph.plans[position];
+        self.record_graph_barriers(command_buffer, &plan.barriers, image_index);
+        let target = match &self.frame_graph.targets.pass_targets[position]{
+            Some(target)=>target,
+            None=>return false,
+        };
+        let pass = &self.frame_graph.graph.passes[plan.pass];
+        let clear_values = plan.attachments.iter().map(|attachment|match pass.images[attachment.access].load{
+            AttachmentLoad::Clear(value)=>value,
+            _=>ClearValue::default(),
+        }).collect::<Vec<_>>();
+        let render_area = Rect2D{offset : Offset2D{x : 0 , y : 0} , extent : target.extent};
+        match &self.dynamic_rendering{
+            Some(dynamic_rendering)=>{
+                let mut attachments = plan.attachments.iter().zip(clear_values.iter()).map(|(attachment,&clear_value)|{
+                    let view = self.resolve_graph_image(attachment.image, image_index).view;
+                    RenderingAttachmentInfo::new(view, attachment.layout, attachment.load_op, attachment.store_op, clear_value)
+                }).collect::<Vec<_>>();
+                //Resolve attachments are part of the color attachment they resolve, render passes average color samples as well.
+                for (i,attachment) in plan.attachments.iter().enumerate(){
+                    if let AttachmentKind::Resolve(color) = attachment.kind{
+                        attachments[color].resolve_mode = ResolveModeFlags::AVERAGE;
+                        attachments[color].resolve_image_view = attachments[i].image_view;
+                        attachments[color].resolve_image_layout = attachment.layout;
+                    }
+                }
+                //The depth attachment comes after the color attachments.
+                let (color_attachments,depth_attachment) = attachments.split_at(target.color_formats.len());
+                let depth_attachment = depth_attachment.first().filter(|_|target.depth_format.is_some());
+                let has_stencil = target.depth_format.is_some_and(|format|format_aspects(format).contains(ImageAspectFlags::STENCIL));
+                let stencil_attachment = if has_stencil{depth_attachment}else{None};
+                let rendering_info = RenderingInfo::new(render_area, color_attachments, depth_attachment, stencil_attachment);
+                dynamic_rendering.cmd_begin_rendering(command_buffer, &rendering_info);
+            }
+            None=>{
+                let framebuffers = &self.frame_graph.targets.framebuffers[position];
+                let framebuffer = if framebuffers.len() == 1{framebuffers[0]}else{framebuffers[image_index as usize]};
+                let render_pass_begin_info = RenderPassBeginInfo{
+                    s_type : StructureType::RENDER_PASS_BEGIN_INFO,
+                    p_next : std::ptr::null(),
+                    render_pass : target.render_pass,
+                    framebuffer,
+                    render_area,
+                    clear_value_count : clear_values.len() as u32,
+                    p_clear_values : clear_values.as_ptr(),
+                };
+                self.device.cmd_begin_render_pass(command_buffer, &render_pass_begin_info, SubpassContents::INLINE);
+            }
+        }
+        self.device.cmd_set_viewport(command_buffer, 0, &[viewport(target.extent)]);
+        self.device.cmd_set_scissor(command_buffer, 0, &[render_area]);
+        return true;
+    }
+    ///Ends rendering begun by `begin_graph_pass`.
+    pub(crate) unsafe fn end_graph_pass(&self , command_buffer : CommandBuffer){
+        match &self.dynamic_rendering{
+            Some(dynamic_rendering)=>dynamic_rendering.cmd_end_rendering(command_buffer),
+            None=>self.device.cmd_end_render_pass(command_buffer),
+        }
+    }
+    ///Records the planned passes in `positions` through their callbacks.
+    ///The pass's rendering is ended before the error of a failed callback is returned.
+    pub(crate) unsafe fn record_graph_passes(&mut self , command_buffer : CommandBuffer , image_index : u32 , frame_index : usize , positions : Range<usize>)->Result<(),RendererError>{
+        for position in positions{
+            let in_render_pass = self.begin_graph_pass(command_buffer, image_index, position);
+            let pass = self.frame_graph.plans[position].pass;
+            //The callback is taken out of the graph while it runs, so it can borrow the renderer.
+            let result = match self.frame_graph.graph.passes[pass].execute.take(){
+                Some(mut execute)=>{
+                    let (render_pass,extent) = match &self.frame_graph.targets.pass_targets[position]{
+                        Some(target)=>(target.render_pass,target.extent),
+                        None=>(RenderPass::null(),self.extent),
+                    };
+                    let mut context = PassContext{renderer : self , command_buffer , image_index , frame_index , render_pass , extent};
+                    let result = execute(&mut context);
+                    self.frame_graph.graph.passes[pass].execute = Some(execute);
+                    result
+                }
+                None=>Ok(()),
+            };
+            if in_render_pass{self.end_graph_pass(command_buffer)}
+            result?;
+        }
+        return Ok(());
+    }
+    ///Transitions the backbuffer and imported images to their final layouts.
+    pub(crate) unsafe fn record_graph_final_barriers(&self , command_buffer : CommandBuffer , image_index : u32){
+        self.record_graph_barriers(command_buffer, &self.frame_graph.final_barriers, image_index);
+    }
+}
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn targets()->RendererTargets{
+        return RendererTargets{
+            backbuffer_usage : ImageUsageFlags::COLOR_ATTACHMENT | ImageUsageFlags::TRANSFER_DST,
+            backbuffer_final_layout : ImageLayout::PRESENT_SRC_KHR,
+            depth_usage : ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+        };
+    }
+    fn clear()->AttachmentLoad{
+        return AttachmentLoad::Clear(ClearValue{color : ClearColorValue{float32 : [0.0;4]}});
+    }
+    ///The external pass drawing to the backbuffer, configured further by `pass`.
+    fn external(graph : &mut RenderGraph , pass : impl FnOnce(GraphPassBuilder)->GraphPassBuilder)->GraphPass{
+        let backbuffer = graph.backbuffer();
+        return graph.add_pass(pass(GraphPassBuilder::new("main").color_attachment(backbuffer, clear()).external()));
+    }
+    fn plan_error(graph : RenderGraph)->String{
+        match CompiledGraph::plan(graph, &targets()){
+            Ok(_)=>panic!("the graph is valid"),
+            Err(e)=>return e,
+        }
+    }
+    fn image_barriers(compiled : &CompiledGraph , pass : GraphPass , image : GraphImage)->Vec<&ImageBarrier>{
+        let position = compiled.positions[pass.0].unwrap();
+        return compiled.plans[position].barriers.images.iter().filter(|barrier|barrier.image == image.0).collect();
+    }
+    #[test]
+    fn passes_whose_output_is_unused_are_culled(){
+        let mut graph = RenderGraph::new();
+        let unused = graph.create_image("unused", TransientImage::new(Format::R8G8B8A8_UNORM));
+        let producer = graph.add_pass(GraphPassBuilder::new("producer").color_attachment(unused, clear()));
+        let main = external(&mut graph, |pass|pass);
+        let compiled = CompiledGraph::plan(graph, &targets()).unwrap();
+        assert!(compiled.is_culled(producer));
+        assert!(!compiled.is_culled(main));
+        assert_eq!(compiled.pass_count(), 1);
+    }
+    #[test]
+    fn side_effects_keep_passes_alive(){
+        let mut graph = RenderGraph::new();
+        let unused = graph.create_image("unused", TransientImage::new(Format::R8G8B8A8_UNORM));
+        let producer = graph.add_pass(GraphPassBuilder::new("producer").color_attachment(unused, clear()).side_effect());
+        external(&mut graph, |pass|pass);
+        let compiled = CompiledGraph::plan(graph, &targets()).unwrap();
+        assert!(!compiled.is_culled(producer));
+        assert_eq!(compiled.pass_count(), 2);
+    }
+    #[test]
+    fn reading_an_unwritten_image_fails(){
+        let mut graph = RenderGraph::new();
+        let shadow = graph.create_image("shadow", TransientImage::new(Format::D32_SFLOAT));
+        external(&mut graph, |pass|pass.sampled(shadow, PipelineStageFlags::FRAGMENT_SHADER));
+        assert!(plan_error(graph).contains("reads \"shadow\" before any pass writes it"));
+    }
+    #[test]
+    fn read_after_write_has_one_barrier(){
+        let mut graph = RenderGraph::new();
+        let scene = graph.create_image("scene", TransientImage::new(Format::R8G8B8A8_UNORM));
+        let producer = graph.add_pass(GraphPassBuilder::new("producer").color_attachment(scene, clear()));
+        let main = external(&mut graph, |pass|pass.sampled(scene, PipelineStageFlags::FRAGMENT_SHADER));
+        let compiled = CompiledGraph::plan(graph, &targets()).unwrap();
+        let barriers = image_barriers(&compiled, main, scene);
+        assert_eq!(barriers.len(), 1);
+        assert_eq!(barriers[0].old_layout, ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+        assert_eq!(barriers[0].new_layout, ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        assert_eq!(barriers[0].src_access, AccessFlags::COLOR_ATTACHMENT_WRITE);
+        assert_eq!(barriers[0].dst_access, AccessFlags::SHADER_READ);
+        let plan = &compiled.plans[compiled.positions[main.0].unwrap()];
+        assert!(plan.barriers.src_stages.contains(PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT));
+        assert!(plan.barriers.dst_stages.contains(PipelineStageFlags::FRAGMENT_SHADER));
+        let producer = &compiled.plans[compiled.positions[producer.0].unwrap()];
+        assert_eq!(producer.attachments[0].store_op, AttachmentStoreOp::STORE);
+    }
+    #[test]
+    fn reads_in_the_same_stages_share_a_barrier(){
+        let mut graph = RenderGraph::new();
+        let scene = graph.create_image("scene", TransientImage::new(Format::R8G8B8A8_UNORM));
+        graph.add_pass(GraphPassBuilder::new("producer").color_attachment(scene, clear()));
+        let first = graph.add_pass(GraphPassBuilder::new("first reader").sampled(scene, PipelineStageFlags::FRAGMENT_SHADER).side_effect());
+        let main = external(&mut graph, |pass|pass.sampled(scene, PipelineStageFlags::FRAGMENT_SHADER));
+        let compiled = CompiledGraph::plan(graph, &targets()).unwrap();
+        assert_eq!(image_barriers(&compiled, first, scene).len(), 1);
+        assert_eq!(image_barriers(&compiled, main, scene).len(), 0);
+    }
+    #[test]
+    fn frame_local_images_without_readers_are_not_stored(){
+        let mut graph = RenderGraph::new();
+        let scratch = graph.create_image("scratch", TransientImage::new(Format::R8G8B8A8_UNORM));
+        let main = external(&mut graph, |pass|pass.color_attachment(scratch, clear()));
+        let compiled = CompiledGraph::plan(graph, &targets()).unwrap();
+        let plan = &compiled.plans[compiled.positions[main.0].unwrap()];
+        let store_op = |image : GraphImage|plan.attachments.iter().find(|attachment|attachment.image == image.0).unwrap().store_op;
+        assert_eq!(store_op(compiled.graph.backbuffer()), AttachmentStoreOp::STORE);
+        assert_eq!(store_op(scratch), AttachmentStoreOp::DONT_CARE);
+    }
+    #[test]
+    fn resolves_need_a_single_color_attachment_source(){
+        let mut graph = RenderGraph::new();
+        let samples = graph.create_image("samples", TransientImage::new(Format::R8G8B8A8_UNORM).samples(SampleCountFlags::TYPE_4));
+        let backbuffer = graph.backbuffer();
+        graph.add_pass(GraphPassBuilder::new("main").resolve_attachment(samples, backbuffer).external());
+        assert!(plan_error(graph).contains("isn't one of its color attachments"));
+
+        let mut graph = RenderGraph::new();
+        let samples = graph.create_image("samples", TransientImage::new(Format::R8G8B8A8_UNORM).samples(SampleCountFlags::TYPE_4));
+        let copy = graph.create_image("copy", TransientImage::new(Format::R8G8B8A8_UNORM));
+        let backbuffer = graph.backbuffer();
+        graph.add_pass(GraphPassBuilder::new("main").color_attachment(samples, clear()).resolve_attachment(samples, backbuffer).resolve_attachment(samples, copy).external());
+        assert!(plan_error(graph).contains("resolves \"samples\" more than once"));
+    }
+}
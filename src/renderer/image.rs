@@ -0,0 +1,331 @@
+use ash::{Device, version::DeviceV1_0, vk::{self, AccessFlags, CommandBuffer, ComponentMapping, ComponentSwizzle, DependencyFlags, DeviceMemory, DeviceSize, Extent3D, Format, ImageAspectFlags, ImageCreateFlags, ImageCreateInfo, ImageLayout, ImageMemoryBarrier, ImageSubresourceRange, ImageTiling, ImageType, ImageUsageFlags, ImageView, ImageViewCreateFlags, ImageViewCreateInfo, ImageViewType, PipelineStageFlags, QUEUE_FAMILY_IGNORED, SampleCountFlags, SharingMode, StructureType}};
+use log::error;
+
+use super::{Allocation, MemoryAllocator, MemoryLocation, Renderer, RendererError, deletion::Garbage};
+
+///The shape of an image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageKind{
+    ///A 2D image, or a 2D array image when it has more than one layer.
+    Image2D{width : u32 , height : u32},
+    ///A 3D image, which always has a single layer.
+    Image3D{width : u32 , height : u32 , depth : u32},
+    ///A cube map with six layers per cube, or a cube map array when it has more than six layers.
+    Cube{size : u32},
+}
+///Describes an image to create, built from `ImageDescription::new` and the setters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageDescription{
+    pub kind : ImageKind,
+    pub format : Format,
+    pub mip_levels : u32,
+    ///The total number of layers, cube maps have six layers per cube.
+    pub array_layers : u32,
+    pub samples : SampleCountFlags,
+    pub usage : ImageUsageFlags,
+    pub tiling : ImageTiling,
+    pub location : MemoryLocation,
+    ///Where the views read the red, green, blue and alpha components from.
+    pub swizzle : [ComponentSwizzle;4],
+}
+impl ImageDescription{
+    ///A single mip, single sample, optimally tiled device local image, with one layer or one cube.
+    pub fn new(kind : ImageKind , format : Format , usage : ImageUsageFlags)->Self{
+        return Self{
+            kind,
+            format,
+            mip_levels : 1,
+            array_layers : if let ImageKind::Cube{..} = kind{6}else{1},
+            samples : SampleCountFlags::TYPE_1,
+            usage,
+            tiling : ImageTiling::OPTIMAL,
+            location : MemoryLocation::GpuOnly,
+            swizzle : [ComponentSwizzle::R,ComponentSwizzle::G,ComponentSwizzle::B,ComponentSwizzle::A],
+        };
+    }
+    pub fn mip_levels(mut self , mip_levels : u32)->Self{
+        self.mip_levels = mip_levels;
+        return self;
+    }
+    ///Uses every mip level down to 1x1.
+    pub fn full_mip_chain(mut self)->Self{
+        self.mip_levels = self.max_mip_levels();
+        return self;
+    }
+    pub fn array_layers(mut self , array_layers : u32)->Self{
+        self.array_layers = array_layers;
+        return self;
+    }
+    pub fn samples(mut self , samples : SampleCountFlags)->Self{
+        self.samples = samples;
+        return self;
+    }
+    pub fn tiling(mut self , tiling : ImageTiling)->Self{
+        self.tiling = tiling;
+        return self;
+    }
+    pub fn location(mut self , location : MemoryLocation)->Self{
+        self.location = location;
+        return self;
+    }
+    ///Remaps the components of the views, like reading alpha from the red channel of a single channel format.
+    pub fn swizzle(mut self , swizzle : [ComponentSwizzle;4])->Self{
+        self.swizzle = swizzle;
+        return self;
+    }
+    pub fn extent(&self)->Extent3D{
+        match self.kind{
+            ImageKind::Image2D{width , height}=>return Extent3D{width , height , depth : 1},
+            ImageKind::Image3D{width , height , depth}=>return Extent3D{width , height , depth},
+            ImageKind::Cube{size}=>return Extent3D{width : size , height : size , depth : 1},
+        }
+    }
+    ///The number of mip levels of a full mip chain.
+    pub fn max_mip_levels(&self)->u32{
+        let extent = self.extent();
+        let largest = extent.width.max(extent.height).max(extent.depth).max(1);
+        return 32 - largest.leading_zeros();
+    }
+    fn image_type(&self)->ImageType{
+        match self.kind{
+            ImageKind::Image3D{..}=>return ImageType::TYPE_3D,
+            _=>return ImageType::TYPE_2D,
+        }
+    }
+    fn view_type(&self)->ImageViewType{
+        match self.kind{
+            ImageKind::Image2D{..} if self.array_layers > 1=>return ImageViewType::TYPE_2D_ARRAY,
+            ImageKind::Image2D{..}=>return ImageViewType::TYPE_2D,
+            ImageKind::Image3D{..}=>return ImageViewType::TYPE_3D,
+            ImageKind::Cube{..} if self.array_layers > 6=>return ImageViewType::CUBE_ARRAY,
+            ImageKind::Cube{..}=>return ImageViewType::CUBE,
+        }
+    }
+    ///Why the description can't be created, or `None` when it is valid.
+    fn validate(&self)->Option<String>{
+        let extent = self.extent();
+        if extent.width == 0 || extent.height == 0 || extent.depth == 0{return Some(format!("the extent {}x{}x{} is empty",extent.width,extent.height,extent.depth))}
+        if self.mip_levels == 0 || self.mip_levels > self.max_mip_levels(){return Some(format!("{} mip levels requested, the extent allows 1 to {}",self.mip_levels,self.max_mip_levels()))}
+        if self.array_layers == 0{return Some("no array layers requested".to_string())}
+        if self.samples.as_raw().count_ones() != 1{return Some(format!("the sample count {:?} is not a single count",self.samples))}
+        if self.samples != SampleCountFlags::TYPE_1 && self.mip_levels > 1{return Some("multisampled images can't have mip levels".to_string())}
+        match self.kind{
+            ImageKind::Image3D{..} if self.array_layers != 1=>return Some("3D images can't have array layers".to_string()),
+            ImageKind::Cube{..} if !self.array_layers.is_multiple_of(6)=>return Some(format!("{} layers isn't a whole number of cubes",self.array_layers)),
+            _=>return None,
+        }
+    }
+}
+///The aspects of an image format.
+pub fn format_aspects(format : Format)->ImageAspectFlags{
+    match format{
+        Format::D16_UNORM | Format::X8_D24_UNORM_PACK32 | Format::D32_SFLOAT=>return ImageAspectFlags::DEPTH,
+        Format::S8_UINT=>return ImageAspectFlags::STENCIL,
+        Format::D16_UNORM_S8_UINT | Format::D24_UNORM_S8_UINT | Format::D32_SFLOAT_S8_UINT=>return ImageAspectFlags::DEPTH | ImageAspectFlags::STENCIL,
+        _=>return ImageAspectFlags::COLOR,
+    }
+}
+///The accesses and pipeline stages that usually use an image in `layout`, used for the barriers of layout transitions.
+pub fn layout_access_and_stages(layout : ImageLayout)->(AccessFlags,PipelineStageFlags){
+    match layout{
+        ImageLayout::UNDEFINED=>return (AccessFlags::empty(),PipelineStageFlags::TOP_OF_PIPE),
+        ImageLayout::PREINITIALIZED=>return (AccessFlags::HOST_WRITE,PipelineStageFlags::HOST),
+        ImageLayout::COLOR_ATTACHMENT_OPTIMAL=>return (AccessFlags::COLOR_ATTACHMENT_READ | AccessFlags::COLOR_ATTACHMENT_WRITE,PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT),
+        ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL=>return (AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ | AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,PipelineStageFlags::EARLY_FRAGMENT_TESTS | PipelineStageFlags::LATE_FRAGMENT_TESTS),
+        ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL=>return (AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ | AccessFlags::SHADER_READ,PipelineStageFlags::EARLY_FRAGMENT_TESTS | PipelineStageFlags::LATE_FRAGMENT_TESTS | PipelineStageFlags::FRAGMENT_SHADER),
+        ImageLayout::SHADER_READ_ONLY_OPTIMAL=>return (AccessFlags::SHADER_READ,PipelineStageFlags::VERTEX_SHADER | PipelineStageFlags::FRAGMENT_SHADER | PipelineStageFlags::COMPUTE_SHADER),
+        ImageLayout::TRANSFER_SRC_OPTIMAL=>return (AccessFlags::TRANSFER_READ,PipelineStageFlags::TRANSFER),
+        ImageLayout::TRANSFER_DST_OPTIMAL=>return (AccessFlags::TRANSFER_WRITE,PipelineStageFlags::TRANSFER),
+        //Presentation is synchronized with semaphores, the barrier only has to finish the layout transition.
+        ImageLayout::PRESENT_SRC_KHR=>return (AccessFlags::empty(),PipelineStageFlags::BOTTOM_OF_PIPE),
+        _=>return (AccessFlags::MEMORY_READ | AccessFlags::MEMORY_WRITE,PipelineStageFlags::ALL_COMMANDS),
+    }
+}
+///An image and its views, created with `Renderer::create_image` and destroyed with `Renderer::destroy_image`.
+///The renderer's swapchain images, offscreen color image and depth buffer are images as well.
+pub struct Image{
+    handle : vk::Image,
+    description : ImageDescription,
+    ///Views of every mip level and layer, the first one has every aspect of the format, followed by one view per aspect when there are several.
+    views : Vec<(ImageAspectFlags,ImageView)>,
+    ///`None` for swapchain images, which are owned by the swapchain.
+    allocation : Option<Allocation>,
+    swapchain_image : bool,
+}
+impl Image{
+    ///Creates the image, its memory and its views, `to_error` wraps the errors of the Vulkan calls.
+    ///Everything created is destroyed again when a later step fails.
+    pub(crate) unsafe fn new(device : &Device , allocator : &mut MemoryAllocator , description : &ImageDescription , to_error : fn(vk::Result)->RendererError)->Result<Self,RendererError>{
+        let mut image = Self::new_unbound(device, description, to_error)?;
+        match allocator.allocate_for_image(image.handle, description.tiling, description.location){
+            Ok(allocation)=>image.allocation = Some(allocation),
+            Err(e)=>{
+                error!("Failed to allocate memory for the {:?} image, {}.",description.format,e);
+                image.destroy(device, allocator);
+                return Err(e);
+            }
+        }
+        if let Err(e) = image.create_views(device){
+            error!("Failed to create {:?} image view, {}.",description.format,e);
+            image.destroy(device, allocator);
+            return Err(to_error(e));
+        }
+        return Ok(image);
+    }
+    ///Creates the image without memory or views, they are added with `bind_memory`.
+    pub(crate) unsafe fn new_unbound(device : &Device , description : &ImageDescription , to_error : fn(vk::Result)->RendererError)->Result<Self,RendererError>{
+        if let Some(reason) = description.validate(){
+            error!("Invalid image description, {}.",reason);
+            return Err(RendererError::InvalidImageDescription(reason));
+        }
+        let image_create_info = ImageCreateInfo{
+            s_type : StructureType::IMAGE_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : if let ImageKind::Cube{..} = description.kind{ImageCreateFlags::CUBE_COMPATIBLE}else{ImageCreateFlags::empty()},
+            image_type : description.image_type(),
+            format : description.format,
+            mip_levels : description.mip_levels,
+            array_layers : description.array_layers,
+            sharing_mode : SharingMode::EXCLUSIVE,
+            samples : description.samples,
+            queue_family_index_count : 0,
+            p_queue_family_indices : std::ptr::null(),
+            usage : description.usage,
+            initial_layout : ImageLayout::UNDEFINED,
+            tiling : description.tiling,
+            extent : description.extent(),
+        };
+        let handle = device.create_image(&image_create_info, None).map_err(|e|{
+            error!("Failed to create {:?} image, {}.",description.format,e);
+            to_error(e)
+        })?;
+        return Ok(Self{handle , description : *description , views : vec!() , allocation : None , swapchain_image : false});
+    }
+    ///Binds memory owned by the caller to an image created with `new_unbound` and creates its views.
+    ///The memory is not freed when the image is destroyed.
+    pub(crate) unsafe fn bind_memory(&mut self , device : &Device , memory : DeviceMemory , offset : DeviceSize , to_error : fn(vk::Result)->RendererError)->Result<(),RendererError>{
+        device.bind_image_memory(self.handle, memory, offset).map_err(|e|{
+            error!("Failed to bind {:?} image memory, {}.",self.description.format,e);
+            RendererError::AllocateMemory(e)
+        })?;
+        return self.create_views(device).map_err(|e|{
+            error!("Failed to create {:?} image view, {}.",self.description.format,e);
+            to_error(e)
+        });
+    }
+    ///Wraps an image of the swapchain and creates its view, the image itself is never destroyed.
+    pub(crate) unsafe fn from_swapchain(device : &Device , handle : vk::Image , format : Format , width : u32 , height : u32 , usage : ImageUsageFlags)->Result<Self,vk::Result>{
+        let description = ImageDescription::new(ImageKind::Image2D{width , height}, format, usage);
+        let mut image = Self{handle , description , views : vec!() , allocation : None , swapchain_image : true};
+        if let Err(e) = image.create_views(device){
+            for (_,view) in image.views.drain(..){device.destroy_image_view(view, None)}
+            return Err(e);
+        }
+        return Ok(image);
+    }
+    unsafe fn create_views(&mut self , device : &Device)->Result<(),vk::Result>{
+        let aspects = format_aspects(self.description.format);
+        let mut view_aspects = vec!(aspects);
+        for aspect in [ImageAspectFlags::DEPTH,ImageAspectFlags::STENCIL].iter(){
+            if aspects != *aspect && aspects.contains(*aspect){view_aspects.push(*aspect)}
+        }
+        for aspect_mask in view_aspects{
+            let image_view_create_info = ImageViewCreateInfo{
+                s_type : StructureType::IMAGE_VIEW_CREATE_INFO,
+                p_next : std::ptr::null(),
+                flags : ImageViewCreateFlags::empty(),
+                image : self.handle,
+                view_type : self.description.view_type(),
+                format : self.description.format,
+                components : ComponentMapping{ r : self.description.swizzle[0] , g : self.description.swizzle[1] , b : self.description.swizzle[2] , a : self.description.swizzle[3]},
+                subresource_range : self.subresource_range(aspect_mask),
+            };
+            let view = device.create_image_view(&image_view_create_info, None)?;
+            self.views.push((aspect_mask,view));
+        }
+        return Ok(());
+    }
+    ///Destroys the views, and the image and its memory unless it belongs to a swapchain, the image must no longer be in use.
+    ///Destroyed handles are reset, so destroying an image twice is harmless.
+    pub(crate) unsafe fn destroy(&mut self , device : &Device , allocator : &mut MemoryAllocator){
+        for (_,view) in self.views.drain(..){device.destroy_image_view(view, None)}
+        if !self.swapchain_image{device.destroy_image(self.handle, None)}
+        self.handle = vk::Image::null();
+        if let Some(allocation) = self.allocation.take(){allocator.free(allocation)}
+    }
+    pub fn handle(&self)->vk::Image{
+        return self.handle;
+    }
+    pub fn description(&self)->&ImageDescription{
+        return &self.description;
+    }
+    pub fn format(&self)->Format{
+        return self.description.format;
+    }
+    pub fn extent(&self)->Extent3D{
+        return self.description.extent();
+    }
+    ///Every aspect of the format.
+    pub fn aspects(&self)->ImageAspectFlags{
+        return format_aspects(self.description.format);
+    }
+    ///The view of every aspect, mip level and layer, this is the view used as an attachment.
+    pub fn view(&self)->ImageView{
+        return self.views.first().map(|(_,view)|*view).unwrap_or_default();
+    }
+    ///The view of a single aspect, for example to sample only the depth of a depth stencil image.
+    pub fn aspect_view(&self , aspect : ImageAspectFlags)->Option<ImageView>{
+        return self.views.iter().find(|(aspect_mask,_)|*aspect_mask == aspect).map(|(_,view)|*view);
+    }
+    ///Whether the image belongs to the swapchain.
+    pub fn is_swapchain_image(&self)->bool{
+        return self.swapchain_image;
+    }
+    ///The range of every mip level and layer of the given aspects.
+    pub fn subresource_range(&self , aspect_mask : ImageAspectFlags)->ImageSubresourceRange{
+        return ImageSubresourceRange{
+            aspect_mask,
+            base_mip_level : 0,
+            level_count : self.description.mip_levels,
+            base_array_layer : 0,
+            layer_count : self.description.array_layers,
+        };
+    }
+    ///Records a barrier that transitions the whole image from `old_layout` to `new_layout`.
+    ///The barrier waits for the accesses usual for the old layout and blocks those usual for the new one, see `layout_access_and_stages`.
+    ///# Safety
+    ///The command buffer must be recording on a queue that supports the stages of both layouts.
+    pub unsafe fn transition_layout(&self , device : &Device , command_buffer : CommandBuffer , old_layout : ImageLayout , new_layout : ImageLayout){
+        self.transition_subresource_layout(device, command_buffer, self.subresource_range(self.aspects()), old_layout, new_layout);
+    }
+    ///Records a barrier that transitions the mip levels and layers of `range` from `old_layout` to `new_layout`.
+    ///# Safety
+    ///The command buffer must be recording on a queue that supports the stages of both layouts.
+    pub unsafe fn transition_subresource_layout(&self , device : &Device , command_buffer : CommandBuffer , range : ImageSubresourceRange , old_layout : ImageLayout , new_layout : ImageLayout){
+        let (src_access_mask,src_stage_mask) = layout_access_and_stages(old_layout);
+        let (dst_access_mask,dst_stage_mask) = layout_access_and_stages(new_layout);
+        let barrier = ImageMemoryBarrier{
+            s_type : StructureType::IMAGE_MEMORY_BARRIER,
+            p_next : std::ptr::null(),
+            src_access_mask,
+            dst_access_mask,
+            old_layout,
+            new_layout,
+            src_queue_family_index : QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index : QUEUE_FAMILY_IGNORED,
+            image : self.handle,
+            subresource_range : range,
+        };
+        device.cmd_pipeline_barrier(command_buffer, src_stage_mask, dst_stage_mask, DependencyFlags::empty(), &[], &[], &[barrier]);
+    }
+}
+impl Renderer{
+    ///Creates an image, its memory and a view of each aspect.
+    pub fn create_image(&mut self , description : &ImageDescription)->Result<Image,RendererError>{
+        return unsafe{Image::new(&self.device, &mut self.allocator, description, RendererError::CreateImage)};
+    }
+    ///Destroys the image once the frames and uploads submitted so far have completed, so it may still be in use by the GPU.
+    pub fn destroy_image(&mut self , image : Image){
+        self.destroy_deferred(Garbage::Image(image));
+    }
+}
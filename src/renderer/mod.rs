@@ -1,116 +1,706 @@
-use std::{ffi::{CStr, CString}, u32};
+use std::ffi::{CStr, CString};
 
-use ash::{Device, Entry, Instance, InstanceError, extensions::khr::{Surface, Swapchain}, version::{DeviceV1_0, EntryV1_0, InstanceV1_0}, vk::{AccessFlags, ApplicationInfo, AttachmentDescription, AttachmentDescriptionFlags, AttachmentLoadOp, AttachmentReference, AttachmentStoreOp, ColorSpaceKHR, ComponentMapping, ComponentSwizzle, CompositeAlphaFlagsKHR, DependencyFlags, DeviceCreateFlags, DeviceCreateInfo, DeviceMemory, DeviceQueueCreateFlags, DeviceQueueCreateInfo, Extent2D, Extent3D, Format, FormatFeatureFlags, Framebuffer, FramebufferCreateFlags, FramebufferCreateInfo, Image, ImageAspectFlags, ImageCreateFlags, ImageCreateInfo, ImageLayout, ImageSubresourceRange, ImageTiling, ImageType, ImageUsageFlags, ImageView, ImageViewCreateFlags, ImageViewCreateInfo, ImageViewType, InstanceCreateFlags, InstanceCreateInfo, MemoryAllocateInfo, MemoryPropertyFlags, MemoryRequirements, PhysicalDevice, PhysicalDeviceFeatures, PhysicalDeviceMemoryProperties, PhysicalDeviceType, PipelineBindPoint, PipelineStageFlags, PresentModeKHR, QueueFamilyProperties, QueueFlags, RenderPass, RenderPassCreateFlags, RenderPassCreateInfo, SUBPASS_EXTERNAL, SampleCountFlags, SharingMode, StructureType, SubpassDependency, SubpassDescription, SubpassDescriptionFlags, SurfaceCapabilitiesKHR, SurfaceFormatKHR, SurfaceKHR, SwapchainCreateFlagsKHR, SwapchainCreateInfoKHR, SwapchainKHR}};
+use ash::{Device, Entry, Instance, InstanceError, extensions::khr::{Surface, Swapchain}, version::{DeviceV1_0, EntryV1_0, InstanceV1_0}, vk::{ApplicationInfo, CompositeAlphaFlagsKHR, DeviceCreateFlags, DeviceCreateInfo, DeviceQueueCreateFlags, DeviceQueueCreateInfo, ExtDescriptorIndexingFn, Extent2D, Fence, Format, FormatFeatureFlags, Framebuffer, ImageCreateFlags, ImageLayout, ImageTiling, ImageType, ImageUsageFlags, InstanceCreateFlags, InstanceCreateInfo, MemoryPropertyFlags, MemoryRequirements, PhysicalDevice, PhysicalDeviceDescriptorIndexingFeatures, PhysicalDeviceMemoryProperties, PipelineStageFlags, PresentInfoKHR, PresentModeKHR, Queue, QueueFamilyProperties, QueueFlags, RenderPass, SampleCountFlags, Semaphore, SharingMode, SubmitInfo, StructureType, SurfaceCapabilitiesKHR, SurfaceFormatKHR, SurfaceKHR, SwapchainCreateFlagsKHR, SwapchainCreateInfoKHR, SwapchainKHR}};
 use log::{error,info,warn,debug,trace};
 use winit::window::Window;
 
+mod allocator;
+mod bc;
+mod bindless;
+mod buffer;
+mod builder;
+mod compute;
+#[cfg(feature = "shader-compiler")]
+mod compiler;
+mod deletion;
+mod descriptor;
+mod device;
+mod dynamic_rendering;
+mod error;
+mod frame;
+mod graph;
+mod image;
+mod pipeline;
+mod pipeline_cache;
+mod queues;
+mod report;
+mod shader;
+mod spirv;
+mod texture;
+mod upload;
+
+pub use allocator::{Allocation, AllocatorStatistics, MemoryAllocator, MemoryLocation, MemoryTypeStatistics, ResourceTiling};
+pub use bindless::{BindlessHandle, BindlessKind};
+pub use buffer::Buffer;
+///Buffer contents, push constants and specialization constants are reinterpreted as bytes, so they must be `Pod`.
+pub use bytemuck::{Pod, Zeroable};
+#[cfg(feature = "shader-compiler")]
+pub use compiler::{BASELINE_CAPABILITIES, ShaderLanguage, compile, compile_file, compile_for_build};
+#[cfg(feature = "shader-compiler")]
+pub use naga::valid::Capabilities as ShaderCapabilities;
+pub use compute::{ComputeContext, ComputeHandle, ComputePipeline};
+pub use builder::{Preference, RendererBuilder, RendererConfig, RenderingBackend, SwapchainImageCount};
+pub use descriptor::DescriptorWriter;
+pub use device::{DEVICE_OVERRIDE_ENV, DeviceSelector};
+pub use error::RendererError;
+pub use frame::Frame;
+pub use graph::{AttachmentLoad, CompiledGraph, GraphBuffer, GraphImage, GraphImageSize, GraphPass, GraphPassBuilder, PassContext, RenderGraph, TransientImage};
+pub use image::{Image, ImageDescription, ImageKind, format_aspects, layout_access_and_stages};
+pub use pipeline::{BlendMode, GraphicsPipeline, GraphicsPipelineBuilder};
+pub use queues::{DeviceQueue, Queues};
+pub use upload::{UploadDestination, UploadHandle};
+pub use texture::TextureData;
+pub use shader::{PipelineLayout, ShaderModule, merge_bindings, merge_push_constants};
+pub use spirv::{DescriptorBinding, EntryPoint, MAX_SPIRV_VERSION, PushConstantBlock, ShaderReflection, SpecializationConstant, VertexInput};
+pub use report::{DepthFormatReport, DeviceReport, LimitsReport, MemoryHeapReport, MemoryTypeReport, QueueFamilyReport};
+
+use bindless::{BindlessSet, BindlessSupport};
+use compute::ComputeDispatcher;
+use deletion::DeletionQueue;
+use descriptor::{DescriptorAllocator, DescriptorLayoutCache};
+use dynamic_rendering::{DynamicRendering, DynamicRenderingSupport, PhysicalDeviceDynamicRenderingFeatures};
+use frame::FrameResources;
+use pipeline_cache::PipelineCache;
+use upload::Uploader;
+
+///Owns the Vulkan instance, device and the render targets used for drawing, either to a window or to offscreen images.
 pub struct Renderer{
-    _entry : Entry,
+    config : RendererConfig,
+    entry : Entry,
     instance : Instance,
+    physical_device : PhysicalDevice,
+    device : Device,
+    queues : Queues,
+    target : Target,
+    color_format : Format,
+    extent : Extent2D,
+    color_images : Vec<Image>,
+    depth_image_format : Format,
+    sample_count : SampleCountFlags,
+    depth_image : Image,
+    pipeline_cache : PipelineCache,
+    ///The passes of every frame, the application records the external pass between `begin_frame` and `end_frame`.
+    frame_graph : CompiledGraph,
+    ///Set while the render target has a zero extent, for example when the window is minimized.
+    paused : bool,
+    frames : Vec<FrameResources>,
+    current_frame : usize,
+    ///The fence of the frame in flight that last used each color image.
+    images_in_flight : Vec<Fence>,
+    clear_color : [f32;4],
+    uploader : Uploader,
+    compute : ComputeDispatcher,
+    ///Descriptor sets that live as long as the renderer.
+    descriptor_allocator : DescriptorAllocator,
+    descriptor_layouts : DescriptorLayoutCache,
+    ///`None` unless bindless mode was requested and is supported.
+    bindless : Option<BindlessSet>,
+    ///The dynamic rendering commands, `None` when the frame graph uses render pass objects.
+    dynamic_rendering : Option<DynamicRendering>,
+    deletion_queue : DeletionQueue,
+    allocator : MemoryAllocator,
+}
+///The images a renderer draws to.
+enum Target{
+    ///The swapchain images of a window surface.
+    Swapchain(SwapchainTarget),
+    ///A single device owned color image, used by headless renderers.
+    Offscreen,
+}
+struct SwapchainTarget{
     surface_loader : Surface,
     surface : SurfaceKHR,
-    _physical_device : PhysicalDevice,
-    device : Device,
     swapchain_loader : Swapchain,
     swapchain : SwapchainKHR,
-    _swapchain_images : Vec<Image>,
-    swapchain_image_views : Vec<ImageView>,
-    depth_image : Image,
-    depth_image_memory : DeviceMemory,
-    depth_image_view : ImageView,
-    render_pass : RenderPass,
-    framebuffers : Vec<Framebuffer>,
+    surface_format : SurfaceFormatKHR,
+    presentation_queue_family : u32,
+    presentation_queue : Queue,
+    ///The latest known size of the window, used when the surface doesn't dictate the swapchain extent.
+    window_extent : Extent2D,
+    ///Set when the swapchain no longer matches the surface exactly and should be recreated after presenting.
+    suboptimal : bool,
+}
+impl Target{
+    ///The layout the color image is left in by the render pass.
+    fn color_final_layout(&self)->ImageLayout{
+        match self{
+            Target::Swapchain(_)=>return ImageLayout::PRESENT_SRC_KHR,
+            Target::Offscreen=>return ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        }
+    }
+}
+///What the renderer is created for.
+#[derive(Clone, Copy)]
+enum Presentation<'a>{
+    Window(&'a Window),
+    Headless(Extent2D),
 }
 impl Renderer {
-    pub fn new(window : &Window)->Self{
-        let entry = unsafe{Entry::new()}.unwrap_or_else(|e|{error!("Failed to load Vulkan library:{}.",e);panic!("Failed to load Vulkan library.")});
-        let vulkan_version = match entry.try_enumerate_instance_version().unwrap_or_else(|e|{error!("Generic error:{}.",e);panic!("Something went wrong whith the Vulkan library")}){
-            Some(v)=>{(ash::vk::version_major(v),ash::vk::version_minor(v),ash::vk::version_patch(v))}
-            None=>{(1,0,0)}
+    ///Creates a renderer that draws to the given window, using the default `RendererConfig`.
+    pub fn new(window : &Window)->Result<Self,RendererError>{
+        return RendererBuilder::new().build(window);
+    }
+    ///Creates a renderer without a window that draws to offscreen color and depth images of the given size, using the default `RendererConfig`.
+    ///The surface and swapchain extensions are not required, so this works on machines without a display.
+    pub fn new_headless(width : u32 , height : u32)->Result<Self,RendererError>{
+        return RendererBuilder::new().build_headless(width, height);
+    }
+    fn create(presentation : Presentation , config : RendererConfig)->Result<Self,RendererError>{
+        let entry = unsafe{Entry::new()}.map_err(|e|{error!("Failed to load Vulkan library:{}.",e);RendererError::LoadLibrary(e)})?;
+        let instance_version = match entry.try_enumerate_instance_version().map_err(|e|{error!("Generic error:{}.",e);RendererError::CreateInstance(InstanceError::VkError(e))})?{
+            Some(v)=>v,
+            None=>ash::vk::make_version(1, 0, 0),
         };
-        info!("Successfully loaded Vulkan library, version:{}.{}.{}.",vulkan_version.0,vulkan_version.1,vulkan_version.2);
-        let instance = unsafe{create_instance(&entry, window).unwrap_or_else(|e|{
-            error!("Failed to create Vulkan instance, {}.",e);
-            panic!()})
+        info!("Successfully loaded Vulkan library, version:{}.{}.{}.",ash::vk::version_major(instance_version),ash::vk::version_minor(instance_version),ash::vk::version_patch(instance_version));
+        //Everything pushed onto the cleanup stack is destroyed in reverse order if a later step fails.
+        let mut cleanup = CleanupStack::new();
+        let instance_extensions = match presentation{
+            Presentation::Window(window)=>ash_window::enumerate_required_extensions(window).map_err(|e|{
+                error!("Failed to acquire Vulkan surface extensions, {}.",e);
+                RendererError::CreateInstance(InstanceError::VkError(e))
+            })?,
+            Presentation::Headless(_)=>vec!(),
         };
+        let instance = unsafe{create_instance(&entry, &instance_extensions)}.map_err(|e|{
+            error!("Failed to create Vulkan instance, {}.",e);
+            RendererError::CreateInstance(e)
+        })?;
+        let cleanup_instance = instance.clone();
+        cleanup.push(move ||unsafe{cleanup_instance.destroy_instance(None)});
         info!("Created Vulkan instance.");
-        let surface_loader = Surface::new(&entry , &instance);
-        let surface = unsafe{ash_window::create_surface(&entry, &instance, window, None)}.unwrap_or_else(|e|{
-            error!("Failed to create Vulkan surface, {}.",e);
-            panic!()}
-        );
-        info!("Succesfully created Vulkan surface.");
-        let (physical_device,physical_device_name) = unsafe{get_physical_device(&instance, &surface_loader, &surface)};
+        let surface = match presentation{
+            Presentation::Window(window)=>{
+                let surface_loader = Surface::new(&entry , &instance);
+                let surface = unsafe{ash_window::create_surface(&entry, &instance, window, None)}.map_err(|e|{
+                    error!("Failed to create Vulkan surface, {}.",e);
+                    RendererError::CreateSurface(e)
+                })?;
+                let cleanup_surface_loader = surface_loader.clone();
+                cleanup.push(move ||unsafe{cleanup_surface_loader.destroy_surface(surface, None)});
+                info!("Succesfully created Vulkan surface.");
+                Some((surface_loader,surface))
+            }
+            Presentation::Headless(_)=>None,
+        };
+        let surface_ref = surface.as_ref().map(|(surface_loader,surface)|(surface_loader,surface));
+        let (physical_device,physical_device_name) = unsafe{device::select_physical_device(&instance, instance_version, surface_ref, &config)}?;
         info!("Selected {} as GPU for rendering",physical_device_name);
-        let device = unsafe{create_device(&instance, &physical_device , &surface_loader , &surface)};
+        let bindless_support = if config.bindless{
+            let support = unsafe{bindless::query_support(&instance, instance_version, physical_device)};
+            if support.is_none(){warn!("The GPU doesn't support descriptor indexing, bindless mode is disabled.")}
+            support
+        }else{None};
+        let dynamic_rendering_support = if config.rendering_backend == RenderingBackend::DynamicRendering{
+            let support = unsafe{dynamic_rendering::query_support(&instance, instance_version, physical_device)};
+            if support.is_none(){warn!("The GPU doesn't support dynamic rendering, falling back to render passes.")}
+            support
+        }else{None};
+        let device = unsafe{create_device(&instance, &physical_device , surface_ref, &config, bindless_support.as_ref(), dynamic_rendering_support.as_ref())}?;
+        let cleanup_device = device.clone();
+        cleanup.push(move ||unsafe{cleanup_device.destroy_device(None)});
         info!("Created Vulkan device handle");
-        let queue_family_properties = unsafe{instance.get_physical_device_queue_family_properties(physical_device)};
-        let graphics_queue = get_graphics_queue_family(&queue_family_properties);
-        let compute_queue_family = get_compute_queue_family(&queue_family_properties);
-        let presentation_queue = if unsafe{check_queue_family_presentation_support(&surface_loader, &surface, &physical_device, graphics_queue)}{graphics_queue}
-        else if unsafe{check_queue_family_presentation_support(&surface_loader, &surface, &physical_device, compute_queue_family)}{compute_queue_family}
-        else{error!("Failed to get presentation queue family.");panic!()};
-        let swapchain_loader = Swapchain::new(&instance, &device);
-        let swapchain = unsafe{create_swapchain(&physical_device, &swapchain_loader, &surface_loader, &surface, graphics_queue, presentation_queue, window)};
-        let swapchain_images = unsafe{swapchain_loader.get_swapchain_images(swapchain)}.unwrap_or_else(|e|{
-            error!("Failed to acquire swapchain images, {}.",e);
-            panic!();
+        let dynamic_rendering = dynamic_rendering_support.as_ref().and_then(|support|{
+            let dynamic_rendering = unsafe{DynamicRendering::load(&instance, &device, support)};
+            if dynamic_rendering.is_none(){warn!("Failed to load the dynamic rendering commands, falling back to render passes.")}
+            dynamic_rendering
         });
-        let swapchain_format = unsafe{get_surface_format(&surface_loader, &surface, &physical_device)};
-        let capabilities = unsafe{surface_loader.get_physical_device_surface_capabilities(physical_device, surface)}.unwrap_or_else(|e|{
-            error!("Failed to get surface capabilities, {}.",e);
-            panic!();
+        //Declared after the cleanup stack so it is dropped first, freeing its memory before the device is destroyed.
+        let mut allocator = unsafe{MemoryAllocator::new(&instance, physical_device, &device)};
+        let queue_family_properties = unsafe{instance.get_physical_device_queue_family_properties(physical_device)};
+        let queues = unsafe{Queues::new(&device, &queue_family_properties)}?;
+        let graphics_queue_family = queues.graphics().family_index();
+        let compute_queue_family = queues.compute().family_index();
+        let (target,color_format,extent,color_images) = match (presentation,surface){
+            (Presentation::Window(window),Some((surface_loader,surface)))=>{
+                let presentation_queue_family = if unsafe{check_queue_family_presentation_support(&surface_loader, &surface, &physical_device, graphics_queue_family)}?{graphics_queue_family}
+                else if unsafe{check_queue_family_presentation_support(&surface_loader, &surface, &physical_device, compute_queue_family)}?{compute_queue_family}
+                else{error!("Failed to get presentation queue family.");return Err(RendererError::NoPresentationQueueFamily)};
+                let presentation_queue = unsafe{device.get_device_queue(presentation_queue_family, 0)};
+                let swapchain_loader = Swapchain::new(&instance, &device);
+                let surface_format = unsafe{get_surface_format(&surface_loader, &surface, &physical_device, &config.surface_format)}?;
+                let window_size = window.inner_size();
+                let window_extent = Extent2D{width : window_size.width , height : window_size.height};
+                let (swapchain,extent,usage) = unsafe{create_swapchain(&physical_device, &swapchain_loader, &surface_loader, &surface, [graphics_queue_family,presentation_queue_family], &surface_format, &config, window_extent, SwapchainKHR::null())}?;
+                let cleanup_swapchain_loader = swapchain_loader.clone();
+                cleanup.push(move ||unsafe{cleanup_swapchain_loader.destroy_swapchain(swapchain, None)});
+                let swapchain_images = unsafe{swapchain_loader.get_swapchain_images(swapchain)}.map_err(|e|{
+                    error!("Failed to acquire swapchain images, {}.",e);
+                    RendererError::CreateSwapchain(e)
+                })?;
+                let swapchain_images = unsafe{create_swapchain_images(&device, &swapchain_images, &surface_format.format, &extent, usage)}?;
+                let cleanup_device = device.clone();
+                let cleanup_image_views = swapchain_images.iter().map(|image|image.view()).collect::<Vec<_>>();
+                cleanup.push(move ||unsafe{for &image_view in cleanup_image_views.iter(){cleanup_device.destroy_image_view(image_view, None)}});
+                info!("Created Vulkan swapchain.");
+                let target = Target::Swapchain(SwapchainTarget{
+                    surface_loader , surface , swapchain_loader , swapchain , surface_format , presentation_queue_family , presentation_queue , window_extent , suboptimal : false,
+                });
+                (target,surface_format.format,extent,swapchain_images)
+            }
+            (Presentation::Headless(extent),_)=>{
+                let color_format = unsafe{get_offscreen_color_format(&instance, &physical_device, &config.surface_format)}?;
+                let color_image = unsafe{create_offscreen_color_image(&device, &mut allocator, &color_format, &extent)}?;
+                let (color_image_handle,color_image_view) = (color_image.handle(),color_image.view());
+                let cleanup_device = device.clone();
+                cleanup.push(move ||unsafe{
+                    cleanup_device.destroy_image_view(color_image_view, None);
+                    cleanup_device.destroy_image(color_image_handle, None);
+                });
+                info!("Created offscreen color image.");
+                (Target::Offscreen,color_format,extent,vec!(color_image))
+            }
+            (Presentation::Window(_),None)=>unreachable!("a surface is always created for a window"),
+        };
+        let (depth_image_format,depth_image_tiling) = unsafe{get_depth_image_format_and_tiling(&instance, &physical_device, &config.depth_format)}?;
+        let sample_count = unsafe{get_sample_count(&instance, &physical_device, &config.sample_count, color_format, depth_image_format, depth_image_tiling)}?;
+        let depth_image = unsafe{create_depth_image(&device, &mut allocator, &depth_image_format, &depth_image_tiling, &extent, sample_count)}?;
+        let (depth_image_handle,depth_image_view) = (depth_image.handle(),depth_image.view());
+        let cleanup_device = device.clone();
+        cleanup.push(move ||unsafe{
+            cleanup_device.destroy_image_view(depth_image_view, None);
+            cleanup_device.destroy_image(depth_image_handle, None);
         });
-        let swapchain_extent = unsafe{get_surface_extent(&capabilities, window)};
-        let swapchain_image_views = unsafe{create_swapchain_image_views(&device, &swapchain_images, &swapchain_format.format)};
-        info!("Created Vulkan swapchain.");
-        let (depth_image_format,depth_image_tiling) = unsafe{get_depth_image_format_and_tiling(&instance, &physical_device)};
-        let (depth_image,depth_image_memory,depth_image_view) = unsafe{create_depth_images_and_view(&device, &depth_image_format, &depth_image_tiling, &swapchain_extent, &instance, &physical_device)};
         info!("Created depth buffer.");
-        let render_pass = unsafe{create_render_pass(&device , &swapchain_format.format , &depth_image_format)};
-        info!("Created Render Pass");
-        let framebuffers = unsafe{create_framebuffers(&device, &render_pass, &swapchain_extent, &swapchain_image_views, &depth_image_view)};
-        
-        return Self{
-            _entry : entry , instance , surface_loader , surface , _physical_device : physical_device , device , swapchain_loader , swapchain , 
-            _swapchain_images : swapchain_images , swapchain_image_views , depth_image , depth_image_memory , depth_image_view , render_pass , framebuffers ,
+        let pipeline_cache = unsafe{PipelineCache::new(&instance, physical_device, &device, config.persistent_pipeline_cache)}?;
+        let cleanup_device = device.clone();
+        let cleanup_pipeline_cache = pipeline_cache.handle();
+        cleanup.push(move ||unsafe{cleanup_device.destroy_pipeline_cache(cleanup_pipeline_cache, None)});
+        let bindless = match &bindless_support{
+            Some(support)=>{
+                let bindless = unsafe{BindlessSet::new(&device, support)}?;
+                let cleanup_device = device.clone();
+                let (cleanup_pool,cleanup_layout) = (bindless.pool(),bindless.layout());
+                cleanup.push(move ||unsafe{
+                    cleanup_device.destroy_descriptor_pool(cleanup_pool, None);
+                    cleanup_device.destroy_descriptor_set_layout(cleanup_layout, None);
+                });
+                Some(bindless)
+            }
+            None=>None,
+        };
+        let frames = unsafe{frame::create_frame_resources(&device, graphics_queue_family, config.frames_in_flight.max(1))}?;
+        debug!("Created resources for {} frames in flight.",frames.len());
+        let images_in_flight = vec!(Fence::null();color_images.len());
+        cleanup.release();
+        let mut renderer = Self{
+            config , entry , instance , physical_device , device , queues , target , color_format , extent , color_images ,
+            depth_image_format , sample_count , depth_image , pipeline_cache , frame_graph : CompiledGraph::empty() , paused : false,
+            frames , current_frame : 0 , images_in_flight , clear_color : [0.0,0.0,0.0,1.0] , uploader : Uploader::new() , compute : ComputeDispatcher::new() , descriptor_allocator : DescriptorAllocator::new() , descriptor_layouts : DescriptorLayoutCache::new() , bindless , dynamic_rendering , deletion_queue : DeletionQueue::new() , allocator,
+        };
+        //Dropping the renderer destroys everything created so far when compiling the default graph fails.
+        let frame_graph = renderer.default_frame_graph();
+        renderer.set_frame_graph(frame_graph)?;
+        info!("Compiled the default frame graph.");
+        return Ok(renderer);
+    }
+    ///Recreates the swapchain, passing the old swapchain, and rebuilds the image views, depth buffer and the frame graph's targets.
+    ///The default frame graph is rebuilt when the color format changed, custom graphs keep their transient images.
+    ///Headless renderers recreate their offscreen color image instead.
+    ///The width and height are the size of the window, they are only used when the surface doesn't dictate the swapchain extent.
+    ///Rendering is paused while the extent is zero, for example while the window is minimized.
+    pub fn recreate_swapchain(&mut self , width : u32 , height : u32)->Result<(),RendererError>{
+        let window_extent = Extent2D{width , height};
+        let extent = match &mut self.target{
+            Target::Swapchain(target)=>{
+                target.window_extent = window_extent;
+                let capabilities = unsafe{target.surface_loader.get_physical_device_surface_capabilities(self.physical_device, target.surface)}.map_err(|e|{
+                    error!("Failed to get surface capabilities, {}.",e);
+                    RendererError::CreateSwapchain(e)
+                })?;
+                get_surface_extent(&capabilities, window_extent)
+            }
+            Target::Offscreen=>window_extent,
+        };
+        if extent.width == 0 || extent.height == 0{
+            if !self.paused{info!("The render target has a zero extent, pausing rendering.")}
+            self.paused = true;
+            return Ok(());
+        }
+        debug!("Recreating render targets with extent {}x{}.",extent.width,extent.height);
+        unsafe{
+            self.device.device_wait_idle().map_err(|e|{error!("Failed to wait for the device to become idle, {}.",e);RendererError::WaitIdle(e)})?;
+            self.destroy_render_targets();
+            let color_format = match &mut self.target{
+                Target::Swapchain(target)=>{
+                    let old_swapchain = target.swapchain;
+                    let queue_families = [self.queues.graphics().family_index(),target.presentation_queue_family];
+                    target.surface_format = get_surface_format(&target.surface_loader, &target.surface, &self.physical_device, &self.config.surface_format)?;
+                    let (swapchain,extent,usage) = create_swapchain(&self.physical_device, &target.swapchain_loader, &target.surface_loader, &target.surface, queue_families, &target.surface_format, &self.config, window_extent, old_swapchain)?;
+                    target.swapchain_loader.destroy_swapchain(old_swapchain, None);
+                    target.swapchain = swapchain;
+                    target.suboptimal = false;
+                    self.extent = extent;
+                    let swapchain_images = target.swapchain_loader.get_swapchain_images(swapchain).map_err(|e|{
+                        error!("Failed to acquire swapchain images, {}.",e);
+                        RendererError::CreateSwapchain(e)
+                    })?;
+                    self.color_images = create_swapchain_images(&self.device, &swapchain_images, &target.surface_format.format, &extent, usage)?;
+                    target.surface_format.format
+                }
+                Target::Offscreen=>{
+                    self.color_images = vec!(create_offscreen_color_image(&self.device, &mut self.allocator, &self.color_format, &extent)?);
+                    self.extent = extent;
+                    self.color_format
+                }
+            };
+            let format_changed = color_format != self.color_format;
+            if format_changed{
+                debug!("The color format changed from {:?} to {:?}.",self.color_format,color_format);
+                self.color_format = color_format;
+            }
+            let (_,depth_image_tiling) = get_depth_image_format_and_tiling(&self.instance, &self.physical_device, &Preference::Require(self.depth_image_format))?;
+            self.depth_image = create_depth_image(&self.device, &mut self.allocator, &self.depth_image_format, &depth_image_tiling, &self.extent, self.sample_count)?;
+            if format_changed && self.frame_graph.is_default(){
+                //The multisampled color image of the default graph has the old format, so the graph is rebuilt for the new one.
+                self.frame_graph = self.compile_frame_graph(self.default_frame_graph(), None)?;
+            }else{
+                self.frame_graph.create_targets(&self.device, &mut self.allocator, &self.color_images, &self.depth_image, self.extent, self.dynamic_rendering.is_some())?;
+            }
+        }
+        self.reset_images_in_flight();
+        if self.paused{info!("Resuming rendering.")}
+        self.paused = false;
+        return Ok(());
+    }
+    ///Destroys everything sized after the render target, except the swapchain itself.
+    ///Destroyed handles are reset so the renderer can still be dropped safely when recreating them fails.
+    unsafe fn destroy_render_targets(&mut self){
+        self.frame_graph.destroy_targets(&self.device, &mut self.allocator);
+        self.depth_image.destroy(&self.device, &mut self.allocator);
+        //Swapchain images only destroy their views, the images belong to the swapchain.
+        for mut image in self.color_images.drain(..){
+            image.destroy(&self.device, &mut self.allocator);
         }
     }
+    ///The allocator that backs the renderer's images and buffers, resources created with it must be freed before the renderer is dropped.
+    pub fn allocator(&mut self)->&mut MemoryAllocator{
+        return &mut self.allocator;
+    }
+    ///Usage and fragmentation of the device memory handed out by the allocator.
+    pub fn memory_statistics(&self)->AllocatorStatistics{
+        return self.allocator.statistics();
+    }
+    ///Whether rendering is paused because the render target has a zero extent.
+    pub fn is_paused(&self)->bool{
+        return self.paused;
+    }
+    ///Acquires the index of the next color image to draw to, `semaphore` and `fence` are signaled once it can be used, either may be null.
+    ///The swapchain is recreated when it is out of date, `None` is returned in that case and while rendering is paused, nothing is signaled then.
+    ///Headless renderers always return their single offscreen image and signal through an empty submission on the graphics queue.
+    pub fn acquire_next_image(&mut self , semaphore : Semaphore , fence : Fence)->Result<Option<u32>,RendererError>{
+        if self.paused{
+            if let Target::Swapchain(target) = &self.target{
+                let window_extent = target.window_extent;
+                self.recreate_swapchain(window_extent.width, window_extent.height)?;
+            }
+            if self.paused{return Ok(None)}
+        }
+        match &mut self.target{
+            Target::Swapchain(target)=>{
+                match unsafe{target.swapchain_loader.acquire_next_image(target.swapchain, u64::MAX, semaphore, fence)}{
+                    Ok((image_index,suboptimal))=>{
+                        if suboptimal{
+                            debug!("The swapchain is suboptimal, it will be recreated after presenting.");
+                            target.suboptimal = true;
+                        }
+                        return Ok(Some(image_index));
+                    }
+                    Err(ash::vk::Result::ERROR_OUT_OF_DATE_KHR)=>{
+                        debug!("The swapchain is out of date, recreating it.");
+                        let window_extent = target.window_extent;
+                        self.recreate_swapchain(window_extent.width, window_extent.height)?;
+                        return Ok(None);
+                    }
+                    Err(e)=>{
+                        error!("Failed to acquire a swapchain image, {}.",e);
+                        return Err(RendererError::AcquireImage(e));
+                    }
+                }
+            }
+            Target::Offscreen=>{
+                let signal_semaphores = [semaphore];
+                let submit_info = SubmitInfo{
+                    s_type : StructureType::SUBMIT_INFO,
+                    p_next : std::ptr::null(),
+                    wait_semaphore_count : 0,
+                    p_wait_semaphores : std::ptr::null(),
+                    p_wait_dst_stage_mask : std::ptr::null(),
+                    command_buffer_count : 0,
+                    p_command_buffers : std::ptr::null(),
+                    signal_semaphore_count : if semaphore == Semaphore::null(){0}else{1},
+                    p_signal_semaphores : signal_semaphores.as_ptr(),
+                };
+                unsafe{self.device.queue_submit(self.queues.graphics().handle(), &[submit_info], fence)}.map_err(|e|{
+                    error!("Failed to signal the offscreen image acquisition, {}.",e);
+                    RendererError::AcquireImage(e)
+                })?;
+                return Ok(Some(0));
+            }
+        }
+    }
+    ///Presents a color image once the `wait_semaphores` are signaled.
+    ///The swapchain is recreated when it is out of date or suboptimal.
+    ///Headless renderers have nothing to present, the semaphores are waited on by an empty submission on the graphics queue so they can be reused.
+    pub fn present(&mut self , image_index : u32 , wait_semaphores : &[Semaphore])->Result<(),RendererError>{
+        match &mut self.target{
+            Target::Swapchain(target)=>{
+                let swapchains = [target.swapchain];
+                let image_indices = [image_index];
+                let present_info = PresentInfoKHR{
+                    s_type : StructureType::PRESENT_INFO_KHR,
+                    p_next : std::ptr::null(),
+                    wait_semaphore_count : wait_semaphores.len() as u32,
+                    p_wait_semaphores : wait_semaphores.as_ptr(),
+                    swapchain_count : swapchains.len() as u32,
+                    p_swapchains : swapchains.as_ptr(),
+                    p_image_indices : image_indices.as_ptr(),
+                    p_results : std::ptr::null_mut(),
+                };
+                let recreate = match unsafe{target.swapchain_loader.queue_present(target.presentation_queue, &present_info)}{
+                    Ok(suboptimal)=>suboptimal || target.suboptimal,
+                    Err(ash::vk::Result::ERROR_OUT_OF_DATE_KHR)=>true,
+                    Err(e)=>{
+                        error!("Failed to present a swapchain image, {}.",e);
+                        return Err(RendererError::Present(e));
+                    }
+                };
+                if recreate{
+                    debug!("The swapchain is out of date or suboptimal, recreating it.");
+                    let window_extent = target.window_extent;
+                    self.recreate_swapchain(window_extent.width, window_extent.height)?;
+                }
+            }
+            Target::Offscreen=>{
+                if wait_semaphores.is_empty(){return Ok(())}
+                let wait_stages = vec!(PipelineStageFlags::ALL_COMMANDS;wait_semaphores.len());
+                let submit_info = SubmitInfo{
+                    s_type : StructureType::SUBMIT_INFO,
+                    p_next : std::ptr::null(),
+                    wait_semaphore_count : wait_semaphores.len() as u32,
+                    p_wait_semaphores : wait_semaphores.as_ptr(),
+                    p_wait_dst_stage_mask : wait_stages.as_ptr(),
+                    command_buffer_count : 0,
+                    p_command_buffers : std::ptr::null(),
+                    signal_semaphore_count : 0,
+                    p_signal_semaphores : std::ptr::null(),
+                };
+                unsafe{self.device.queue_submit(self.queues.graphics().handle(), &[submit_info], Fence::null())}.map_err(|e|{
+                    error!("Failed to submit the offscreen presentation, {}.",e);
+                    RendererError::Present(e)
+                })?;
+            }
+        }
+        return Ok(());
+    }
+    ///The configuration the renderer was created with.
+    pub fn config(&self)->&RendererConfig{
+        return &self.config;
+    }
+    ///The loaded Vulkan library.
+    pub fn entry(&self)->&Entry{
+        return &self.entry;
+    }
+    ///The Vulkan instance.
+    pub fn instance(&self)->&Instance{
+        return &self.instance;
+    }
+    ///The GPU selected for rendering.
+    pub fn physical_device(&self)->PhysicalDevice{
+        return self.physical_device;
+    }
+    ///The logical device, all resources are created from this device.
+    pub fn device(&self)->&Device{
+        return &self.device;
+    }
+    ///Whether the renderer draws to offscreen images instead of a window.
+    pub fn is_headless(&self)->bool{
+        return matches!(self.target,Target::Offscreen);
+    }
+    ///The surface of the window the renderer draws to, `None` for headless renderers.
+    pub fn surface(&self)->Option<SurfaceKHR>{
+        match &self.target{
+            Target::Swapchain(swapchain)=>return Some(swapchain.surface),
+            Target::Offscreen=>return None,
+        }
+    }
+    ///The current swapchain, `None` for headless renderers.
+    pub fn swapchain(&self)->Option<SwapchainKHR>{
+        match &self.target{
+            Target::Swapchain(swapchain)=>return Some(swapchain.swapchain),
+            Target::Offscreen=>return None,
+        }
+    }
+    ///The format and color space of the swapchain images, `None` for headless renderers.
+    pub fn surface_format(&self)->Option<SurfaceFormatKHR>{
+        match &self.target{
+            Target::Swapchain(swapchain)=>return Some(swapchain.surface_format),
+            Target::Offscreen=>return None,
+        }
+    }
+    ///The format of the color images.
+    pub fn color_format(&self)->Format{
+        return self.color_format;
+    }
+    ///The size of the color and depth images in pixels.
+    pub fn extent(&self)->Extent2D{
+        return self.extent;
+    }
+    ///The images drawn to, the swapchain images or a single offscreen image for headless renderers.
+    pub fn color_images(&self)->&[Image]{
+        return &self.color_images;
+    }
+    ///The format of the depth buffer.
+    pub fn depth_format(&self)->Format{
+        return self.depth_image_format;
+    }
+    ///The sample count of the depth buffer and of the color image the default frame graph resolves into the backbuffer.
+    pub fn sample_count(&self)->SampleCountFlags{
+        return self.sample_count;
+    }
+    ///The sample counts `set_sample_count` accepts, from the framebuffer limits and the image format properties of the color and depth formats.
+    pub fn supported_sample_counts(&self)->Result<SampleCountFlags,RendererError>{
+        let (_,depth_tiling) = unsafe{get_depth_image_format_and_tiling(&self.instance, &self.physical_device, &Preference::Require(self.depth_image_format))}?;
+        return unsafe{get_supported_sample_counts(&self.instance, &self.physical_device, self.color_format, self.depth_image_format, depth_tiling)};
+    }
+    ///Changes the sample count, recreating the depth buffer and the default frame graph once the device is idle.
+    ///Fails without changing anything while a custom frame graph is set, set the default graph first. Pipelines must be rebuilt for the new targets.
+    pub fn set_sample_count(&mut self , sample_count : SampleCountFlags)->Result<(),RendererError>{
+        let (_,depth_image_tiling) = unsafe{get_depth_image_format_and_tiling(&self.instance, &self.physical_device, &Preference::Require(self.depth_image_format))}?;
+        let sample_count = unsafe{get_sample_count(&self.instance, &self.physical_device, &Preference::Require(sample_count), self.color_format, self.depth_image_format, depth_image_tiling)}?;
+        if sample_count == self.sample_count{return Ok(())}
+        if !self.frame_graph.is_default(){
+            error!("The sample count can't change while a custom frame graph is set.");
+            return Err(RendererError::CustomFrameGraph);
+        }
+        debug!("Changing the sample count from {:?} to {:?}.",self.sample_count,sample_count);
+        unsafe{
+            //The new depth buffer and graph are built first, so a failure leaves the current ones in place.
+            let mut depth_image = create_depth_image(&self.device, &mut self.allocator, &self.depth_image_format, &depth_image_tiling, &self.extent, sample_count)?;
+            let previous_sample_count = std::mem::replace(&mut self.sample_count, sample_count);
+            let frame_graph = self.default_frame_graph();
+            let compiled = match self.compile_frame_graph(frame_graph, Some(&depth_image)){
+                Ok(compiled)=>compiled,
+                Err(e)=>{
+                    self.sample_count = previous_sample_count;
+                    depth_image.destroy(&self.device, &mut self.allocator);
+                    return Err(e);
+                }
+            };
+            //The graph's framebuffers use the depth buffer, so they are destroyed first.
+            let mut previous_graph = std::mem::replace(&mut self.frame_graph, compiled);
+            previous_graph.destroy_targets(&self.device, &mut self.allocator);
+            let mut previous_depth_image = std::mem::replace(&mut self.depth_image, depth_image);
+            previous_depth_image.destroy(&self.device, &mut self.allocator);
+        }
+        return Ok(());
+    }
+    ///The depth buffer.
+    pub fn depth_image(&self)->&Image{
+        return &self.depth_image;
+    }
+    ///The backend the frame graph is recorded with, render passes when dynamic rendering was requested but isn't supported.
+    pub fn rendering_backend(&self)->RenderingBackend{
+        if self.dynamic_rendering.is_some(){return RenderingBackend::DynamicRendering}
+        return RenderingBackend::RenderPass;
+    }
+    ///The render pass of the frame graph's external pass, the default graph draws to a color image and the depth buffer.
+    ///Null with dynamic rendering, pipelines then take the attachment formats from the graph instead.
+    pub fn render_pass(&self)->RenderPass{
+        return self.frame_graph.external_target().map_or(RenderPass::null(),|target|target.render_pass);
+    }
+    ///The pipeline cache every pipeline is created with.
+    pub fn pipeline_cache(&self)->ash::vk::PipelineCache{
+        return self.pipeline_cache.handle();
+    }
+    ///Saves the pipeline cache to the project data directory now instead of when the renderer is dropped.
+    pub fn save_pipeline_cache(&self){
+        unsafe{self.pipeline_cache.save(&self.device)};
+    }
+    ///The framebuffers of the frame graph's external pass, one per color image in the same order as `color_images` when it draws to the backbuffer.
+    ///Empty with dynamic rendering.
+    pub fn framebuffers(&self)->&[Framebuffer]{
+        return self.frame_graph.external_framebuffers();
+    }
 }
 impl Drop for Renderer{
     fn drop(&mut self){
         unsafe{
-            debug!("Destroying framebuffers.");
-            for (i,&framebuffer) in self.framebuffers.iter().enumerate(){
-                trace!("Destroying framebuffer {}.",i);
-                self.device.destroy_framebuffer(framebuffer, None);
+            debug!("Waiting for the device to become idle.");
+            if let Err(e) = self.device.device_wait_idle(){warn!("Failed to wait for the device to become idle, {}.",e)}
+            debug!("Destroying upload resources.");
+            self.uploader.destroy(&self.device, &mut self.allocator);
+            debug!("Destroying compute dispatch resources.");
+            self.compute.destroy(&self.device);
+            debug!("Destroying deferred resources.");
+            self.deletion_queue.destroy(&self.device, &mut self.allocator);
+            debug!("Destroying frame resources.");
+            for frame in self.frames.iter(){
+                frame.destroy(&self.device);
             }
-            debug!("Destroying render pass.");
-            self.device.destroy_render_pass(self.render_pass, None);
+            debug!("Destroying descriptor pools and set layouts.");
+            self.descriptor_allocator.destroy(&self.device);
+            self.descriptor_layouts.destroy(&self.device);
+            if let Some(bindless) = &self.bindless{bindless.destroy(&self.device)}
+            debug!("Destroying frame graph render passes, framebuffers and transient images.");
+            self.frame_graph.destroy_targets(&self.device, &mut self.allocator);
+            debug!("Saving and destroying pipeline cache.");
+            self.pipeline_cache.save(&self.device);
+            self.pipeline_cache.destroy(&self.device);
             debug!("Destroying depth image.");
-            trace!("Destroying depth image view.");
-            self.device.destroy_image_view(self.depth_image_view, None);
-            trace!("Freeing depth image memory.");
-            self.device.free_memory(self.depth_image_memory, None);
-            trace!("Destroying depth image.");
-            self.device.destroy_image(self.depth_image, None);
-            debug!("Destroying Swapchain.");
-            for (i,&image_view) in self.swapchain_image_views.iter().enumerate(){
-                trace!("Destroying swapchain image view {}.",i);
-                self.device.destroy_image_view(image_view, None);
+            self.depth_image.destroy(&self.device, &mut self.allocator);
+            match &self.target{
+                Target::Swapchain(swapchain)=>{
+                    debug!("Destroying Swapchain.");
+                    for (i,image) in self.color_images.iter_mut().enumerate(){
+                        trace!("Destroying swapchain image view {}.",i);
+                        image.destroy(&self.device, &mut self.allocator);
+                    }
+                    swapchain.swapchain_loader.destroy_swapchain(swapchain.swapchain, None);
+                }
+                Target::Offscreen=>{
+                    debug!("Destroying offscreen color image.");
+                    for image in self.color_images.iter_mut(){image.destroy(&self.device, &mut self.allocator)}
+                }
             }
-            self.swapchain_loader.destroy_swapchain(self.swapchain, None);
+            debug!("Destroying memory allocator.");
+            self.allocator.destroy();
             debug!("Destroying Device.");
             self.device.destroy_device(None);
-            debug!("Destroying Surface.");
-            self.surface_loader.destroy_surface(self.surface, None);
+            if let Target::Swapchain(swapchain) = &self.target{
+                debug!("Destroying Surface.");
+                swapchain.surface_loader.destroy_surface(swapchain.surface, None);
+            }
             debug!("Destroying Instance.");
             self.instance.destroy_instance(None);
         }
     }
 }
-unsafe fn create_instance(entry : &Entry , window : &Window) -> Result<Instance,InstanceError>{
-    let api_version = match entry.try_enumerate_instance_version().unwrap(){Some(v)=>{v}None=>{ash::vk::make_version(1, 0, 0)}};
+///Destroys the Vulkan objects created so far, in reverse order, when renderer creation is aborted.
+struct CleanupStack{
+    actions : Vec<Box<dyn FnOnce()>>,
+}
+impl CleanupStack{
+    fn new()->Self{
+        return Self{actions : vec!()};
+    }
+    fn push<F : FnOnce() + 'static>(&mut self , action : F){
+        self.actions.push(Box::new(action));
+    }
+    ///Keeps the created objects alive, called once creation has succeeded.
+    fn release(mut self){
+        self.actions.clear();
+    }
+}
+impl Drop for CleanupStack{
+    fn drop(&mut self){
+        if !self.actions.is_empty(){debug!("Cleaning up partially created renderer.")}
+        while let Some(action) = self.actions.pop(){
+            action();
+        }
+    }
+}
+unsafe fn create_instance(entry : &Entry , instance_extensions : &[&CStr]) -> Result<Instance,InstanceError>{
+    let api_version = match entry.try_enumerate_instance_version().map_err(InstanceError::VkError)?{Some(v)=>{v}None=>{ash::vk::make_version(1, 0, 0)}};
     let name = CString::new("gpengine").unwrap();
     let app_info = ApplicationInfo{
         s_type : StructureType::APPLICATION_INFO,
@@ -121,8 +711,7 @@ unsafe fn create_instance(entry : &Entry , window : &Window) -> Result<Instance,
         p_engine_name : name.as_ptr(),
         p_application_name : name.as_ptr(),
     };
-    let window_extensions = ash_window::enumerate_required_extensions(window).unwrap_or_else(|e|{error!("Failed to acquire Vulkan surface extensions, {}.",e);panic!("Failed to get surface extensions.")});
-    let extensions = window_extensions.iter().map(|v|v.as_ptr()).collect::<Vec<_>>();
+    let extensions = instance_extensions.iter().map(|v|v.as_ptr()).collect::<Vec<_>>();
     let mut validation = false;
     for arg in std::env::args(){
         if arg == "--vkdebug"{validation = true; warn!("Vulkan validation is enabled, performance is degraded significantly!")}
@@ -145,86 +734,75 @@ unsafe fn create_instance(entry : &Entry , window : &Window) -> Result<Instance,
     };
     return entry.create_instance(&instance_create_info, None);
 }
-unsafe fn get_physical_device(instance : &Instance , surface_loader : &Surface , surface : &SurfaceKHR)->(PhysicalDevice,String){
-    let supported_gpus = get_supported_physical_devices(instance, surface_loader, surface);
-    let mut prefered_gpu = None;
-    for (physical_device,name) in supported_gpus.iter(){
-        let device_properties = instance.get_physical_device_properties(*physical_device);
-        if prefered_gpu.is_none(){prefered_gpu = Some((*physical_device,name.clone().to_string()))}
-        else if device_properties.device_type == PhysicalDeviceType::DISCRETE_GPU{return (*physical_device,name.clone().to_string())}
-    }
-    return prefered_gpu.unwrap_or_else(||{error!("No supported GPU's found.");panic!("No supported GPU's found")});
-}
-unsafe fn get_supported_physical_devices(instance : &Instance , surface_loader : &Surface , surface : &SurfaceKHR)->Vec<(PhysicalDevice,String)>{
-    let physical_devices = instance.enumerate_physical_devices().unwrap_or_else(|e|{error!("Failed to get supported devices, {}.",e);panic!("Failed to get supported GPU's")});
-    let mut supported_devices = vec!();
-    for &physical_device in physical_devices.iter(){
-        let device_properties = instance.get_physical_device_properties(physical_device);
-        let gpu_name = CStr::from_ptr(device_properties.device_name.as_ptr()).to_str().to_owned().unwrap();
-        trace!("Found GPU :{} of type:{:?}.",gpu_name,device_properties.device_type);
-        let device_queue_family_properties = instance.get_physical_device_queue_family_properties(physical_device);
-        let mut supports_graphics = false;
-        let mut supports_compute = false;
-        let mut supports_presentation = false;
-        for (i,&queue_family) in device_queue_family_properties.iter().enumerate(){
-            supports_graphics = supports_graphics || queue_family.queue_flags.contains(QueueFlags::GRAPHICS);
-            supports_compute = supports_compute || queue_family.queue_flags.contains(QueueFlags::COMPUTE);
-            supports_presentation = supports_presentation || surface_loader.get_physical_device_surface_support(physical_device, i as u32, *surface).unwrap_or_else(|e|{error!("Failed to check GPU surface support, {}.",e);panic!("Failed to check GPU surface support.")});
-        }
-        if supports_graphics && supports_compute && supports_presentation {
-            trace!("GPU is compatible :{}.",gpu_name);
-            supported_devices.push((physical_device,gpu_name.to_string()));
-        }
-    }
-    return supported_devices;
-}
-fn get_graphics_queue_family(queue_family_properties : &Vec<QueueFamilyProperties>)->u32{
+fn get_graphics_queue_family(queue_family_properties : &[QueueFamilyProperties])->Result<u32,RendererError>{
     for (i,queue_family) in queue_family_properties.iter().enumerate(){
-        if queue_family.queue_flags.contains(QueueFlags::GRAPHICS){return i as u32}
+        if queue_family.queue_flags.contains(QueueFlags::GRAPHICS){return Ok(i as u32)}
     }
     error!("No supported graphics queue found.");
-    panic!("No supported graphics queue found.");
+    return Err(RendererError::NoGraphicsQueueFamily);
 }
-fn get_compute_queue_family(queue_family_properties : &Vec<QueueFamilyProperties>)->u32{
+fn get_compute_queue_family(queue_family_properties : &[QueueFamilyProperties])->Result<u32,RendererError>{
     let mut fallback_family = None;
     for (i,queue_family) in queue_family_properties.iter().enumerate(){
-        if queue_family.queue_flags.contains(QueueFlags::COMPUTE) && !queue_family.queue_flags.contains(QueueFlags::GRAPHICS){return i as u32}
+        if queue_family.queue_flags.contains(QueueFlags::COMPUTE) && !queue_family.queue_flags.contains(QueueFlags::GRAPHICS){return Ok(i as u32)}
         else if queue_family.queue_flags.contains(QueueFlags::COMPUTE) && fallback_family.is_none(){fallback_family = Some(i as u32)}
     }
-    return fallback_family.unwrap_or_else(||{
+    return fallback_family.ok_or_else(||{
         error!("No queue family that supports compute operations found");
-        panic!();
+        RendererError::NoComputeQueueFamily
     });
 }
 ///Gets the asynchronous transfer queue family.
 ///Not all GPU's have a dedicated transfer queue family.
-fn get_dma_queue_family(queue_family_properties : &Vec<QueueFamilyProperties>)->Option<u32>{
+fn get_dma_queue_family(queue_family_properties : &[QueueFamilyProperties])->Option<u32>{
     let dma_queue_family = None;
     for (i,queue_family) in queue_family_properties.iter().enumerate(){
         if queue_family.queue_flags.contains(QueueFlags::TRANSFER) && !queue_family.queue_flags.contains(QueueFlags::GRAPHICS) && !queue_family.queue_flags.contains(QueueFlags::COMPUTE){return Some(i as u32)}
     }
     return dma_queue_family;
 }
-unsafe fn check_queue_family_presentation_support(surface_loader : &Surface , surface : &SurfaceKHR , physical_device : &PhysicalDevice , index : u32)->bool{
-    return surface_loader.get_physical_device_surface_support(*physical_device, index, *surface).unwrap_or_else(|e|{
+unsafe fn check_queue_family_presentation_support(surface_loader : &Surface , surface : &SurfaceKHR , physical_device : &PhysicalDevice , index : u32)->Result<bool,RendererError>{
+    return surface_loader.get_physical_device_surface_support(*physical_device, index, *surface).map_err(|e|{
         error!("Failed to check device presentation support, {}.",e);
-        panic!();
+        RendererError::PhysicalDevice(e)
     });
 }
-unsafe fn create_device(instance : &Instance , physical_device : &PhysicalDevice , surface_loader : &Surface , surface : &SurfaceKHR)->Device{
-    let device_features = PhysicalDeviceFeatures{
-        ..Default::default()
-    };
+///Creates the logical device with the required features and extensions of the config.
+///The swapchain extension is only enabled when there is a surface to present to.
+///Descriptor indexing and dynamic rendering are enabled through the feature chain when they are used and supported.
+unsafe fn create_device(instance : &Instance , physical_device : &PhysicalDevice , surface : Option<(&Surface,&SurfaceKHR)> , config : &RendererConfig , bindless : Option<&BindlessSupport> , dynamic_rendering : Option<&DynamicRenderingSupport>)->Result<Device,RendererError>{
+    let device_features = config.required_features;
     let queue_family_properties = instance.get_physical_device_queue_family_properties(*physical_device);
-    let graphics_queue_family = get_graphics_queue_family(&queue_family_properties);
-    let compute_queue_family = get_compute_queue_family(&queue_family_properties);
+    let graphics_queue_family = get_graphics_queue_family(&queue_family_properties)?;
+    let compute_queue_family = get_compute_queue_family(&queue_family_properties)?;
     let dma_queue_family = get_dma_queue_family(&queue_family_properties);
-    if !check_queue_family_presentation_support(surface_loader, surface, physical_device, graphics_queue_family) &&
-    !check_queue_family_presentation_support(surface_loader, surface, physical_device, compute_queue_family){
-        error!("Nor the graphics or the compute family support presentation capabilities.");
-        panic!();
+    let mut device_extensions = config.required_extensions.iter().map(|e|e.as_ptr()).collect::<Vec<_>>();
+    if let Some((surface_loader,surface)) = surface{
+        if !check_queue_family_presentation_support(surface_loader, surface, physical_device, graphics_queue_family)? &&
+        !check_queue_family_presentation_support(surface_loader, surface, physical_device, compute_queue_family)?{
+            error!("Nor the graphics or the compute family support presentation capabilities.");
+            return Err(RendererError::NoPresentationQueueFamily);
+        }
+        if !config.required_extensions.iter().any(|e|e.as_c_str() == Swapchain::name()){device_extensions.push(Swapchain::name().as_ptr())}
+    }
+    if let Some(bindless) = bindless{
+        if bindless.needs_extension && !config.required_extensions.iter().any(|e|e.as_c_str() == ExtDescriptorIndexingFn::name()){device_extensions.push(ExtDescriptorIndexingFn::name().as_ptr())}
+    }
+    if let Some(dynamic_rendering) = dynamic_rendering{
+        let name = dynamic_rendering::extension_name();
+        if dynamic_rendering.needs_extension && !config.required_extensions.iter().any(|e|e.as_c_str() == name){device_extensions.push(name.as_ptr())}
+    }
+    let mut indexing_features = bindless.map(|bindless|bindless.features);
+    let mut rendering_features = dynamic_rendering.map(|dynamic_rendering|dynamic_rendering.features());
+    let mut features_chain = std::ptr::null_mut::<std::ffi::c_void>();
+    if let Some(features) = &mut rendering_features{
+        features.p_next = features_chain;
+        features_chain = features as *mut PhysicalDeviceDynamicRenderingFeatures as *mut std::ffi::c_void;
+    }
+    if let Some(features) = &mut indexing_features{
+        features.p_next = features_chain;
+        features_chain = features as *mut PhysicalDeviceDescriptorIndexingFeatures as *mut std::ffi::c_void;
     }
-    let device_extensions = [Swapchain::name().as_ptr()];
     let priority = [1.0];
     let mut queue_create_infos = vec!(
         DeviceQueueCreateInfo{
@@ -250,8 +828,7 @@ unsafe fn create_device(instance : &Instance , physical_device : &PhysicalDevice
         );
     }
     debug!("Using queue family {} as the compute queue family.",compute_queue_family);
-    if dma_queue_family.is_some(){
-        let dma_queue_family = dma_queue_family.unwrap();
+    if let Some(dma_queue_family) = dma_queue_family{
         debug!("Using queue family {} as the dma queue family.", dma_queue_family);
         if dma_queue_family != compute_queue_family && dma_queue_family != graphics_queue_family{
             queue_create_infos.push(
@@ -268,7 +845,7 @@ unsafe fn create_device(instance : &Instance , physical_device : &PhysicalDevice
     } else {warn!("GPU does not have a dedicated DMA queue family.")}
     let device_create_info = DeviceCreateInfo{
         s_type : StructureType::DEVICE_CREATE_INFO,
-        p_next : std::ptr::null(),
+        p_next : features_chain as *const std::ffi::c_void,
         flags : DeviceCreateFlags::empty(),
         enabled_layer_count : 0,
         pp_enabled_layer_names : std::ptr::null(),
@@ -278,57 +855,83 @@ unsafe fn create_device(instance : &Instance , physical_device : &PhysicalDevice
         queue_create_info_count : queue_create_infos.len() as u32,
         p_queue_create_infos : queue_create_infos.as_ptr(),
     };
-    return instance.create_device(*physical_device, &device_create_info, None).unwrap_or_else(|e|{error!("Failed to create Vulkan device handle, {}.",e);panic!("Failed to create Vulkan device.")});
+    return instance.create_device(*physical_device, &device_create_info, None).map_err(|e|{error!("Failed to create Vulkan device handle, {}.",e);RendererError::CreateDevice(e)});
 }
-unsafe fn get_surface_present_mode(surface_loader : &Surface , surface : &SurfaceKHR , physical_device : &PhysicalDevice) ->PresentModeKHR{
-    let available_present_modes = surface_loader.get_physical_device_surface_present_modes(*physical_device, *surface).unwrap_or_else(|e|{
+unsafe fn get_surface_present_mode(surface_loader : &Surface , surface : &SurfaceKHR , physical_device : &PhysicalDevice , present_mode : &Preference<PresentModeKHR>) ->Result<PresentModeKHR,RendererError>{
+    let available_present_modes = surface_loader.get_physical_device_surface_present_modes(*physical_device, *surface).map_err(|e|{
         error!("Failed to get surface present modes, {}.",e);
-        panic!();
-    });
-    if available_present_modes.contains(&PresentModeKHR::MAILBOX){return PresentModeKHR::MAILBOX}else{return PresentModeKHR::FIFO}
+        RendererError::CreateSwapchain(e)
+    })?;
+    match present_mode.select(|m|available_present_modes.contains(m)){
+        Ok(Some(present_mode))=>return Ok(present_mode),
+        //FIFO is the only present mode every surface is required to support.
+        Ok(None)=>return Ok(PresentModeKHR::FIFO),
+        Err(present_mode)=>{
+            error!("The required present mode {:?} is not supported.",present_mode);
+            return Err(RendererError::RequiredPresentModeUnsupported(present_mode));
+        }
+    }
 }
-unsafe fn get_surface_format(surface_loader : &Surface , surface : &SurfaceKHR , physical_device : &PhysicalDevice) -> SurfaceFormatKHR{
-    let available_surface_formats = surface_loader.get_physical_device_surface_formats(*physical_device, *surface).unwrap_or_else(|e|{
+unsafe fn get_surface_format(surface_loader : &Surface , surface : &SurfaceKHR , physical_device : &PhysicalDevice , surface_format : &Preference<SurfaceFormatKHR>) -> Result<SurfaceFormatKHR,RendererError>{
+    let available_surface_formats = surface_loader.get_physical_device_surface_formats(*physical_device, *surface).map_err(|e|{
         error!("Failed to get surface formats, {}",e);
-        panic!();
-    });
-    let prefered_surface_formats = [
-        SurfaceFormatKHR{format : Format::R8G8B8A8_UNORM, color_space : ColorSpaceKHR::SRGB_NONLINEAR},
-        SurfaceFormatKHR{format : Format::B8G8R8A8_UNORM, color_space : ColorSpaceKHR::SRGB_NONLINEAR},
-    ];
-    for surface_format in prefered_surface_formats.iter(){
-        if available_surface_formats.contains(surface_format){return *surface_format}
-    }
-    return available_surface_formats[0];
+        RendererError::CreateSwapchain(e)
+    })?;
+    match surface_format.select(|f|available_surface_formats.contains(f)){
+        Ok(Some(surface_format))=>return Ok(surface_format),
+        Ok(None)=>{},
+        Err(surface_format)=>{
+            error!("The required surface format {:?} is not supported.",surface_format);
+            return Err(RendererError::RequiredSurfaceFormatUnsupported(surface_format));
+        }
+    }
+    return available_surface_formats.first().copied().ok_or(RendererError::CreateSwapchain(ash::vk::Result::ERROR_FORMAT_NOT_SUPPORTED));
 }
-unsafe fn get_surface_extent(capabilities : &SurfaceCapabilitiesKHR , window : &Window) -> Extent2D{
+///Gets the swapchain extent, the window extent is only used when the surface doesn't dictate it.
+fn get_surface_extent(capabilities : &SurfaceCapabilitiesKHR , window_extent : Extent2D) -> Extent2D{
     if capabilities.current_extent.width != u32::MAX{return capabilities.current_extent} else {
-        let size = window.inner_size();
         return Extent2D{
-            width : size.width,
-            height : size.height,
+            width : window_extent.width.max(capabilities.min_image_extent.width).min(capabilities.max_image_extent.width),
+            height : window_extent.height.max(capabilities.min_image_extent.height).min(capabilities.max_image_extent.height),
         }
     }
 }
-unsafe fn create_swapchain(physical_device : &PhysicalDevice , swapchain_loader : &Swapchain , surface_loader : &Surface , surface : &SurfaceKHR , graphics_queue : u32 , presentation_queue : u32 , window : &Window) -> SwapchainKHR{
+fn get_swapchain_image_count(capabilities : &SurfaceCapabilitiesKHR , image_count : SwapchainImageCount) -> Result<u32,RendererError>{
+    //A maximum image count of 0 means there is no maximum.
+    let max_image_count = if capabilities.max_image_count == 0{u32::MAX}else{capabilities.max_image_count};
+    match image_count{
+        SwapchainImageCount::MinimumPlus(extra)=>return Ok(capabilities.min_image_count.saturating_add(extra).min(max_image_count)),
+        SwapchainImageCount::Prefer(count)=>return Ok(count.max(capabilities.min_image_count).min(max_image_count)),
+        SwapchainImageCount::Require(count)=>{
+            if count >= capabilities.min_image_count && count <= max_image_count{return Ok(count)}
+            error!("The required swapchain image count {} is not supported.",count);
+            return Err(RendererError::RequiredImageCountUnsupported{requested : count , min : capabilities.min_image_count , max : capabilities.max_image_count});
+        }
+    }
+}
+#[allow(clippy::too_many_arguments)]
+unsafe fn create_swapchain(physical_device : &PhysicalDevice , swapchain_loader : &Swapchain , surface_loader : &Surface , surface : &SurfaceKHR , queue_families : [u32;2] , surface_format : &SurfaceFormatKHR , config : &RendererConfig , window_extent : Extent2D , old_swapchain : SwapchainKHR) -> Result<(SwapchainKHR,Extent2D,ImageUsageFlags),RendererError>{
+    let [graphics_queue,presentation_queue] = queue_families;
     let queues = [graphics_queue,presentation_queue];
-    let present_mode = get_surface_present_mode(surface_loader, surface, physical_device);
-    let surface_format = get_surface_format(surface_loader, surface, physical_device);
-    let surface_capabilities = surface_loader.get_physical_device_surface_capabilities(*physical_device, *surface).unwrap_or_else(|e|{
+    let present_mode = get_surface_present_mode(surface_loader, surface, physical_device, &config.present_mode)?;
+    let surface_capabilities = surface_loader.get_physical_device_surface_capabilities(*physical_device, *surface).map_err(|e|{
         error!("Failed to get surface capabilities, {}.",e);
-        panic!();
-    });
-    let surface_extent = get_surface_extent(&surface_capabilities, window);
+        RendererError::CreateSwapchain(e)
+    })?;
+    let surface_extent = get_surface_extent(&surface_capabilities, window_extent);
+    let min_image_count = get_swapchain_image_count(&surface_capabilities, config.swapchain_image_count)?;
+    //Abandoned frames clear their image before presenting it, when the surface allows transfers to its images.
+    let image_usage = ImageUsageFlags::COLOR_ATTACHMENT | (surface_capabilities.supported_usage_flags & ImageUsageFlags::TRANSFER_DST);
     let swapchain_create_info = SwapchainCreateInfoKHR{
         s_type : StructureType::SWAPCHAIN_CREATE_INFO_KHR,
         p_next : std::ptr::null(),
         flags : SwapchainCreateFlagsKHR::empty(),
         surface : *surface,
-        image_usage : ImageUsageFlags::COLOR_ATTACHMENT,
+        image_usage,
         image_array_layers : 1,
         clipped : 1,
         composite_alpha : CompositeAlphaFlagsKHR::OPAQUE,
-        old_swapchain : SwapchainKHR::null(),
+        old_swapchain,
         queue_family_index_count : if graphics_queue == presentation_queue{0}else{2},
         image_sharing_mode : if graphics_queue == presentation_queue{SharingMode::EXCLUSIVE}else{SharingMode::CONCURRENT},
         p_queue_family_indices : if graphics_queue == presentation_queue{std::ptr::null()}else{queues.as_ptr()},
@@ -337,56 +940,68 @@ unsafe fn create_swapchain(physical_device : &PhysicalDevice , swapchain_loader
         image_color_space : surface_format.color_space,
         image_extent : surface_extent,
         pre_transform : surface_capabilities.current_transform,
-        min_image_count : if surface_capabilities.min_image_count + 1 <= surface_capabilities.max_image_count || surface_capabilities.max_image_count == 0{surface_capabilities.min_image_count+1}else{surface_capabilities.max_image_count}
+        min_image_count,
     };
-    return swapchain_loader.create_swapchain(&swapchain_create_info, None).unwrap_or_else(|e|{
+    let swapchain = swapchain_loader.create_swapchain(&swapchain_create_info, None).map_err(|e|{
         error!("Failed to create Swapchain, {}.",e);
-        panic!();
-    });
+        RendererError::CreateSwapchain(e)
+    })?;
+    return Ok((swapchain,surface_extent,image_usage));
 }
-unsafe fn create_swapchain_image_views(device : &Device , images : &Vec<Image> , format : &Format)->Vec<ImageView>{
-    let mut image_views = vec!();
+unsafe fn create_swapchain_images(device : &Device , images : &[ash::vk::Image] , format : &Format , extent : &Extent2D , usage : ImageUsageFlags)->Result<Vec<Image>,RendererError>{
+    let mut swapchain_images = vec!();
     for &image in images.iter(){
-        let image_view_create_info = ImageViewCreateInfo{
-            s_type : StructureType::IMAGE_VIEW_CREATE_INFO,
-            p_next : std::ptr::null(),
-            flags : ImageViewCreateFlags::empty(),
-            image,
-            view_type : ImageViewType::TYPE_2D,
-            format : *format,
-            components : ComponentMapping{ r : ComponentSwizzle::R , g : ComponentSwizzle::G , b : ComponentSwizzle::B , a : ComponentSwizzle::A},
-            subresource_range : ImageSubresourceRange{
-                aspect_mask : ImageAspectFlags::COLOR,
-                base_array_layer : 0,
-                base_mip_level : 0,
-                layer_count : 1,
-                level_count : 1,
-            },
-        };
-        image_views.push(device.create_image_view(&image_view_create_info, None).unwrap_or_else(|e|{
-            error!("Failed to create Swapchain image view, {}.",e);
-            panic!();
-        }))
+        match Image::from_swapchain(device, image, *format, extent.width, extent.height, usage){
+            Ok(image)=>swapchain_images.push(image),
+            Err(e)=>{
+                error!("Failed to create Swapchain image view, {}.",e);
+                for image in swapchain_images.iter(){device.destroy_image_view(image.view(), None)}
+                return Err(RendererError::CreateSwapchain(e));
+            }
+        }
     }
-    return image_views;
+    return Ok(swapchain_images);
 }
-unsafe fn get_depth_image_format_and_tiling(instance : &Instance , physical_device : &PhysicalDevice)->(Format,ImageTiling){
-    let prefered_formats = [
-        Format::D24_UNORM_S8_UINT,
-        Format::D16_UNORM,
-        Format::D16_UNORM_S8_UINT,
-        Format::D32_SFLOAT,
-        Format::D32_SFLOAT_S8_UINT,
-        Format::X8_D24_UNORM_PACK32,
-    ];
-    for &format in prefered_formats.iter(){
-        if instance.get_physical_device_format_properties(*physical_device, format).optimal_tiling_features.contains(FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT){return (format,ImageTiling::OPTIMAL)}
-    }
-    for &format in prefered_formats.iter(){
-        if instance.get_physical_device_format_properties(*physical_device, format).linear_tiling_features.contains(FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT){return (format,ImageTiling::LINEAR)}
-    }
-    error!("No supported depth format found.");
-    panic!();
+unsafe fn get_depth_image_format_and_tiling(instance : &Instance , physical_device : &PhysicalDevice , depth_format : &Preference<Format>)->Result<(Format,ImageTiling),RendererError>{
+    let supports_optimal = |format : &Format|instance.get_physical_device_format_properties(*physical_device, *format).optimal_tiling_features.contains(FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT);
+    let supports_linear = |format : &Format|instance.get_physical_device_format_properties(*physical_device, *format).linear_tiling_features.contains(FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT);
+    if let Ok(Some(format)) = depth_format.select(supports_optimal){return Ok((format,ImageTiling::OPTIMAL))}
+    match depth_format.select(supports_linear){
+        Ok(Some(format))=>return Ok((format,ImageTiling::LINEAR)),
+        Ok(None)=>{
+            error!("No supported depth format found.");
+            return Err(RendererError::NoSupportedDepthFormat);
+        }
+        Err(format)=>{
+            error!("The required depth format {:?} is not supported.",format);
+            return Err(RendererError::RequiredDepthFormatUnsupported(format));
+        }
+    }
+}
+///The sample counts supported by both color and depth attachments, from the framebuffer limits and the image format properties of both formats.
+///Linear tiling only supports a single sample, which the depth format's properties reflect.
+unsafe fn get_supported_sample_counts(instance : &Instance , physical_device : &PhysicalDevice , color_format : Format , depth_format : Format , depth_tiling : ImageTiling)->Result<SampleCountFlags,RendererError>{
+    let limits = instance.get_physical_device_properties(*physical_device).limits;
+    let format_sample_counts = |format : Format , tiling : ImageTiling , usage : ImageUsageFlags|{
+        return instance.get_physical_device_image_format_properties(*physical_device, format, ImageType::TYPE_2D, tiling, usage, ImageCreateFlags::empty()).map(|properties|properties.sample_counts).map_err(|e|{
+            error!("Failed to get the image format properties of {:?}, {}.",format,e);
+            RendererError::ImageFormatProperties(e)
+        });
+    };
+    let color_sample_counts = format_sample_counts(color_format, ImageTiling::OPTIMAL, ImageUsageFlags::COLOR_ATTACHMENT)?;
+    let depth_sample_counts = format_sample_counts(depth_format, depth_tiling, ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT)?;
+    return Ok(limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts & color_sample_counts & depth_sample_counts);
+}
+unsafe fn get_sample_count(instance : &Instance , physical_device : &PhysicalDevice , sample_count : &Preference<SampleCountFlags> , color_format : Format , depth_format : Format , depth_tiling : ImageTiling)->Result<SampleCountFlags,RendererError>{
+    let supported_sample_counts = get_supported_sample_counts(instance, physical_device, color_format, depth_format, depth_tiling)?;
+    match sample_count.select(|c|c.as_raw().count_ones() == 1 && supported_sample_counts.contains(*c)){
+        Ok(Some(sample_count))=>return Ok(sample_count),
+        Ok(None)=>return Ok(SampleCountFlags::TYPE_1),
+        Err(sample_count)=>{
+            error!("The required sample count {:?} is not supported.",sample_count);
+            return Err(RendererError::RequiredSampleCountUnsupported(sample_count));
+        }
+    }
 }
 fn get_memorytype_index(memory_requirements: &MemoryRequirements, memory_properties: &PhysicalDeviceMemoryProperties, flags: MemoryPropertyFlags) -> Option<u32> {
     memory_properties.memory_types[..memory_properties.memory_type_count as _]
@@ -398,157 +1013,29 @@ fn get_memorytype_index(memory_requirements: &MemoryRequirements, memory_propert
         })
         .map(|(index, _memory_type)| index as _)
 }
-unsafe fn create_depth_images_and_view(device : &Device , format : &Format , tiling : &ImageTiling , extent : &Extent2D , instance : &Instance , physical_device : &PhysicalDevice) -> (Image,DeviceMemory,ImageView){
-    let image_create_info = ImageCreateInfo{
-        s_type : StructureType::IMAGE_CREATE_INFO,
-        p_next : std::ptr::null(),
-        flags : ImageCreateFlags::empty(),
-        image_type : ImageType::TYPE_2D,
-        format : *format,
-        mip_levels : 1,
-        array_layers : 1,
-        sharing_mode : SharingMode::EXCLUSIVE,
-        samples : SampleCountFlags::TYPE_1,
-        queue_family_index_count : 0,
-        p_queue_family_indices : std::ptr::null(),
-        usage : ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
-        initial_layout : ImageLayout::UNDEFINED,
-        tiling : *tiling,
-        extent : Extent3D{width : extent.width , height : extent.height , depth : 1},
-    };
-    let depth_image = device.create_image(&image_create_info, None).unwrap_or_else(|e|{
-        error!("Failed to create depth image, {}.",e);
-        panic!();
-    });
-    let memory_requirements = device.get_image_memory_requirements(depth_image);
-    let memory_properties = instance.get_physical_device_memory_properties(*physical_device);
-    let device_local_memory = get_memorytype_index(&memory_requirements, &memory_properties, MemoryPropertyFlags::DEVICE_LOCAL);
-    let memory_allocate_info = MemoryAllocateInfo{
-        s_type : StructureType::MEMORY_ALLOCATE_INFO,
-        p_next : std::ptr::null(),
-        allocation_size : memory_requirements.size,
-        memory_type_index : if device_local_memory.is_some(){device_local_memory.unwrap()}else{get_memorytype_index(&memory_requirements, &memory_properties, MemoryPropertyFlags::empty()).unwrap_or_else(||{
-            error!("No supported memory type index");
-            panic!();
-        })}
-    };
-    let allocation = device.allocate_memory(&memory_allocate_info, None).unwrap_or_else(|e|{
-        error!("Failed to allocate memory for the depth image, {}.",e);
-        panic!();
-    });
-    device.bind_image_memory(depth_image, allocation, 0).unwrap_or_else(|e|{
-        error!("Failed to bind depth image memory, {}.",e);
-        panic!();
-    });
-    let image_view_create_info = ImageViewCreateInfo{
-        s_type : StructureType::IMAGE_VIEW_CREATE_INFO,
-        p_next : std::ptr::null(),
-        flags : ImageViewCreateFlags::empty(),
-        format : *format,
-        image : depth_image,
-        view_type : ImageViewType::TYPE_2D,
-        components : ComponentMapping{ r : ComponentSwizzle::R , g : ComponentSwizzle::G , b : ComponentSwizzle::B , a : ComponentSwizzle::A},
-        subresource_range : ImageSubresourceRange{
-            aspect_mask : ImageAspectFlags::DEPTH,
-            base_array_layer : 0,
-            base_mip_level : 0,
-            layer_count : 1,
-            level_count : 1,
-        },
-    };
-    let depth_image_view = device.create_image_view(&image_view_create_info, None).unwrap_or_else(|e|{
-        error!("Failed to create depth image view, {}.",e);
-        panic!("");
-    });
-    return (depth_image,allocation,depth_image_view);
+unsafe fn create_depth_image(device : &Device , allocator : &mut MemoryAllocator , format : &Format , tiling : &ImageTiling , extent : &Extent2D , samples : SampleCountFlags) -> Result<Image,RendererError>{
+    let description = ImageDescription::new(ImageKind::Image2D{width : extent.width , height : extent.height}, *format, ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT).samples(samples).tiling(*tiling);
+    return Image::new(device, allocator, &description, RendererError::CreateDepthImage);
 }
-unsafe fn create_render_pass(device : &Device  , format : &Format , depth_format : &Format) -> RenderPass{
-    let attachments = [AttachmentDescription{
-        flags : AttachmentDescriptionFlags::empty(),
-        format : *format,
-        samples : SampleCountFlags::TYPE_1,
-        load_op : AttachmentLoadOp::CLEAR,
-        store_op : AttachmentStoreOp::STORE,
-        stencil_load_op : AttachmentLoadOp::DONT_CARE,
-        stencil_store_op : AttachmentStoreOp::DONT_CARE,
-        initial_layout : ImageLayout::UNDEFINED,
-        final_layout : ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-    },
-    AttachmentDescription{
-        flags : AttachmentDescriptionFlags::empty(),
-        format : *depth_format,
-        samples : SampleCountFlags::TYPE_1,
-        load_op : AttachmentLoadOp::CLEAR,
-        store_op : AttachmentStoreOp::STORE,
-        stencil_load_op : AttachmentLoadOp::DONT_CARE,
-        stencil_store_op : AttachmentStoreOp::DONT_CARE,
-        initial_layout : ImageLayout::UNDEFINED,
-        final_layout : ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
-    }];
-    let color_references = [AttachmentReference{
-        attachment : 0,
-        layout : ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-    }];
-    let depth_reference = AttachmentReference{
-        attachment : 1,
-        layout : ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
-    };
-    let subpasses = [SubpassDescription{
-        flags : SubpassDescriptionFlags::empty(),
-        pipeline_bind_point : PipelineBindPoint::GRAPHICS,
-        input_attachment_count : 0,
-        p_input_attachments : std::ptr::null(),
-        color_attachment_count : color_references.len() as u32,
-        p_color_attachments : color_references.as_ptr(),
-        p_resolve_attachments : std::ptr::null(),
-        p_depth_stencil_attachment : &depth_reference,
-        preserve_attachment_count : 0,
-        p_preserve_attachments : std::ptr::null(),
-    }];
-    let subpass_dependencies = [SubpassDependency{
-        src_subpass : 0,
-        dst_subpass : SUBPASS_EXTERNAL,
-        src_stage_mask : PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-        dst_stage_mask : PipelineStageFlags::FRAGMENT_SHADER,
-        src_access_mask :  AccessFlags::COLOR_ATTACHMENT_WRITE,
-        dst_access_mask : AccessFlags::SHADER_READ,
-        dependency_flags : DependencyFlags::empty(),
-    }];
-    let render_pass_create_info = RenderPassCreateInfo{
-        s_type : StructureType::RENDER_PASS_CREATE_INFO,
-        p_next : std::ptr::null(),
-        flags : RenderPassCreateFlags::empty(),
-        attachment_count : attachments.len() as u32,
-        p_attachments : attachments.as_ptr(),
-        subpass_count : subpasses.len() as u32,
-        p_subpasses : subpasses.as_ptr(),
-        dependency_count : subpass_dependencies.len() as u32,
-        p_dependencies : subpass_dependencies.as_ptr(),
-    };
-    return device.create_render_pass(&render_pass_create_info, None).unwrap_or_else(|e|{
-        error!("Failed to create Render Pass, {}.",e);
-        panic!();
+///Gets the color format of the offscreen image, using the formats of the surface format preference.
+unsafe fn get_offscreen_color_format(instance : &Instance , physical_device : &PhysicalDevice , surface_format : &Preference<SurfaceFormatKHR>)->Result<Format,RendererError>{
+    let supports_color_attachment = |format : &Format|instance.get_physical_device_format_properties(*physical_device, *format).optimal_tiling_features.contains(FormatFeatureFlags::COLOR_ATTACHMENT);
+    match surface_format.select(|f|supports_color_attachment(&f.format)){
+        Ok(Some(surface_format))=>return Ok(surface_format.format),
+        Ok(None)=>{},
+        Err(surface_format)=>{
+            error!("The required color format {:?} is not supported.",surface_format.format);
+            return Err(RendererError::RequiredSurfaceFormatUnsupported(surface_format));
+        }
+    }
+    //Every Vulkan implementation supports these formats as color attachments.
+    return [Format::R8G8B8A8_UNORM,Format::B8G8R8A8_UNORM].iter().copied().find(supports_color_attachment).ok_or_else(||{
+        error!("No supported offscreen color format found.");
+        RendererError::NoSupportedColorFormat
     });
 }
-unsafe fn create_framebuffers(device : &Device , render_pass : &RenderPass , extent : &Extent2D , swapchain_image_views : &Vec<ImageView> , depth_image : &ImageView)->Vec<Framebuffer>{
-    let mut framebuffers = vec!();
-    for &swapchain_image_view in swapchain_image_views.iter(){
-        let attachments = [swapchain_image_view,*depth_image];
-        let framebuffer_create_info = FramebufferCreateInfo{
-            s_type : StructureType::FRAMEBUFFER_CREATE_INFO,
-            p_next : std::ptr::null(),
-            flags : FramebufferCreateFlags::empty(),
-            render_pass : *render_pass,
-            width : extent.width,
-            height : extent.height,
-            attachment_count : attachments.len() as u32,
-            p_attachments : attachments.as_ptr(),
-            layers : 1,
-        };
-        framebuffers.push(device.create_framebuffer(&framebuffer_create_info, None).unwrap_or_else(|e|{
-            error!("Failed to create Framebuffer, {}.",e);
-            panic!();
-        }));
-    }
-    return framebuffers;
-}
\ No newline at end of file
+unsafe fn create_offscreen_color_image(device : &Device , allocator : &mut MemoryAllocator , format : &Format , extent : &Extent2D) -> Result<Image,RendererError>{
+    let usage = ImageUsageFlags::COLOR_ATTACHMENT | ImageUsageFlags::SAMPLED | ImageUsageFlags::TRANSFER_SRC | ImageUsageFlags::TRANSFER_DST;
+    let description = ImageDescription::new(ImageKind::Image2D{width : extent.width , height : extent.height}, *format, usage);
+    return Image::new(device, allocator, &description, RendererError::CreateOffscreenImage);
+}
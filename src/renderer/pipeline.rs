@@ -0,0 +1,413 @@
+use std::{collections::BTreeMap, ffi::CString};
+
+use ash::{Device, version::DeviceV1_0, vk::{self, BlendFactor, BlendOp, ColorComponentFlags, CompareOp, CullModeFlags, DynamicState, Format, FrontFace, GraphicsPipelineCreateInfo, ImageAspectFlags, LogicOp, PipelineColorBlendAttachmentState, PipelineColorBlendStateCreateFlags, PipelineColorBlendStateCreateInfo, PipelineCreateFlags, PipelineDepthStencilStateCreateFlags, PipelineDepthStencilStateCreateInfo, PipelineDynamicStateCreateFlags, PipelineDynamicStateCreateInfo, PipelineInputAssemblyStateCreateFlags, PipelineInputAssemblyStateCreateInfo, PipelineMultisampleStateCreateFlags, PipelineMultisampleStateCreateInfo, PipelineRasterizationStateCreateFlags, PipelineRasterizationStateCreateInfo, PipelineVertexInputStateCreateFlags, PipelineVertexInputStateCreateInfo, PipelineViewportStateCreateFlags, PipelineViewportStateCreateInfo, PolygonMode, PrimitiveTopology, RenderPass, SpecializationInfo, SpecializationMapEntry, StencilOpState, StructureType, VertexInputAttributeDescription, VertexInputBindingDescription, VertexInputRate}};
+use bytemuck::Pod;
+use log::{error,info};
+
+use super::{CompiledGraph, GraphPass, PipelineLayout, Renderer, RendererError, ShaderModule, deletion::Garbage, dynamic_rendering::PipelineRenderingCreateInfo, format_aspects, graph::PassTarget};
+
+///How the output of the fragment shader is combined with a color attachment.
+#[derive(Clone, Copy, Debug)]
+pub enum BlendMode{
+    ///The output replaces the attachment.
+    Opaque,
+    ///Blends by the output alpha, for colors that aren't premultiplied.
+    Alpha,
+    ///Blends by the output alpha, for colors that are premultiplied.
+    PremultipliedAlpha,
+    ///Adds the output to the attachment.
+    Additive,
+    ///Any other blend state.
+    Custom(PipelineColorBlendAttachmentState),
+}
+impl BlendMode{
+    fn attachment_state(&self)->PipelineColorBlendAttachmentState{
+        let blend = |src_color : BlendFactor , dst_color : BlendFactor|PipelineColorBlendAttachmentState{
+            blend_enable : vk::TRUE,
+            src_color_blend_factor : src_color,
+            dst_color_blend_factor : dst_color,
+            color_blend_op : BlendOp::ADD,
+            src_alpha_blend_factor : BlendFactor::ONE,
+            dst_alpha_blend_factor : dst_color,
+            alpha_blend_op : BlendOp::ADD,
+            color_write_mask : ColorComponentFlags::all(),
+        };
+        match self{
+            BlendMode::Opaque=>return PipelineColorBlendAttachmentState{blend_enable : vk::FALSE , color_write_mask : ColorComponentFlags::all() , ..Default::default()},
+            BlendMode::Alpha=>return blend(BlendFactor::SRC_ALPHA, BlendFactor::ONE_MINUS_SRC_ALPHA),
+            BlendMode::PremultipliedAlpha=>return blend(BlendFactor::ONE, BlendFactor::ONE_MINUS_SRC_ALPHA),
+            BlendMode::Additive=>return blend(BlendFactor::ONE, BlendFactor::ONE),
+            BlendMode::Custom(state)=>return *state,
+        }
+    }
+}
+///A graphics pipeline and its layout.
+pub struct GraphicsPipeline{
+    handle : vk::Pipeline,
+    layout : vk::PipelineLayout,
+    ///The layout generated from the shaders, when the builder wasn't given one.
+    owned_layout : Option<PipelineLayout>,
+}
+impl GraphicsPipeline{
+    pub fn handle(&self)->vk::Pipeline{
+        return self.handle;
+    }
+    pub fn layout(&self)->vk::PipelineLayout{
+        return self.layout;
+    }
+    ///The layout generated from the shaders, `None` when the builder was given a layout.
+    pub fn generated_layout(&self)->Option<&PipelineLayout>{
+        return self.owned_layout.as_ref();
+    }
+}
+///Creates a `GraphicsPipeline` for the renderer's render pass.
+///Viewport and scissor are dynamic state, `Renderer::begin_frame` sets them to the full extent.
+///The defaults draw opaque triangle lists with back face culling and a depth test against the depth attachment,
+///the vertex layout is taken from the vertex shader and the pipeline layout from every shader.
+///```no_run
+///# use gpengine::renderer::{GraphicsPipelineBuilder, Renderer};
+///# fn example(renderer : &mut Renderer)->Result<(),gpengine::RendererError>{
+///let vertex = renderer.load_shader("shaders/mesh.vert.spv")?;
+///let fragment = renderer.load_shader("shaders/mesh.frag.spv")?;
+///let pipeline = GraphicsPipelineBuilder::new(&vertex, &fragment).build(renderer)?;
+///# Ok(())}
+///```
+#[derive(Clone)]
+pub struct GraphicsPipelineBuilder<'a>{
+    shaders : Vec<&'a ShaderModule>,
+    layout : Option<vk::PipelineLayout>,
+    ///The vertex bindings and attributes, `None` to take them from the vertex shader.
+    vertex_input : Option<(Vec<VertexInputBindingDescription>,Vec<VertexInputAttributeDescription>)>,
+    topology : PrimitiveTopology,
+    primitive_restart : bool,
+    polygon_mode : PolygonMode,
+    cull_mode : CullModeFlags,
+    front_face : FrontFace,
+    line_width : f32,
+    depth_test : bool,
+    depth_write : bool,
+    depth_compare_op : CompareOp,
+    blend_modes : Vec<BlendMode>,
+    dynamic_states : Vec<DynamicState>,
+    ///The bytes of each specialization constant by constant id.
+    specialization_constants : BTreeMap<u32,Vec<u8>>,
+    ///The frame graph pass drawn in, `None` for the external pass and `Some(None)` when the chosen pass has no render pass.
+    target : Option<Option<PassTarget>>,
+}
+impl<'a> GraphicsPipelineBuilder<'a>{
+    pub fn new(vertex_shader : &'a ShaderModule , fragment_shader : &'a ShaderModule)->Self{
+        return Self{
+            shaders : vec!(vertex_shader,fragment_shader),
+            layout : None,
+            vertex_input : None,
+            topology : PrimitiveTopology::TRIANGLE_LIST,
+            primitive_restart : false,
+            polygon_mode : PolygonMode::FILL,
+            cull_mode : CullModeFlags::BACK,
+            front_face : FrontFace::COUNTER_CLOCKWISE,
+            line_width : 1.0,
+            depth_test : true,
+            depth_write : true,
+            depth_compare_op : CompareOp::LESS,
+            blend_modes : vec!(BlendMode::Opaque),
+            dynamic_states : vec!(DynamicState::VIEWPORT,DynamicState::SCISSOR),
+            specialization_constants : BTreeMap::new(),
+            target : None,
+        };
+    }
+    ///Adds another stage, such as a geometry shader.
+    pub fn shader(mut self , shader : &'a ShaderModule)->Self{
+        self.shaders.push(shader);
+        return self;
+    }
+    ///Uses an existing pipeline layout instead of generating one from the shaders.
+    pub fn layout(mut self , layout : vk::PipelineLayout)->Self{
+        self.layout = Some(layout);
+        return self;
+    }
+    ///Adds a vertex buffer binding, replacing the layout taken from the vertex shader.
+    pub fn vertex_binding(mut self , binding : u32 , stride : u32 , input_rate : VertexInputRate)->Self{
+        self.vertex_input.get_or_insert_with(Default::default).0.push(VertexInputBindingDescription{binding , stride , input_rate});
+        return self;
+    }
+    ///Adds a vertex attribute read from a binding added with `vertex_binding`.
+    pub fn vertex_attribute(mut self , location : u32 , binding : u32 , format : Format , offset : u32)->Self{
+        self.vertex_input.get_or_insert_with(Default::default).1.push(VertexInputAttributeDescription{location , binding , format , offset});
+        return self;
+    }
+    ///Draws without vertex buffers, for shaders that generate their vertices.
+    pub fn no_vertex_input(mut self)->Self{
+        self.vertex_input = Some((vec!(),vec!()));
+        return self;
+    }
+    pub fn topology(mut self , topology : PrimitiveTopology)->Self{
+        self.topology = topology;
+        return self;
+    }
+    ///Lets the maximum index restart strip and fan topologies.
+    pub fn primitive_restart(mut self , primitive_restart : bool)->Self{
+        self.primitive_restart = primitive_restart;
+        return self;
+    }
+    ///Line and point modes need the `fill_mode_non_solid` feature.
+    pub fn polygon_mode(mut self , polygon_mode : PolygonMode)->Self{
+        self.polygon_mode = polygon_mode;
+        return self;
+    }
+    pub fn cull_mode(mut self , cull_mode : CullModeFlags)->Self{
+        self.cull_mode = cull_mode;
+        return self;
+    }
+    pub fn front_face(mut self , front_face : FrontFace)->Self{
+        self.front_face = front_face;
+        return self;
+    }
+    ///Widths other than 1 need the `wide_lines` feature.
+    pub fn line_width(mut self , line_width : f32)->Self{
+        self.line_width = line_width;
+        return self;
+    }
+    ///Configures the depth test against the depth attachment, fragments pass when `compare_op` is true for their depth and the stored depth.
+    pub fn depth(mut self , test : bool , write : bool , compare_op : CompareOp)->Self{
+        self.depth_test = test;
+        self.depth_write = write;
+        self.depth_compare_op = compare_op;
+        return self;
+    }
+    ///Sets the blend mode of every color attachment.
+    pub fn blend(mut self , blend_mode : BlendMode)->Self{
+        for mode in self.blend_modes.iter_mut(){*mode = blend_mode}
+        return self;
+    }
+    ///Sets the blend mode of one color attachment.
+    pub fn attachment_blend(mut self , attachment : usize , blend_mode : BlendMode)->Self{
+        if self.blend_modes.len() <= attachment{self.blend_modes.resize(attachment + 1, BlendMode::Opaque)}
+        self.blend_modes[attachment] = blend_mode;
+        return self;
+    }
+    ///Adds dynamic state besides the viewport and scissor.
+    pub fn dynamic_state(mut self , state : DynamicState)->Self{
+        if !self.dynamic_states.contains(&state){self.dynamic_states.push(state)}
+        return self;
+    }
+    ///Specializes the constant with the given `constant_id` in every stage that declares it.
+    ///Booleans are specialized with a `u32` of 0 or 1.
+    pub fn specialization_constant<T : Pod>(mut self , id : u32 , value : T)->Self{
+        self.specialization_constants.insert(id, bytemuck::bytes_of(&value).to_vec());
+        return self;
+    }
+    ///The vertex layout taken from the inputs of the vertex shader, tightly packed into binding 0 in location order.
+    fn reflected_vertex_layout(&self)->(Vec<VertexInputBindingDescription>,Vec<VertexInputAttributeDescription>){
+        let inputs = self.shaders.iter().filter(|s|s.reflection().stages().contains(vk::ShaderStageFlags::VERTEX)).flat_map(|s|s.reflection().vertex_inputs.iter());
+        let mut attributes = vec!();
+        let mut offset = 0;
+        for input in inputs{
+            attributes.push(VertexInputAttributeDescription{location : input.location , binding : 0 , format : input.format , offset});
+            offset += format_size(input.format);
+        }
+        if attributes.is_empty(){return (vec!(),attributes)}
+        return (vec!(VertexInputBindingDescription{binding : 0 , stride : offset , input_rate : VertexInputRate::VERTEX}),attributes);
+    }
+    ///Draws in a pass of the compiled frame graph instead of its external pass.
+    ///Every color attachment of the pass gets the blend mode of the first one, later calls to `attachment_blend` override it.
+    pub fn graph_pass(mut self , graph : &CompiledGraph , pass : GraphPass)->Self{
+        let target = graph.pass_target(pass).cloned();
+        if let Some(target) = &target{
+            let blend_mode = self.blend_modes.first().copied().unwrap_or(BlendMode::Opaque);
+            self.blend_modes.resize(target.color_formats.len(), blend_mode);
+        }
+        self.target = Some(target);
+        return self;
+    }
+    ///Creates the pipeline, generating the pipeline layout from the shaders when none was given.
+    pub fn build(self , renderer : &mut Renderer)->Result<GraphicsPipeline,RendererError>{
+        let invalid = |reason : String|{error!("Invalid graphics pipeline, {}.",reason);RendererError::InvalidPipeline(reason)};
+        let target = match &self.target{
+            Some(Some(target))=>target.clone(),
+            Some(None)=>return Err(invalid("the graph pass is culled or has no attachments".to_string())),
+            None=>renderer.frame_graph().external_target().cloned().ok_or_else(||invalid("the frame graph has no external pass".to_string()))?,
+        };
+        if self.blend_modes.len() != target.color_formats.len(){return Err(invalid(format!("{} blend modes were given for {} color attachments",self.blend_modes.len(),target.color_formats.len())))}
+        for shader in self.shaders.iter(){
+            if shader.entry_point().is_none(){return Err(invalid("every shader must have exactly one entry point".to_string()))}
+            for constant in shader.reflection().specialization_constants.iter(){
+                match self.specialization_constants.get(&constant.id){
+                    Some(bytes) if bytes.len() != constant.size as usize=>return Err(invalid(format!("specialization constant {} is {} bytes, not {}",constant.id,constant.size,bytes.len()))),
+                    _=>{},
+                }
+            }
+        }
+        let (vertex_bindings,vertex_attributes) = self.vertex_input.clone().unwrap_or_else(||self.reflected_vertex_layout());
+        let owned_layout = match self.layout{
+            Some(_)=>None,
+            None=>Some(renderer.create_pipeline_layout(&self.shaders)?),
+        };
+        let layout = self.layout.unwrap_or_else(||owned_layout.as_ref().map(|l|l.handle()).unwrap_or_default());
+        let names = self.shaders.iter().map(|s|CString::new(s.entry_point().map(|e|e.name.as_str()).unwrap_or_default()).unwrap_or_default()).collect::<Vec<_>>();
+        //Every stage only gets the constants it declares.
+        let specialization = self.shaders.iter().map(|shader|{
+            let mut data = vec!();
+            let mut entries = vec!();
+            for constant in shader.reflection().specialization_constants.iter(){
+                if let Some(bytes) = self.specialization_constants.get(&constant.id){
+                    entries.push(SpecializationMapEntry{constant_id : constant.id , offset : data.len() as u32 , size : bytes.len()});
+                    data.extend_from_slice(bytes);
+                }
+            }
+            return (entries,data);
+        }).collect::<Vec<_>>();
+        let specialization_infos = specialization.iter().map(|(entries,data)|SpecializationInfo{
+            map_entry_count : entries.len() as u32,
+            p_map_entries : entries.as_ptr(),
+            data_size : data.len(),
+            p_data : data.as_ptr() as *const std::ffi::c_void,
+        }).collect::<Vec<_>>();
+        let stages = self.shaders.iter().enumerate().map(|(i,shader)|{
+            let mut stage = shader.stage_create_info(shader.entry_point().unwrap(), &names[i]);
+            if !specialization[i].0.is_empty(){stage.p_specialization_info = &specialization_infos[i]}
+            return stage;
+        }).collect::<Vec<_>>();
+        let vertex_input_state = PipelineVertexInputStateCreateInfo{
+            s_type : StructureType::PIPELINE_VERTEX_INPUT_STATE_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : PipelineVertexInputStateCreateFlags::empty(),
+            vertex_binding_description_count : vertex_bindings.len() as u32,
+            p_vertex_binding_descriptions : vertex_bindings.as_ptr(),
+            vertex_attribute_description_count : vertex_attributes.len() as u32,
+            p_vertex_attribute_descriptions : vertex_attributes.as_ptr(),
+        };
+        let input_assembly_state = PipelineInputAssemblyStateCreateInfo{
+            s_type : StructureType::PIPELINE_INPUT_ASSEMBLY_STATE_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : PipelineInputAssemblyStateCreateFlags::empty(),
+            topology : self.topology,
+            primitive_restart_enable : self.primitive_restart as u32,
+        };
+        let viewport_state = PipelineViewportStateCreateInfo{
+            s_type : StructureType::PIPELINE_VIEWPORT_STATE_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : PipelineViewportStateCreateFlags::empty(),
+            viewport_count : 1,
+            p_viewports : std::ptr::null(),
+            scissor_count : 1,
+            p_scissors : std::ptr::null(),
+        };
+        let rasterization_state = PipelineRasterizationStateCreateInfo{
+            s_type : StructureType::PIPELINE_RASTERIZATION_STATE_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : PipelineRasterizationStateCreateFlags::empty(),
+            depth_clamp_enable : vk::FALSE,
+            rasterizer_discard_enable : vk::FALSE,
+            polygon_mode : self.polygon_mode,
+            cull_mode : self.cull_mode,
+            front_face : self.front_face,
+            depth_bias_enable : vk::FALSE,
+            depth_bias_constant_factor : 0.0,
+            depth_bias_clamp : 0.0,
+            depth_bias_slope_factor : 0.0,
+            line_width : self.line_width,
+        };
+        let multisample_state = PipelineMultisampleStateCreateInfo{
+            s_type : StructureType::PIPELINE_MULTISAMPLE_STATE_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : PipelineMultisampleStateCreateFlags::empty(),
+            rasterization_samples : target.samples,
+            sample_shading_enable : vk::FALSE,
+            min_sample_shading : 1.0,
+            p_sample_mask : std::ptr::null(),
+            alpha_to_coverage_enable : vk::FALSE,
+            alpha_to_one_enable : vk::FALSE,
+        };
+        let depth_stencil_state = PipelineDepthStencilStateCreateInfo{
+            s_type : StructureType::PIPELINE_DEPTH_STENCIL_STATE_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : PipelineDepthStencilStateCreateFlags::empty(),
+            //Passes without a depth attachment ignore the depth settings.
+            depth_test_enable : (self.depth_test && target.depth_format.is_some()) as u32,
+            depth_write_enable : (self.depth_write && target.depth_format.is_some()) as u32,
+            depth_compare_op : self.depth_compare_op,
+            depth_bounds_test_enable : vk::FALSE,
+            stencil_test_enable : vk::FALSE,
+            front : StencilOpState::default(),
+            back : StencilOpState::default(),
+            min_depth_bounds : 0.0,
+            max_depth_bounds : 1.0,
+        };
+        let blend_attachments = self.blend_modes.iter().map(|mode|mode.attachment_state()).collect::<Vec<_>>();
+        let color_blend_state = PipelineColorBlendStateCreateInfo{
+            s_type : StructureType::PIPELINE_COLOR_BLEND_STATE_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : PipelineColorBlendStateCreateFlags::empty(),
+            logic_op_enable : vk::FALSE,
+            logic_op : LogicOp::COPY,
+            attachment_count : blend_attachments.len() as u32,
+            p_attachments : blend_attachments.as_ptr(),
+            blend_constants : [0.0;4],
+        };
+        let dynamic_state = PipelineDynamicStateCreateInfo{
+            s_type : StructureType::PIPELINE_DYNAMIC_STATE_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : PipelineDynamicStateCreateFlags::empty(),
+            dynamic_state_count : self.dynamic_states.len() as u32,
+            p_dynamic_states : self.dynamic_states.as_ptr(),
+        };
+        //Without a render pass the attachment formats are given to the pipeline directly.
+        let depth_format = target.depth_format.unwrap_or(Format::UNDEFINED);
+        let stencil_format = if format_aspects(depth_format).contains(ImageAspectFlags::STENCIL){depth_format}else{Format::UNDEFINED};
+        let rendering_info = PipelineRenderingCreateInfo::new(&target.color_formats, depth_format, stencil_format);
+        let create_info = GraphicsPipelineCreateInfo{
+            s_type : StructureType::GRAPHICS_PIPELINE_CREATE_INFO,
+            p_next : if target.render_pass == RenderPass::null(){&rendering_info as *const PipelineRenderingCreateInfo as *const std::ffi::c_void}else{std::ptr::null()},
+            flags : PipelineCreateFlags::empty(),
+            stage_count : stages.len() as u32,
+            p_stages : stages.as_ptr(),
+            p_vertex_input_state : &vertex_input_state,
+            p_input_assembly_state : &input_assembly_state,
+            p_tessellation_state : std::ptr::null(),
+            p_viewport_state : &viewport_state,
+            p_rasterization_state : &rasterization_state,
+            p_multisample_state : &multisample_state,
+            p_depth_stencil_state : &depth_stencil_state,
+            p_color_blend_state : &color_blend_state,
+            p_dynamic_state : &dynamic_state,
+            layout,
+            render_pass : target.render_pass,
+            subpass : 0,
+            base_pipeline_handle : vk::Pipeline::null(),
+            base_pipeline_index : -1,
+        };
+        let handle = match unsafe{renderer.device().create_graphics_pipelines(renderer.pipeline_cache(), &[create_info], None)}{
+            Ok(pipelines)=>pipelines[0],
+            Err((_,e))=>{
+                error!("Failed to create graphics pipeline, {}.",e);
+                if let Some(owned_layout) = owned_layout{renderer.destroy_pipeline_layout(owned_layout)}
+                return Err(RendererError::CreatePipeline(e));
+            }
+        };
+        info!("Created graphics pipeline with {} stages and {} vertex attributes.",stages.len(),vertex_attributes.len());
+        return Ok(GraphicsPipeline{handle , layout , owned_layout});
+    }
+}
+///The size of a vertex attribute format in bytes.
+fn format_size(format : Format)->u32{
+    match format{
+        Format::R32_SFLOAT | Format::R32_SINT | Format::R32_UINT=>return 4,
+        Format::R32G32_SFLOAT | Format::R32G32_SINT | Format::R32G32_UINT | Format::R64_SFLOAT=>return 8,
+        Format::R32G32B32_SFLOAT | Format::R32G32B32_SINT | Format::R32G32B32_UINT=>return 12,
+        Format::R32G32B32A32_SFLOAT | Format::R32G32B32A32_SINT | Format::R32G32B32A32_UINT | Format::R64G64_SFLOAT=>return 16,
+        Format::R64G64B64_SFLOAT=>return 24,
+        Format::R64G64B64A64_SFLOAT=>return 32,
+        _=>return 0,
+    }
+}
+///Destroys a pipeline and the layout it owns.
+pub(crate) unsafe fn destroy_pipeline(device : &Device , pipeline : vk::Pipeline , layout : Option<PipelineLayout>){
+    device.destroy_pipeline(pipeline, None);
+    if let Some(layout) = layout{layout.destroy(device)}
+}
+impl Renderer{
+    ///Destroys a graphics pipeline and its generated layout once the GPU is no longer using them.
+    pub fn destroy_graphics_pipeline(&mut self , pipeline : GraphicsPipeline){
+        self.destroy_deferred(Garbage::Pipeline{pipeline : pipeline.handle , layout : pipeline.owned_layout});
+    }
+}
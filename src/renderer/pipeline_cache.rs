@@ -0,0 +1,229 @@
+use std::{convert::TryInto, io::Write, path::{Path, PathBuf}};
+
+use ash::{Device, Instance, version::{DeviceV1_0, InstanceV1_0}, vk::{self, PhysicalDevice, PipelineCacheCreateFlags, PipelineCacheCreateInfo, StructureType}};
+use directories::ProjectDirs;
+use log::{debug,error,info,warn};
+
+use super::RendererError;
+
+const MAGIC : [u8;8] = *b"GPENGPC\0";
+///Increased whenever the layout of the file header changes.
+const FORMAT_VERSION : u32 = 1;
+///Magic, format version, vendor ID, device ID, driver version, cache UUID, data length and checksum.
+const HEADER_SIZE : usize = 8 + 4 + 4 + 4 + 4 + 16 + 8 + 4;
+///The size of the header Vulkan puts in front of the cache data.
+const VULKAN_HEADER_SIZE : usize = 16 + 16;
+
+///Identifies the GPU and driver a cache was created by, caches of any other GPU or driver are thrown away.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct CacheKey{
+    vendor_id : u32,
+    device_id : u32,
+    driver_version : u32,
+    uuid : [u8;16],
+}
+///A pipeline cache that is loaded from and saved to the project data directory.
+pub(crate) struct PipelineCache{
+    handle : vk::PipelineCache,
+    key : CacheKey,
+    ///`None` when the cache isn't persistent.
+    path : Option<PathBuf>,
+}
+fn read_u32(bytes : &[u8] , offset : usize)->u32{
+    return u32::from_le_bytes([bytes[offset],bytes[offset + 1],bytes[offset + 2],bytes[offset + 3]]);
+}
+///FNV-1a, to detect truncated or corrupted files.
+fn checksum(data : &[u8])->u32{
+    return data.iter().fold(0x811c_9dc5u32,|hash,byte|(hash ^ *byte as u32).wrapping_mul(0x0100_0193));
+}
+impl CacheKey{
+    fn header(&self , data : &[u8])->Vec<u8>{
+        let mut header = Vec::with_capacity(HEADER_SIZE);
+        header.extend_from_slice(&MAGIC);
+        header.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        header.extend_from_slice(&self.vendor_id.to_le_bytes());
+        header.extend_from_slice(&self.device_id.to_le_bytes());
+        header.extend_from_slice(&self.driver_version.to_le_bytes());
+        header.extend_from_slice(&self.uuid);
+        header.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        header.extend_from_slice(&checksum(data).to_le_bytes());
+        return header;
+    }
+    ///Returns the cache data of a file written by `header`, or why the file can't be used.
+    fn validate<'a>(&self , file : &'a [u8])->Result<&'a [u8],String>{
+        if file.len() < HEADER_SIZE || file[0..8] != MAGIC{return Err("the file is not a pipeline cache".to_string())}
+        if read_u32(file, 8) != FORMAT_VERSION{return Err(format!("the file format version {} is not supported",read_u32(file, 8)))}
+        let key = CacheKey{vendor_id : read_u32(file, 12) , device_id : read_u32(file, 16) , driver_version : read_u32(file, 20) , uuid : file[24..40].try_into().unwrap()};
+        if key != *self{return Err(format!("the cache was created by another GPU or driver, {:?}",key))}
+        let length = u64::from_le_bytes(file[40..48].try_into().unwrap());
+        let data = &file[HEADER_SIZE..];
+        if data.len() as u64 != length{return Err(format!("the cache data is {} bytes instead of {}",data.len(),length))}
+        if checksum(data) != read_u32(file, 48){return Err("the checksum doesn't match".to_string())}
+        //The header Vulkan writes itself, the driver validates the rest.
+        if data.len() < VULKAN_HEADER_SIZE || (read_u32(data, 0) as usize) < VULKAN_HEADER_SIZE || read_u32(data, 4) != vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32 ||
+            read_u32(data, 8) != self.vendor_id || read_u32(data, 12) != self.device_id || data[16..32] != self.uuid{
+            return Err("the Vulkan cache header doesn't match the GPU".to_string())
+        }
+        return Ok(data);
+    }
+}
+///The file the cache of a GPU is stored in, GPUs are told apart by vendor and device ID so several can share the directory.
+fn cache_path(key : &CacheKey)->Option<PathBuf>{
+    let project_dirs = ProjectDirs::from("com", "gpengine", "gpengine")?;
+    return Some(project_dirs.data_dir().join("pipeline_cache").join(format!("{:04x}-{:04x}.bin",key.vendor_id,key.device_id)));
+}
+///Reads the cache data from a file, removing the file when it is stale or corrupt.
+fn load(path : &Path , key : &CacheKey)->Option<Vec<u8>>{
+    let file = match std::fs::read(path){
+        Ok(file)=>file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound=>{debug!("No pipeline cache at {}.",path.display());return None}
+        Err(e)=>{warn!("Failed to read pipeline cache {}, {}.",path.display(),e);return None}
+    };
+    match key.validate(&file){
+        Ok(data)=>return Some(data.to_vec()),
+        Err(reason)=>{
+            warn!("Discarding pipeline cache {}, {}.",path.display(),reason);
+            if let Err(e) = std::fs::remove_file(path){warn!("Failed to remove pipeline cache {}, {}.",path.display(),e)}
+            return None;
+        }
+    }
+}
+///Writes to a temporary file that replaces the cache, so an interrupted write never leaves a partial cache behind.
+fn write_atomically(path : &Path , header : &[u8] , data : &[u8])->std::io::Result<()>{
+    if let Some(parent) = path.parent(){std::fs::create_dir_all(parent)?}
+    //The process id keeps renderers in different processes from writing to the same temporary file.
+    let temporary = path.with_extension(format!("{}.tmp",std::process::id()));
+    let result = (||{
+        let mut file = std::fs::File::create(&temporary)?;
+        file.write_all(header)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        drop(file);
+        return std::fs::rename(&temporary, path);
+    })();
+    if result.is_err(){let _ = std::fs::remove_file(&temporary);}
+    return result;
+}
+impl PipelineCache{
+    ///Creates the pipeline cache, loading it from the project data directory when `persistent` is set.
+    pub(crate) unsafe fn new(instance : &Instance , physical_device : PhysicalDevice , device : &Device , persistent : bool)->Result<Self,RendererError>{
+        let properties = instance.get_physical_device_properties(physical_device);
+        let key = CacheKey{vendor_id : properties.vendor_id , device_id : properties.device_id , driver_version : properties.driver_version , uuid : properties.pipeline_cache_uuid};
+        let path = if persistent{cache_path(&key)}else{None};
+        if persistent && path.is_none(){warn!("Failed to find the project data directory, the pipeline cache won't be saved.")}
+        let data = path.as_ref().and_then(|path|load(path, &key)).unwrap_or_default();
+        let create = |data : &[u8]|{
+            let create_info = PipelineCacheCreateInfo{
+                s_type : StructureType::PIPELINE_CACHE_CREATE_INFO,
+                p_next : std::ptr::null(),
+                flags : PipelineCacheCreateFlags::empty(),
+                initial_data_size : data.len(),
+                p_initial_data : data.as_ptr() as *const std::ffi::c_void,
+            };
+            return device.create_pipeline_cache(&create_info, None);
+        };
+        let handle = match create(&data){
+            Ok(handle)=>Ok(handle),
+            Err(e) if !data.is_empty()=>{
+                warn!("Failed to create the pipeline cache from {} bytes of cache data, {}, starting with an empty cache.",data.len(),e);
+                create(&[])
+            }
+            Err(e)=>Err(e),
+        }.map_err(|e|{error!("Failed to create the pipeline cache, {}.",e);RendererError::CreatePipelineCache(e)})?;
+        if !data.is_empty(){info!("Loaded {} bytes of pipeline cache data.",data.len())}
+        return Ok(Self{handle , key , path});
+    }
+    pub(crate) fn handle(&self)->vk::PipelineCache{
+        return self.handle;
+    }
+    ///Writes the cache to the project data directory, failures are only logged as the cache can always be rebuilt.
+    pub(crate) unsafe fn save(&self , device : &Device){
+        let path = match &self.path{Some(path)=>path,None=>return};
+        let data = match device.get_pipeline_cache_data(self.handle){
+            Ok(data)=>data,
+            Err(e)=>{warn!("Failed to get the pipeline cache data, {}.",e);return}
+        };
+        match write_atomically(path, &self.key.header(&data), &data){
+            Ok(())=>info!("Saved {} bytes of pipeline cache data to {}.",data.len(),path.display()),
+            Err(e)=>warn!("Failed to save the pipeline cache to {}, {}.",path.display(),e),
+        }
+    }
+    pub(crate) unsafe fn destroy(&self , device : &Device){
+        device.destroy_pipeline_cache(self.handle, None);
+    }
+}
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn key()->CacheKey{
+        return CacheKey{vendor_id : 0x10de , device_id : 0x2484 , driver_version : 0x1f4c_8000 , uuid : [7;16]};
+    }
+    ///Cache data as the driver of `key` writes it, the Vulkan header followed by some payload.
+    fn vulkan_data(key : &CacheKey)->Vec<u8>{
+        let mut data = vec!();
+        data.extend_from_slice(&(VULKAN_HEADER_SIZE as u32).to_le_bytes());
+        data.extend_from_slice(&(vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32).to_le_bytes());
+        data.extend_from_slice(&key.vendor_id.to_le_bytes());
+        data.extend_from_slice(&key.device_id.to_le_bytes());
+        data.extend_from_slice(&key.uuid);
+        data.extend_from_slice(&[1,2,3,4,5,6,7,8]);
+        return data;
+    }
+    fn cache_file(key : &CacheKey , data : &[u8])->Vec<u8>{
+        let mut file = key.header(data);
+        file.extend_from_slice(data);
+        return file;
+    }
+    #[test]
+    fn valid_files_round_trip(){
+        let data = vulkan_data(&key());
+        let file = cache_file(&key(), &data);
+        assert_eq!(key().header(&data).len(), HEADER_SIZE);
+        assert_eq!(key().validate(&file), Ok(data.as_slice()));
+    }
+    #[test]
+    fn files_of_another_format_are_rejected(){
+        let mut file = cache_file(&key(), &vulkan_data(&key()));
+        file[0] = b'X';
+        assert!(key().validate(&file).is_err());
+        let mut file = cache_file(&key(), &vulkan_data(&key()));
+        file[8..12].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert!(key().validate(&file).unwrap_err().contains("format version"));
+    }
+    #[test]
+    fn files_of_other_gpus_or_drivers_are_rejected(){
+        let others = [
+            CacheKey{vendor_id : 0x1002 , ..key()},
+            CacheKey{device_id : 0x2485 , ..key()},
+            CacheKey{driver_version : 0x1f4c_8001 , ..key()},
+            CacheKey{uuid : [8;16] , ..key()},
+        ];
+        for other in others.iter(){
+            let file = cache_file(other, &vulkan_data(other));
+            assert!(key().validate(&file).unwrap_err().contains("another GPU or driver"));
+        }
+    }
+    #[test]
+    fn truncated_files_are_rejected(){
+        let file = cache_file(&key(), &vulkan_data(&key()));
+        assert!(key().validate(&file[..file.len() - 1]).unwrap_err().contains("bytes instead of"));
+        assert!(key().validate(&file[..HEADER_SIZE - 1]).is_err());
+        assert!(key().validate(&[]).is_err());
+    }
+    #[test]
+    fn corrupted_data_is_rejected(){
+        let mut file = cache_file(&key(), &vulkan_data(&key()));
+        let last = file.len() - 1;
+        file[last] ^= 0xff;
+        assert!(key().validate(&file).unwrap_err().contains("checksum"));
+    }
+    #[test]
+    fn mismatched_vulkan_headers_are_rejected(){
+        let other = CacheKey{device_id : 0x2485 , ..key()};
+        let file = cache_file(&key(), &vulkan_data(&other));
+        assert!(key().validate(&file).unwrap_err().contains("Vulkan cache header"));
+        let file = cache_file(&key(), &vulkan_data(&key())[..VULKAN_HEADER_SIZE - 1]);
+        assert!(key().validate(&file).unwrap_err().contains("Vulkan cache header"));
+    }
+}
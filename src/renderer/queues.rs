@@ -0,0 +1,110 @@
+use ash::{Device, version::DeviceV1_0, vk::{Fence, Queue, QueueFamilyProperties, QueueFlags, SubmitInfo}};
+use log::{error,debug};
+
+use super::{Renderer, RendererError, get_compute_queue_family, get_dma_queue_family, get_graphics_queue_family};
+
+///A queue created with the device, together with the family it belongs to.
+#[derive(Clone, Copy, Debug)]
+pub struct DeviceQueue{
+    handle : Queue,
+    family_index : u32,
+    capabilities : QueueFlags,
+}
+impl DeviceQueue{
+    unsafe fn new(device : &Device , queue_family_properties : &[QueueFamilyProperties] , family_index : u32)->Self{
+        return Self{
+            handle : device.get_device_queue(family_index, 0),
+            family_index,
+            capabilities : queue_family_properties[family_index as usize].queue_flags,
+        };
+    }
+    pub fn handle(&self)->Queue{
+        return self.handle;
+    }
+    pub fn family_index(&self)->u32{
+        return self.family_index;
+    }
+    ///The operations the queue family supports.
+    pub fn capabilities(&self)->QueueFlags{
+        return self.capabilities;
+    }
+}
+///The graphics, compute and transfer queues of the renderer.
+///When the GPU has no separate family for compute or transfer operations, that queue is the same queue as the graphics queue.
+#[derive(Clone, Copy, Debug)]
+pub struct Queues{
+    graphics : DeviceQueue,
+    compute : DeviceQueue,
+    transfer : DeviceQueue,
+}
+impl Queues{
+    ///Gets the queues `create_device` created, using the same queue family selection.
+    pub(crate) unsafe fn new(device : &Device , queue_family_properties : &[QueueFamilyProperties])->Result<Self,RendererError>{
+        let graphics_queue_family = get_graphics_queue_family(queue_family_properties)?;
+        let compute_queue_family = get_compute_queue_family(queue_family_properties)?;
+        //Every graphics queue family supports transfer operations, even when it doesn't report it.
+        let transfer_queue_family = get_dma_queue_family(queue_family_properties).unwrap_or(graphics_queue_family);
+        let queues = Self{
+            graphics : DeviceQueue::new(device, queue_family_properties, graphics_queue_family),
+            compute : DeviceQueue::new(device, queue_family_properties, compute_queue_family),
+            transfer : DeviceQueue::new(device, queue_family_properties, transfer_queue_family),
+        };
+        debug!("Queue families, graphics:{} compute:{} transfer:{}.",graphics_queue_family,compute_queue_family,transfer_queue_family);
+        return Ok(queues);
+    }
+    pub fn graphics(&self)->&DeviceQueue{
+        return &self.graphics;
+    }
+    ///The compute queue, from a compute only family when the GPU has one.
+    pub fn compute(&self)->&DeviceQueue{
+        return &self.compute;
+    }
+    ///The transfer queue, from a transfer only family when the GPU has one.
+    pub fn transfer(&self)->&DeviceQueue{
+        return &self.transfer;
+    }
+    ///Whether compute work runs on a different queue family than graphics work.
+    pub fn has_dedicated_compute(&self)->bool{
+        return self.compute.family_index != self.graphics.family_index;
+    }
+    ///Whether transfers run on a different queue family than graphics work.
+    pub fn has_dedicated_transfer(&self)->bool{
+        return self.transfer.family_index != self.graphics.family_index;
+    }
+    ///The distinct family indices of the queues, for resources that are shared between them.
+    pub fn unique_family_indices(&self)->Vec<u32>{
+        let mut families = vec!(self.graphics.family_index);
+        for family in [self.compute.family_index,self.transfer.family_index].iter(){
+            if !families.contains(family){families.push(*family)}
+        }
+        return families;
+    }
+}
+impl Renderer{
+    ///The graphics, compute and transfer queues.
+    pub fn queues(&self)->&Queues{
+        return &self.queues;
+    }
+    ///Submits work to the graphics queue, `fence` may be null.
+    pub fn submit_graphics(&mut self , submits : &[SubmitInfo] , fence : Fence)->Result<(),RendererError>{
+        let queue = self.queues.graphics;
+        return self.submit(&queue, "graphics", submits, fence);
+    }
+    ///Submits work to the compute queue, which is the graphics queue when the GPU has no separate compute family.
+    pub fn submit_compute(&mut self , submits : &[SubmitInfo] , fence : Fence)->Result<(),RendererError>{
+        let queue = self.queues.compute;
+        return self.submit(&queue, "compute", submits, fence);
+    }
+    ///Submits work to the transfer queue, which is the graphics queue when the GPU has no separate transfer family.
+    pub fn submit_transfer(&mut self , submits : &[SubmitInfo] , fence : Fence)->Result<(),RendererError>{
+        let queue = self.queues.transfer;
+        return self.submit(&queue, "transfer", submits, fence);
+    }
+    //Takes `&mut self` because queues must be externally synchronized, and queues of shared families are the same queue.
+    fn submit(&mut self , queue : &DeviceQueue , name : &str , submits : &[SubmitInfo] , fence : Fence)->Result<(),RendererError>{
+        return unsafe{self.device.queue_submit(queue.handle, submits, fence)}.map_err(|e|{
+            error!("Failed to submit to the {} queue, {}.",name,e);
+            RendererError::Submit(e)
+        });
+    }
+}
@@ -0,0 +1,410 @@
+use std::{ffi::CStr, fmt::Write};
+
+use ash::{Entry, Instance, InstanceError, extensions::khr::Surface, version::{EntryV1_0, InstanceV1_0}, vk::{self, FormatFeatureFlags, PhysicalDevice, SurfaceKHR}};
+use log::{debug,error};
+use serde::Serialize;
+use winit::window::Window;
+
+use super::{Preference, RendererConfig, RendererError, create_instance, device};
+
+///A description of one GPU, meant to be attached to bug reports.
+#[derive(Clone, Debug, Serialize)]
+pub struct DeviceReport{
+    ///The position in the order the driver enumerates the GPU's, this is the index `--gpu` expects.
+    pub index : usize,
+    pub name : String,
+    pub device_type : String,
+    pub vendor_id : u32,
+    pub device_id : u32,
+    pub api_version : String,
+    ///The driver version as reported, its encoding is vendor specific.
+    pub driver_version : u32,
+    ///Only known for GPU's that support Vulkan 1.1.
+    pub uuid : Option<String>,
+    ///Whether the renderer can use this GPU at all.
+    pub supported : bool,
+    pub limits : LimitsReport,
+    pub queue_families : Vec<QueueFamilyReport>,
+    pub memory_heaps : Vec<MemoryHeapReport>,
+    pub memory_types : Vec<MemoryTypeReport>,
+    pub depth_formats : Vec<DepthFormatReport>,
+    pub extensions : Vec<String>,
+}
+///The device limits most relevant to the renderer.
+#[derive(Clone, Debug, Serialize)]
+pub struct LimitsReport{
+    pub max_image_dimension_2d : u32,
+    pub max_image_dimension_3d : u32,
+    pub max_image_dimension_cube : u32,
+    pub max_image_array_layers : u32,
+    pub max_memory_allocation_count : u32,
+    pub max_sampler_allocation_count : u32,
+    pub buffer_image_granularity : u64,
+    pub max_bound_descriptor_sets : u32,
+    pub max_per_stage_descriptor_samplers : u32,
+    pub max_per_stage_descriptor_uniform_buffers : u32,
+    pub max_per_stage_descriptor_storage_buffers : u32,
+    pub max_per_stage_descriptor_sampled_images : u32,
+    pub max_per_stage_descriptor_storage_images : u32,
+    pub max_push_constants_size : u32,
+    pub max_uniform_buffer_range : u32,
+    pub max_storage_buffer_range : u32,
+    pub max_vertex_input_attributes : u32,
+    pub max_vertex_input_bindings : u32,
+    pub max_color_attachments : u32,
+    pub max_framebuffer_width : u32,
+    pub max_framebuffer_height : u32,
+    pub framebuffer_color_sample_counts : String,
+    pub framebuffer_depth_sample_counts : String,
+    pub max_compute_work_group_count : [u32;3],
+    pub max_compute_work_group_size : [u32;3],
+    pub max_compute_work_group_invocations : u32,
+    pub max_compute_shared_memory_size : u32,
+    pub max_sampler_anisotropy : f32,
+    pub timestamp_period : f32,
+    pub min_uniform_buffer_offset_alignment : u64,
+    pub min_storage_buffer_offset_alignment : u64,
+    pub optimal_buffer_copy_offset_alignment : u64,
+    pub non_coherent_atom_size : u64,
+}
+#[derive(Clone, Debug, Serialize)]
+pub struct QueueFamilyReport{
+    pub index : u32,
+    pub flags : String,
+    pub queue_count : u32,
+    pub timestamp_valid_bits : u32,
+    pub min_image_transfer_granularity : [u32;3],
+    ///`None` when there was neither a surface nor a display server connection to test against.
+    pub presentation : Option<bool>,
+}
+#[derive(Clone, Debug, Serialize)]
+pub struct MemoryHeapReport{
+    pub index : u32,
+    pub size : u64,
+    pub flags : String,
+}
+#[derive(Clone, Debug, Serialize)]
+pub struct MemoryTypeReport{
+    pub index : u32,
+    pub heap_index : u32,
+    pub flags : String,
+}
+///Depth attachment support of one of the depth formats the renderer chooses from.
+#[derive(Clone, Debug, Serialize)]
+pub struct DepthFormatReport{
+    pub format : String,
+    pub optimal_tiling : bool,
+    pub linear_tiling : bool,
+}
+impl DeviceReport{
+    ///Describes every GPU, presentation support is tested against a surface for the window when there is one.
+    ///Without a window it is queried from the platform's display server, so no window has to be created.
+    pub fn gather(window : Option<&Window>)->Result<Vec<DeviceReport>,RendererError>{
+        let entry = unsafe{Entry::new()}.map_err(|e|{error!("Failed to load Vulkan library:{}.",e);RendererError::LoadLibrary(e)})?;
+        let instance_version = match entry.try_enumerate_instance_version().map_err(|e|RendererError::CreateInstance(InstanceError::VkError(e)))?{
+            Some(v)=>v,
+            None=>vk::make_version(1, 0, 0),
+        };
+        let display = match window{
+            Some(_)=>None,
+            None=>PlatformDisplay::connect(&entry),
+        };
+        let instance_extensions = match (window,&display){
+            (Some(window),_)=>ash_window::enumerate_required_extensions(window).map_err(|e|RendererError::CreateInstance(InstanceError::VkError(e)))?,
+            (None,Some(display))=>vec!(Surface::name(),display.extension_name()),
+            (None,None)=>vec!(),
+        };
+        let instance = unsafe{create_instance(&entry, &instance_extensions)}.map_err(|e|{
+            error!("Failed to create Vulkan instance, {}.",e);
+            RendererError::CreateInstance(e)
+        })?;
+        let surface = match window{
+            Some(window)=>match unsafe{ash_window::create_surface(&entry, &instance, window, None)}{
+                Ok(surface)=>Some((Surface::new(&entry, &instance),surface)),
+                Err(e)=>{
+                    unsafe{instance.destroy_instance(None)};
+                    return Err(RendererError::CreateSurface(e));
+                }
+            },
+            None=>None,
+        };
+        let presentation = match (&surface,&display){
+            (Some((surface_loader,surface)),_)=>PresentationTarget::Surface(surface_loader,surface),
+            (None,Some(display))=>PresentationTarget::Display(&entry,display),
+            (None,None)=>PresentationTarget::Unknown,
+        };
+        let result = unsafe{gather_reports(&instance, instance_version, presentation)};
+        unsafe{
+            if let Some((surface_loader,surface)) = surface{surface_loader.destroy_surface(surface, None)}
+            instance.destroy_instance(None);
+        }
+        return result;
+    }
+    ///Serializes the reports as pretty printed JSON.
+    pub fn to_json(reports : &[DeviceReport])->String{
+        return serde_json::to_string_pretty(reports).expect("device reports only contain serializable values");
+    }
+    ///Formats the report as indented plain text.
+    pub fn to_text(&self)->String{
+        let mut text = String::new();
+        //Writing to a String can't fail.
+        let _ = self.write_text(&mut text);
+        return text;
+    }
+    fn write_text(&self , w : &mut String)->std::fmt::Result{
+        writeln!(w,"GPU {}: {}{}",self.index,self.name,if self.supported{""}else{" (not supported by the renderer)"})?;
+        writeln!(w,"  Type: {}",self.device_type)?;
+        writeln!(w,"  Vendor ID: {:#06x}, Device ID: {:#06x}",self.vendor_id,self.device_id)?;
+        writeln!(w,"  Vulkan {}, driver version {:#x}",self.api_version,self.driver_version)?;
+        writeln!(w,"  UUID: {}",self.uuid.as_deref().unwrap_or("unknown"))?;
+        let l = &self.limits;
+        writeln!(w,"  Limits:")?;
+        writeln!(w,"    Image dimensions: 2D {}, 3D {}, cube {}, {} array layers",l.max_image_dimension_2d,l.max_image_dimension_3d,l.max_image_dimension_cube,l.max_image_array_layers)?;
+        writeln!(w,"    Allocations: {} memory, {} samplers, buffer image granularity {}",l.max_memory_allocation_count,l.max_sampler_allocation_count,l.buffer_image_granularity)?;
+        writeln!(w,"    Descriptors: {} sets, per stage {} samplers, {} uniform buffers, {} storage buffers, {} sampled images, {} storage images",
+            l.max_bound_descriptor_sets,l.max_per_stage_descriptor_samplers,l.max_per_stage_descriptor_uniform_buffers,l.max_per_stage_descriptor_storage_buffers,
+            l.max_per_stage_descriptor_sampled_images,l.max_per_stage_descriptor_storage_images)?;
+        writeln!(w,"    Push constants: {} bytes, uniform buffer range {}, storage buffer range {}",l.max_push_constants_size,l.max_uniform_buffer_range,l.max_storage_buffer_range)?;
+        writeln!(w,"    Vertex input: {} attributes, {} bindings",l.max_vertex_input_attributes,l.max_vertex_input_bindings)?;
+        writeln!(w,"    Framebuffer: {}x{}, {} color attachments, color samples {}, depth samples {}",
+            l.max_framebuffer_width,l.max_framebuffer_height,l.max_color_attachments,l.framebuffer_color_sample_counts,l.framebuffer_depth_sample_counts)?;
+        writeln!(w,"    Compute: work group count {:?}, size {:?}, {} invocations, {} bytes shared memory",
+            l.max_compute_work_group_count,l.max_compute_work_group_size,l.max_compute_work_group_invocations,l.max_compute_shared_memory_size)?;
+        writeln!(w,"    Alignment: uniform offset {}, storage offset {}, buffer copy offset {}, non coherent atom {}",
+            l.min_uniform_buffer_offset_alignment,l.min_storage_buffer_offset_alignment,l.optimal_buffer_copy_offset_alignment,l.non_coherent_atom_size)?;
+        writeln!(w,"    Max anisotropy {}, timestamp period {} ns",l.max_sampler_anisotropy,l.timestamp_period)?;
+        writeln!(w,"  Queue families:")?;
+        for q in self.queue_families.iter(){
+            let presentation = match q.presentation{Some(true)=>"yes",Some(false)=>"no",None=>"unknown"};
+            writeln!(w,"    {}: {} queues, {}, presentation {}, {} timestamp bits, transfer granularity {:?}",
+                q.index,q.queue_count,q.flags,presentation,q.timestamp_valid_bits,q.min_image_transfer_granularity)?;
+        }
+        writeln!(w,"  Memory heaps:")?;
+        for h in self.memory_heaps.iter(){
+            writeln!(w,"    {}: {} MiB, {}",h.index,h.size >> 20,h.flags)?;
+        }
+        writeln!(w,"  Memory types:")?;
+        for t in self.memory_types.iter(){
+            writeln!(w,"    {}: heap {}, {}",t.index,t.heap_index,t.flags)?;
+        }
+        writeln!(w,"  Depth formats:")?;
+        for d in self.depth_formats.iter(){
+            writeln!(w,"    {}: optimal tiling {}, linear tiling {}",d.format,if d.optimal_tiling{"yes"}else{"no"},if d.linear_tiling{"yes"}else{"no"})?;
+        }
+        writeln!(w,"  Extensions ({}):",self.extensions.len())?;
+        for e in self.extensions.iter(){
+            writeln!(w,"    {}",e)?;
+        }
+        return Ok(());
+    }
+}
+///What the presentation support of queue families is tested against.
+enum PresentationTarget<'a>{
+    Surface(&'a Surface,&'a SurfaceKHR),
+    Display(&'a Entry,&'a PlatformDisplay),
+    Unknown,
+}
+///A connection to the platform's display server, to query presentation support without a window or surface.
+enum PlatformDisplay{
+    #[cfg(windows)]
+    Win32,
+    #[cfg(all(unix, not(any(target_os = "macos", target_os = "ios", target_os = "android"))))]
+    Wayland(wayland_client::Display),
+    #[cfg(all(unix, not(any(target_os = "macos", target_os = "ios", target_os = "android"))))]
+    Xlib{xlib : Box<x11_dl::xlib::Xlib> , display : *mut x11_dl::xlib::Display , visual_id : vk::VisualID},
+}
+impl PlatformDisplay{
+    ///Connects to the display server, `None` when there is none, the platform has no presentation query or the driver lacks the surface extension.
+    fn connect(entry : &Entry)->Option<Self>{
+        let display = Self::connect_platform()?;
+        let extensions = entry.enumerate_instance_extension_properties().unwrap_or_default();
+        let available = |name : &CStr|extensions.iter().any(|e|unsafe{CStr::from_ptr(e.extension_name.as_ptr())} == name);
+        if !available(Surface::name()) || !available(display.extension_name()){
+            debug!("The driver doesn't support {}, presentation support is not tested.",display.extension_name().to_string_lossy());
+            return None;
+        }
+        return Some(display);
+    }
+    #[cfg(windows)]
+    fn connect_platform()->Option<Self>{
+        return Some(PlatformDisplay::Win32);
+    }
+    #[cfg(all(unix, not(any(target_os = "macos", target_os = "ios", target_os = "android"))))]
+    fn connect_platform()->Option<Self>{
+        if std::env::var_os("WAYLAND_DISPLAY").is_some(){
+            match wayland_client::Display::connect_to_env(){
+                Ok(display)=>return Some(PlatformDisplay::Wayland(display)),
+                Err(e)=>debug!("Failed to connect to the Wayland display, {}.",e),
+            }
+        }
+        let xlib = Box::new(x11_dl::xlib::Xlib::open().map_err(|e|debug!("Failed to load Xlib, {}.",e)).ok()?);
+        unsafe{
+            let display = (xlib.XOpenDisplay)(std::ptr::null());
+            if display.is_null(){
+                debug!("No X11 display available.");
+                return None;
+            }
+            let visual_id = (xlib.XVisualIDFromVisual)((xlib.XDefaultVisual)(display, (xlib.XDefaultScreen)(display))) as vk::VisualID;
+            return Some(PlatformDisplay::Xlib{xlib , display , visual_id});
+        }
+    }
+    #[cfg(not(any(windows, all(unix, not(any(target_os = "macos", target_os = "ios", target_os = "android"))))))]
+    fn connect_platform()->Option<Self>{
+        debug!("The platform has no presentation support query.");
+        return None;
+    }
+    ///The instance extension of the platform's surfaces, which provides the presentation support query.
+    fn extension_name(&self)->&'static CStr{
+        match *self{
+            #[cfg(windows)]
+            PlatformDisplay::Win32=>return ash::extensions::khr::Win32Surface::name(),
+            #[cfg(all(unix, not(any(target_os = "macos", target_os = "ios", target_os = "android"))))]
+            PlatformDisplay::Wayland(_)=>return ash::extensions::khr::WaylandSurface::name(),
+            #[cfg(all(unix, not(any(target_os = "macos", target_os = "ios", target_os = "android"))))]
+            PlatformDisplay::Xlib{..}=>return ash::extensions::khr::XlibSurface::name(),
+        }
+    }
+    ///Whether the queue family can present to this display, the instance must have the extension enabled.
+    unsafe fn presentation_support(&self , entry : &Entry , instance : &Instance , physical_device : PhysicalDevice , queue_family_index : u32)->bool{
+        match self{
+            #[cfg(windows)]
+            PlatformDisplay::Win32=>{
+                return ash::extensions::khr::Win32Surface::new(entry, instance).get_physical_device_win32_presentation_support(physical_device, queue_family_index);
+            }
+            #[cfg(all(unix, not(any(target_os = "macos", target_os = "ios", target_os = "android"))))]
+            PlatformDisplay::Wayland(display)=>{
+                let wl_display = &mut *(display.get_display_ptr() as *mut vk::wl_display);
+                return ash::extensions::khr::WaylandSurface::new(entry, instance).get_physical_device_wayland_presentation_support(physical_device, queue_family_index, wl_display);
+            }
+            //ash declares `Display` as a pointer, the X11 display pointer itself is what the command expects.
+            #[cfg(all(unix, not(any(target_os = "macos", target_os = "ios", target_os = "android"))))]
+            PlatformDisplay::Xlib{display , visual_id , ..}=>{
+                let x_display = &mut *(*display as *mut vk::Display);
+                return ash::extensions::khr::XlibSurface::new(entry, instance).get_physical_device_xlib_presentation_support(physical_device, queue_family_index, x_display, *visual_id);
+            }
+        }
+    }
+}
+#[cfg(all(unix, not(any(target_os = "macos", target_os = "ios", target_os = "android"))))]
+impl Drop for PlatformDisplay{
+    fn drop(&mut self){
+        if let PlatformDisplay::Xlib{xlib , display , ..} = self{
+            unsafe{(xlib.XCloseDisplay)(*display)};
+        }
+    }
+}
+unsafe fn gather_reports(instance : &Instance , instance_version : u32 , presentation : PresentationTarget)->Result<Vec<DeviceReport>,RendererError>{
+    let physical_devices = instance.enumerate_physical_devices().map_err(|e|{error!("Failed to get supported devices, {}.",e);RendererError::PhysicalDevice(e)})?;
+    let surface = match presentation{
+        PresentationTarget::Surface(surface_loader,surface)=>Some((surface_loader,surface)),
+        _=>None,
+    };
+    let supported = device::get_supported_physical_devices(instance, instance_version, surface)?;
+    let mut reports = vec!();
+    for (index,&physical_device) in physical_devices.iter().enumerate(){
+        let is_supported = supported.iter().any(|c|c.physical_device == physical_device);
+        reports.push(gather_report(instance, instance_version, &presentation, physical_device, index, is_supported)?);
+    }
+    return Ok(reports);
+}
+unsafe fn gather_report(instance : &Instance , instance_version : u32 , presentation : &PresentationTarget , physical_device : PhysicalDevice , index : usize , supported : bool)->Result<DeviceReport,RendererError>{
+    let properties = instance.get_physical_device_properties(physical_device);
+    let limits = properties.limits;
+    let mut queue_families = vec!();
+    for (i,family) in instance.get_physical_device_queue_family_properties(physical_device).iter().enumerate(){
+        let presentation = match presentation{
+            PresentationTarget::Surface(surface_loader,surface)=>Some(surface_loader.get_physical_device_surface_support(physical_device, i as u32, **surface).map_err(RendererError::PhysicalDevice)?),
+            PresentationTarget::Display(entry,display)=>Some(display.presentation_support(entry, instance, physical_device, i as u32)),
+            PresentationTarget::Unknown=>None,
+        };
+        let granularity = family.min_image_transfer_granularity;
+        queue_families.push(QueueFamilyReport{
+            index : i as u32,
+            flags : format!("{:?}",family.queue_flags),
+            queue_count : family.queue_count,
+            timestamp_valid_bits : family.timestamp_valid_bits,
+            min_image_transfer_granularity : [granularity.width,granularity.height,granularity.depth],
+            presentation,
+        });
+    }
+    let memory_properties = instance.get_physical_device_memory_properties(physical_device);
+    let memory_heaps = memory_properties.memory_heaps[..memory_properties.memory_heap_count as usize].iter().enumerate().map(|(i,heap)|MemoryHeapReport{
+        index : i as u32,
+        size : heap.size,
+        flags : format!("{:?}",heap.flags),
+    }).collect();
+    let memory_types = memory_properties.memory_types[..memory_properties.memory_type_count as usize].iter().enumerate().map(|(i,memory_type)|MemoryTypeReport{
+        index : i as u32,
+        heap_index : memory_type.heap_index,
+        flags : format!("{:?}",memory_type.property_flags),
+    }).collect();
+    //The same candidates `get_depth_image_format_and_tiling` picks from with the default config.
+    let depth_candidates = match RendererConfig::default().depth_format{
+        Preference::Prefer(formats)=>formats,
+        Preference::Require(format)=>vec!(format),
+    };
+    let depth_formats = depth_candidates.iter().map(|&format|{
+        let format_properties = instance.get_physical_device_format_properties(physical_device, format);
+        DepthFormatReport{
+            format : format!("{:?}",format),
+            optimal_tiling : format_properties.optimal_tiling_features.contains(FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT),
+            linear_tiling : format_properties.linear_tiling_features.contains(FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT),
+        }
+    }).collect();
+    let mut extensions = instance.enumerate_device_extension_properties(physical_device).map_err(|e|{
+        error!("Failed to get the device extensions, {}.",e);
+        RendererError::PhysicalDevice(e)
+    })?.iter().map(|e|format!("{} (revision {})",CStr::from_ptr(e.extension_name.as_ptr()).to_string_lossy(),e.spec_version)).collect::<Vec<_>>();
+    extensions.sort();
+    return Ok(DeviceReport{
+        index,
+        name : CStr::from_ptr(properties.device_name.as_ptr()).to_string_lossy().to_string(),
+        device_type : format!("{:?}",properties.device_type),
+        vendor_id : properties.vendor_id,
+        device_id : properties.device_id,
+        api_version : format!("{}.{}.{}",vk::version_major(properties.api_version),vk::version_minor(properties.api_version),vk::version_patch(properties.api_version)),
+        driver_version : properties.driver_version,
+        uuid : device::get_device_uuid(instance, instance_version, physical_device, &properties).map(|uuid|device::format_uuid(&uuid)),
+        supported,
+        limits : LimitsReport{
+            max_image_dimension_2d : limits.max_image_dimension2_d,
+            max_image_dimension_3d : limits.max_image_dimension3_d,
+            max_image_dimension_cube : limits.max_image_dimension_cube,
+            max_image_array_layers : limits.max_image_array_layers,
+            max_memory_allocation_count : limits.max_memory_allocation_count,
+            max_sampler_allocation_count : limits.max_sampler_allocation_count,
+            buffer_image_granularity : limits.buffer_image_granularity,
+            max_bound_descriptor_sets : limits.max_bound_descriptor_sets,
+            max_per_stage_descriptor_samplers : limits.max_per_stage_descriptor_samplers,
+            max_per_stage_descriptor_uniform_buffers : limits.max_per_stage_descriptor_uniform_buffers,
+            max_per_stage_descriptor_storage_buffers : limits.max_per_stage_descriptor_storage_buffers,
+            max_per_stage_descriptor_sampled_images : limits.max_per_stage_descriptor_sampled_images,
+            max_per_stage_descriptor_storage_images : limits.max_per_stage_descriptor_storage_images,
+            max_push_constants_size : limits.max_push_constants_size,
+            max_uniform_buffer_range : limits.max_uniform_buffer_range,
+            max_storage_buffer_range : limits.max_storage_buffer_range,
+            max_vertex_input_attributes : limits.max_vertex_input_attributes,
+            max_vertex_input_bindings : limits.max_vertex_input_bindings,
+            max_color_attachments : limits.max_color_attachments,
+            max_framebuffer_width : limits.max_framebuffer_width,
+            max_framebuffer_height : limits.max_framebuffer_height,
+            framebuffer_color_sample_counts : format!("{:?}",limits.framebuffer_color_sample_counts),
+            framebuffer_depth_sample_counts : format!("{:?}",limits.framebuffer_depth_sample_counts),
+            max_compute_work_group_count : limits.max_compute_work_group_count,
+            max_compute_work_group_size : limits.max_compute_work_group_size,
+            max_compute_work_group_invocations : limits.max_compute_work_group_invocations,
+            max_compute_shared_memory_size : limits.max_compute_shared_memory_size,
+            max_sampler_anisotropy : limits.max_sampler_anisotropy,
+            timestamp_period : limits.timestamp_period,
+            min_uniform_buffer_offset_alignment : limits.min_uniform_buffer_offset_alignment,
+            min_storage_buffer_offset_alignment : limits.min_storage_buffer_offset_alignment,
+            optimal_buffer_copy_offset_alignment : limits.optimal_buffer_copy_offset_alignment,
+            non_coherent_atom_size : limits.non_coherent_atom_size,
+        },
+        queue_families,
+        memory_heaps,
+        memory_types,
+        depth_formats,
+        extensions,
+    });
+}
@@ -0,0 +1,218 @@
+use std::{collections::BTreeMap, ffi::CString, path::Path};
+
+use ash::{Device, version::{DeviceV1_0, InstanceV1_0}, vk::{self, DescriptorSetLayout, DescriptorSetLayoutBinding, PipelineLayoutCreateFlags, PipelineLayoutCreateInfo, PipelineShaderStageCreateFlags, PipelineShaderStageCreateInfo, PushConstantRange, ShaderModuleCreateFlags, ShaderModuleCreateInfo, StructureType}};
+use log::{error,info};
+
+use super::{Renderer, RendererError, spirv::{self, DescriptorBinding, EntryPoint, ShaderReflection}};
+
+///A Vulkan shader module together with the reflection of its SPIR-V.
+pub struct ShaderModule{
+    handle : vk::ShaderModule,
+    reflection : ShaderReflection,
+}
+impl ShaderModule{
+    pub fn handle(&self)->vk::ShaderModule{
+        return self.handle;
+    }
+    pub fn reflection(&self)->&ShaderReflection{
+        return &self.reflection;
+    }
+    ///The single entry point of the module, `None` if it has several.
+    pub fn entry_point(&self)->Option<&EntryPoint>{
+        if self.reflection.entry_points.len() != 1{return None}
+        return self.reflection.entry_points.first();
+    }
+    ///The stage create info of an entry point, `name` must outlive the info.
+    pub fn stage_create_info(&self , entry_point : &EntryPoint , name : &CString)->PipelineShaderStageCreateInfo{
+        return PipelineShaderStageCreateInfo{
+            s_type : StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : PipelineShaderStageCreateFlags::empty(),
+            stage : entry_point.stage,
+            module : self.handle,
+            p_name : name.as_ptr(),
+            p_specialization_info : std::ptr::null(),
+        };
+    }
+}
+///A pipeline layout and the descriptor set layouts it was created from, indexed by set number.
+pub struct PipelineLayout{
+    handle : vk::PipelineLayout,
+    set_layouts : Vec<DescriptorSetLayout>,
+    bindings : Vec<DescriptorBinding>,
+    push_constant_ranges : Vec<PushConstantRange>,
+    bindless_set : Option<u32>,
+}
+impl PipelineLayout{
+    pub fn handle(&self)->vk::PipelineLayout{
+        return self.handle;
+    }
+    ///The descriptor set layouts, sets no shader uses have an empty layout.
+    ///They are owned by the renderer's layout cache and can be used to allocate sets with `Renderer::allocate_descriptor_set`.
+    pub fn set_layouts(&self)->&[DescriptorSetLayout]{
+        return &self.set_layouts;
+    }
+    ///The bindings of every stage merged, sorted by set and binding.
+    pub fn bindings(&self)->&[DescriptorBinding]{
+        return &self.bindings;
+    }
+    pub fn push_constant_ranges(&self)->&[PushConstantRange]{
+        return &self.push_constant_ranges;
+    }
+    ///The set number the bindless set is used at, see `Renderer::bind_bindless_set`.
+    pub fn bindless_set(&self)->Option<u32>{
+        return self.bindless_set;
+    }
+    pub(crate) unsafe fn destroy(&self , device : &Device){
+        if self.handle != vk::PipelineLayout::null(){device.destroy_pipeline_layout(self.handle, None)}
+    }
+}
+///Merges the bindings of several shaders, combining the stages of bindings they share.
+pub fn merge_bindings(reflections : &[&ShaderReflection])->Result<Vec<DescriptorBinding>,String>{
+    let mut merged = BTreeMap::<(u32,u32),DescriptorBinding>::new();
+    for binding in reflections.iter().flat_map(|r|r.descriptor_bindings.iter()){
+        match merged.get_mut(&(binding.set,binding.binding)){
+            Some(existing)=>{
+                if existing.descriptor_type != binding.descriptor_type{
+                    return Err(format!("binding {} in set {} is a {:?} and a {:?}",binding.binding,binding.set,existing.descriptor_type,binding.descriptor_type))
+                }
+                if (existing.count == 0) != (binding.count == 0){
+                    return Err(format!("binding {} in set {} is both a runtime sized and a fixed size array",binding.binding,binding.set))
+                }
+                existing.count = existing.count.max(binding.count);
+                existing.stages |= binding.stages;
+            }
+            None=>{merged.insert((binding.set,binding.binding), binding.clone());}
+        }
+    }
+    return Ok(merged.into_values().collect());
+}
+///Merges the push constant blocks of several shaders into one range visible to every stage that uses push constants.
+pub fn merge_push_constants(reflections : &[&ShaderReflection])->Option<PushConstantRange>{
+    return reflections.iter().filter_map(|r|r.push_constants).fold(None,|range : Option<PushConstantRange>,block|{
+        match range{
+            None=>return Some(PushConstantRange{stage_flags : block.stages , offset : block.offset , size : block.size}),
+            Some(range)=>{
+                let offset = range.offset.min(block.offset);
+                let end = (range.offset + range.size).max(block.offset + block.size);
+                return Some(PushConstantRange{stage_flags : range.stage_flags | block.stages , offset , size : end - offset});
+            }
+        }
+    });
+}
+///The newest SPIR-V version a device of the given Vulkan version accepts.
+fn max_spirv_version(api_version : u32)->(u32,u32){
+    match (vk::version_major(api_version),vk::version_minor(api_version)){
+        (1,0)=>return (1,0),
+        (1,1)=>return (1,3),
+        (1,2)=>return (1,5),
+        _=>return spirv::MAX_SPIRV_VERSION,
+    }
+}
+impl Renderer{
+    ///Loads a SPIR-V file, see `Renderer::create_shader_module`.
+    pub fn load_shader<P : AsRef<Path>>(&self , path : P)->Result<ShaderModule,RendererError>{
+        let bytes = std::fs::read(path.as_ref()).map_err(|e|{
+            error!("Failed to read shader {}, {}.",path.as_ref().display(),e);
+            RendererError::InvalidShader(format!("{}: {}",path.as_ref().display(),e))
+        })?;
+        let module = self.create_shader_module(&bytes).inspect_err(|_|error!("Failed to load shader {}.",path.as_ref().display()))?;
+        info!("Loaded shader {} with entry points {:?}.",path.as_ref().display(),module.reflection.entry_points.iter().map(|e|e.name.as_str()).collect::<Vec<_>>());
+        return Ok(module);
+    }
+    ///Validates and reflects SPIR-V code and creates a shader module for it.
+    ///The code is rejected if its SPIR-V version is newer than the device supports.
+    pub fn create_shader_module(&self , bytes : &[u8])->Result<ShaderModule,RendererError>{
+        let words = spirv::words_from_bytes(bytes).map_err(|e|{error!("Invalid SPIR-V, {}.",e);RendererError::InvalidShader(e)})?;
+        let version = spirv::version(&words).map_err(RendererError::InvalidShader)?;
+        let api_version = unsafe{self.instance.get_physical_device_properties(self.physical_device)}.api_version;
+        let max_version = max_spirv_version(api_version);
+        if version > max_version{
+            error!("SPIR-V {}.{} is newer than the {}.{} the device supports.",version.0,version.1,max_version.0,max_version.1);
+            return Err(RendererError::InvalidShader(format!("SPIR-V {}.{} is not supported by the device",version.0,version.1)));
+        }
+        let reflection = ShaderReflection::new(&words).map_err(|e|{error!("Failed to reflect SPIR-V, {}.",e);RendererError::InvalidShader(e)})?;
+        let create_info = ShaderModuleCreateInfo{
+            s_type : StructureType::SHADER_MODULE_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : ShaderModuleCreateFlags::empty(),
+            code_size : std::mem::size_of_val(words.as_slice()),
+            p_code : words.as_ptr(),
+        };
+        let handle = unsafe{self.device.create_shader_module(&create_info, None)}.map_err(|e|{error!("Failed to create shader module, {}.",e);RendererError::CreateShaderModule(e)})?;
+        return Ok(ShaderModule{handle , reflection});
+    }
+    ///Destroys a shader module, pipelines created from it remain valid.
+    pub fn destroy_shader_module(&self , module : ShaderModule){
+        unsafe{self.device.destroy_shader_module(module.handle, None)};
+    }
+    ///Creates the pipeline layout of a pipeline from the reflection of its shaders, the descriptor set layouts come from the renderer's layout cache.
+    ///Bindings shared by several shaders must have the same descriptor type, their stages are combined.
+    ///A set with runtime sized arrays uses the bindless set layout, which requires bindless mode.
+    pub fn create_pipeline_layout(&mut self , shaders : &[&ShaderModule])->Result<PipelineLayout,RendererError>{
+        let reflections = shaders.iter().map(|s|&s.reflection).collect::<Vec<_>>();
+        let bindings = merge_bindings(&reflections).map_err(|e|{error!("Failed to merge shader bindings, {}.",e);RendererError::IncompatibleShaders(e)})?;
+        //Runtime sized arrays are only found in the bindless set.
+        let bindless_sets = bindings.iter().filter(|b|b.count == 0).map(|b|b.set).collect::<std::collections::BTreeSet<_>>();
+        let bindless_set = match (bindless_sets.iter().next(),&self.bindless){
+            (None,_)=>None,
+            (Some(_),None)=>{
+                error!("Runtime sized descriptor arrays need bindless mode.");
+                return Err(RendererError::IncompatibleShaders("runtime sized descriptor arrays need bindless mode".to_string()));
+            }
+            (Some(&set),Some(bindless))=>{
+                if bindless_sets.len() > 1{
+                    error!("Runtime sized descriptor arrays are used in sets {:?}, only one bindless set is supported.",bindless_sets);
+                    return Err(RendererError::IncompatibleShaders(format!("runtime sized descriptor arrays in sets {:?}",bindless_sets)));
+                }
+                bindless.validate_bindings(bindings.iter().filter(|b|b.set == set).map(|b|(b.binding,b.descriptor_type,b.count))).map_err(|e|{
+                    error!("Set {} doesn't match the bindless set, {}.",set,e);
+                    RendererError::IncompatibleShaders(e)
+                })?;
+                Some(set)
+            }
+        };
+        let push_constant_ranges = merge_push_constants(&reflections).into_iter().collect::<Vec<_>>();
+        let set_count = bindings.iter().map(|b|b.set + 1).max().unwrap_or(0);
+        let mut layout = PipelineLayout{handle : vk::PipelineLayout::null() , set_layouts : vec!() , bindings , push_constant_ranges , bindless_set};
+        for set in 0..set_count{
+            if let (Some(bindless_set),Some(bindless)) = (bindless_set,&self.bindless){
+                if set == bindless_set{
+                    layout.set_layouts.push(bindless.layout());
+                    continue;
+                }
+            }
+            let set_bindings = layout.bindings.iter().filter(|b|b.set == set).map(|b|DescriptorSetLayoutBinding{
+                binding : b.binding,
+                descriptor_type : b.descriptor_type,
+                descriptor_count : b.count,
+                stage_flags : b.stages,
+                p_immutable_samplers : std::ptr::null(),
+            }).collect::<Vec<_>>();
+            let set_layout = unsafe{self.descriptor_layouts.get(&self.device, &set_bindings)}.inspect_err(|_|error!("Failed to create descriptor set layout {}.",set))?;
+            layout.set_layouts.push(set_layout);
+        }
+        let create_info = PipelineLayoutCreateInfo{
+            s_type : StructureType::PIPELINE_LAYOUT_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : PipelineLayoutCreateFlags::empty(),
+            set_layout_count : layout.set_layouts.len() as u32,
+            p_set_layouts : layout.set_layouts.as_ptr(),
+            push_constant_range_count : layout.push_constant_ranges.len() as u32,
+            p_push_constant_ranges : layout.push_constant_ranges.as_ptr(),
+        };
+        match unsafe{self.device.create_pipeline_layout(&create_info, None)}{
+            Ok(handle)=>layout.handle = handle,
+            Err(e)=>{
+                error!("Failed to create pipeline layout, {}.",e);
+                return Err(RendererError::CreatePipelineLayout(e));
+            }
+        }
+        return Ok(layout);
+    }
+    ///Destroys a pipeline layout, its descriptor set layouts are cached and stay valid.
+    ///Command buffers that use it must have finished recording.
+    pub fn destroy_pipeline_layout(&self , layout : PipelineLayout){
+        unsafe{layout.destroy(&self.device)};
+    }
+}
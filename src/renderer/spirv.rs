@@ -0,0 +1,618 @@
+use std::collections::{HashMap, HashSet};
+
+use ash::vk::{DescriptorType, Format, ShaderStageFlags};
+
+const MAGIC : u32 = 0x0723_0203;
+///The newest SPIR-V version a Vulkan 1.3 device accepts.
+pub const MAX_SPIRV_VERSION : (u32,u32) = (1,6);
+
+const OP_NAME : u32 = 5;
+const OP_ENTRY_POINT : u32 = 15;
+const OP_EXECUTION_MODE : u32 = 16;
+const OP_TYPE_BOOL : u32 = 20;
+const OP_TYPE_INT : u32 = 21;
+const OP_TYPE_FLOAT : u32 = 22;
+const OP_TYPE_VECTOR : u32 = 23;
+const OP_TYPE_MATRIX : u32 = 24;
+const OP_TYPE_IMAGE : u32 = 25;
+const OP_TYPE_SAMPLER : u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE : u32 = 27;
+const OP_TYPE_ARRAY : u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY : u32 = 29;
+const OP_TYPE_STRUCT : u32 = 30;
+const OP_TYPE_POINTER : u32 = 32;
+const OP_CONSTANT : u32 = 43;
+const OP_SPEC_CONSTANT_TRUE : u32 = 48;
+const OP_SPEC_CONSTANT_FALSE : u32 = 49;
+const OP_SPEC_CONSTANT : u32 = 50;
+const OP_VARIABLE : u32 = 59;
+const OP_DECORATE : u32 = 71;
+const OP_MEMBER_DECORATE : u32 = 72;
+const OP_TYPE_ACCELERATION_STRUCTURE : u32 = 5341;
+
+const DECORATION_SPEC_ID : u32 = 1;
+const DECORATION_BUFFER_BLOCK : u32 = 3;
+const DECORATION_ARRAY_STRIDE : u32 = 6;
+const DECORATION_MATRIX_STRIDE : u32 = 7;
+const DECORATION_BUILT_IN : u32 = 11;
+const DECORATION_LOCATION : u32 = 30;
+const DECORATION_BINDING : u32 = 33;
+const DECORATION_DESCRIPTOR_SET : u32 = 34;
+const DECORATION_OFFSET : u32 = 35;
+
+const STORAGE_CLASS_UNIFORM_CONSTANT : u32 = 0;
+const STORAGE_CLASS_INPUT : u32 = 1;
+const STORAGE_CLASS_UNIFORM : u32 = 2;
+const STORAGE_CLASS_PUSH_CONSTANT : u32 = 9;
+const STORAGE_CLASS_STORAGE_BUFFER : u32 = 12;
+
+const EXECUTION_MODE_LOCAL_SIZE : u32 = 17;
+
+const DIM_BUFFER : u32 = 5;
+const DIM_SUBPASS_DATA : u32 = 6;
+
+///A function a pipeline can use as a shader stage.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntryPoint{
+    pub name : String,
+    pub stage : ShaderStageFlags,
+    ///The workgroup size of compute shaders.
+    pub local_size : Option<[u32;3]>,
+}
+///A resource the module accesses through a descriptor set.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DescriptorBinding{
+    pub set : u32,
+    pub binding : u32,
+    pub descriptor_type : DescriptorType,
+    ///The number of descriptors, 0 for runtime sized arrays.
+    pub count : u32,
+    ///Every stage of the module, as bindings aren't tracked per entry point.
+    pub stages : ShaderStageFlags,
+    pub name : String,
+}
+///The push constant block of the module.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PushConstantBlock{
+    pub stages : ShaderStageFlags,
+    ///The offset of the first member.
+    pub offset : u32,
+    pub size : u32,
+}
+///A vertex shader input, matrices take one input per column.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VertexInput{
+    pub location : u32,
+    pub format : Format,
+    pub name : String,
+}
+///A constant that can be specialized when a pipeline is created.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpecializationConstant{
+    pub id : u32,
+    ///The size in bytes, booleans are 4 bytes.
+    pub size : u32,
+    pub name : String,
+}
+///What a SPIR-V module contains and uses, gathered from its instructions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ShaderReflection{
+    ///The major and minor SPIR-V version.
+    pub version : (u32,u32),
+    pub entry_points : Vec<EntryPoint>,
+    ///Sorted by set and binding.
+    pub descriptor_bindings : Vec<DescriptorBinding>,
+    pub push_constants : Option<PushConstantBlock>,
+    ///Sorted by location.
+    pub vertex_inputs : Vec<VertexInput>,
+    pub specialization_constants : Vec<SpecializationConstant>,
+}
+#[derive(Clone, Copy)]
+enum Type{
+    Scalar{float : bool , signed : bool , width : u32},
+    Vector{component : u32 , count : u32},
+    Matrix{column : u32 , count : u32},
+    Image{dim : u32 , sampled : u32},
+    Sampler,
+    SampledImage,
+    AccelerationStructure,
+    Array{element : u32 , length : u32},
+    RuntimeArray{element : u32},
+    Struct,
+    Pointer{pointee : u32},
+}
+#[derive(Default)]
+struct Decorations{
+    spec_id : Option<u32>,
+    buffer_block : bool,
+    array_stride : Option<u32>,
+    built_in : bool,
+    location : Option<u32>,
+    binding : Option<u32>,
+    descriptor_set : Option<u32>,
+}
+#[derive(Default)]
+struct MemberDecorations{
+    offset : Option<u32>,
+    matrix_stride : Option<u32>,
+}
+///The state gathered while walking the instructions.
+#[derive(Default)]
+struct Module{
+    names : HashMap<u32,String>,
+    types : HashMap<u32,Type>,
+    struct_members : HashMap<u32,Vec<u32>>,
+    constants : HashMap<u32,u32>,
+    decorations : HashMap<u32,Decorations>,
+    member_decorations : HashMap<(u32,u32),MemberDecorations>,
+    ///The result id, pointer type and storage class of each global variable.
+    variables : Vec<(u32,u32,u32)>,
+    ///The result id and type of each specialization constant.
+    spec_constants : Vec<(u32,u32)>,
+    ///The function, the entry point and the interface variables of each entry point.
+    entry_points : Vec<(u32,EntryPoint,Vec<u32>)>,
+    local_sizes : HashMap<u32,[u32;3]>,
+}
+///Checks the header of a SPIR-V module given as bytes and converts it to words.
+pub fn words_from_bytes(bytes : &[u8])->Result<Vec<u32>,String>{
+    if !bytes.len().is_multiple_of(4){return Err(format!("the size of {} bytes is not a multiple of 4",bytes.len()))}
+    let words = bytes.chunks_exact(4).map(|word|u32::from_le_bytes([word[0],word[1],word[2],word[3]])).collect::<Vec<_>>();
+    if words.len() < 5{return Err("the module is shorter than its header".to_string())}
+    if words[0] != MAGIC{
+        if words[0].swap_bytes() == MAGIC{return Err("the module is big endian".to_string())}
+        return Err(format!("the magic number {:#010x} is not the SPIR-V magic number",words[0]))
+    }
+    return Ok(words);
+}
+///Decodes the major and minor version from the header.
+pub fn version(words : &[u32])->Result<(u32,u32),String>{
+    if words.len() < 5{return Err("the module is shorter than its header".to_string())}
+    return Ok(((words[1] >> 16) & 0xFF,(words[1] >> 8) & 0xFF));
+}
+fn string(words : &[u32])->String{
+    let bytes = words.iter().flat_map(|word|word.to_le_bytes()).take_while(|byte|*byte != 0).collect::<Vec<_>>();
+    return String::from_utf8_lossy(&bytes).into_owned();
+}
+///The number of words a string takes, including the word holding its terminator.
+fn string_words(words : &[u32])->usize{
+    return words.iter().position(|word|word.to_le_bytes().contains(&0)).map_or(words.len(),|i|i + 1);
+}
+fn stage(execution_model : u32)->Option<ShaderStageFlags>{
+    match execution_model{
+        0=>return Some(ShaderStageFlags::VERTEX),
+        1=>return Some(ShaderStageFlags::TESSELLATION_CONTROL),
+        2=>return Some(ShaderStageFlags::TESSELLATION_EVALUATION),
+        3=>return Some(ShaderStageFlags::GEOMETRY),
+        4=>return Some(ShaderStageFlags::FRAGMENT),
+        5=>return Some(ShaderStageFlags::COMPUTE),
+        _=>return None,
+    }
+}
+impl ShaderReflection{
+    ///Reflects a module given as words, see `words_from_bytes`.
+    pub fn new(words : &[u32])->Result<Self,String>{
+        let version = version(words)?;
+        if words[0] != MAGIC{return Err(format!("the magic number {:#010x} is not the SPIR-V magic number",words[0]))}
+        if version.0 != 1 || version > MAX_SPIRV_VERSION{return Err(format!("SPIR-V {}.{} is not supported, the newest supported version is {}.{}",version.0,version.1,MAX_SPIRV_VERSION.0,MAX_SPIRV_VERSION.1))}
+        let module = Module::parse(&words[5..])?;
+        if module.entry_points.is_empty(){return Err("the module has no entry points".to_string())}
+        let stages = module.entry_points.iter().fold(ShaderStageFlags::empty(),|stages,(_,entry_point,_)|stages | entry_point.stage);
+        //Other stages have inputs too, only the ones a vertex shader reads are vertex attributes.
+        let vertex_interface = module.entry_points.iter().filter(|(_,entry_point,_)|entry_point.stage == ShaderStageFlags::VERTEX).flat_map(|(_,_,interface)|interface.iter().copied()).collect::<HashSet<_>>();
+        let mut reflection = Self{version , ..Default::default()};
+        for (function,entry_point,_) in module.entry_points.iter(){
+            let mut entry_point = entry_point.clone();
+            entry_point.local_size = module.local_sizes.get(function).copied();
+            reflection.entry_points.push(entry_point);
+        }
+        for &(id,pointer_type,storage_class) in module.variables.iter(){
+            let pointee = match module.types.get(&pointer_type){
+                Some(Type::Pointer{pointee})=>*pointee,
+                _=>return Err(format!("variable {} doesn't have a pointer type",id)),
+            };
+            let decorations = module.decorations.get(&id);
+            match storage_class{
+                STORAGE_CLASS_UNIFORM_CONSTANT | STORAGE_CLASS_UNIFORM | STORAGE_CLASS_STORAGE_BUFFER=>{
+                    let (set,binding) = match decorations.map(|d|(d.descriptor_set,d.binding)){
+                        Some((Some(set),Some(binding)))=>(set,binding),
+                        _=>continue,
+                    };
+                    let (element,count) = module.array_element(pointee);
+                    let descriptor_type = module.descriptor_type(element, storage_class).ok_or_else(||format!("the type of binding {} in set {} isn't a descriptor",binding,set))?;
+                    reflection.descriptor_bindings.push(DescriptorBinding{set , binding , descriptor_type , count , stages , name : module.name(id, element)});
+                }
+                STORAGE_CLASS_PUSH_CONSTANT=>{
+                    let (offset,size) = module.struct_range(pointee);
+                    reflection.push_constants = Some(PushConstantBlock{stages , offset , size : size - offset});
+                }
+                STORAGE_CLASS_INPUT if vertex_interface.contains(&id)=>{
+                    let location = match decorations{
+                        Some(decorations) if !decorations.built_in=>match decorations.location{Some(location)=>location,None=>continue},
+                        _=>continue,
+                    };
+                    let (format,locations) = module.vertex_format(pointee).ok_or_else(||format!("the type of vertex input {} is not supported",location))?;
+                    for i in 0..locations{reflection.vertex_inputs.push(VertexInput{location : location + i , format , name : module.name(id, pointee)})}
+                }
+                _=>{},
+            }
+        }
+        for &(id,result_type) in module.spec_constants.iter(){
+            if let Some(spec_id) = module.decorations.get(&id).and_then(|d|d.spec_id){
+                let size = match module.types.get(&result_type){Some(Type::Scalar{width , ..})=>width / 8,_=>4};
+                reflection.specialization_constants.push(SpecializationConstant{id : spec_id , size , name : module.names.get(&id).cloned().unwrap_or_default()});
+            }
+        }
+        reflection.descriptor_bindings.sort_by_key(|b|(b.set,b.binding));
+        reflection.vertex_inputs.sort_by_key(|i|i.location);
+        reflection.specialization_constants.sort_by_key(|c|c.id);
+        return Ok(reflection);
+    }
+    ///The entry point with the given name.
+    pub fn entry_point(&self , name : &str)->Option<&EntryPoint>{
+        return self.entry_points.iter().find(|entry_point|entry_point.name == name);
+    }
+    ///Every stage of the entry points.
+    pub fn stages(&self)->ShaderStageFlags{
+        return self.entry_points.iter().fold(ShaderStageFlags::empty(),|stages,entry_point|stages | entry_point.stage);
+    }
+}
+impl Module{
+    fn parse(mut words : &[u32])->Result<Self,String>{
+        let mut module = Self::default();
+        while !words.is_empty(){
+            let word_count = (words[0] >> 16) as usize;
+            let opcode = words[0] & 0xFFFF;
+            if word_count == 0 || word_count > words.len(){return Err(format!("instruction {} has an invalid length",opcode))}
+            let operands = &words[1..word_count];
+            words = &words[word_count..];
+            let operand = |i : usize|operands.get(i).copied().ok_or_else(||format!("instruction {} is missing operands",opcode));
+            match opcode{
+                OP_NAME=>{module.names.insert(operand(0)?, string(&operands[1..]));}
+                OP_ENTRY_POINT=>{
+                    let execution_model = operand(0)?;
+                    let stage = stage(execution_model).ok_or_else(||format!("execution model {} is not supported",execution_model))?;
+                    let function = operand(1)?;
+                    let name_and_interface = operands.get(2..).unwrap_or_default();
+                    let name = string(name_and_interface);
+                    let interface = name_and_interface.get(string_words(name_and_interface)..).unwrap_or_default().to_vec();
+                    module.entry_points.push((function,EntryPoint{name , stage , local_size : None},interface));
+                }
+                OP_EXECUTION_MODE if operand(1)? == EXECUTION_MODE_LOCAL_SIZE=>{
+                    module.local_sizes.insert(operand(0)?, [operand(2)?,operand(3)?,operand(4)?]);
+                }
+                OP_TYPE_BOOL=>{module.types.insert(operand(0)?, Type::Scalar{float : false , signed : false , width : 32});}
+                OP_TYPE_INT=>{module.types.insert(operand(0)?, Type::Scalar{float : false , signed : operand(2)? == 1 , width : operand(1)?});}
+                OP_TYPE_FLOAT=>{module.types.insert(operand(0)?, Type::Scalar{float : true , signed : true , width : operand(1)?});}
+                OP_TYPE_VECTOR=>{module.types.insert(operand(0)?, Type::Vector{component : operand(1)? , count : operand(2)?});}
+                OP_TYPE_MATRIX=>{module.types.insert(operand(0)?, Type::Matrix{column : operand(1)? , count : operand(2)?});}
+                OP_TYPE_IMAGE=>{module.types.insert(operand(0)?, Type::Image{dim : operand(2)? , sampled : operand(6)?});}
+                OP_TYPE_SAMPLER=>{module.types.insert(operand(0)?, Type::Sampler);}
+                OP_TYPE_SAMPLED_IMAGE=>{module.types.insert(operand(0)?, Type::SampledImage);}
+                OP_TYPE_ACCELERATION_STRUCTURE=>{module.types.insert(operand(0)?, Type::AccelerationStructure);}
+                //The length is resolved later, constants may be declared after the array type refers to them.
+                OP_TYPE_ARRAY=>{module.types.insert(operand(0)?, Type::Array{element : operand(1)? , length : operand(2)?});}
+                OP_TYPE_RUNTIME_ARRAY=>{module.types.insert(operand(0)?, Type::RuntimeArray{element : operand(1)?});}
+                OP_TYPE_STRUCT=>{
+                    module.types.insert(operand(0)?, Type::Struct);
+                    module.struct_members.insert(operand(0)?, operands[1..].to_vec());
+                }
+                OP_TYPE_POINTER=>{module.types.insert(operand(0)?, Type::Pointer{pointee : operand(2)?});}
+                OP_CONSTANT | OP_SPEC_CONSTANT=>{
+                    module.constants.insert(operand(1)?, operand(2)?);
+                    if opcode == OP_SPEC_CONSTANT{module.spec_constants.push((operand(1)?,operand(0)?))}
+                }
+                OP_SPEC_CONSTANT_TRUE | OP_SPEC_CONSTANT_FALSE=>module.spec_constants.push((operand(1)?,operand(0)?)),
+                OP_VARIABLE=>module.variables.push((operand(1)?,operand(0)?,operand(2)?)),
+                OP_DECORATE=>{
+                    let decorations = module.decorations.entry(operand(0)?).or_default();
+                    match operand(1)?{
+                        DECORATION_SPEC_ID=>decorations.spec_id = Some(operand(2)?),
+                        DECORATION_BUFFER_BLOCK=>decorations.buffer_block = true,
+                        DECORATION_ARRAY_STRIDE=>decorations.array_stride = Some(operand(2)?),
+                        DECORATION_BUILT_IN=>decorations.built_in = true,
+                        DECORATION_LOCATION=>decorations.location = Some(operand(2)?),
+                        DECORATION_BINDING=>decorations.binding = Some(operand(2)?),
+                        DECORATION_DESCRIPTOR_SET=>decorations.descriptor_set = Some(operand(2)?),
+                        _=>{},
+                    }
+                }
+                OP_MEMBER_DECORATE=>{
+                    let decorations = module.member_decorations.entry((operand(0)?,operand(1)?)).or_default();
+                    match operand(2)?{
+                        DECORATION_OFFSET=>decorations.offset = Some(operand(3)?),
+                        DECORATION_MATRIX_STRIDE=>decorations.matrix_stride = Some(operand(3)?),
+                        _=>{},
+                    }
+                }
+                _=>{},
+            }
+        }
+        return Ok(module);
+    }
+    ///The name of a variable, or of its type for blocks declared without an instance name.
+    fn name(&self , variable : u32 , type_id : u32)->String{
+        match self.names.get(&variable){
+            Some(name) if !name.is_empty()=>return name.clone(),
+            _=>return self.names.get(&type_id).cloned().unwrap_or_default(),
+        }
+    }
+    ///Strips one level of arrays, returning the element type and the number of elements.
+    fn array_element(&self , type_id : u32)->(u32,u32){
+        match self.types.get(&type_id){
+            Some(Type::Array{element , length})=>return (*element,self.constants.get(length).copied().unwrap_or(1)),
+            Some(Type::RuntimeArray{element})=>return (*element,0),
+            _=>return (type_id,1),
+        }
+    }
+    fn descriptor_type(&self , type_id : u32 , storage_class : u32)->Option<DescriptorType>{
+        let decorations = self.decorations.get(&type_id);
+        match (self.types.get(&type_id)?,storage_class){
+            (Type::Sampler,_)=>return Some(DescriptorType::SAMPLER),
+            (Type::SampledImage,_)=>return Some(DescriptorType::COMBINED_IMAGE_SAMPLER),
+            (Type::AccelerationStructure,_)=>return Some(DescriptorType::ACCELERATION_STRUCTURE_KHR),
+            (Type::Image{dim : DIM_BUFFER , sampled : 2},_)=>return Some(DescriptorType::STORAGE_TEXEL_BUFFER),
+            (Type::Image{dim : DIM_BUFFER , ..},_)=>return Some(DescriptorType::UNIFORM_TEXEL_BUFFER),
+            (Type::Image{dim : DIM_SUBPASS_DATA , ..},_)=>return Some(DescriptorType::INPUT_ATTACHMENT),
+            (Type::Image{sampled : 2 , ..},_)=>return Some(DescriptorType::STORAGE_IMAGE),
+            (Type::Image{..},_)=>return Some(DescriptorType::SAMPLED_IMAGE),
+            (Type::Struct,STORAGE_CLASS_STORAGE_BUFFER)=>return Some(DescriptorType::STORAGE_BUFFER),
+            //Before SPIR-V 1.3 storage buffers are uniform blocks decorated with BufferBlock.
+            (Type::Struct,STORAGE_CLASS_UNIFORM) if decorations.is_some_and(|d|d.buffer_block)=>return Some(DescriptorType::STORAGE_BUFFER),
+            (Type::Struct,STORAGE_CLASS_UNIFORM)=>return Some(DescriptorType::UNIFORM_BUFFER),
+            _=>return None,
+        }
+    }
+    ///The size of a type in bytes, using the offset and stride decorations of blocks.
+    fn size(&self , type_id : u32 , matrix_stride : Option<u32>)->u32{
+        match self.types.get(&type_id){
+            Some(Type::Scalar{width , ..})=>return width / 8,
+            Some(Type::Vector{component , count})=>return self.size(*component, None) * count,
+            Some(Type::Matrix{column , count})=>return matrix_stride.unwrap_or_else(||self.size(*column, None)) * count,
+            Some(Type::Array{element , length})=>{
+                let stride = self.decorations.get(&type_id).and_then(|d|d.array_stride).unwrap_or_else(||self.size(*element, matrix_stride));
+                return stride * self.constants.get(length).copied().unwrap_or(1);
+            }
+            Some(Type::Struct)=>return self.struct_range(type_id).1,
+            _=>return 0,
+        }
+    }
+    ///The offset of the first member of a struct and the end of its last member.
+    fn struct_range(&self , type_id : u32)->(u32,u32){
+        let members = match self.struct_members.get(&type_id){Some(members)=>members,None=>return (0,0)};
+        let mut range = (u32::MAX,0);
+        for (i,&member) in members.iter().enumerate(){
+            let decorations = self.member_decorations.get(&(type_id,i as u32));
+            let offset = decorations.and_then(|d|d.offset).unwrap_or(range.1);
+            range.0 = range.0.min(offset);
+            range.1 = range.1.max(offset + self.size(member, decorations.and_then(|d|d.matrix_stride)));
+        }
+        if range.0 == u32::MAX{range.0 = 0}
+        return range;
+    }
+    ///The vertex attribute format of an input type and the number of locations it takes.
+    fn vertex_format(&self , type_id : u32)->Option<(Format,u32)>{
+        let (component,count,locations) = match self.types.get(&type_id)?{
+            Type::Scalar{..}=>(type_id,1,1),
+            Type::Vector{component , count}=>(*component,*count,1),
+            Type::Matrix{column , count}=>match self.types.get(column)?{
+                Type::Vector{component , count : rows}=>(*component,*rows,*count),
+                _=>return None,
+            },
+            _=>return None,
+        };
+        let format = match (self.types.get(&component)?,count){
+            (Type::Scalar{float : true , width : 32 , ..},1)=>Format::R32_SFLOAT,
+            (Type::Scalar{float : true , width : 32 , ..},2)=>Format::R32G32_SFLOAT,
+            (Type::Scalar{float : true , width : 32 , ..},3)=>Format::R32G32B32_SFLOAT,
+            (Type::Scalar{float : true , width : 32 , ..},4)=>Format::R32G32B32A32_SFLOAT,
+            (Type::Scalar{float : true , width : 64 , ..},1)=>Format::R64_SFLOAT,
+            (Type::Scalar{float : true , width : 64 , ..},2)=>Format::R64G64_SFLOAT,
+            (Type::Scalar{float : true , width : 64 , ..},3)=>Format::R64G64B64_SFLOAT,
+            (Type::Scalar{float : true , width : 64 , ..},4)=>Format::R64G64B64A64_SFLOAT,
+            (Type::Scalar{float : false , signed : true , width : 32},1)=>Format::R32_SINT,
+            (Type::Scalar{float : false , signed : true , width : 32},2)=>Format::R32G32_SINT,
+            (Type::Scalar{float : false , signed : true , width : 32},3)=>Format::R32G32B32_SINT,
+            (Type::Scalar{float : false , signed : true , width : 32},4)=>Format::R32G32B32A32_SINT,
+            (Type::Scalar{float : false , signed : false , width : 32},1)=>Format::R32_UINT,
+            (Type::Scalar{float : false , signed : false , width : 32},2)=>Format::R32G32_UINT,
+            (Type::Scalar{float : false , signed : false , width : 32},3)=>Format::R32G32B32_UINT,
+            (Type::Scalar{float : false , signed : false , width : 32},4)=>Format::R32G32B32A32_UINT,
+            _=>return None,
+        };
+        return Some((format,locations));
+    }
+}
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    const VERTEX : u32 = 0;
+    const FRAGMENT : u32 = 4;
+    const GL_COMPUTE : u32 = 5;
+    const DECORATION_BLOCK : u32 = 2;
+    const BUILT_IN_VERTEX_INDEX : u32 = 42;
+
+    fn op(opcode : u32 , operands : &[u32])->Vec<u32>{
+        let mut words = vec![((operands.len() as u32 + 1) << 16) | opcode];
+        words.extend_from_slice(operands);
+        return words;
+    }
+    fn literal(string : &str)->Vec<u32>{
+        let mut bytes = string.as_bytes().to_vec();
+        bytes.resize((bytes.len() + 1).div_ceil(4) * 4, 0);
+        return bytes.chunks_exact(4).map(|word|u32::from_le_bytes([word[0],word[1],word[2],word[3]])).collect();
+    }
+    fn entry_point(execution_model : u32 , function : u32 , name : &str , interface : &[u32])->Vec<u32>{
+        let mut operands = vec![execution_model,function];
+        operands.extend(literal(name));
+        operands.extend_from_slice(interface);
+        return op(OP_ENTRY_POINT, &operands);
+    }
+    fn name(id : u32 , name : &str)->Vec<u32>{
+        let mut operands = vec![id];
+        operands.extend(literal(name));
+        return op(OP_NAME, &operands);
+    }
+    fn module(minor : u32 , instructions : &[Vec<u32>])->Vec<u32>{
+        let mut words = vec![MAGIC,(1 << 16) | (minor << 8),0,100,0];
+        for instruction in instructions{words.extend_from_slice(instruction)}
+        return words;
+    }
+
+    #[test]
+    fn bindings_and_arrays(){
+        let words = module(0, &[
+            entry_point(FRAGMENT, 1, "main", &[]),
+            name(11, "Globals"),
+            name(22, "textures"),
+            op(OP_DECORATE, &[11,DECORATION_BLOCK]),
+            op(OP_MEMBER_DECORATE, &[11,0,DECORATION_OFFSET,0]),
+            op(OP_DECORATE, &[13,DECORATION_DESCRIPTOR_SET,0]),
+            op(OP_DECORATE, &[13,DECORATION_BINDING,1]),
+            op(OP_DECORATE, &[22,DECORATION_DESCRIPTOR_SET,1]),
+            op(OP_DECORATE, &[22,DECORATION_BINDING,0]),
+            op(OP_DECORATE, &[33,DECORATION_DESCRIPTOR_SET,0]),
+            op(OP_DECORATE, &[33,DECORATION_BINDING,0]),
+            op(OP_TYPE_FLOAT, &[2,32]),
+            op(OP_TYPE_INT, &[3,32,0]),
+            op(OP_TYPE_VECTOR, &[4,2,4]),
+            op(OP_TYPE_STRUCT, &[11,4]),
+            op(OP_TYPE_POINTER, &[12,STORAGE_CLASS_UNIFORM,11]),
+            op(OP_VARIABLE, &[12,13,STORAGE_CLASS_UNIFORM]),
+            op(OP_TYPE_IMAGE, &[18,2,1,0,0,0,1,0]),
+            op(OP_TYPE_SAMPLED_IMAGE, &[19,18]),
+            op(OP_TYPE_ARRAY, &[20,19,5]),
+            op(OP_CONSTANT, &[3,5,4]),
+            op(OP_TYPE_POINTER, &[21,STORAGE_CLASS_UNIFORM_CONSTANT,20]),
+            op(OP_VARIABLE, &[21,22,STORAGE_CLASS_UNIFORM_CONSTANT]),
+            op(OP_TYPE_RUNTIME_ARRAY, &[30,4]),
+            op(OP_TYPE_STRUCT, &[31,30]),
+            op(OP_TYPE_POINTER, &[32,STORAGE_CLASS_STORAGE_BUFFER,31]),
+            op(OP_VARIABLE, &[32,33,STORAGE_CLASS_STORAGE_BUFFER]),
+        ]);
+        let reflection = ShaderReflection::new(&words).unwrap();
+        let bindings = reflection.descriptor_bindings.iter().map(|b|(b.set,b.binding,b.descriptor_type,b.count,b.name.as_str())).collect::<Vec<_>>();
+        assert_eq!(bindings, vec![
+            (0,0,DescriptorType::STORAGE_BUFFER,1,""),
+            (0,1,DescriptorType::UNIFORM_BUFFER,1,"Globals"),
+            (1,0,DescriptorType::COMBINED_IMAGE_SAMPLER,4,"textures"),
+        ]);
+        assert!(reflection.descriptor_bindings.iter().all(|b|b.stages == ShaderStageFlags::FRAGMENT));
+    }
+    #[test]
+    fn push_constants_span_their_members(){
+        let words = module(5, &[
+            entry_point(VERTEX, 1, "main", &[]),
+            op(OP_MEMBER_DECORATE, &[11,0,DECORATION_OFFSET,16]),
+            op(OP_MEMBER_DECORATE, &[11,1,DECORATION_OFFSET,32]),
+            op(OP_MEMBER_DECORATE, &[11,1,DECORATION_MATRIX_STRIDE,16]),
+            op(OP_TYPE_FLOAT, &[2,32]),
+            op(OP_TYPE_VECTOR, &[3,2,3]),
+            op(OP_TYPE_MATRIX, &[4,3,4]),
+            op(OP_TYPE_STRUCT, &[11,2,4]),
+            op(OP_TYPE_POINTER, &[12,STORAGE_CLASS_PUSH_CONSTANT,11]),
+            op(OP_VARIABLE, &[12,13,STORAGE_CLASS_PUSH_CONSTANT]),
+        ]);
+        let reflection = ShaderReflection::new(&words).unwrap();
+        assert_eq!(reflection.push_constants, Some(PushConstantBlock{stages : ShaderStageFlags::VERTEX , offset : 16 , size : 80}));
+    }
+    #[test]
+    fn specialization_constants(){
+        let words = module(0, &[
+            entry_point(GL_COMPUTE, 1, "main", &[]),
+            name(10, "count"),
+            name(11, "enabled"),
+            op(OP_DECORATE, &[10,DECORATION_SPEC_ID,2]),
+            op(OP_DECORATE, &[11,DECORATION_SPEC_ID,0]),
+            op(OP_DECORATE, &[12,DECORATION_SPEC_ID,1]),
+            op(OP_TYPE_INT, &[2,32,1]),
+            op(OP_TYPE_BOOL, &[3]),
+            op(OP_TYPE_FLOAT, &[4,64]),
+            op(OP_SPEC_CONSTANT, &[2,10,8]),
+            op(OP_SPEC_CONSTANT_TRUE, &[3,11]),
+            op(OP_SPEC_CONSTANT, &[4,12,0,0]),
+        ]);
+        let reflection = ShaderReflection::new(&words).unwrap();
+        let constants = reflection.specialization_constants.iter().map(|c|(c.id,c.size,c.name.as_str())).collect::<Vec<_>>();
+        assert_eq!(constants, vec![(0,4,"enabled"),(1,8,""),(2,4,"count")]);
+    }
+    #[test]
+    fn matrix_vertex_inputs_take_a_location_per_column(){
+        let words = module(4, &[
+            entry_point(VERTEX, 1, "main", &[13,15,17]),
+            name(13, "position"),
+            name(15, "transform"),
+            op(OP_DECORATE, &[13,DECORATION_LOCATION,0]),
+            op(OP_DECORATE, &[15,DECORATION_LOCATION,1]),
+            op(OP_DECORATE, &[17,DECORATION_BUILT_IN,BUILT_IN_VERTEX_INDEX]),
+            op(OP_TYPE_FLOAT, &[2,32]),
+            op(OP_TYPE_VECTOR, &[3,2,3]),
+            op(OP_TYPE_VECTOR, &[4,2,4]),
+            op(OP_TYPE_MATRIX, &[5,4,4]),
+            op(OP_TYPE_INT, &[6,32,1]),
+            op(OP_TYPE_POINTER, &[12,STORAGE_CLASS_INPUT,3]),
+            op(OP_VARIABLE, &[12,13,STORAGE_CLASS_INPUT]),
+            op(OP_TYPE_POINTER, &[14,STORAGE_CLASS_INPUT,5]),
+            op(OP_VARIABLE, &[14,15,STORAGE_CLASS_INPUT]),
+            op(OP_TYPE_POINTER, &[16,STORAGE_CLASS_INPUT,6]),
+            op(OP_VARIABLE, &[16,17,STORAGE_CLASS_INPUT]),
+        ]);
+        let reflection = ShaderReflection::new(&words).unwrap();
+        let inputs = reflection.vertex_inputs.iter().map(|i|(i.location,i.format,i.name.as_str())).collect::<Vec<_>>();
+        assert_eq!(inputs, vec![
+            (0,Format::R32G32B32_SFLOAT,"position"),
+            (1,Format::R32G32B32A32_SFLOAT,"transform"),
+            (2,Format::R32G32B32A32_SFLOAT,"transform"),
+            (3,Format::R32G32B32A32_SFLOAT,"transform"),
+            (4,Format::R32G32B32A32_SFLOAT,"transform"),
+        ]);
+    }
+    #[test]
+    fn multiple_entry_points(){
+        let words = module(6, &[
+            entry_point(VERTEX, 1, "vs", &[13]),
+            entry_point(FRAGMENT, 2, "fragment", &[15]),
+            entry_point(GL_COMPUTE, 3, "main", &[]),
+            op(OP_EXECUTION_MODE, &[3,EXECUTION_MODE_LOCAL_SIZE,8,4,1]),
+            name(13, "position"),
+            name(15, "colour"),
+            op(OP_DECORATE, &[13,DECORATION_LOCATION,0]),
+            op(OP_DECORATE, &[15,DECORATION_LOCATION,0]),
+            op(OP_TYPE_FLOAT, &[4,32]),
+            op(OP_TYPE_VECTOR, &[5,4,2]),
+            op(OP_TYPE_VECTOR, &[6,4,4]),
+            op(OP_TYPE_POINTER, &[12,STORAGE_CLASS_INPUT,5]),
+            op(OP_VARIABLE, &[12,13,STORAGE_CLASS_INPUT]),
+            op(OP_TYPE_POINTER, &[14,STORAGE_CLASS_INPUT,6]),
+            op(OP_VARIABLE, &[14,15,STORAGE_CLASS_INPUT]),
+        ]);
+        let reflection = ShaderReflection::new(&words).unwrap();
+        assert_eq!(reflection.version, (1,6));
+        assert_eq!(reflection.entry_points, vec![
+            EntryPoint{name : "vs".to_string() , stage : ShaderStageFlags::VERTEX , local_size : None},
+            EntryPoint{name : "fragment".to_string() , stage : ShaderStageFlags::FRAGMENT , local_size : None},
+            EntryPoint{name : "main".to_string() , stage : ShaderStageFlags::COMPUTE , local_size : Some([8,4,1])},
+        ]);
+        assert_eq!(reflection.stages(), ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT | ShaderStageFlags::COMPUTE);
+        assert_eq!(reflection.vertex_inputs, vec![VertexInput{location : 0 , format : Format::R32G32_SFLOAT , name : "position".to_string()}]);
+    }
+    #[test]
+    fn rejects_unsupported_versions(){
+        let words = module(7, &[entry_point(VERTEX, 1, "main", &[])]);
+        assert!(ShaderReflection::new(&words).is_err());
+        assert!(ShaderReflection::new(&module(6, &[])).is_err());
+    }
+    #[test]
+    fn rejects_malformed_modules(){
+        assert!(version(&[MAGIC,0x10000]).is_err());
+        assert!(ShaderReflection::new(&[]).is_err());
+        assert!(ShaderReflection::new(&[MAGIC,0x10000,0,0]).is_err());
+        let mut words = module(3, &[]);
+        words[0] = 0;
+        assert!(ShaderReflection::new(&words).is_err());
+        //An OpEntryPoint with only its execution model.
+        assert!(ShaderReflection::new(&module(3, &[op(OP_ENTRY_POINT, &[VERTEX])])).is_err());
+        //An OpEntryPoint without a name.
+        assert!(ShaderReflection::new(&module(3, &[op(OP_ENTRY_POINT, &[VERTEX,1])])).is_ok());
+    }
+}
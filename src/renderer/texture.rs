@@ -0,0 +1,421 @@
+use std::path::Path;
+
+use ash::{version::InstanceV1_0, vk::{self, BufferImageCopy, ComponentSwizzle, Extent3D, Format, FormatFeatureFlags, ImageAspectFlags, ImageLayout, ImageSubresourceLayers, ImageUsageFlags, Offset3D}};
+use ddsfile::{D3DFormat, Dds, DxgiFormat, MiscFlag};
+use log::{error,info,warn};
+
+use super::{Image, ImageDescription, ImageKind, Renderer, RendererError, UploadDestination, UploadHandle, bc};
+
+///The pixels of a texture, with every mip level and layer it was stored with.
+///Mip levels are stored largest first, the layers of a level are consecutive, cube maps have six layers per cube.
+#[derive(Clone, Debug)]
+pub struct TextureData{
+    kind : ImageKind,
+    format : Format,
+    mip_levels : u32,
+    array_layers : u32,
+    ///Where the red, green, blue and alpha components are read from when sampled.
+    swizzle : [ComponentSwizzle;4],
+    data : Vec<u8>,
+    ///The offset of every mip level in `data`, aligned for buffer to image copies.
+    level_offsets : Vec<usize>,
+}
+impl TextureData{
+    ///Loads a PNG, JPEG, KTX2 or DDS file, the container is detected from the contents.
+    pub fn from_file<P : AsRef<Path>>(path : P)->Result<Self,RendererError>{
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|e|{
+            error!("Failed to read texture {}, {}.",path.display(),e);
+            RendererError::LoadTexture(format!("failed to read {}: {}",path.display(),e))
+        })?;
+        return Self::from_bytes(&bytes);
+    }
+    ///Decodes a PNG, JPEG, KTX2 or DDS file in memory.
+    ///PNG and JPEG images are decoded to sRGB RGBA8 with a single mip level, KTX2 and DDS textures keep their format, mip levels and layers.
+    pub fn from_bytes(bytes : &[u8])->Result<Self,RendererError>{
+        if bytes.starts_with(&[0xAB,0x4B,0x54,0x58,0x20,0x32,0x30,0xBB]){return Self::from_ktx2(bytes)}
+        if bytes.starts_with(b"DDS "){return Self::from_dds(bytes)}
+        if bytes.starts_with(b"\x89PNG") || bytes.starts_with(&[0xFF,0xD8,0xFF]){
+            let image = image::load_from_memory(bytes).map_err(|e|{
+                error!("Failed to decode texture, {}.",e);
+                RendererError::LoadTexture(e.to_string())
+            })?.to_rgba8();
+            let (width,height) = image.dimensions();
+            return Ok(Self::new_rgba8(width, height, image.into_raw()));
+        }
+        error!("Unknown texture container.");
+        return Err(RendererError::LoadTexture("the data is not a PNG, JPEG, KTX2 or DDS file".to_string()));
+    }
+    ///A single mip 2D texture from sRGB RGBA8 pixels.
+    pub fn new_rgba8(width : u32 , height : u32 , pixels : Vec<u8>)->Self{
+        return Self{kind : ImageKind::Image2D{width , height} , format : Format::R8G8B8A8_SRGB , mip_levels : 1 , array_layers : 1 , swizzle : IDENTITY_SWIZZLE , data : pixels , level_offsets : vec!(0)};
+    }
+    fn from_ktx2(bytes : &[u8])->Result<Self,RendererError>{
+        let reader = ktx2::Reader::new(bytes).map_err(|e|{
+            error!("Failed to parse KTX2 texture, {:?}.",e);
+            RendererError::LoadTexture(format!("invalid KTX2 file: {:?}",e))
+        })?;
+        let header = reader.header();
+        if let Some(scheme) = header.supercompression_scheme{
+            error!("KTX2 supercompression {:?} is not supported.",scheme);
+            return Err(RendererError::LoadTexture(format!("unsupported KTX2 supercompression {:?}",scheme)));
+        }
+        let format = match header.format{
+            Some(format)=>Format::from_raw(format.0.get() as i32),
+            None=>{
+                error!("KTX2 textures without a Vulkan format are not supported.");
+                return Err(RendererError::LoadTexture("KTX2 texture without a Vulkan format".to_string()));
+            }
+        };
+        let (width,height,depth) = (header.pixel_width,header.pixel_height.max(1),header.pixel_depth);
+        let kind = if depth > 0{ImageKind::Image3D{width , height , depth}}
+        else if header.face_count == 6{ImageKind::Cube{size : width}}
+        else{ImageKind::Image2D{width , height}};
+        let array_layers = header.layer_count.max(1) * header.face_count;
+        let mut texture = Self::empty(kind, format, header.level_count.max(1), array_layers)?;
+        for (level,data) in reader.levels().enumerate(){
+            texture.push_level(level as u32, data)?;
+        }
+        return Ok(texture);
+    }
+    fn from_dds(bytes : &[u8])->Result<Self,RendererError>{
+        let dds = Dds::read(bytes).map_err(|e|{
+            error!("Failed to parse DDS texture, {}.",e);
+            RendererError::LoadTexture(format!("invalid DDS file: {}",e))
+        })?;
+        let format = dds_format(&dds).ok_or_else(||{
+            error!("The DDS texture has an unsupported format.");
+            RendererError::LoadTexture("unsupported DDS format".to_string())
+        })?;
+        let (width,height,depth) = (dds.get_width(),dds.get_height().max(1),dds.get_depth());
+        let cube = match &dds.header10{
+            Some(header10)=>header10.misc_flag.contains(MiscFlag::TEXTURECUBE),
+            None=>dds.header.caps2.contains(ddsfile::Caps2::CUBEMAP),
+        };
+        let kind = if depth > 1{ImageKind::Image3D{width , height , depth}}
+        else if cube{ImageKind::Cube{size : width}}
+        else{ImageKind::Image2D{width , height}};
+        //The array size of DX10 cube maps counts cubes, not faces.
+        let array_layers = match &dds.header10{
+            Some(header10) if cube=>header10.array_size.max(1) * 6,
+            _=>dds.get_num_array_layers().max(1),
+        };
+        let mip_levels = dds.get_num_mipmap_levels().max(1);
+        let mut texture = Self::empty(kind, format, mip_levels, array_layers)?;
+        //Alpha only textures are stored in the red channel and read back as alpha.
+        if dds.get_dxgi_format().is_none() && dds.get_d3d_format() == Some(D3DFormat::A8){
+            texture.swizzle = [ComponentSwizzle::ZERO,ComponentSwizzle::ZERO,ComponentSwizzle::ZERO,ComponentSwizzle::R];
+        }
+        //DDS stores the mip chain of each layer in turn, gather each level from every layer.
+        let layer_size = (0..mip_levels).map(|level|texture.level_size(level)).sum::<usize>() / array_layers as usize;
+        for level in 0..mip_levels{
+            let level_offset = (0..level).map(|level|texture.level_size(level) / array_layers as usize).sum::<usize>();
+            let size = texture.level_size(level) / array_layers as usize;
+            let mut level_data = Vec::with_capacity(size * array_layers as usize);
+            for layer in 0..array_layers as usize{
+                let offset = layer * layer_size + level_offset;
+                let data = dds.data.get(offset..offset + size).ok_or_else(||{
+                    error!("The DDS texture is truncated.");
+                    RendererError::LoadTexture("truncated DDS file".to_string())
+                })?;
+                level_data.extend_from_slice(data);
+            }
+            texture.push_level(level, &level_data)?;
+        }
+        return Ok(texture);
+    }
+    fn empty(kind : ImageKind , format : Format , mip_levels : u32 , array_layers : u32)->Result<Self,RendererError>{
+        if format_block(format).is_none(){
+            error!("The texture format {:?} is not supported.",format);
+            return Err(RendererError::UnsupportedTextureFormat(format));
+        }
+        return Ok(Self{kind , format , mip_levels , array_layers , swizzle : IDENTITY_SWIZZLE , data : vec!() , level_offsets : vec!()});
+    }
+    ///Appends the data of the next mip level, checking its size.
+    fn push_level(&mut self , level : u32 , data : &[u8])->Result<(),RendererError>{
+        let size = self.level_size(level);
+        if data.len() < size{
+            error!("Mip level {} has {} bytes, {} are needed.",level,data.len(),size);
+            return Err(RendererError::LoadTexture(format!("mip level {} is truncated",level)));
+        }
+        //Buffer to image copies need offsets that are a multiple of 4 and of the block size.
+        let (_,_,block_bytes) = format_block(self.format).expect("checked when the texture was created");
+        let alignment = block_bytes.max(4);
+        self.data.resize(self.data.len().div_ceil(alignment) * alignment, 0);
+        self.level_offsets.push(self.data.len());
+        self.data.extend_from_slice(&data[..size]);
+        return Ok(());
+    }
+    pub fn kind(&self)->ImageKind{
+        return self.kind;
+    }
+    pub fn format(&self)->Format{
+        return self.format;
+    }
+    pub fn mip_levels(&self)->u32{
+        return self.mip_levels;
+    }
+    pub fn array_layers(&self)->u32{
+        return self.array_layers;
+    }
+    ///Where the red, green, blue and alpha components are read from when sampled.
+    pub fn swizzle(&self)->[ComponentSwizzle;4]{
+        return self.swizzle;
+    }
+    ///The pixels of every layer of a mip level.
+    pub fn level(&self , level : u32)->&[u8]{
+        let offset = self.level_offsets[level as usize];
+        return &self.data[offset..offset + self.level_size(level)];
+    }
+    ///The size of a mip level in pixels.
+    pub fn level_extent(&self , level : u32)->Extent3D{
+        let extent = ImageDescription::new(self.kind, self.format, ImageUsageFlags::empty()).extent();
+        return Extent3D{width : (extent.width >> level).max(1) , height : (extent.height >> level).max(1) , depth : (extent.depth >> level).max(1)};
+    }
+    ///The size of every layer of a mip level in bytes.
+    fn level_size(&self , level : u32)->usize{
+        let (block_width,block_height,block_bytes) = format_block(self.format).expect("checked when the texture was created");
+        let extent = self.level_extent(level);
+        let blocks = extent.width.div_ceil(block_width) as usize * extent.height.div_ceil(block_height) as usize * extent.depth as usize;
+        return blocks * block_bytes * self.array_layers as usize;
+    }
+    ///Interprets sRGB data as linear, for textures that don't hold colors like normal maps.
+    pub fn into_linear(mut self)->Self{
+        self.format = match self.format{
+            Format::R8G8B8A8_SRGB=>Format::R8G8B8A8_UNORM,
+            Format::B8G8R8A8_SRGB=>Format::B8G8R8A8_UNORM,
+            Format::BC1_RGB_SRGB_BLOCK=>Format::BC1_RGB_UNORM_BLOCK,
+            Format::BC1_RGBA_SRGB_BLOCK=>Format::BC1_RGBA_UNORM_BLOCK,
+            Format::BC2_SRGB_BLOCK=>Format::BC2_UNORM_BLOCK,
+            Format::BC3_SRGB_BLOCK=>Format::BC3_UNORM_BLOCK,
+            Format::BC7_SRGB_BLOCK=>Format::BC7_UNORM_BLOCK,
+            format=>format,
+        };
+        return self;
+    }
+    ///Decompresses every mip level and layer of a BC1 to BC5 texture to RGBA8, `None` for other formats including BC6H and BC7.
+    pub fn decompress(&self)->Option<Self>{
+        let format = bc::decompressed_format(self.format)?;
+        let mut texture = Self{kind : self.kind , format , mip_levels : self.mip_levels , array_layers : self.array_layers , swizzle : self.swizzle , data : vec!() , level_offsets : vec!()};
+        for level in 0..self.mip_levels{
+            let extent = self.level_extent(level);
+            let images = self.array_layers * extent.depth;
+            let compressed = self.level(level);
+            let image_size = compressed.len() / images as usize;
+            let mut pixels = Vec::with_capacity(extent.width as usize * extent.height as usize * 4 * images as usize);
+            for image in compressed.chunks_exact(image_size){
+                pixels.extend_from_slice(&bc::decompress(self.format, extent.width, extent.height, image)?);
+            }
+            texture.push_level(level, &pixels).ok()?;
+        }
+        return Some(texture);
+    }
+}
+const IDENTITY_SWIZZLE : [ComponentSwizzle;4] = [ComponentSwizzle::R,ComponentSwizzle::G,ComponentSwizzle::B,ComponentSwizzle::A];
+///Maps the format of a DDS file to a Vulkan format.
+fn dds_format(dds : &Dds)->Option<Format>{
+    if let Some(format) = dds.get_dxgi_format(){
+        let format = match format{
+            DxgiFormat::BC1_UNorm=>Format::BC1_RGBA_UNORM_BLOCK,
+            DxgiFormat::BC1_UNorm_sRGB=>Format::BC1_RGBA_SRGB_BLOCK,
+            DxgiFormat::BC2_UNorm=>Format::BC2_UNORM_BLOCK,
+            DxgiFormat::BC2_UNorm_sRGB=>Format::BC2_SRGB_BLOCK,
+            DxgiFormat::BC3_UNorm=>Format::BC3_UNORM_BLOCK,
+            DxgiFormat::BC3_UNorm_sRGB=>Format::BC3_SRGB_BLOCK,
+            DxgiFormat::BC4_UNorm=>Format::BC4_UNORM_BLOCK,
+            DxgiFormat::BC4_SNorm=>Format::BC4_SNORM_BLOCK,
+            DxgiFormat::BC5_UNorm=>Format::BC5_UNORM_BLOCK,
+            DxgiFormat::BC5_SNorm=>Format::BC5_SNORM_BLOCK,
+            DxgiFormat::BC6H_UF16=>Format::BC6H_UFLOAT_BLOCK,
+            DxgiFormat::BC6H_SF16=>Format::BC6H_SFLOAT_BLOCK,
+            DxgiFormat::BC7_UNorm=>Format::BC7_UNORM_BLOCK,
+            DxgiFormat::BC7_UNorm_sRGB=>Format::BC7_SRGB_BLOCK,
+            DxgiFormat::R8G8B8A8_UNorm=>Format::R8G8B8A8_UNORM,
+            DxgiFormat::R8G8B8A8_UNorm_sRGB=>Format::R8G8B8A8_SRGB,
+            DxgiFormat::B8G8R8A8_UNorm=>Format::B8G8R8A8_UNORM,
+            DxgiFormat::B8G8R8A8_UNorm_sRGB=>Format::B8G8R8A8_SRGB,
+            DxgiFormat::R8_UNorm=>Format::R8_UNORM,
+            DxgiFormat::R8G8_UNorm=>Format::R8G8_UNORM,
+            DxgiFormat::R16G16B16A16_Float=>Format::R16G16B16A16_SFLOAT,
+            DxgiFormat::R32G32B32A32_Float=>Format::R32G32B32A32_SFLOAT,
+            _=>return None,
+        };
+        return Some(format);
+    }
+    match dds.get_d3d_format()?{
+        D3DFormat::A8B8G8R8=>return Some(Format::R8G8B8A8_UNORM),
+        D3DFormat::A8R8G8B8=>return Some(Format::B8G8R8A8_UNORM),
+        D3DFormat::L8 | D3DFormat::A8=>return Some(Format::R8_UNORM),
+        D3DFormat::A16B16G16R16F=>return Some(Format::R16G16B16A16_SFLOAT),
+        D3DFormat::A32B32G32R32F=>return Some(Format::R32G32B32A32_SFLOAT),
+        _=>return None,
+    }
+}
+///The width and height of a block of the format in pixels and its size in bytes, `None` for formats textures can't be loaded in.
+fn format_block(format : Format)->Option<(u32,u32,usize)>{
+    match format{
+        Format::R8_UNORM | Format::R8_SNORM | Format::R8_SRGB=>return Some((1,1,1)),
+        Format::R8G8_UNORM | Format::R8G8_SNORM | Format::R16_SFLOAT | Format::R16_UNORM=>return Some((1,1,2)),
+        Format::R8G8B8A8_UNORM | Format::R8G8B8A8_SNORM | Format::R8G8B8A8_SRGB | Format::B8G8R8A8_UNORM | Format::B8G8R8A8_SRGB |
+        Format::A2B10G10R10_UNORM_PACK32 | Format::B10G11R11_UFLOAT_PACK32 | Format::E5B9G9R9_UFLOAT_PACK32 | Format::R16G16_SFLOAT | Format::R32_SFLOAT=>return Some((1,1,4)),
+        Format::R16G16B16A16_SFLOAT | Format::R16G16B16A16_UNORM | Format::R32G32_SFLOAT=>return Some((1,1,8)),
+        Format::R32G32B32A32_SFLOAT=>return Some((1,1,16)),
+        Format::BC1_RGB_UNORM_BLOCK | Format::BC1_RGB_SRGB_BLOCK | Format::BC1_RGBA_UNORM_BLOCK | Format::BC1_RGBA_SRGB_BLOCK |
+        Format::BC4_UNORM_BLOCK | Format::BC4_SNORM_BLOCK=>return Some((4,4,8)),
+        Format::BC2_UNORM_BLOCK | Format::BC2_SRGB_BLOCK | Format::BC3_UNORM_BLOCK | Format::BC3_SRGB_BLOCK | Format::BC5_UNORM_BLOCK | Format::BC5_SNORM_BLOCK |
+        Format::BC6H_UFLOAT_BLOCK | Format::BC6H_SFLOAT_BLOCK | Format::BC7_UNORM_BLOCK | Format::BC7_SRGB_BLOCK=>return Some((4,4,16)),
+        _=>return None,
+    }
+}
+impl Renderer{
+    ///Whether optimally tiled images of the format can be sampled and copied to on the GPU.
+    pub fn texture_format_supported(&self , format : Format)->bool{
+        let api_version = unsafe{self.instance.get_physical_device_properties(self.physical_device)}.api_version;
+        let properties = unsafe{self.instance.get_physical_device_format_properties(self.physical_device, format)};
+        //Vulkan 1.0 doesn't report transfer features, every format can be copied to there.
+        let features = if api_version < vk::make_version(1, 1, 0){FormatFeatureFlags::SAMPLED_IMAGE}else{FormatFeatureFlags::SAMPLED_IMAGE | FormatFeatureFlags::TRANSFER_DST};
+        return properties.optimal_tiling_features.contains(features);
+    }
+    ///Loads a texture file and uploads it, see `TextureData::from_file` and `Renderer::create_texture`.
+    pub fn load_texture<P : AsRef<Path>>(&mut self , path : P , destination : UploadDestination)->Result<(Image,UploadHandle),RendererError>{
+        let texture = TextureData::from_file(path.as_ref())?;
+        info!("Loaded texture {}, {:?} with {} mip levels and {} layers.",path.as_ref().display(),texture.format,texture.mip_levels,texture.array_layers);
+        return self.create_texture(&texture, destination);
+    }
+    ///Creates a sampled image for the texture and uploads every mip level and layer, leaving it in `SHADER_READ_ONLY_OPTIMAL`.
+    ///Block compressed formats the GPU can't sample are decompressed on the CPU when possible, BC6H and BC7 have no CPU decoder.
+    ///The image can be used once the returned upload has completed on the destination queue.
+    pub fn create_texture(&mut self , texture : &TextureData , destination : UploadDestination)->Result<(Image,UploadHandle),RendererError>{
+        let decompressed;
+        let texture = if self.texture_format_supported(texture.format){texture}else{
+            decompressed = texture.decompress().ok_or_else(||{
+                error!("The texture format {:?} is not supported by the GPU and can't be decompressed.",texture.format);
+                RendererError::UnsupportedTextureFormat(texture.format)
+            })?;
+            warn!("The texture format {:?} is not supported by the GPU, decompressed it to {:?}.",texture.format,decompressed.format);
+            &decompressed
+        };
+        if !self.texture_format_supported(texture.format){
+            error!("The texture format {:?} is not supported by the GPU.",texture.format);
+            return Err(RendererError::UnsupportedTextureFormat(texture.format));
+        }
+        let description = ImageDescription::new(texture.kind, texture.format, ImageUsageFlags::SAMPLED | ImageUsageFlags::TRANSFER_DST).mip_levels(texture.mip_levels).array_layers(texture.array_layers).swizzle(texture.swizzle);
+        let limits = unsafe{self.instance.get_physical_device_properties(self.physical_device)}.limits;
+        let extent = description.extent();
+        let max_dimension = match texture.kind{
+            ImageKind::Image2D{..}=>limits.max_image_dimension2_d,
+            ImageKind::Image3D{..}=>limits.max_image_dimension3_d,
+            ImageKind::Cube{..}=>limits.max_image_dimension_cube,
+        };
+        if extent.width.max(extent.height).max(extent.depth) > max_dimension || texture.array_layers > limits.max_image_array_layers{
+            error!("The texture is {}x{}x{} with {} layers, the GPU allows {} pixels and {} layers.",extent.width,extent.height,extent.depth,texture.array_layers,max_dimension,limits.max_image_array_layers);
+            return Err(RendererError::InvalidImageDescription(format!("the texture exceeds the GPU limits of {} pixels and {} layers",max_dimension,limits.max_image_array_layers)));
+        }
+        let image = self.create_image(&description)?;
+        let regions = (0..texture.mip_levels).map(|level|BufferImageCopy{
+            buffer_offset : texture.level_offsets[level as usize] as u64,
+            buffer_row_length : 0,
+            buffer_image_height : 0,
+            image_subresource : ImageSubresourceLayers{aspect_mask : ImageAspectFlags::COLOR , mip_level : level , base_array_layer : 0 , layer_count : texture.array_layers},
+            image_offset : Offset3D{x : 0 , y : 0 , z : 0},
+            image_extent : texture.level_extent(level),
+        }).collect::<Vec<_>>();
+        let range = image.subresource_range(ImageAspectFlags::COLOR);
+        match self.upload_to_image(&texture.data, image.handle(), &regions, range, ImageLayout::SHADER_READ_ONLY_OPTIMAL, destination){
+            Ok(upload)=>return Ok((image,upload)),
+            Err(e)=>{
+                self.destroy_image(image);
+                return Err(e);
+            }
+        }
+    }
+}
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use ddsfile::{AlphaMode, D3D10ResourceDimension, NewDxgiParams};
+
+    ///A 4x4 RGBA8 DDS array with two layers and three mip levels, every byte of a level of a layer is `layer * 16 + level`.
+    fn dds_array()->Vec<u8>{
+        let mut dds = Dds::new_dxgi(NewDxgiParams{
+            height : 4,
+            width : 4,
+            depth : None,
+            format : DxgiFormat::R8G8B8A8_UNorm,
+            mipmap_levels : Some(3),
+            array_layers : Some(2),
+            caps2 : None,
+            is_cubemap : false,
+            resource_dimension : D3D10ResourceDimension::Texture2D,
+            alpha_mode : AlphaMode::Unknown,
+        }).unwrap();
+        dds.data.clear();
+        for layer in 0..2u8{
+            for (level,size) in [64,16,4].iter().enumerate(){
+                dds.data.extend(std::iter::repeat_n(layer * 16 + level as u8, *size));
+            }
+        }
+        let mut bytes = vec!();
+        dds.write(&mut bytes).unwrap();
+        return bytes;
+    }
+    ///A 4x4 RGBA8 KTX2 cube map with three mip levels, every byte of a level is the level.
+    fn ktx2_cube()->Vec<u8>{
+        let sizes = [64 * 6,16 * 6,4 * 6];
+        let mut bytes = vec!(0xAB,0x4B,0x54,0x58,0x20,0x32,0x30,0xBB,0x0D,0x0A,0x1A,0x0A);
+        //Format, type size, width, height, depth, layers, faces, levels and supercompression.
+        for value in [vk::Format::R8G8B8A8_UNORM.as_raw() as u32,1,4,4,0,0,6,3,0].iter(){
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        //Empty data format descriptor, key/value data and supercompression data.
+        bytes.extend_from_slice(&[0;32]);
+        //Levels are stored smallest first after the level index.
+        let data_offset = bytes.len() + sizes.len() * 24;
+        for (level,size) in sizes.iter().enumerate(){
+            let offset = data_offset + sizes[level + 1..].iter().sum::<usize>();
+            for value in [offset as u64,*size as u64,*size as u64].iter(){
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        for (level,size) in sizes.iter().enumerate().rev(){
+            bytes.extend(std::iter::repeat_n(level as u8, *size));
+        }
+        return bytes;
+    }
+    #[test]
+    fn loads_dds_arrays_with_mip_chains(){
+        let texture = TextureData::from_bytes(&dds_array()).unwrap();
+        assert_eq!(texture.kind(), ImageKind::Image2D{width : 4 , height : 4});
+        assert_eq!(texture.format(), Format::R8G8B8A8_UNORM);
+        assert_eq!(texture.mip_levels(), 3);
+        assert_eq!(texture.array_layers(), 2);
+        for (level,size) in [64,16,4].iter().enumerate(){
+            let data = texture.level(level as u32);
+            assert_eq!(data.len(), size * 2);
+            assert!(data[..*size].iter().all(|&byte|byte == level as u8));
+            assert!(data[*size..].iter().all(|&byte|byte == 16 + level as u8));
+        }
+        assert_eq!(texture.level_extent(2), Extent3D{width : 1 , height : 1 , depth : 1});
+    }
+    #[test]
+    fn loads_ktx2_cube_maps_with_mip_chains(){
+        let texture = TextureData::from_bytes(&ktx2_cube()).unwrap();
+        assert_eq!(texture.kind(), ImageKind::Cube{size : 4});
+        assert_eq!(texture.format(), Format::R8G8B8A8_UNORM);
+        assert_eq!(texture.mip_levels(), 3);
+        assert_eq!(texture.array_layers(), 6);
+        for (level,size) in [64 * 6,16 * 6,4 * 6].iter().enumerate(){
+            let data = texture.level(level as u32);
+            assert_eq!(data.len(), *size);
+            assert!(data.iter().all(|&byte|byte == level as u8));
+        }
+    }
+    #[test]
+    fn truncated_files_fail_to_load(){
+        for file in [dds_array(),ktx2_cube()].iter(){
+            for length in 0..file.len(){
+                assert!(TextureData::from_bytes(&file[..length]).is_err(), "{} of {} bytes loaded",length,file.len());
+            }
+        }
+    }
+}
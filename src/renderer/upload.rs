@@ -0,0 +1,359 @@
+use ash::{Device, version::DeviceV1_0, vk::{AccessFlags, Buffer, BufferCopy, BufferCreateFlags, BufferCreateInfo, BufferImageCopy, BufferMemoryBarrier, BufferUsageFlags, CommandBuffer, CommandBufferAllocateInfo, CommandBufferBeginInfo, CommandBufferLevel, CommandBufferUsageFlags, CommandPool, CommandPoolCreateFlags, CommandPoolCreateInfo, DependencyFlags, DeviceSize, Fence, FenceCreateFlags, FenceCreateInfo, Image, ImageLayout, ImageMemoryBarrier, ImageSubresourceRange, PipelineStageFlags, QUEUE_FAMILY_IGNORED, Semaphore, SemaphoreCreateFlags, SemaphoreCreateInfo, SharingMode, StructureType, SubmitInfo}};
+use log::{error,debug,trace};
+
+use super::{Allocation, DeviceQueue, MemoryAllocator, MemoryLocation, Renderer, RendererError};
+
+///The queue that uses an uploaded resource, ownership of the resource is transferred to its family.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UploadDestination{
+    Graphics,
+    Compute,
+}
+///Identifies an upload, returned by `Renderer::upload_to_buffer` and `Renderer::upload_to_image`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct UploadHandle(u64);
+
+///What an upload writes to.
+enum UploadTarget<'a>{
+    Buffer{buffer : Buffer , offset : DeviceSize},
+    Image{image : Image , regions : &'a [BufferImageCopy] , range : ImageSubresourceRange , final_layout : ImageLayout},
+}
+///An upload that was submitted but may not have finished yet.
+struct PendingUpload{
+    id : u64,
+    staging_buffer : Buffer,
+    staging_allocation : Option<Allocation>,
+    transfer_pool : CommandPool,
+    transfer_command_buffer : CommandBuffer,
+    acquire_pool : CommandPool,
+    acquire_command_buffer : CommandBuffer,
+    ///Signaled by the transfer submission and waited on by the acquire submission, only used when ownership is transferred.
+    transferred : Semaphore,
+    ///Signaled when the last submission of the upload has completed.
+    complete : Fence,
+}
+impl PendingUpload{
+    unsafe fn destroy(mut self , device : &Device , allocator : &mut MemoryAllocator){
+        device.destroy_fence(self.complete, None);
+        device.destroy_semaphore(self.transferred, None);
+        if self.acquire_command_buffer != CommandBuffer::null(){device.free_command_buffers(self.acquire_pool, &[self.acquire_command_buffer])}
+        if self.transfer_command_buffer != CommandBuffer::null(){device.free_command_buffers(self.transfer_pool, &[self.transfer_command_buffer])}
+        device.destroy_buffer(self.staging_buffer, None);
+        if let Some(allocation) = self.staging_allocation.take(){allocator.free(allocation)}
+    }
+}
+///Copies data to device local resources through staging buffers on the transfer queue.
+///Without a dedicated transfer family the transfer queue is the graphics queue, and no ownership transfer is needed.
+pub(crate) struct Uploader{
+    ///One command pool per queue family, created when the family is first used.
+    command_pools : Vec<(u32,CommandPool)>,
+    pending : Vec<PendingUpload>,
+    next_id : u64,
+}
+impl Uploader{
+    pub(crate) fn new()->Self{
+        return Self{command_pools : vec!() , pending : vec!() , next_id : 0};
+    }
+    ///Destroys every upload and command pool, the device must be idle.
+    pub(crate) unsafe fn destroy(&mut self , device : &Device , allocator : &mut MemoryAllocator){
+        for upload in self.pending.drain(..){upload.destroy(device, allocator)}
+        for (_,command_pool) in self.command_pools.drain(..){device.destroy_command_pool(command_pool, None)}
+    }
+    ///The id the next upload will get.
+    pub(crate) fn next_upload_id(&self)->u64{
+        return self.next_id;
+    }
+    ///The id of the oldest upload that hasn't been collected yet.
+    pub(crate) fn oldest_pending_upload_id(&self)->Option<u64>{
+        return self.pending.iter().map(|upload|upload.id).min();
+    }
+    unsafe fn command_pool(&mut self , device : &Device , queue_family_index : u32)->Result<CommandPool,RendererError>{
+        if let Some((_,command_pool)) = self.command_pools.iter().find(|(family,_)|*family == queue_family_index){return Ok(*command_pool)}
+        let command_pool_create_info = CommandPoolCreateInfo{
+            s_type : StructureType::COMMAND_POOL_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : CommandPoolCreateFlags::TRANSIENT,
+            queue_family_index,
+        };
+        let command_pool = device.create_command_pool(&command_pool_create_info, None).map_err(|e|{
+            error!("Failed to create the upload command pool for queue family {}, {}.",queue_family_index,e);
+            RendererError::CreateUploadResources(e)
+        })?;
+        trace!("Created upload command pool for queue family {}.",queue_family_index);
+        self.command_pools.push((queue_family_index,command_pool));
+        return Ok(command_pool);
+    }
+}
+impl Renderer{
+    ///Copies `data` into `buffer` at `offset` and makes it available to the destination queue.
+    ///The buffer must have been created with `TRANSFER_DST` usage, and exclusive sharing when the queue families differ.
+    pub fn upload_to_buffer(&mut self , data : &[u8] , buffer : Buffer , offset : DeviceSize , destination : UploadDestination)->Result<UploadHandle,RendererError>{
+        //Copies of zero bytes are invalid, an empty upload is complete immediately.
+        if data.is_empty(){
+            self.uploader.next_id += 1;
+            return Ok(UploadHandle(self.uploader.next_id - 1));
+        }
+        return self.upload(data, UploadTarget::Buffer{buffer , offset}, destination);
+    }
+    ///Copies `data` into `image` using `regions`, whose buffer offsets are offsets into `data`, and makes it available to the destination queue.
+    ///The contents of `range` are discarded before the copy and the range is left in `final_layout`.
+    ///The image must have been created with `TRANSFER_DST` usage.
+    pub fn upload_to_image(&mut self , data : &[u8] , image : Image , regions : &[BufferImageCopy] , range : ImageSubresourceRange , final_layout : ImageLayout , destination : UploadDestination)->Result<UploadHandle,RendererError>{
+        return self.upload(data, UploadTarget::Image{image , regions , range , final_layout}, destination);
+    }
+    ///Whether the upload has completed, the staging resources of completed uploads are freed.
+    pub fn is_upload_complete(&mut self , handle : UploadHandle)->Result<bool,RendererError>{
+        self.collect_uploads()?;
+        return Ok(!self.uploader.pending.iter().any(|upload|upload.id == handle.0));
+    }
+    ///Blocks until the upload has completed.
+    pub fn wait_for_upload(&mut self , handle : UploadHandle)->Result<(),RendererError>{
+        if let Some(upload) = self.uploader.pending.iter().find(|upload|upload.id == handle.0){
+            unsafe{self.device.wait_for_fences(&[upload.complete], true, u64::MAX)}.map_err(|e|{
+                error!("Failed to wait for upload {}, {}.",handle.0,e);
+                RendererError::WaitFence(e)
+            })?;
+        }
+        return self.collect_uploads();
+    }
+    ///Frees the staging resources of every completed upload and the deferred resources that are no longer in use.
+    pub(crate) fn collect_uploads(&mut self)->Result<(),RendererError>{
+        let mut i = 0;
+        while i < self.uploader.pending.len(){
+            let complete = unsafe{self.device.get_fence_status(self.uploader.pending[i].complete)}.map_err(|e|{
+                error!("Failed to get the status of an upload, {}.",e);
+                RendererError::WaitFence(e)
+            })?;
+            if complete{
+                let upload = self.uploader.pending.swap_remove(i);
+                trace!("Upload {} completed.",upload.id);
+                unsafe{upload.destroy(&self.device, &mut self.allocator)};
+            }else{i += 1}
+        }
+        self.collect_garbage();
+        return Ok(());
+    }
+    fn upload(&mut self , data : &[u8] , target : UploadTarget , destination : UploadDestination)->Result<UploadHandle,RendererError>{
+        let transfer_queue = *self.queues.transfer();
+        let destination_queue = match destination{
+            UploadDestination::Graphics=>*self.queues.graphics(),
+            UploadDestination::Compute=>*self.queues.compute(),
+        };
+        let ownership_transfer = transfer_queue.family_index() != destination_queue.family_index();
+        let id = self.uploader.next_id;
+        self.uploader.next_id += 1;
+        let mut upload = PendingUpload{
+            id,
+            staging_buffer : Buffer::null(),
+            staging_allocation : None,
+            transfer_pool : unsafe{self.uploader.command_pool(&self.device, transfer_queue.family_index())}?,
+            transfer_command_buffer : CommandBuffer::null(),
+            acquire_pool : CommandPool::null(),
+            acquire_command_buffer : CommandBuffer::null(),
+            transferred : Semaphore::null(),
+            complete : Fence::null(),
+        };
+        if ownership_transfer{
+            upload.acquire_pool = unsafe{self.uploader.command_pool(&self.device, destination_queue.family_index())}?;
+        }
+        //Destroying null handles is a no-op, so a partially created upload can be destroyed as a whole.
+        let result = unsafe{self.record_and_submit_upload(&mut upload, data, &target, destination, &transfer_queue, &destination_queue)};
+        if let Err(e) = result{
+            unsafe{upload.destroy(&self.device, &mut self.allocator)};
+            return Err(e);
+        }
+        debug!("Submitted upload {} of {} bytes{}.",id,data.len(),if ownership_transfer{" with a queue family ownership transfer"}else{""});
+        self.uploader.pending.push(upload);
+        return Ok(UploadHandle(id));
+    }
+    unsafe fn record_and_submit_upload(&mut self , upload : &mut PendingUpload , data : &[u8] , target : &UploadTarget , destination : UploadDestination , transfer_queue : &DeviceQueue , destination_queue : &DeviceQueue)->Result<(),RendererError>{
+        let ownership_transfer = transfer_queue.family_index() != destination_queue.family_index();
+        let (staging_buffer,staging_allocation) = create_staging_buffer(&self.device, &mut self.allocator, data)?;
+        upload.staging_buffer = staging_buffer;
+        upload.staging_allocation = Some(staging_allocation);
+        let fence_create_info = FenceCreateInfo{
+            s_type : StructureType::FENCE_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : FenceCreateFlags::empty(),
+        };
+        upload.complete = self.device.create_fence(&fence_create_info, None).map_err(|e|{
+            error!("Failed to create the upload fence, {}.",e);
+            RendererError::CreateUploadResources(e)
+        })?;
+        upload.transfer_command_buffer = allocate_command_buffer(&self.device, upload.transfer_pool)?;
+        let (src_family,dst_family) = if ownership_transfer{(transfer_queue.family_index(),destination_queue.family_index())}else{(QUEUE_FAMILY_IGNORED,QUEUE_FAMILY_IGNORED)};
+        let dst_access = destination_access(target, destination);
+        //Without an ownership transfer the barrier after the copy makes the data visible directly, otherwise it is the release half of the transfer.
+        let (release_access,release_stage) = if ownership_transfer{(AccessFlags::empty(),PipelineStageFlags::BOTTOM_OF_PIPE)}else{(dst_access,PipelineStageFlags::ALL_COMMANDS)};
+        let command_buffer = upload.transfer_command_buffer;
+        begin_command_buffer(&self.device, command_buffer)?;
+        match *target{
+            UploadTarget::Buffer{buffer , offset}=>{
+                let region = BufferCopy{src_offset : 0 , dst_offset : offset , size : data.len() as DeviceSize};
+                self.device.cmd_copy_buffer(command_buffer, staging_buffer, buffer, &[region]);
+                let barrier = buffer_barrier(buffer, offset, data.len() as DeviceSize, AccessFlags::TRANSFER_WRITE, release_access, src_family, dst_family);
+                self.device.cmd_pipeline_barrier(command_buffer, PipelineStageFlags::TRANSFER, release_stage, DependencyFlags::empty(), &[], &[barrier], &[]);
+            }
+            UploadTarget::Image{image , regions , range , final_layout}=>{
+                let to_transfer = image_barrier(image, range, ImageLayout::UNDEFINED, ImageLayout::TRANSFER_DST_OPTIMAL, AccessFlags::empty(), AccessFlags::TRANSFER_WRITE, QUEUE_FAMILY_IGNORED, QUEUE_FAMILY_IGNORED);
+                self.device.cmd_pipeline_barrier(command_buffer, PipelineStageFlags::TOP_OF_PIPE, PipelineStageFlags::TRANSFER, DependencyFlags::empty(), &[], &[], &[to_transfer]);
+                self.device.cmd_copy_buffer_to_image(command_buffer, staging_buffer, image, ImageLayout::TRANSFER_DST_OPTIMAL, regions);
+                let barrier = image_barrier(image, range, ImageLayout::TRANSFER_DST_OPTIMAL, final_layout, AccessFlags::TRANSFER_WRITE, release_access, src_family, dst_family);
+                self.device.cmd_pipeline_barrier(command_buffer, PipelineStageFlags::TRANSFER, release_stage, DependencyFlags::empty(), &[], &[], &[barrier]);
+            }
+        }
+        end_command_buffer(&self.device, command_buffer)?;
+        if !ownership_transfer{
+            let command_buffers = [command_buffer];
+            let submit_info = submit_info(&command_buffers, &[], &[], &[]);
+            return self.submit_transfer(&[submit_info], upload.complete);
+        }
+        let semaphore_create_info = SemaphoreCreateInfo{
+            s_type : StructureType::SEMAPHORE_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : SemaphoreCreateFlags::empty(),
+        };
+        upload.transferred = self.device.create_semaphore(&semaphore_create_info, None).map_err(|e|{
+            error!("Failed to create the upload semaphore, {}.",e);
+            RendererError::CreateUploadResources(e)
+        })?;
+        upload.acquire_command_buffer = allocate_command_buffer(&self.device, upload.acquire_pool)?;
+        let command_buffer = upload.acquire_command_buffer;
+        begin_command_buffer(&self.device, command_buffer)?;
+        //The acquire half repeats the release barrier, including the layout transition, on the destination family.
+        match *target{
+            UploadTarget::Buffer{buffer , offset}=>{
+                let barrier = buffer_barrier(buffer, offset, data.len() as DeviceSize, AccessFlags::empty(), dst_access, src_family, dst_family);
+                self.device.cmd_pipeline_barrier(command_buffer, PipelineStageFlags::TOP_OF_PIPE, PipelineStageFlags::ALL_COMMANDS, DependencyFlags::empty(), &[], &[barrier], &[]);
+            }
+            UploadTarget::Image{image , range , final_layout , ..}=>{
+                let barrier = image_barrier(image, range, ImageLayout::TRANSFER_DST_OPTIMAL, final_layout, AccessFlags::empty(), dst_access, src_family, dst_family);
+                self.device.cmd_pipeline_barrier(command_buffer, PipelineStageFlags::TOP_OF_PIPE, PipelineStageFlags::ALL_COMMANDS, DependencyFlags::empty(), &[], &[], &[barrier]);
+            }
+        }
+        end_command_buffer(&self.device, command_buffer)?;
+        let transfer_command_buffers = [upload.transfer_command_buffer];
+        let transferred = [upload.transferred];
+        //Once the transfer half is submitted the transfer queue may be using the staging buffer, the command buffer and the semaphore,
+        //so if anything fails after this point the queue has to be idle before the caller destroys the upload.
+        self.submit_transfer(&[submit_info(&transfer_command_buffers, &[], &[], &transferred)], Fence::null())?;
+        let acquire_command_buffers = [upload.acquire_command_buffer];
+        let wait_stages = [PipelineStageFlags::ALL_COMMANDS];
+        let acquire_submit_info = submit_info(&acquire_command_buffers, &transferred, &wait_stages, &[]);
+        let result = match destination{
+            UploadDestination::Graphics=>self.submit_graphics(&[acquire_submit_info], upload.complete),
+            UploadDestination::Compute=>self.submit_compute(&[acquire_submit_info], upload.complete),
+        };
+        if result.is_err(){
+            if let Err(e) = self.device.queue_wait_idle(transfer_queue.handle()){error!("Failed to wait for the transfer queue after a failed upload, {}.",e)}
+        }
+        return result;
+    }
+}
+///The accesses the destination queue may use the uploaded data for.
+fn destination_access(target : &UploadTarget , destination : UploadDestination)->AccessFlags{
+    match (target,destination){
+        (UploadTarget::Buffer{..},UploadDestination::Graphics)=>return AccessFlags::VERTEX_ATTRIBUTE_READ | AccessFlags::INDEX_READ | AccessFlags::UNIFORM_READ | AccessFlags::SHADER_READ | AccessFlags::INDIRECT_COMMAND_READ,
+        (UploadTarget::Buffer{..},UploadDestination::Compute)=>return AccessFlags::UNIFORM_READ | AccessFlags::SHADER_READ | AccessFlags::INDIRECT_COMMAND_READ,
+        (UploadTarget::Image{..},_)=>return AccessFlags::SHADER_READ,
+    }
+}
+unsafe fn create_staging_buffer(device : &Device , allocator : &mut MemoryAllocator , data : &[u8])->Result<(Buffer,Allocation),RendererError>{
+    let buffer_create_info = BufferCreateInfo{
+        s_type : StructureType::BUFFER_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : BufferCreateFlags::empty(),
+        size : data.len().max(1) as DeviceSize,
+        usage : BufferUsageFlags::TRANSFER_SRC,
+        sharing_mode : SharingMode::EXCLUSIVE,
+        queue_family_index_count : 0,
+        p_queue_family_indices : std::ptr::null(),
+    };
+    let buffer = device.create_buffer(&buffer_create_info, None).map_err(|e|{
+        error!("Failed to create a staging buffer, {}.",e);
+        RendererError::CreateUploadResources(e)
+    })?;
+    let allocation = match allocator.allocate_for_buffer(buffer, MemoryLocation::CpuToGpu){
+        Ok(allocation)=>allocation,
+        Err(e)=>{
+            device.destroy_buffer(buffer, None);
+            return Err(e);
+        }
+    };
+    //CpuToGpu memory is always mapped and host coherent.
+    let mapped = allocation.mapped_ptr().expect("staging memory is host visible");
+    std::ptr::copy_nonoverlapping(data.as_ptr(), mapped, data.len());
+    return Ok((buffer,allocation));
+}
+unsafe fn allocate_command_buffer(device : &Device , command_pool : CommandPool)->Result<CommandBuffer,RendererError>{
+    let command_buffer_allocate_info = CommandBufferAllocateInfo{
+        s_type : StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
+        p_next : std::ptr::null(),
+        command_pool,
+        level : CommandBufferLevel::PRIMARY,
+        command_buffer_count : 1,
+    };
+    return device.allocate_command_buffers(&command_buffer_allocate_info).map(|command_buffers|command_buffers[0]).map_err(|e|{
+        error!("Failed to allocate an upload command buffer, {}.",e);
+        RendererError::CreateUploadResources(e)
+    });
+}
+unsafe fn begin_command_buffer(device : &Device , command_buffer : CommandBuffer)->Result<(),RendererError>{
+    let command_buffer_begin_info = CommandBufferBeginInfo{
+        s_type : StructureType::COMMAND_BUFFER_BEGIN_INFO,
+        p_next : std::ptr::null(),
+        flags : CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+        p_inheritance_info : std::ptr::null(),
+    };
+    return device.begin_command_buffer(command_buffer, &command_buffer_begin_info).map_err(|e|{
+        error!("Failed to begin an upload command buffer, {}.",e);
+        RendererError::RecordCommands(e)
+    });
+}
+unsafe fn end_command_buffer(device : &Device , command_buffer : CommandBuffer)->Result<(),RendererError>{
+    return device.end_command_buffer(command_buffer).map_err(|e|{
+        error!("Failed to end an upload command buffer, {}.",e);
+        RendererError::RecordCommands(e)
+    });
+}
+fn submit_info(command_buffers : &[CommandBuffer] , wait_semaphores : &[Semaphore] , wait_stages : &[PipelineStageFlags] , signal_semaphores : &[Semaphore])->SubmitInfo{
+    return SubmitInfo{
+        s_type : StructureType::SUBMIT_INFO,
+        p_next : std::ptr::null(),
+        wait_semaphore_count : wait_semaphores.len() as u32,
+        p_wait_semaphores : wait_semaphores.as_ptr(),
+        p_wait_dst_stage_mask : wait_stages.as_ptr(),
+        command_buffer_count : command_buffers.len() as u32,
+        p_command_buffers : command_buffers.as_ptr(),
+        signal_semaphore_count : signal_semaphores.len() as u32,
+        p_signal_semaphores : signal_semaphores.as_ptr(),
+    };
+}
+fn buffer_barrier(buffer : Buffer , offset : DeviceSize , size : DeviceSize , src_access_mask : AccessFlags , dst_access_mask : AccessFlags , src_queue_family_index : u32 , dst_queue_family_index : u32)->BufferMemoryBarrier{
+    return BufferMemoryBarrier{
+        s_type : StructureType::BUFFER_MEMORY_BARRIER,
+        p_next : std::ptr::null(),
+        src_access_mask,
+        dst_access_mask,
+        src_queue_family_index,
+        dst_queue_family_index,
+        buffer,
+        offset,
+        size,
+    };
+}
+#[allow(clippy::too_many_arguments)]
+fn image_barrier(image : Image , subresource_range : ImageSubresourceRange , old_layout : ImageLayout , new_layout : ImageLayout , src_access_mask : AccessFlags , dst_access_mask : AccessFlags , src_queue_family_index : u32 , dst_queue_family_index : u32)->ImageMemoryBarrier{
+    return ImageMemoryBarrier{
+        s_type : StructureType::IMAGE_MEMORY_BARRIER,
+        p_next : std::ptr::null(),
+        src_access_mask,
+        dst_access_mask,
+        old_layout,
+        new_layout,
+        src_queue_family_index,
+        dst_queue_family_index,
+        image,
+        subresource_range,
+    };
+}